@@ -0,0 +1,3 @@
+// Empty, so that Cargo sets $OUT_DIR for the `split_includes`, `generated_file`, and
+// `count_total` tests.
+fn main() {}
@@ -0,0 +1,17 @@
+#[conformance::tests(exact, serde=json, file="tests/count_total.test", count_total)]
+fn words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+#[conformance::tests(exact, serde=json, file="tests/generated_file.test", count_total, generated_file)]
+fn generated_words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+// after the invocations, so that they're expanded first
+const TOTAL: usize = conformance::total!();
+
+#[test]
+fn cases_are_counted() {
+    assert_eq!(TOTAL, 3);
+}
@@ -0,0 +1,18 @@
+#[conformance::tests(exact, serde=json, file="tests/generated_file.test", generated_file)]
+fn words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+#[test]
+fn tests_are_written_to_out_dir() {
+    let path = std::path::Path::new(env!("OUT_DIR"))
+        .join("conformance")
+        .join("generated_file_words_tests.rs");
+    let generated = std::fs::read_to_string(path).unwrap();
+    assert!(generated.starts_with(
+        "// Generated by `conformance::tests` from `tests/generated_file.test`. Do not edit.\n"
+    ));
+    // the generated code is wrapped at whatever token the line fills up at
+    let generated = generated.split_whitespace().collect::<Vec<_>>().join(" ");
+    assert!(generated.contains("fn generated_file_two_words"));
+}
@@ -0,0 +1,28 @@
+// Shadow the prelude items the generated code refers to,
+// so that any unqualified use of them fails to compile.
+#![allow(dead_code, unused_imports)]
+
+type Result<T> = std::result::Result<T, ()>;
+struct Box;
+struct String;
+enum Option {
+    Some,
+    None,
+}
+use Option::{None, Some};
+struct Ok;
+struct Err;
+
+#[conformance::tests(exact, serde=json, file="tests/hygiene.test")]
+fn words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+mod strip_ansi {
+    struct Ok;
+
+    #[conformance::tests(exact, serde=json, file="tests/hygiene.test", strip_ansi)]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+}
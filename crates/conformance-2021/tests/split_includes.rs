@@ -0,0 +1,15 @@
+#[conformance::tests(exact, serde=json, file="tests/split_includes.test", split_includes)]
+fn words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+#[test]
+fn long_bodies_are_written_to_out_dir() {
+    let dir = std::path::Path::new(env!("OUT_DIR"))
+        .join("conformance")
+        .join("tests_split_includes_test");
+    let input = std::fs::read_to_string(dir.join("_first.input")).unwrap();
+    assert!(input.starts_with("word000 word001"));
+    assert!(dir.join("_second.output").exists());
+    assert!(!dir.join("_short.input").exists());
+}
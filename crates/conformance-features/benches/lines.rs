@@ -0,0 +1,7 @@
+#[conformance::tests(exact, serde=serde_json, file="tests/lines.json.test", bench)]
+fn lines(s: &str) -> Vec<&str> {
+    s.lines().collect()
+}
+
+criterion::criterion_group!(benches, lines_json_bench);
+criterion::criterion_main!(benches);
@@ -0,0 +1,4 @@
+#[conformance::tests(exact, serde=serde_json, file="tests/lengths.json.test", jq = "length")]
+fn lines(s: &str) -> Vec<&str> {
+    s.lines().collect()
+}
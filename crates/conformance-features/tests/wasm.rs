@@ -0,0 +1,6 @@
+// off `wasm32`, the tests are built but left for `wasm-bindgen-test-runner`, which isn't used,
+// so that building them checks the generated code against `wasm-bindgen-test`
+#[conformance::tests(exact, serde=serde_json, file="tests/lines.json.test", wasm)]
+fn lines(s: &str) -> Vec<&str> {
+    s.lines().collect()
+}
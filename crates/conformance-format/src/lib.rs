@@ -0,0 +1,1896 @@
+//! The test file format of the `conformance` crate.
+//!
+//! A file is a sequence of tests, each terminated by a `...` line:
+//!
+//! ```text
+//! name
+//! ===
+//! input
+//! ---
+//! output
+//! ...
+//! ```
+//!
+//! [`parse_tests`] splits a file into [`Case`]s, expanding `@table`s and reading the other
+//! `@` directives of each test's head. Bodies are returned as written, untrimmed, except that
+//! a literal `...` line is written escaped as `\...`, and is unescaped in the [`Case`].
+//!
+//! A file may start with a [`Frontmatter`] between `+++` lines, read by [`parse_frontmatter`].
+//!
+//! A block whose head is just `@prelude` or `@epilogue` is not a test, but text shared by every
+//! input of the file, which [`parse_tests`] puts on its own lines before or after each:
+//!
+//! ```text
+//! @prelude
+//! ===
+//! use std::io;
+//! ...
+//! ```
+//!
+//! With the `runtime` feature, [`runtime::run_reader`] runs the cases of a file too.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt,
+    ops::Range,
+};
+
+#[cfg(feature = "runtime")]
+pub mod runtime;
+
+/// How the sections of a test are interpreted. The default is that of `exact` mode.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// The output of each test is its input (`idempotent` mode),
+    /// unless it is an `@error` or `@incomplete` test.
+    pub idempotent: bool,
+    /// A test may omit `---` and its output,
+    /// as when the output only describes what a predicate checks.
+    pub optional_output: bool,
+    /// Tests may have the same name (or `@id`),
+    /// as when they are told apart by something else.
+    pub allow_duplicate_names: bool,
+    /// Anything after the final `...` is ignored, such as notes, rather than an error.
+    pub allow_trailing_content: bool,
+    /// The final test may leave out its `...`: content after the last `...` with a `===`
+    /// is read as a test, rather than trailing content.
+    pub lenient_terminator: bool,
+}
+
+/// One case of a test file. A test with a `@table` gives one case per `@row`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Case<'a> {
+    /// The name before `===`, with lines joined by `\n`,
+    /// followed by a space and the key of its `@row`, if any.
+    pub name: String,
+    /// The `@id`, followed by a space and the key of its `@row`, if any.
+    pub id: Option<String>,
+    /// The index of the test in the file, counting from 0.
+    pub index: usize,
+    /// The line of the file the test starts on, counting from 1.
+    pub line: usize,
+    /// The `@repeat` count, 1 by default.
+    pub repeat: usize,
+    /// Whether this is an `@error` test, whose input must be rejected.
+    pub error: bool,
+    /// The kind of error an `@error` test's input must be rejected with,
+    /// given as `@error kind=Syntax`.
+    pub error_kind: Option<String>,
+    /// The line and column, counting from 1, an `@error` test's input must be rejected at,
+    /// given as `@error_at 2:5`, which makes it an `@error` test.
+    pub error_at: Option<(usize, usize)>,
+    /// Whether this is an `@incomplete` test, whose input is cut short,
+    /// so that `de` must report that it needs more data.
+    pub incomplete: bool,
+    /// Whether the output is an `@expr` test's Rust expression, giving the expected value
+    /// rather than its serialization.
+    pub expr: bool,
+    /// For an `@ignore` test, the reason given, which may be empty.
+    pub ignore: Option<String>,
+    /// For an `@xfail` test, which is expected to fail until a known bug is fixed,
+    /// the reason given, which may be empty.
+    pub xfail: Option<String>,
+    /// The `@tolerance` of an `approx` test, overriding the file's, as written: a finite,
+    /// non-negative float, such as `1e-3`.
+    pub tolerance: Option<String>,
+    /// The group given by the last `@group`, from this test or an earlier one.
+    pub group: Option<String>,
+    /// The input section, or the first of the inputs separated by `&&&`.
+    pub input: Cow<'a, str>,
+    /// The inputs after the first, for tests whose inputs must serialize the same.
+    pub equivalents: Vec<Cow<'a, str>>,
+    /// The output section, which is empty if omitted.
+    pub output: Cow<'a, str>,
+    /// The gzip-compressed file holding the output instead, given as `@file.gz PATH`,
+    /// relative to the directory of the test file.
+    pub output_file: Option<String>,
+    /// The binary file holding the output instead, given as `@bin PATH`,
+    /// relative to the directory of the test file.
+    pub output_bin: Option<String>,
+    /// The checks given with `@modes`, in order, or empty for just the one of the file.
+    pub modes: Vec<Mode>,
+    /// The path of the function serializing this case instead of the file's, given as `@ser PATH`.
+    pub ser: Option<String>,
+    /// The path of the function deserializing this case instead of the file's,
+    /// given as `@de PATH`.
+    pub de: Option<String>,
+    /// Whether the test may have an empty input or output, given as `@allow_empty`.
+    pub allow_empty: bool,
+    /// The substrings of the warnings the input must give, one per `@warn SUBSTRING`.
+    pub warnings: Vec<String>,
+    /// Metadata for reporting, such as the section of a specification the case covers,
+    /// given as `@meta spec=RFC8259 section=7`.
+    pub meta: BTreeMap<String, String>,
+}
+
+impl<'a> Case<'a> {
+    /// A case named `name`, the test with `index` starting on `line`, whose `input` must give
+    /// `output`, with none of the directives given.
+    pub fn new(
+        name: String,
+        index: usize,
+        line: usize,
+        input: Cow<'a, str>,
+        output: Cow<'a, str>,
+    ) -> Self {
+        Case {
+            name,
+            id: None,
+            index,
+            line,
+            repeat: 1,
+            error: false,
+            error_kind: None,
+            error_at: None,
+            incomplete: false,
+            expr: false,
+            ignore: None,
+            xfail: None,
+            tolerance: None,
+            group: None,
+            input,
+            equivalents: Vec::new(),
+            output,
+            output_file: None,
+            output_bin: None,
+            modes: Vec::new(),
+            ser: None,
+            de: None,
+            allow_empty: false,
+            warnings: Vec::new(),
+            meta: BTreeMap::new(),
+        }
+    }
+}
+
+/// A check of a case listed with `@modes`, each running the case's input or output
+/// through the tested function and comparing it with one of them:
+///
+/// ```text
+/// @modes exact, roundtrip
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mode {
+    /// The input serializes to the output.
+    Exact,
+    /// The input serializes to itself.
+    Idempotent,
+    /// The output serializes to itself, so it can be read back.
+    Roundtrip,
+}
+
+impl Mode {
+    /// The name of the mode, as written in `@modes`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Exact => "exact",
+            Mode::Idempotent => "idempotent",
+            Mode::Roundtrip => "roundtrip",
+        }
+    }
+}
+
+/// The settings of a whole file, given before its first test:
+///
+/// ```text
+/// +++
+/// type = "Vec<u8>"
+/// +++
+/// ```
+///
+/// Each line is a `key = "value"` pair, as in TOML, with values restricted to basic strings
+/// without escapes. Blank lines and lines starting with `#` are ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Frontmatter {
+    /// The `type` the tested function is instantiated with in this file.
+    pub r#type: Option<String>,
+    /// The version of the crate the expected outputs of this file were captured from,
+    /// given as `baseline_version`.
+    pub baseline_version: Option<String>,
+}
+
+/// A problem with a test file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// The file doesn't end with a newline.
+    MissingTrailingNewline,
+    /// The file contains only `...` separators.
+    OnlySeparators,
+    /// The file starts with a `...` separator.
+    LeadingSeparator,
+    /// The file starts with `+++`, but has no `+++` line closing its frontmatter.
+    UnclosedFrontmatter,
+    /// The frontmatter has a line which isn't a `key = "value"` pair,
+    /// counting lines of the file from 1.
+    InvalidFrontmatter { line: usize },
+    /// The frontmatter has an unknown key.
+    UnknownFrontmatterKey { key: String },
+    /// The frontmatter has the same key more than once.
+    DuplicateFrontmatterKey { key: String },
+    /// The file has content after its final `...` separator,
+    /// with the first line that looks like a mistyped `...`, if any.
+    TrailingContent { near_miss: Option<String> },
+    /// The test at `index` has nothing before its `...`.
+    EmptyTest { index: usize },
+    /// The test at `index` has no `===` after its name,
+    /// with the first line that looks like a mistyped `===`, if any.
+    MissingNameSeparator {
+        index: usize,
+        near_miss: Option<String>,
+    },
+    /// The test has no `---` after its input,
+    /// with the first line that looks like a mistyped `---`, if any.
+    MissingBodySeparator {
+        name: String,
+        near_miss: Option<String>,
+    },
+    /// The test has the same name (or `@id`) as an earlier one.
+    DuplicateName { name: String },
+    /// The test has a `@repeat` count which isn't a positive integer.
+    InvalidRepeat { name: String, count: String },
+    /// The test has an `@id` without a value.
+    EmptyId { name: String },
+    /// The test has a `@group` without a name.
+    EmptyGroup { name: String },
+    /// The test has a `@ser` or `@de` without a path.
+    EmptyOverride { name: String, directive: String },
+    /// The test has a `@warn` without a substring.
+    EmptyWarning { name: String },
+    /// The test has a `@meta` without pairs, or with a pair other than `key=value`.
+    InvalidMeta { name: String, pair: String },
+    /// The test gives a `@meta` key more than once.
+    DuplicateMetaKey { name: String, key: String },
+    /// The test has an unknown `@` directive.
+    UnknownDirective { name: String, directive: String },
+    /// The test has an `@error` with an argument other than `kind=<tag>`.
+    InvalidErrorKind { name: String, arg: String },
+    /// The test has an `@error_at` whose argument isn't `line:column`.
+    InvalidErrorPosition { name: String, arg: String },
+    /// The test has a `@tolerance` which isn't a finite, non-negative float.
+    InvalidTolerance { name: String, tolerance: String },
+    /// The test has a `@modes` listing no modes, or an unknown one.
+    UnknownMode { name: String, mode: String },
+    /// The test has `@modes`, but no output to check, being an `@error` test,
+    /// a test with `&&&` inputs, or a test of an `idempotent` file.
+    ModesWithoutOutput { name: String },
+    /// The test has a `@row` without a key.
+    RowWithoutKey { name: String },
+    /// The test has a `@row` but no `@table`.
+    RowWithoutTable { name: String },
+    /// The test has a `@table` but no `@row`.
+    TableWithoutRows { name: String },
+    /// A `@row` of the test has a different number of values than its table has columns.
+    RowLength {
+        name: String,
+        key: String,
+        values: usize,
+        columns: usize,
+    },
+    /// A `@row` of the test leaves a `${placeholder}` in its bodies.
+    UnsubstitutedPlaceholder {
+        name: String,
+        key: String,
+        placeholder: String,
+    },
+    /// The test has a `@file.gz` or `@bin`, named by `directive`, without a path.
+    EmptyOutputFile { name: String, directive: String },
+    /// The test has both a `@file.gz` or `@bin`, named by `directive`, and an output section.
+    OutputFileWithOutput { name: String, directive: String },
+    /// The test has a `@file.gz` or `@bin`, named by `directive`, but is in an `idempotent`
+    /// file, whose output is the input.
+    IdempotentOutputFile { name: String, directive: String },
+    /// The test has both a `@file.gz` and a `@bin`.
+    ConflictingOutputFiles { name: String },
+    /// A `@prelude` or `@epilogue` block has a name, an argument, or other directives.
+    InvalidSharedBlock { directive: String },
+    /// The file has more than one `@prelude`, or more than one `@epilogue`.
+    DuplicateSharedBlock { directive: String },
+    /// The test is `@incomplete`, but also has what such a test can't,
+    /// such as an `@error` or `&&&` inputs.
+    IncompleteWith { name: String, conflict: String },
+    /// The test is `@expr`, but also has what such a test can't,
+    /// such as an `@error` or an empty output.
+    ExprWith { name: String, conflict: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingTrailingNewline => write!(f, "file needs to have trailing newline"),
+            ParseError::OnlySeparators => write!(f, "file has no tests, only `...` separators"),
+            ParseError::LeadingSeparator => write!(
+                f,
+                "file starts with `...`, but a separator must follow a test"
+            ),
+            ParseError::UnclosedFrontmatter => {
+                write!(
+                    f,
+                    "file starts with `+++`, but its frontmatter is never closed"
+                )
+            }
+            ParseError::InvalidFrontmatter { line } => write!(
+                f,
+                "frontmatter line {} is not a `key = \"value\"` pair",
+                line
+            ),
+            ParseError::UnknownFrontmatterKey { key } => {
+                write!(f, "frontmatter has unknown key `{}`", key)
+            }
+            ParseError::DuplicateFrontmatterKey { key } => {
+                write!(f, "frontmatter has key `{}` more than once", key)
+            }
+            ParseError::TrailingContent { near_miss } => {
+                write!(f, "file has disallowed content after final `...`")?;
+                write_hint(f, near_miss, "...")
+            }
+            ParseError::EmptyTest { index } => {
+                write!(f, "test {} is empty; is there an extra `...`?", index)
+            }
+            ParseError::MissingNameSeparator { index, near_miss } => {
+                write!(f, "test {} does not have `===` after name", index)?;
+                write_hint(f, near_miss, "===")
+            }
+            ParseError::MissingBodySeparator { name, near_miss } => {
+                write!(f, "test `{}` does not have `---` after input", name)?;
+                write_hint(f, near_miss, "---")
+            }
+            ParseError::DuplicateName { name } => {
+                write!(f, "test `{}` has the same name as an earlier test", name)
+            }
+            ParseError::InvalidRepeat { name, count } => {
+                write!(f, "test `{}` has invalid `@repeat` count `{}`", name, count)
+            }
+            ParseError::EmptyId { name } => write!(f, "test `{}` has an empty `@id`", name),
+            ParseError::EmptyGroup { name } => {
+                write!(f, "test `{}` has a `@group` without a name", name)
+            }
+            ParseError::EmptyOverride { name, directive } => {
+                write!(f, "test `{}` has a `@{}` without a path", name, directive)
+            }
+            ParseError::EmptyWarning { name } => {
+                write!(f, "test `{}` has a `@warn` without a substring", name)
+            }
+            ParseError::InvalidMeta { name, pair } if pair.is_empty() => {
+                write!(
+                    f,
+                    "test `{}` has a `@meta` without any `key=value` pair",
+                    name
+                )
+            }
+            ParseError::InvalidMeta { name, pair } => write!(
+                f,
+                "test `{}` has invalid `@meta` pair `{}`; expected `key=value`",
+                name, pair
+            ),
+            ParseError::DuplicateMetaKey { name, key } => {
+                write!(
+                    f,
+                    "test `{}` gives `@meta` key `{}` more than once",
+                    name, key
+                )
+            }
+            ParseError::UnknownDirective { name, directive } => {
+                write!(f, "test `{}` has unknown directive `@{}`", name, directive)
+            }
+            ParseError::InvalidErrorPosition { name, arg } => write!(
+                f,
+                "test `{}` has invalid `@error_at` argument `{}`; expected `line:column`, \
+                 counting from 1",
+                name, arg
+            ),
+            ParseError::InvalidTolerance { name, tolerance } => write!(
+                f,
+                "test `{}` has invalid `@tolerance` `{}`; expected a non-negative float, \
+                 such as `1e-3`",
+                name, tolerance
+            ),
+            ParseError::InvalidErrorKind { name, arg } => write!(
+                f,
+                "test `{}` has invalid `@error` argument `{}`; expected `kind=<tag>`",
+                name, arg
+            ),
+            ParseError::UnknownMode { name, mode } if mode.is_empty() => {
+                write!(f, "test `{}` has `@modes` without any mode", name)
+            }
+            ParseError::UnknownMode { name, mode } => write!(
+                f,
+                "test `{}` has unknown mode `{}` in `@modes`; \
+                 expected `exact`, `idempotent`, or `roundtrip`",
+                name, mode
+            ),
+            ParseError::ModesWithoutOutput { name } => write!(
+                f,
+                "test `{}` has `@modes`, but no output section for them to check",
+                name
+            ),
+            ParseError::RowWithoutKey { name } => {
+                write!(f, "test `{}` has `@row` without a key", name)
+            }
+            ParseError::RowWithoutTable { name } => {
+                write!(f, "test `{}` has `@row` without `@table`", name)
+            }
+            ParseError::TableWithoutRows { name } => {
+                write!(f, "test `{}` has `@table` without any `@row`", name)
+            }
+            ParseError::RowLength {
+                name,
+                key,
+                values,
+                columns,
+            } => write!(
+                f,
+                "test `{}` row `{}` has {} values for {} columns",
+                name, key, values, columns,
+            ),
+            ParseError::UnsubstitutedPlaceholder {
+                name,
+                key,
+                placeholder,
+            } => write!(
+                f,
+                "test `{}` row `{}` has unsubstituted placeholder `${{{}}}`",
+                name, key, placeholder,
+            ),
+            ParseError::EmptyOutputFile { name, directive } => {
+                write!(f, "test `{}` has a `@{}` without a path", name, directive)
+            }
+            ParseError::OutputFileWithOutput { name, directive } => write!(
+                f,
+                "test `{}` has both a `@{}` and an output section",
+                name, directive
+            ),
+            ParseError::IdempotentOutputFile { name, directive } => write!(
+                f,
+                "test `{}` has a `@{}`, but its output is its input, being `idempotent`",
+                name, directive
+            ),
+            ParseError::ConflictingOutputFiles { name } => write!(
+                f,
+                "test `{}` has both a `@file.gz` and a `@bin`; its output can only be in one",
+                name
+            ),
+            ParseError::InvalidSharedBlock { directive } => write!(
+                f,
+                "a `@{}` block may have no name, argument, or other directives",
+                directive,
+            ),
+            ParseError::DuplicateSharedBlock { directive } => {
+                write!(f, "file has more than one `@{}` block", directive)
+            }
+            ParseError::IncompleteWith { name, conflict } => write!(
+                f,
+                "test `{}` is `@incomplete`, so it can't have {}",
+                name, conflict
+            ),
+            ParseError::ExprWith { name, conflict } => write!(
+                f,
+                "test `{}` is `@expr`, so it can't have {}",
+                name, conflict
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Suggest `separator` in place of the line found, if any.
+fn write_hint(
+    f: &mut fmt::Formatter<'_>,
+    near_miss: &Option<String>,
+    separator: &str,
+) -> fmt::Result {
+    match near_miss {
+        Some(line) => write!(f, "; found `{}`, did you mean `{}`?", line, separator),
+        None => Ok(()),
+    }
+}
+
+/// The first line of `s` which is `separator` mistyped with too few or too many characters,
+/// such as `==` or `====` for `===`.
+fn near_miss(s: &str, separator: &str) -> Option<String> {
+    let c = separator.chars().next().unwrap();
+    s.lines()
+        .map(str::trim)
+        .find(|line| {
+            line.len() >= 2 && line.len() != separator.len() && line.chars().all(|it| it == c)
+        })
+        .map(str::to_string)
+}
+
+/// The lines of `s` which are just `separator`, each as the range from the line ending before
+/// it to the end of its own, in order.
+///
+/// A line may end with `\r\n`, as in a file written on Windows, rather than `\n`.
+fn separators<'s>(s: &'s str, separator: &'s str) -> impl Iterator<Item = Range<usize>> + 's {
+    s.match_indices(separator).filter_map(move |(ix, _)| {
+        let end = ix + separator.len();
+        let newline = if s[end..].starts_with('\n') {
+            1
+        } else if s[end..].starts_with("\r\n") {
+            2
+        } else {
+            return None;
+        };
+        let start = if s[..ix].ends_with("\r\n") {
+            ix - 2
+        } else if s[..ix].ends_with('\n') {
+            ix - 1
+        } else {
+            return None;
+        };
+        Some(start..end + newline)
+    })
+}
+
+/// `s` split at each of its `separator` lines, as by `str::split`, where a line overlapping
+/// the one before, as the second of `\n...\n...\n` does, isn't a separator.
+fn split_at_separators<'s>(s: &'s str, separator: &'s str) -> Vec<&'s str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for range in separators(s, separator) {
+        if range.start >= start {
+            pieces.push(&s[start..range.start]);
+            start = range.end;
+        }
+    }
+    pieces.push(&s[start..]);
+    pieces
+}
+
+/// The line ending which `s` starts with, if it does.
+fn strip_line_ending(s: &str) -> Option<&str> {
+    s.strip_prefix('\n').or_else(|| s.strip_prefix("\r\n"))
+}
+
+/// Split `source` into the contents of its frontmatter, if any, and the rest of the file.
+fn split_frontmatter(source: &str) -> Result<(Option<&str>, &str), ParseError> {
+    let contents = match source.strip_prefix("+++").and_then(strip_line_ending) {
+        Some(contents) => contents,
+        None => return Ok((None, source)),
+    };
+    if let Some(rest) = contents.strip_prefix("+++").and_then(strip_line_ending) {
+        return Ok((Some(""), rest));
+    }
+    match separators(contents, "+++").next() {
+        Some(range) => Ok((Some(&contents[..range.start]), &contents[range.end..])),
+        None => Err(ParseError::UnclosedFrontmatter),
+    }
+}
+
+/// Parse the frontmatter of `source`, which is the default if it has none.
+///
+/// On failure, every problem found is returned, in order of appearance.
+pub fn parse_frontmatter(source: &str) -> Result<Frontmatter, Vec<ParseError>> {
+    let contents = match split_frontmatter(source) {
+        Ok((contents, _)) => contents.unwrap_or(""),
+        Err(e) => return Err(vec![e]),
+    };
+
+    let mut frontmatter = Frontmatter::default();
+    let mut errs = Vec::new();
+    for (ix, line) in contents.lines().enumerate() {
+        // the first line of the file is the opening `+++`
+        let line_number = ix + 2;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let pair = line.find('=').and_then(|eq| {
+            let value = line[eq + 1..].trim();
+            let value = value.strip_prefix('"')?.strip_suffix('"')?;
+            let key = line[..eq].trim();
+            Some((key, value)).filter(|_| !key.is_empty() && !value.contains(['"', '\\']))
+        });
+        let (key, value) = match pair {
+            Some(pair) => pair,
+            None => {
+                errs.push(ParseError::InvalidFrontmatter { line: line_number });
+                continue;
+            }
+        };
+        let field = match key {
+            "type" => &mut frontmatter.r#type,
+            "baseline_version" => &mut frontmatter.baseline_version,
+            _ => {
+                errs.push(ParseError::UnknownFrontmatterKey {
+                    key: key.to_string(),
+                });
+                continue;
+            }
+        };
+        if field.is_some() {
+            errs.push(ParseError::DuplicateFrontmatterKey {
+                key: key.to_string(),
+            });
+        }
+        *field = Some(value.to_string());
+    }
+
+    if errs.is_empty() {
+        Ok(frontmatter)
+    } else {
+        Err(errs)
+    }
+}
+
+/// Parse the tests of `source`, as in `exact` mode.
+///
+/// On failure, every problem found is returned, in order of appearance.
+pub fn parse_tests(source: &str) -> Result<Vec<Case<'_>>, Vec<ParseError>> {
+    parse_tests_with(source, ParseOptions::default())
+}
+
+/// Parse the tests of `source`, interpreting their sections according to `options`.
+/// Its frontmatter, if any, is skipped.
+///
+/// On failure, every problem found is returned, in order of appearance.
+pub fn parse_tests_with(
+    source: &str,
+    options: ParseOptions,
+) -> Result<Vec<Case<'_>>, Vec<ParseError>> {
+    if !source.ends_with('\n') {
+        return Err(vec![ParseError::MissingTrailingNewline]);
+    }
+    let tests = match split_frontmatter(source) {
+        Ok((_, tests)) => tests,
+        Err(e) => return Err(vec![e]),
+    };
+
+    let mut lines = tests.lines().map(str::trim).filter(|line| !line.is_empty());
+    match lines.next() {
+        Some("...") if lines.all(|line| line == "...") => {
+            return Err(vec![ParseError::OnlySeparators]);
+        }
+        Some("...") => return Err(vec![ParseError::LeadingSeparator]),
+        _ => (),
+    }
+
+    let (s, trailing) = tests.split_at(separators(tests, "...").last().map_or(0, |r| r.end));
+    // a final test without its `...`, ending at the file's final newline rather than at one
+    let unterminated = Some(trailing)
+        .filter(|trailing| {
+            options.lenient_terminator && separators(trailing, "===").next().is_some()
+        })
+        .map(|trailing| trailing.strip_suffix('\n').unwrap_or(trailing))
+        .map(|trailing| trailing.strip_suffix('\r').unwrap_or(trailing));
+    if !trailing.trim().is_empty() && !options.allow_trailing_content && unterminated.is_none() {
+        return Err(vec![ParseError::TrailingContent {
+            near_miss: near_miss(trailing, "..."),
+        }]);
+    }
+
+    // a table gives more cases than tests, but usually there is one case per test
+    let mut tests = split_at_separators(s, "...");
+    // `s` ends with a separator, after which there is no test
+    if tests.last() == Some(&"") {
+        tests.pop();
+    }
+    let mut cases = Vec::with_capacity(tests.len());
+    let mut errs = Vec::new();
+    let mut names = BTreeSet::new();
+    // a `@group` lasts until the next one
+    let mut group: Option<String> = None;
+    // lines are counted on from the previous test, so that large files are read in linear time
+    let (mut counted, mut line) = (0, 1);
+    // the text of the `@prelude` and `@epilogue` blocks, put around every input
+    let (mut prelude, mut epilogue) = (None, None);
+
+    for (index, test) in tests.into_iter().chain(unterminated).enumerate() {
+        let offset = test.as_ptr() as usize - source.as_ptr() as usize;
+        let offset = offset + (test.len() - test.trim_start().len());
+        line += source.as_bytes()[counted..offset]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count();
+        counted = offset;
+
+        if test.trim().is_empty() {
+            errs.push(ParseError::EmptyTest { index });
+            continue;
+        }
+
+        let (head, rest) = match separators(test, "===").next() {
+            Some(range) => (&test[..range.start], &test[range.end..]),
+            None => {
+                errs.push(ParseError::MissingNameSeparator {
+                    index,
+                    near_miss: near_miss(test, "==="),
+                });
+                continue;
+            }
+        };
+
+        // lines of the head starting with `@` are directives; the rest is the name
+        let (directives, name): (Vec<&str>, Vec<&str>) = head
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .partition(|line| line.starts_with('@'));
+        let name = name.join("\n");
+
+        // a shared block is not a test, and its body is all text
+        let shared = directives.iter().find_map(|directive| {
+            let key = directive[1..].split_whitespace().next()?;
+            ["prelude", "epilogue"]
+                .iter()
+                .find(|&&shared| shared == key)
+        });
+        if let Some(&shared) = shared {
+            let block = if shared == "prelude" {
+                &mut prelude
+            } else {
+                &mut epilogue
+            };
+            if !name.is_empty() || directives.len() > 1 || directives[0][1..].trim() != shared {
+                errs.push(ParseError::InvalidSharedBlock {
+                    directive: shared.to_string(),
+                });
+            } else if block.is_some() {
+                errs.push(ParseError::DuplicateSharedBlock {
+                    directive: shared.to_string(),
+                });
+            } else {
+                *block = Some(unescape_terminators(rest));
+            }
+            continue;
+        }
+
+        let mut repeat = 1;
+        let mut id = None;
+        let mut error = false;
+        let mut error_kind = None;
+        let mut error_at = None;
+        let mut incomplete = false;
+        let mut expr = false;
+        let mut output_file = None;
+        let mut output_bin = None;
+        let mut ser = None;
+        let mut de = None;
+        let mut allow_empty = false;
+        let mut warnings = Vec::new();
+        let mut meta = BTreeMap::new();
+        let mut ignore = None;
+        let mut xfail = None;
+        let mut tolerance = None;
+        let mut modes = Vec::new();
+        let mut columns: Option<Vec<&str>> = None;
+        let mut rows: Vec<(&str, Vec<&str>)> = Vec::new();
+        for directive in directives {
+            let directive = directive[1..].trim();
+            let (key, arg) = match directive.find(char::is_whitespace) {
+                Some(ix) => (&directive[..ix], directive[ix..].trim()),
+                None => (directive, ""),
+            };
+            match key {
+                "repeat" => match arg.parse() {
+                    Ok(count) if count > 0 => repeat = count,
+                    _ => errs.push(ParseError::InvalidRepeat {
+                        name: name.clone(),
+                        count: arg.to_string(),
+                    }),
+                },
+                "id" if arg.is_empty() => errs.push(ParseError::EmptyId { name: name.clone() }),
+                "id" => id = Some(arg),
+                "error" => {
+                    error = true;
+                    if !arg.is_empty() {
+                        match arg.strip_prefix("kind=").map(str::trim) {
+                            Some(kind)
+                                if !kind.is_empty() && !kind.contains(char::is_whitespace) =>
+                            {
+                                error_kind = Some(kind.to_string())
+                            }
+                            _ => errs.push(ParseError::InvalidErrorKind {
+                                name: name.clone(),
+                                arg: arg.to_string(),
+                            }),
+                        }
+                    }
+                }
+                "tolerance" => match arg.parse::<f64>() {
+                    Ok(bound) if bound.is_finite() && bound >= 0.0 => {
+                        tolerance = Some(arg.to_string())
+                    }
+                    _ => errs.push(ParseError::InvalidTolerance {
+                        name: name.clone(),
+                        tolerance: arg.to_string(),
+                    }),
+                },
+                "error_at" => {
+                    error = true;
+                    let position = arg.split_once(':').and_then(|(line, column)| {
+                        Some((line.trim().parse().ok()?, column.trim().parse().ok()?))
+                    });
+                    match position {
+                        Some((line, column)) if line > 0 && column > 0 => {
+                            error_at = Some((line, column))
+                        }
+                        _ => errs.push(ParseError::InvalidErrorPosition {
+                            name: name.clone(),
+                            arg: arg.to_string(),
+                        }),
+                    }
+                }
+                "incomplete" if !arg.is_empty() => errs.push(ParseError::IncompleteWith {
+                    name: name.clone(),
+                    conflict: String::from("an argument"),
+                }),
+                "incomplete" => incomplete = true,
+                "expr" if !arg.is_empty() => errs.push(ParseError::ExprWith {
+                    name: name.clone(),
+                    conflict: String::from("an argument"),
+                }),
+                "expr" => expr = true,
+                "ignore" => ignore = Some(arg.to_string()),
+                // the argument is a reason, as for `@ignore`
+                "xfail" => xfail = Some(arg.to_string()),
+                // the argument is a reason too, but only for the reader of the file
+                "allow_empty" => allow_empty = true,
+                "file.gz" | "bin" if arg.is_empty() => errs.push(ParseError::EmptyOutputFile {
+                    name: name.clone(),
+                    directive: key.to_string(),
+                }),
+                "file.gz" => output_file = Some(arg.to_string()),
+                "bin" => output_bin = Some(arg.to_string()),
+                "modes" => {
+                    let names: Vec<_> = arg
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|mode| !mode.is_empty())
+                        .collect();
+                    if names.is_empty() {
+                        errs.push(ParseError::UnknownMode {
+                            name: name.clone(),
+                            mode: String::new(),
+                        });
+                    }
+                    for mode in names {
+                        let mode = match mode {
+                            "exact" => Mode::Exact,
+                            "idempotent" => Mode::Idempotent,
+                            "roundtrip" => Mode::Roundtrip,
+                            _ => {
+                                errs.push(ParseError::UnknownMode {
+                                    name: name.clone(),
+                                    mode: mode.to_string(),
+                                });
+                                continue;
+                            }
+                        };
+                        if !modes.contains(&mode) {
+                            modes.push(mode);
+                        }
+                    }
+                }
+                "group" if arg.is_empty() => {
+                    errs.push(ParseError::EmptyGroup { name: name.clone() })
+                }
+                "group" => group = Some(arg.to_string()),
+                "ser" | "de" if arg.is_empty() => errs.push(ParseError::EmptyOverride {
+                    name: name.clone(),
+                    directive: key.to_string(),
+                }),
+                "warn" if arg.is_empty() => {
+                    errs.push(ParseError::EmptyWarning { name: name.clone() })
+                }
+                "warn" => warnings.push(arg.to_string()),
+                "meta" if arg.is_empty() => errs.push(ParseError::InvalidMeta {
+                    name: name.clone(),
+                    pair: String::new(),
+                }),
+                "meta" => {
+                    for pair in arg.split_whitespace() {
+                        match pair.split_once('=') {
+                            Some((key, value)) if !key.is_empty() => {
+                                if meta.insert(key.to_string(), value.to_string()).is_some() {
+                                    errs.push(ParseError::DuplicateMetaKey {
+                                        name: name.clone(),
+                                        key: key.to_string(),
+                                    });
+                                }
+                            }
+                            _ => errs.push(ParseError::InvalidMeta {
+                                name: name.clone(),
+                                pair: pair.to_string(),
+                            }),
+                        }
+                    }
+                }
+                "ser" => ser = Some(arg.to_string()),
+                "de" => de = Some(arg.to_string()),
+                "table" => columns = Some(arg.split_whitespace().collect()),
+                "row" => {
+                    let mut values = arg.split_whitespace();
+                    match values.next() {
+                        Some(key) => rows.push((key, values.collect())),
+                        None => errs.push(ParseError::RowWithoutKey { name: name.clone() }),
+                    }
+                }
+                _ => errs.push(ParseError::UnknownDirective {
+                    name: name.clone(),
+                    directive: key.to_string(),
+                }),
+            }
+        }
+
+        let (input, output) = match separators(rest, "---").last() {
+            _ if options.idempotent && !error && !incomplete && !expr => (rest, rest),
+            Some(range) => (&rest[..range.start], &rest[range.end..]),
+            // a predicate's description is optional, and equivalent inputs have no output
+            None if error
+                || incomplete
+                || options.optional_output
+                || output_file.is_some()
+                || output_bin.is_some()
+                || separators(rest, "&&&").next().is_some() =>
+            {
+                (rest, &rest[rest.len()..])
+            }
+            None => {
+                errs.push(ParseError::MissingBodySeparator {
+                    name,
+                    near_miss: near_miss(rest, "---"),
+                });
+                continue;
+            }
+        };
+        let mut inputs = split_at_separators(input, "&&&")
+            .into_iter()
+            .map(unescape_terminators);
+        let input = inputs.next().unwrap();
+        let equivalents: Vec<_> = inputs.collect();
+        let output = unescape_terminators(output);
+        if output_file.is_some() && output_bin.is_some() {
+            errs.push(ParseError::ConflictingOutputFiles { name });
+            continue;
+        }
+        let directive = match (&output_file, &output_bin) {
+            (Some(_), _) => Some("file.gz"),
+            (_, Some(_)) => Some("bin"),
+            (None, None) => None,
+        };
+        if let Some(directive) = directive {
+            let directive = directive.to_string();
+            if options.idempotent && !error {
+                errs.push(ParseError::IdempotentOutputFile { name, directive });
+                continue;
+            }
+            if !output.trim().is_empty() {
+                errs.push(ParseError::OutputFileWithOutput { name, directive });
+                continue;
+            }
+        }
+        let conflict = if !incomplete {
+            None
+        } else if error {
+            Some("`@error`")
+        } else if !equivalents.is_empty() {
+            Some("`&&&` inputs")
+        } else if !modes.is_empty() {
+            Some("`@modes`")
+        } else if output_file.is_some() {
+            Some("a `@file.gz`")
+        } else if output_bin.is_some() {
+            Some("a `@bin`")
+        } else {
+            None
+        };
+        if let Some(conflict) = conflict {
+            errs.push(ParseError::IncompleteWith {
+                name,
+                conflict: conflict.to_string(),
+            });
+            continue;
+        }
+        let conflict = if !expr {
+            None
+        } else if error {
+            Some("`@error`")
+        } else if incomplete {
+            Some("`@incomplete`")
+        } else if !equivalents.is_empty() {
+            Some("`&&&` inputs")
+        } else if !modes.is_empty() {
+            Some("`@modes`")
+        } else if output_file.is_some() {
+            Some("a `@file.gz`")
+        } else if output_bin.is_some() {
+            Some("a `@bin`")
+        } else if output.trim().is_empty() {
+            Some("an empty output")
+        } else {
+            None
+        };
+        if let Some(conflict) = conflict {
+            errs.push(ParseError::ExprWith {
+                name,
+                conflict: conflict.to_string(),
+            });
+            continue;
+        }
+        if !modes.is_empty() && (error || !equivalents.is_empty() || options.idempotent) {
+            errs.push(ParseError::ModesWithoutOutput { name });
+            continue;
+        }
+
+        let case = |name: String, id: Option<String>, input, equivalents, output| Case {
+            name,
+            id,
+            index,
+            line,
+            repeat,
+            error,
+            error_kind: error_kind.clone(),
+            error_at,
+            incomplete,
+            expr,
+            ignore: ignore.clone(),
+            xfail: xfail.clone(),
+            tolerance: tolerance.clone(),
+            group: group.clone(),
+            input,
+            equivalents,
+            output,
+            output_file: output_file.clone(),
+            output_bin: output_bin.clone(),
+            modes: modes.clone(),
+            ser: ser.clone(),
+            de: de.clone(),
+            allow_empty,
+            warnings: warnings.clone(),
+            meta: meta.clone(),
+        };
+
+        // expand a table into one case per row
+        let expanded = match (columns, rows.is_empty()) {
+            (None, true) => vec![case(
+                name,
+                id.map(str::to_string),
+                input,
+                equivalents,
+                output,
+            )],
+            (None, false) => {
+                errs.push(ParseError::RowWithoutTable { name });
+                continue;
+            }
+            (Some(_), true) => {
+                errs.push(ParseError::TableWithoutRows { name });
+                continue;
+            }
+            (Some(columns), false) => {
+                let mut expanded = Vec::with_capacity(rows.len());
+                for (key, values) in rows {
+                    if values.len() != columns.len() {
+                        errs.push(ParseError::RowLength {
+                            name: name.clone(),
+                            key: key.to_string(),
+                            values: values.len(),
+                            columns: columns.len(),
+                        });
+                        continue;
+                    }
+                    let substitute =
+                        |body: &str| substitute(body, &columns, &values).map(Cow::Owned);
+                    let substituted = substitute(&input).and_then(|input| {
+                        let equivalents = equivalents
+                            .iter()
+                            .map(|body| substitute(body))
+                            .collect::<Result<_, _>>()?;
+                        Ok((input, equivalents, substitute(&output)?))
+                    });
+                    match substituted {
+                        Ok((input, equivalents, output)) => expanded.push(case(
+                            format!("{} {}", name, key),
+                            id.map(|id| format!("{} {}", id, key)),
+                            input,
+                            equivalents,
+                            output,
+                        )),
+                        Err(placeholder) => errs.push(ParseError::UnsubstitutedPlaceholder {
+                            name: name.clone(),
+                            key: key.to_string(),
+                            placeholder,
+                        }),
+                    }
+                }
+                expanded
+            }
+        };
+
+        for case in expanded {
+            let name = case.id.as_ref().unwrap_or(&case.name);
+            if !names.insert(name.clone()) && !options.allow_duplicate_names {
+                errs.push(ParseError::DuplicateName { name: name.clone() });
+                continue;
+            }
+            cases.push(case);
+        }
+    }
+
+    if prelude.is_some() || epilogue.is_some() {
+        let surround = |body: &str| {
+            let mut lines: Vec<&str> = prelude.iter().map(|prelude| &**prelude).collect();
+            lines.push(body);
+            lines.extend(epilogue.iter().map(|epilogue| &**epilogue));
+            Cow::Owned(lines.join("\n"))
+        };
+        for case in &mut cases {
+            // an `idempotent` file's output is the input
+            if options.idempotent && !case.error && !case.incomplete {
+                case.output = surround(&case.output);
+            }
+            case.input = surround(&case.input);
+            for equivalent in &mut case.equivalents {
+                *equivalent = surround(equivalent);
+            }
+        }
+    }
+
+    if errs.is_empty() {
+        Ok(cases)
+    } else {
+        Err(errs)
+    }
+}
+
+/// Remove a backslash from each line of `body` which is a `...` terminator escaped with
+/// backslashes, so that `\...` is a literal `...` line, `\\...` a literal `\...` line, and so on.
+fn unescape_terminators(body: &str) -> Cow<'_, str> {
+    let is_escaped = |line: &str| {
+        line.starts_with('\\') && line.trim_start_matches('\\').trim_end_matches('\r') == "..."
+    };
+    if !body.split('\n').any(is_escaped) {
+        return Cow::Borrowed(body);
+    }
+    let lines: Vec<_> = body
+        .split('\n')
+        .map(|line| if is_escaped(line) { &line[1..] } else { line })
+        .collect();
+    Cow::Owned(lines.join("\n"))
+}
+
+/// Replace each `${column}` in `template` with the corresponding value.
+///
+/// Returns the name of the first placeholder left unsubstituted, if any.
+fn substitute(template: &str, columns: &[&str], values: &[&str]) -> Result<String, String> {
+    let mut s = template.to_string();
+    for (column, value) in columns.iter().zip(values) {
+        s = s.replace(&format!("${{{}}}", column), value);
+    }
+    match s.find("${") {
+        Some(ix) => {
+            let rest = &s[ix + 2..];
+            Err(rest[..rest.find('}').unwrap_or(rest.len())].to_string())
+        }
+        None => Ok(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(source: &str) -> Vec<ParseError> {
+        parse_tests(source).unwrap_err()
+    }
+
+    fn name() -> String {
+        String::from("a test")
+    }
+
+    #[test]
+    fn well_formed() {
+        let cases = parse_tests("a test\n@repeat 2\n===\nin\n---\nout\n...\n").unwrap();
+        assert_eq!(
+            cases,
+            vec![Case {
+                name: name(),
+                id: None,
+                index: 0,
+                line: 1,
+                repeat: 2,
+                error: false,
+                incomplete: false,
+                expr: false,
+                error_kind: None,
+                error_at: None,
+                ignore: None,
+                xfail: None,
+                tolerance: None,
+                group: None,
+                input: Cow::Borrowed("in"),
+                equivalents: vec![],
+                output: Cow::Borrowed("out"),
+                output_file: None,
+                output_bin: None,
+                modes: vec![],
+                ser: None,
+                de: None,
+                allow_empty: false,
+                warnings: vec![],
+                meta: BTreeMap::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn crlf_separators() {
+        let source = "+++\r\nbaseline_version = \"1.0\"\r\n+++\r\none\r\n===\r\nab\r\n\\...\r\n---\r\n[\"ab\"]\r\n...\r\n";
+        let cases = parse_tests(source).unwrap();
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].name, "one");
+        assert_eq!(cases[0].input, "ab\r\n...");
+        assert_eq!(cases[0].output, "[\"ab\"]");
+        let frontmatter = parse_frontmatter(source).unwrap();
+        assert_eq!(frontmatter.baseline_version.as_deref(), Some("1.0"));
+    }
+
+    #[test]
+    fn file_errors() {
+        assert_eq!(
+            errors("a\n===\nb\n---\nc\n..."),
+            vec![ParseError::MissingTrailingNewline]
+        );
+        assert_eq!(errors("...\n\n...\n"), vec![ParseError::OnlySeparators]);
+        assert_eq!(
+            errors("...\na\n===\nb\n---\nc\n...\n"),
+            vec![ParseError::LeadingSeparator]
+        );
+        assert_eq!(
+            errors("a\n===\nb\n---\nc\n...\nd\n"),
+            vec![ParseError::TrailingContent { near_miss: None }]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            errors("a\n===\nb\n---\nc\n...\n\n...\n"),
+            vec![ParseError::EmptyTest { index: 1 }]
+        );
+        assert_eq!(
+            errors("a test\n---\nb\n...\n"),
+            vec![ParseError::MissingNameSeparator {
+                index: 0,
+                near_miss: None,
+            }]
+        );
+        assert_eq!(
+            errors("a test\n===\nb\n...\n"),
+            vec![ParseError::MissingBodySeparator {
+                name: name(),
+                near_miss: None,
+            }]
+        );
+        let duplicated = "a test\n===\nb\n---\nc\n...\na test\n===\nd\n---\ne\n...\n";
+        assert_eq!(
+            errors(duplicated),
+            vec![ParseError::DuplicateName { name: name() }]
+        );
+        let allow_duplicate_names = ParseOptions {
+            allow_duplicate_names: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            parse_tests_with(duplicated, allow_duplicate_names)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn near_misses_are_suggested() {
+        let near_miss = |error: &ParseError| match error {
+            ParseError::TrailingContent { near_miss }
+            | ParseError::MissingNameSeparator { near_miss, .. }
+            | ParseError::MissingBodySeparator { near_miss, .. } => near_miss.clone(),
+            _ => panic!("unexpected error {:?}", error),
+        };
+        for (source, line) in &[
+            ("a\n==\nb\n---\nc\n...\n", "=="),
+            ("a\n====\nb\n---\nc\n...\n", "===="),
+            ("a\n===\nb\n--\nc\n...\n", "--"),
+            ("a\n===\nb\n----\nc\n...\n", "----"),
+            ("a\n===\nb\n---\nc\n..\n", ".."),
+            ("a\n===\nb\n---\nc\n....\n", "...."),
+        ] {
+            let errors = errors(source);
+            assert_eq!(
+                near_miss(&errors[0]).as_deref(),
+                Some(*line),
+                "{}",
+                errors[0]
+            );
+        }
+        assert_eq!(
+            errors("a\n==\nb\n...\n")[0].to_string(),
+            "test 0 does not have `===` after name; found `==`, did you mean `===`?"
+        );
+    }
+
+    #[test]
+    fn directive_errors() {
+        let error = |directive| errors(&format!("a test\n{}\n===\nb\n---\nc\n...\n", directive));
+        assert_eq!(
+            error("@repeat 0"),
+            vec![ParseError::InvalidRepeat {
+                name: name(),
+                count: String::from("0"),
+            }]
+        );
+        assert_eq!(error("@id"), vec![ParseError::EmptyId { name: name() }]);
+        assert_eq!(
+            error("@group"),
+            vec![ParseError::EmptyGroup { name: name() }]
+        );
+        assert_eq!(
+            error("@frobnicate"),
+            vec![ParseError::UnknownDirective {
+                name: name(),
+                directive: String::from("frobnicate"),
+            }]
+        );
+        assert_eq!(
+            error("@table x\n@row"),
+            vec![
+                ParseError::RowWithoutKey { name: name() },
+                ParseError::TableWithoutRows { name: name() },
+            ]
+        );
+        assert_eq!(
+            error("@row k v"),
+            vec![ParseError::RowWithoutTable { name: name() }]
+        );
+        assert_eq!(
+            error("@table x y\n@row k v"),
+            vec![ParseError::RowLength {
+                name: name(),
+                key: String::from("k"),
+                values: 1,
+                columns: 2,
+            }]
+        );
+        assert_eq!(
+            errors("a test\n@table x\n@row k v\n===\n${x}\n---\n${y}\n...\n"),
+            vec![ParseError::UnsubstitutedPlaceholder {
+                name: name(),
+                key: String::from("k"),
+                placeholder: String::from("y"),
+            }]
+        );
+    }
+
+    #[test]
+    fn frontmatter() {
+        let source = "+++\n# the tested type\ntype = \"Vec<u8>\"\n+++\na\n===\nb\n---\nc\n...\n";
+        assert_eq!(
+            parse_frontmatter(source).unwrap().r#type.as_deref(),
+            Some("Vec<u8>")
+        );
+        assert_eq!(parse_tests(source).unwrap()[0].line, 5);
+        assert_eq!(
+            parse_frontmatter("+++\nbaseline_version = \"1.2.0\"\n+++\n")
+                .unwrap()
+                .baseline_version
+                .as_deref(),
+            Some("1.2.0")
+        );
+        assert_eq!(
+            parse_frontmatter("a\n===\nb\n---\nc\n...\n").unwrap(),
+            Frontmatter::default()
+        );
+        assert_eq!(
+            parse_frontmatter("+++\n+++\n").unwrap(),
+            Frontmatter::default()
+        );
+
+        assert_eq!(
+            errors("+++\ntype = \"u8\"\na\n===\nb\n---\nc\n...\n"),
+            vec![ParseError::UnclosedFrontmatter]
+        );
+        let errors =
+            parse_frontmatter("+++\ntype = u8\nkind = \"x\"\ntype = \"u8\"\ntype = \"u16\"\n+++\n")
+                .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::InvalidFrontmatter { line: 2 },
+                ParseError::UnknownFrontmatterKey {
+                    key: String::from("kind"),
+                },
+                ParseError::DuplicateFrontmatterKey {
+                    key: String::from("type"),
+                },
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "frontmatter line 2 is not a `key = \"value\"` pair"
+        );
+    }
+
+    #[test]
+    fn ignore_has_an_optional_reason() {
+        let ignore = |directive| {
+            let source = format!("a test\n{}\n===\nb\n---\nc\n...\n", directive);
+            parse_tests(&source).unwrap()[0].ignore.clone()
+        };
+        assert_eq!(ignore("@repeat 1"), None);
+        assert_eq!(ignore("@ignore"), Some(String::new()));
+        assert_eq!(ignore("@ignore too slow"), Some(String::from("too slow")));
+    }
+
+    #[test]
+    fn xfail_has_an_optional_reason() {
+        let xfail = |directive| {
+            let source = format!("a test\n{}\n===\nb\n---\nc\n...\n", directive);
+            parse_tests(&source).unwrap()[0].xfail.clone()
+        };
+        assert_eq!(xfail("@ignore"), None);
+        assert_eq!(xfail("@xfail"), Some(String::new()));
+        assert_eq!(xfail("@xfail issue 12"), Some(String::from("issue 12")));
+    }
+
+    #[test]
+    fn tolerance_is_a_non_negative_float() {
+        let tolerance = |directive: &str| {
+            let source = format!("a test\n{}\n===\nb\n---\nc\n...\n", directive);
+            parse_tests(&source).map(|cases| cases[0].tolerance.clone())
+        };
+        assert_eq!(tolerance("@tolerance 1e-3"), Ok(Some(String::from("1e-3"))));
+        assert_eq!(tolerance("@tolerance 0"), Ok(Some(String::from("0"))));
+        assert_eq!(tolerance("@repeat 2"), Ok(None));
+        for invalid in &["", "-1", "inf", "NaN", "0.1.2", "tight"] {
+            assert_eq!(
+                tolerance(&format!("@tolerance {}", invalid)),
+                Err(vec![ParseError::InvalidTolerance {
+                    name: name(),
+                    tolerance: invalid.to_string(),
+                }])
+            );
+        }
+    }
+
+    #[test]
+    fn allow_empty_has_an_optional_reason() {
+        let cases = parse_tests(
+            "a\n@allow_empty\n===\n\n---\nb\n...\n\
+                                 c\n@allow_empty no lines\n===\n\n---\nd\n...\n\
+                                 e\n===\nf\n---\ng\n...\n",
+        )
+        .unwrap();
+        let allowed: Vec<_> = cases.iter().map(|case| case.allow_empty).collect();
+        assert_eq!(allowed, [true, true, false]);
+    }
+
+    #[test]
+    fn meta_is_collected_from_every_line() {
+        let cases = parse_tests(
+            "a\n@meta spec=RFC8259 section=7\n@meta note=\n===\nb\n---\nc\n...\n\
+             d\n===\ne\n---\nf\n...\n",
+        )
+        .unwrap();
+        let meta: Vec<_> = cases[0].meta.iter().map(|(k, v)| (&**k, &**v)).collect();
+        assert_eq!(meta, [("note", ""), ("section", "7"), ("spec", "RFC8259")]);
+        assert!(cases[1].meta.is_empty());
+        let name = String::from("a");
+        assert_eq!(
+            parse_tests("a\n@meta spec\n@meta x=1 x=2\n@meta\n===\nb\n---\nc\n...\n"),
+            Err(vec![
+                ParseError::InvalidMeta {
+                    name: name.clone(),
+                    pair: String::from("spec"),
+                },
+                ParseError::DuplicateMetaKey {
+                    name: name.clone(),
+                    key: String::from("x"),
+                },
+                ParseError::InvalidMeta {
+                    name,
+                    pair: String::new(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn modes() {
+        let modes = |directive| {
+            let source = format!("a test\n{}\n===\nb\n---\nc\n...\n", directive);
+            parse_tests(&source).map(|cases| cases[0].modes.clone())
+        };
+        assert_eq!(modes("@repeat 1"), Ok(vec![]));
+        assert_eq!(
+            modes("@modes exact, roundtrip exact"),
+            Ok(vec![Mode::Exact, Mode::Roundtrip])
+        );
+        let name = String::from("a test");
+        assert_eq!(
+            modes("@modes exact,round"),
+            Err(vec![ParseError::UnknownMode {
+                name: name.clone(),
+                mode: String::from("round"),
+            }])
+        );
+        assert_eq!(
+            modes("@modes").unwrap_err()[0].to_string(),
+            "test `a test` has `@modes` without any mode"
+        );
+        assert_eq!(
+            modes("@modes exact\n@error"),
+            Err(vec![ParseError::ModesWithoutOutput { name }])
+        );
+    }
+
+    #[test]
+    fn incomplete_tests() {
+        let incomplete = |head| {
+            let source = format!("a test\n{}\n===\n[1,\n...\n", head);
+            parse_tests(&source).map(|cases| (cases[0].incomplete, cases[0].output.to_string()))
+        };
+        assert_eq!(incomplete("@incomplete"), Ok((true, String::new())));
+        // the output isn't the input, even in an `idempotent` file
+        let idempotent = ParseOptions {
+            idempotent: true,
+            ..ParseOptions::default()
+        };
+        let cases = parse_tests_with("a\n@incomplete\n===\n[1,\n...\n", idempotent).unwrap();
+        assert_eq!(cases[0].output, "");
+        assert_eq!(
+            incomplete("@incomplete\n@error"),
+            Err(vec![ParseError::IncompleteWith {
+                name: String::from("a test"),
+                conflict: String::from("`@error`"),
+            }])
+        );
+        assert_eq!(
+            incomplete("@incomplete soon").unwrap_err()[0].to_string(),
+            "test `a test` is `@incomplete`, so it can't have an argument"
+        );
+        assert_eq!(
+            parse_tests("a\n@incomplete\n===\n[1,\n&&&\n[2,\n...\n").unwrap_err()[0].to_string(),
+            "test `a` is `@incomplete`, so it can't have `&&&` inputs"
+        );
+    }
+
+    #[test]
+    fn expr_tests() {
+        let expr = |head, output| {
+            let source = format!("a test\n{}\n===\n[1]\n---\n{}\n...\n", head, output);
+            parse_tests(&source).map(|cases| (cases[0].expr, cases[0].output.to_string()))
+        };
+        assert_eq!(
+            expr("@expr", "vec![1]"),
+            Ok((true, String::from("vec![1]")))
+        );
+        assert_eq!(
+            expr("@expr", ""),
+            Err(vec![ParseError::ExprWith {
+                name: String::from("a test"),
+                conflict: String::from("an empty output"),
+            }])
+        );
+        assert_eq!(
+            expr("@expr\n@error", "vec![1]").unwrap_err()[0].to_string(),
+            "test `a test` is `@expr`, so it can't have `@error`"
+        );
+        assert_eq!(
+            expr("@expr vec", "vec![1]").unwrap_err()[0].to_string(),
+            "test `a test` is `@expr`, so it can't have an argument"
+        );
+        // the output is still given in an `idempotent` file
+        let idempotent = ParseOptions {
+            idempotent: true,
+            ..ParseOptions::default()
+        };
+        let cases = parse_tests_with("a\n@expr\n===\n[1]\n---\nvec![1]\n...\n", idempotent);
+        assert_eq!(cases.unwrap()[0].output, "vec![1]");
+    }
+
+    #[test]
+    fn overrides() {
+        let overrides = |head| {
+            let source = format!("a test\n{}\n===\nin\n---\nout\n...\n", head);
+            parse_tests(&source).map(|cases| (cases[0].ser.clone(), cases[0].de.clone()))
+        };
+        assert_eq!(overrides("@repeat 1"), Ok((None, None)));
+        assert_eq!(
+            overrides("@ser yaml::to_string\n@de yaml::from_str"),
+            Ok((
+                Some(String::from("yaml::to_string")),
+                Some(String::from("yaml::from_str"))
+            ))
+        );
+        assert_eq!(
+            overrides("@de").unwrap_err()[0].to_string(),
+            "test `a test` has a `@de` without a path"
+        );
+    }
+
+    #[test]
+    fn warnings() {
+        let warnings = |head| {
+            let source = format!("a test\n{}\n===\nin\n---\nout\n...\n", head);
+            parse_tests(&source).map(|cases| cases[0].warnings.clone())
+        };
+        assert_eq!(warnings("@repeat 1"), Ok(vec![]));
+        assert_eq!(
+            warnings("@warn deprecated\n@warn line 2"),
+            Ok(vec![String::from("deprecated"), String::from("line 2")])
+        );
+        assert_eq!(
+            warnings("@warn").unwrap_err()[0].to_string(),
+            "test `a test` has a `@warn` without a substring"
+        );
+    }
+
+    #[test]
+    fn lenient_terminators() {
+        let lenient = ParseOptions {
+            lenient_terminator: true,
+            ..ParseOptions::default()
+        };
+        let source = "a\n===\nin\n---\nout\n...\n\nb\n===\nin\n---\nout\n";
+        assert_eq!(
+            parse_tests(source),
+            Err(vec![ParseError::TrailingContent { near_miss: None }])
+        );
+        let cases = parse_tests_with(source, lenient).unwrap();
+        assert_eq!(cases[1].name, "b");
+        assert_eq!(cases[1].line, 8);
+        assert_eq!(cases[1].output, "out");
+        // also for the only test of a file
+        let cases = parse_tests_with("a\n===\nin\n---\nout\n", lenient).unwrap();
+        assert_eq!((cases.len(), &*cases[0].output), (1, "out"));
+        // content which isn't a test is still trailing
+        assert_eq!(
+            parse_tests_with("a\n===\nin\n---\nout\n...\nnotes\n", lenient),
+            Err(vec![ParseError::TrailingContent { near_miss: None }])
+        );
+    }
+
+    #[test]
+    fn error_kinds() {
+        let kind = |directive| {
+            let source = format!("a test\n{}\n===\nb\n---\nc\n...\n", directive);
+            parse_tests(&source).map(|cases| (cases[0].error, cases[0].error_kind.clone()))
+        };
+        assert_eq!(kind("@error"), Ok((true, None)));
+        assert_eq!(
+            kind("@error kind=Syntax"),
+            Ok((true, Some(String::from("Syntax"))))
+        );
+        assert_eq!(
+            kind("@error Syntax"),
+            Err(vec![ParseError::InvalidErrorKind {
+                name: String::from("a test"),
+                arg: String::from("Syntax"),
+            }])
+        );
+        assert_eq!(
+            kind("@error kind=").unwrap_err()[0].to_string(),
+            "test `a test` has invalid `@error` argument `kind=`; expected `kind=<tag>`"
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        let at = |directive| {
+            let source = format!("a test\n{}\n===\nb\n---\nc\n...\n", directive);
+            parse_tests(&source).map(|cases| (cases[0].error, cases[0].error_at))
+        };
+        assert_eq!(at("@error"), Ok((true, None)));
+        assert_eq!(at("@error_at 2:15"), Ok((true, Some((2, 15)))));
+        assert_eq!(
+            at("@error_at 0:1").unwrap_err()[0].to_string(),
+            "test `a test` has invalid `@error_at` argument `0:1`; \
+             expected `line:column`, counting from 1"
+        );
+        assert!(at("@error_at 2").is_err());
+    }
+
+    /// A regression guard rather than a benchmark: counting lines from the start of the file
+    /// for each test made this take over a minute, where it now takes a fraction of a second.
+    ///
+    /// Parsing four times the tests must take about four times as long, not the sixteen of
+    /// quadratic time; the fastest of a few runs is taken, and the bound is loose, for noise.
+    #[test]
+    fn large_files_parse_in_linear_time() {
+        let small_cases = |count| {
+            let mut source = String::new();
+            for i in 0..count {
+                source.push_str(&format!("case {}\n===\ninput\n---\noutput\n...\n", i));
+            }
+            source
+        };
+        let fastest = |source: &str| {
+            (0..5)
+                .map(|_| {
+                    let start = std::time::Instant::now();
+                    parse_tests(source).unwrap();
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+        let (quarter, whole) = (small_cases(5_000), small_cases(20_000));
+        let ratio = fastest(&whole).as_secs_f64() / fastest(&quarter).as_secs_f64();
+        assert!(
+            ratio < 10.0,
+            "4 times the tests took {:.1} times as long",
+            ratio
+        );
+
+        let mut source = whole;
+        let large = "a".repeat(4 << 20);
+        for i in 0..3 {
+            source.push_str(&format!("large {}\n===\n{}\n---\nb\n...\n", i, large));
+        }
+        let cases = parse_tests(&source).unwrap();
+        assert_eq!(cases.len(), 20_003);
+        assert_eq!(cases[19_999].line, 6 * 19_999 + 1);
+        assert_eq!(cases[20_002].line, 6 * 20_000 + 6 * 2 + 1);
+        assert!(matches!(cases[20_002].input, Cow::Borrowed(input) if input.len() == large.len()));
+    }
+
+    #[test]
+    fn trailing_content() {
+        let source = "a\n===\nb\n---\nc\n...\nnotes\n";
+        assert_eq!(
+            parse_tests(source),
+            Err(vec![ParseError::TrailingContent { near_miss: None }])
+        );
+        let options = ParseOptions {
+            allow_trailing_content: true,
+            ..ParseOptions::default()
+        };
+        let cases = parse_tests_with(source, options).unwrap();
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].output, "c");
+    }
+
+    #[test]
+    fn output_files() {
+        let cases = parse_tests("a\n@file.gz expected/a.json.gz\n===\nb\n...\n").unwrap();
+        assert_eq!(cases[0].output_file.as_deref(), Some("expected/a.json.gz"));
+        assert_eq!(cases[0].output, "");
+        assert_eq!(
+            errors("a\n@file.gz a.gz\n===\nb\n---\nc\n...\n"),
+            vec![ParseError::OutputFileWithOutput {
+                name: String::from("a"),
+                directive: String::from("file.gz"),
+            }]
+        );
+        let idempotent = ParseOptions {
+            idempotent: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            parse_tests_with("a\n@file.gz a.gz\n===\nb\n...\n", idempotent),
+            Err(vec![ParseError::IdempotentOutputFile {
+                name: String::from("a"),
+                directive: String::from("file.gz"),
+            }])
+        );
+    }
+
+    #[test]
+    fn binary_output_files() {
+        let cases = parse_tests("a\n@bin golden/a.bin\n===\nb\n...\n").unwrap();
+        assert_eq!(cases[0].output_bin.as_deref(), Some("golden/a.bin"));
+        assert_eq!(cases[0].output_file, None);
+        assert_eq!(
+            errors("a\n@bin\n===\nb\n...\n")[0],
+            ParseError::EmptyOutputFile {
+                name: String::from("a"),
+                directive: String::from("bin"),
+            }
+        );
+        assert_eq!(
+            errors("a\n@bin a.bin\n===\nb\n---\nc\n...\n")[0].to_string(),
+            "test `a` has both a `@bin` and an output section"
+        );
+        assert_eq!(
+            errors("a\n@bin a.bin\n@file.gz a.gz\n===\nb\n...\n"),
+            vec![ParseError::ConflictingOutputFiles {
+                name: String::from("a")
+            }]
+        );
+    }
+
+    #[test]
+    fn shared_blocks() {
+        let source = "@prelude\n===\nbegin\n...\n\
+                      a\n===\nb\n&&&\nc\n...\n\
+                      d\n===\ne\n---\nf\n...\n\
+                      @epilogue\n===\nend\n...\n";
+        let cases = parse_tests(source).unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].input, "begin\nb\nend");
+        assert_eq!(cases[0].equivalents, vec!["begin\nc\nend"]);
+        assert_eq!(cases[1].input, "begin\ne\nend");
+        assert_eq!(cases[1].output, "f");
+        assert_eq!(cases[1].line, 11);
+
+        assert_eq!(
+            errors("@prelude\n===\na\n...\n@prelude\n===\nb\n...\n"),
+            vec![ParseError::DuplicateSharedBlock {
+                directive: String::from("prelude")
+            }]
+        );
+        assert_eq!(
+            errors("shared\n@epilogue\n===\na\n...\n"),
+            vec![ParseError::InvalidSharedBlock {
+                directive: String::from("epilogue")
+            }]
+        );
+    }
+
+    #[test]
+    fn escaped_terminators() {
+        let source = "a\n===\n\\...\n---\nb\n\\...\n\\\\...\nc\\...\n...\n";
+        let cases = parse_tests(source).unwrap();
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].input, "...");
+        assert_eq!(cases[0].output, "b\n...\n\\...\nc\\...");
+    }
+
+    #[test]
+    fn groups_last_until_the_next() {
+        let source = "a\n===\nb\n---\nc\n...\n\
+                      @group x\nd\n===\ne\n---\nf\n...\n\
+                      g\n===\nh\n---\ni\n...\n\
+                      @group y\nj\n===\nk\n---\nl\n...\n";
+        let groups: Vec<_> = parse_tests(source)
+            .unwrap()
+            .into_iter()
+            .map(|case| case.group)
+            .collect();
+        let group = |name: &str| Some(String::from(name));
+        assert_eq!(groups, vec![None, group("x"), group("x"), group("y")]);
+    }
+
+    #[test]
+    fn options_make_output_optional() {
+        let source = "a test\n===\nb\n...\n";
+        let idempotent = ParseOptions {
+            idempotent: true,
+            ..ParseOptions::default()
+        };
+        let cases = parse_tests_with(source, idempotent).unwrap();
+        assert_eq!(cases[0].output, "b");
+        let optional_output = ParseOptions {
+            optional_output: true,
+            ..ParseOptions::default()
+        };
+        let cases = parse_tests_with(source, optional_output).unwrap();
+        assert_eq!(cases[0].output, "");
+    }
+}
@@ -0,0 +1,332 @@
+//! Running the cases of a test file at runtime, as `conformance::tests` does at compile time.
+//!
+//! The file is parsed when it is run, so unlike with the macro, nothing is rebuilt or
+//! `include_str!`ed: editing a file just changes what the next run reads.
+
+use {
+    crate::{parse_tests_with, Case, Mode, ParseError, ParseOptions},
+    std::{error::Error, fmt, io},
+};
+
+/// The outcome of running every case of a test file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The names of the cases which passed, in order.
+    pub passed: Vec<String>,
+    /// The cases which failed, in order.
+    pub failed: Vec<Failure>,
+}
+
+/// A case which failed, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// The name of the case, or its `@id` if given.
+    pub name: String,
+    /// The line of the file the case starts on, counting from 1.
+    pub line: usize,
+    /// What went wrong.
+    pub message: String,
+}
+
+impl Report {
+    /// Whether every case passed.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for failure in &self.failed {
+            writeln!(
+                f,
+                "case `{}` (line {}) failed: {}",
+                failure.name, failure.line, failure.message
+            )?;
+        }
+        write!(
+            f,
+            "{} passed; {} failed",
+            self.passed.len(),
+            self.failed.len()
+        )
+    }
+}
+
+/// A problem reading a test file, before any case is run.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RunError {
+    /// The reader failed, or the file isn't UTF-8.
+    Io(io::Error),
+    /// The file is malformed.
+    Parse(Vec<ParseError>),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Io(e) => write!(f, "failed to read tests: {}", e),
+            RunError::Parse(errs) => {
+                let errs: Vec<_> = errs.iter().map(ToString::to_string).collect();
+                write!(f, "{}", errs.join("\n"))
+            }
+        }
+    }
+}
+
+impl Error for RunError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RunError::Io(e) => Some(e),
+            RunError::Parse(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for RunError {
+    fn from(e: io::Error) -> Self {
+        RunError::Io(e)
+    }
+}
+
+/// Read a test file from `reader`, such as stdin, and run each of its cases through `ser`,
+/// which serializes an input, as by `de` then `ser` or the tested function then `ser`.
+///
+/// Cases are checked as in the mode given by `options`:
+///
+/// - a case passes if its trimmed input serializes to its trimmed output section, as written;
+///   unlike the macro, the output isn't normalized through `de` and `ser` first,
+/// - an `@error` case passes if `ser` fails with an error containing its output section,
+///   whatever its `kind`,
+/// - the inputs of a case separated by `&&&` must all serialize the same,
+/// - a case with `@modes` passes if each of its checks does, comparing trimmed sections likewise,
+/// - a case is run as many times as its `@repeat` says,
+/// - and a case whose output is in a `@file.gz` or `@bin` fails, as the file isn't read,
+///   as does an `@incomplete` case, as `ser` can't tell that it needs more data,
+///   an `@expr` case, as its expression can't be evaluated,
+///   a case with a `@warn`, as `ser` gives no warnings, nor the position of its errors,
+///   for `@error_at`,
+///   and a case with its own `@ser` or `@de`, as those can't be called.
+///
+/// An `@xfail` case, expected to fail, passes if it fails, and fails if it passes.
+/// `@ignore`d cases are not run, nor reported, even if they are also `@xfail`.
+pub fn run_reader<R, F, E>(
+    mut reader: R,
+    options: ParseOptions,
+    mut ser: F,
+) -> Result<Report, RunError>
+where
+    R: io::Read,
+    F: FnMut(&str) -> Result<String, E>,
+    E: fmt::Display,
+{
+    let mut source = String::new();
+    reader.read_to_string(&mut source)?;
+    let cases = parse_tests_with(&source, options).map_err(RunError::Parse)?;
+
+    let mut report = Report::default();
+    for case in cases.iter().filter(|case| case.ignore.is_none()) {
+        let name = case.id.as_ref().unwrap_or(&case.name).clone();
+        let outcome = (1..=case.repeat).try_for_each(|iteration| {
+            run_case(case, &mut ser).map_err(|e| match case.repeat {
+                1 => e,
+                repeat => format!("iteration {} of {}: {}", iteration, repeat, e),
+            })
+        });
+        let outcome = match (outcome, &case.xfail) {
+            (Ok(()), Some(_)) => Err(String::from("unexpected pass, remove `@xfail`")),
+            (Err(_), Some(_)) => Ok(()),
+            (outcome, None) => outcome,
+        };
+        match outcome {
+            Ok(()) => report.passed.push(name),
+            Err(message) => report.failed.push(Failure {
+                name,
+                line: case.line,
+                message,
+            }),
+        }
+    }
+    Ok(report)
+}
+
+/// Run `case` once, describing how it failed, if it did.
+fn run_case<F, E>(case: &Case<'_>, ser: &mut F) -> Result<(), String>
+where
+    F: FnMut(&str) -> Result<String, E>,
+    E: fmt::Display,
+{
+    if let Some(output_file) = case.output_file.as_ref().or(case.output_bin.as_ref()) {
+        return Err(format!(
+            "the output is in `{}`, which isn't read at runtime",
+            output_file
+        ));
+    }
+    if case.incomplete {
+        return Err(String::from(
+            "the input is `@incomplete`, which can't be checked at runtime",
+        ));
+    }
+    if case.expr {
+        return Err(String::from(
+            "the output is an `@expr`, which can't be evaluated at runtime",
+        ));
+    }
+    if case.error_at.is_some() {
+        return Err(String::from(
+            "the input must be rejected at a position, which `ser`'s error doesn't give",
+        ));
+    }
+    if !case.warnings.is_empty() {
+        return Err(String::from(
+            "the case expects a `@warn`ing, which can't be checked at runtime",
+        ));
+    }
+    if let Some(path) = case.ser.as_ref().or(case.de.as_ref()) {
+        return Err(format!(
+            "the case is run through `{}`, which can't be called at runtime",
+            path
+        ));
+    }
+    let expected = case.output.trim();
+    if case.error {
+        return match ser(case.input.trim()) {
+            Ok(_) => Err(String::from("the input was accepted")),
+            Err(e) if e.to_string().contains(expected) => Ok(()),
+            Err(e) => Err(format!(
+                "error {:?} does not contain {:?}",
+                e.to_string(),
+                expected
+            )),
+        };
+    }
+
+    if case.equivalents.is_empty() {
+        let (input, output) = (case.input.trim(), expected);
+        let check = |ser: &mut F, input: &str, expected: &str| {
+            let actual = ser(input).map_err(|e| e.to_string())?;
+            if actual == expected {
+                Ok(())
+            } else {
+                Err(format!("expected {:?}, found {:?}", expected, actual))
+            }
+        };
+        if case.modes.is_empty() {
+            return check(ser, input, output);
+        }
+        let failures: Vec<_> = case
+            .modes
+            .iter()
+            .filter_map(|&mode| {
+                let checked = match mode {
+                    Mode::Exact => check(ser, input, output),
+                    Mode::Idempotent => check(ser, input, input),
+                    Mode::Roundtrip => check(ser, output, output),
+                };
+                checked
+                    .err()
+                    .map(|e| format!("mode `{}`: {}", mode.name(), e))
+            })
+            .collect();
+        return if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("; "))
+        };
+    }
+
+    let actual = ser(case.input.trim()).map_err(|e| e.to_string())?;
+    for (i, equivalent) in case.equivalents.iter().enumerate() {
+        let other = ser(equivalent.trim()).map_err(|e| e.to_string())?;
+        if other != actual {
+            return Err(format!(
+                "input {} serializes to {:?}, unlike input 1, which serializes to {:?}",
+                i + 2,
+                other,
+                actual
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sorts the words of an input, failing on inputs which aren't words.
+    fn sort_words(s: &str) -> Result<String, String> {
+        if !s.chars().all(|c| c.is_alphabetic() || c.is_whitespace()) {
+            return Err(format!("not words: {}", s));
+        }
+        let mut words: Vec<_> = s.split_whitespace().collect();
+        words.sort_unstable();
+        Ok(words.join(" "))
+    }
+
+    fn run(source: &str) -> Report {
+        run_reader(source.as_bytes(), ParseOptions::default(), sort_words).unwrap()
+    }
+
+    #[test]
+    fn cases_are_reported_in_order() {
+        let report = run("sorted\n===\nb a\n---\na b\n...\n\
+                          unsorted\n===\nb a\n---\nb a\n...\n\
+                          rejected\n@error\n===\n1\n---\nnot words\n...\n\
+                          equivalent\n===\nb a\n&&&\na b\n...\n\
+                          skipped\n@ignore\n===\nb a\n---\nb a\n...\n");
+        assert_eq!(report.passed, vec!["sorted", "rejected", "equivalent"]);
+        assert_eq!(
+            report.failed,
+            vec![Failure {
+                name: String::from("unsorted"),
+                line: 7,
+                message: String::from(r#"expected "b a", found "a b""#),
+            }]
+        );
+        assert!(!report.is_success());
+        assert!(report.to_string().ends_with("3 passed; 1 failed"));
+    }
+
+    #[test]
+    fn modes_are_all_checked() {
+        let report = run("checked\n@modes exact roundtrip idempotent\n===\nb a\n---\na b\n...\n");
+        assert_eq!(
+            report.failed[0].message,
+            r#"mode `idempotent`: expected "b a", found "a b""#
+        );
+    }
+
+    #[test]
+    fn xfail_cases_must_fail() {
+        let report = run(
+            "known bug\n@xfail sorting is broken\n===\nb a\n---\nb a\n...\n\
+                          fixed bug\n@xfail\n===\nb a\n---\na b\n...\n\
+                          skipped\n@ignore\n@xfail\n===\nb a\n---\na b\n...\n",
+        );
+        assert_eq!(report.passed, vec!["known bug"]);
+        assert_eq!(
+            report.failed,
+            vec![Failure {
+                name: String::from("fixed bug"),
+                line: 8,
+                message: String::from("unexpected pass, remove `@xfail`"),
+            }]
+        );
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let invalid_utf8: &[u8] = b"a\n===\n\xff\n---\nb\n...\n";
+        let default = ParseOptions::default();
+        assert!(matches!(
+            run_reader(invalid_utf8, default, sort_words),
+            Err(RunError::Io(_))
+        ));
+        assert!(matches!(
+            run_reader("a\n===\nb\n---\nc\n".as_bytes(), default, sort_words),
+            Err(RunError::Parse(_))
+        ));
+    }
+}
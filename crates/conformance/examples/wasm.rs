@@ -0,0 +1,20 @@
+//! A corpus run with `wasm-bindgen-test` in place of the standard test harness,
+//! which doesn't run on `wasm32` targets. With `wasm-bindgen-test` as a dev-dependency,
+//! run it in Node.js with:
+//!
+//! ```text
+//! wasm-pack test --node crates/conformance -- --features wasm --example wasm
+//! ```
+//!
+//! The test file is included when the tests are built, so none is read where they run.
+//! On other targets, there is nothing to run.
+
+#[cfg(target_arch = "wasm32")]
+mod tests {
+    #[conformance::tests(exact, serde=json, file="tests/ids.json.test", wasm)]
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(String::from).collect()
+    }
+}
+
+fn main() {}
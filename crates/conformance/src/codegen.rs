@@ -0,0 +1,3993 @@
+use {
+    crate::{
+        compile_error, compile_warning, names_tested_type,
+        options::{AttrArgs, Ci, Direction, FileFormat, Message, Mode, Options, Oracle, Source},
+        read::{
+            case_names_of, duplicate_warnings, identical_warnings, include_test_file, inline_tests,
+            outside_manifest_dir, oversized_warning, pair_tests, read_tests, required_cases,
+            same_subjects, similar_name_warnings, testing_fn_compares_outputs, unknown_exclusions,
+            Test,
+        },
+        with_turbofish,
+    },
+    proc_macro2::{Span, TokenStream},
+    quote::{quote, quote_spanned},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        env, fs,
+        path::{Path, PathBuf},
+    },
+    syn::spanned::Spanned,
+};
+
+/// A function comparing two YAML documents, serialized strings, with the crate `yaml`.
+///
+/// If both are mappings, their keys may be in any order, and the first missing, unexpected, or
+/// differing key is reported. Their values, or the documents if either isn't a mapping, are
+/// compared as reserialized, so the order of nested sequences and mappings matters.
+fn yaml_mismatch_fn(yaml: TokenStream) -> TokenStream {
+    quote! {
+        fn __conformance_yaml_mismatch(
+            actual: &str,
+            expected: &str,
+        ) -> ::std::result::Result<::std::option::Option<::std::string::String>, ::std::string::String> {
+            use ::std::{format, option::Option, result::Result, string::String};
+            use #yaml::Value;
+
+            let parse = |s, side| {
+                #yaml::from_str::<Value>(s).map_err(|e| format!("in {} output: {}", side, e))
+            };
+            let text = |value: &Value| #yaml::to_string(value).map_err(|e| e.to_string());
+            // a key is shown as written, without the document start a serialization begins with
+            let key = |key: &Value| -> Result<String, String> {
+                match key {
+                    Value::String(key) => Result::Ok(key.clone()),
+                    key => {
+                        let text = text(key)?;
+                        Result::Ok(String::from(text.trim_start_matches("---").trim()))
+                    }
+                }
+            };
+            let (found, wanted) = (parse(actual, "actual")?, parse(expected, "expected")?);
+            let (found, wanted) = match (&found, &wanted) {
+                (Value::Mapping(found), Value::Mapping(wanted)) => (found, wanted),
+                _ if text(&found)? == text(&wanted)? => return Result::Ok(Option::None),
+                _ => {
+                    let e = String::from("the outputs differ, and aren't both mappings");
+                    return Result::Ok(Option::Some(e));
+                }
+            };
+            for (k, wanted) in wanted.iter() {
+                match found.get(k) {
+                    Option::None => {
+                        let e = format!("top-level key `{}` is missing", key(k)?);
+                        return Result::Ok(Option::Some(e));
+                    }
+                    Option::Some(found) if text(found)? != text(wanted)? => {
+                        let e = format!("top-level key `{}` has a different value", key(k)?);
+                        return Result::Ok(Option::Some(e));
+                    }
+                    Option::Some(_) => (),
+                }
+            }
+            if let Option::Some((k, _)) = found.iter().find(|(k, _)| !wanted.contains_key(k)) {
+                let e = format!("top-level key `{}` is unexpected", key(k)?);
+                return Result::Ok(Option::Some(e));
+            }
+            Result::Ok(Option::None)
+        }
+    }
+}
+
+/// A function for the generated code which compares two XML documents with `roxmltree`,
+/// describing where they first differ, if they do.
+///
+/// Elements are compared by their expanded names, their attributes regardless of order,
+/// and their children in order, skipping comments, processing instructions, and text which is
+/// only whitespace; other text is compared without its surrounding whitespace.
+///
+/// The XML declarations are only compared with `strict_prolog`.
+fn xml_mismatch_fn(strict_prolog: bool) -> TokenStream {
+    let compare_prologs = if strict_prolog {
+        quote! {
+            /// The XML declaration `s` starts with, with its whitespace and quotes normalized.
+            fn declaration(s: &str) -> Option<String> {
+                let s = s.strip_prefix('\u{feff}').unwrap_or(s);
+                let rest = s.strip_prefix("<?xml")?;
+                if !rest.starts_with(char::is_whitespace) {
+                    return Option::None;
+                }
+                let rest = &rest[..rest.find("?>")?];
+                let parts: Vec<_> = rest.split_whitespace().collect();
+                let parts = parts.join(" ").replace(" =", "=").replace("= ", "=");
+                Option::Some(parts.replace('\'', "\""))
+            }
+            let (found, wanted) = (declaration(actual), declaration(expected));
+            if found != wanted {
+                let show = |declaration: ::std::option::Option<::std::string::String>| {
+                    declaration.map_or_else(
+                        || ::std::string::String::from("none"),
+                        |declaration| ::std::format!("`<?xml {}?>`", declaration),
+                    )
+                };
+                return ::std::result::Result::Ok(::std::option::Option::Some(::std::format!(
+                    "the XML declaration is {}, expected {}",
+                    show(found),
+                    show(wanted),
+                )));
+            }
+        }
+    } else {
+        TokenStream::new()
+    };
+    quote! {
+        fn __conformance_xml_mismatch(
+            actual: &str,
+            expected: &str,
+        ) -> ::std::result::Result<::std::option::Option<::std::string::String>, ::std::string::String> {
+            use ::roxmltree::{Document, Node};
+            use ::std::{collections::BTreeMap, format, option::Option, string::String, vec::Vec};
+
+            fn name(node: Node<'_, '_>) -> String {
+                let tag = node.tag_name();
+                match tag.namespace() {
+                    Option::Some(namespace) => format!("{{{}}}{}", namespace, tag.name()),
+                    Option::None => String::from(tag.name()),
+                }
+            }
+            fn attributes(node: Node<'_, '_>) -> BTreeMap<String, String> {
+                node.attributes()
+                    .map(|attribute| {
+                        let name = match attribute.namespace() {
+                            Option::Some(namespace) => {
+                                format!("{{{}}}{}", namespace, attribute.name())
+                            }
+                            Option::None => String::from(attribute.name()),
+                        };
+                        (name, String::from(attribute.value()))
+                    })
+                    .collect()
+            }
+            fn text<'a>(node: Node<'a, '_>) -> &'a str {
+                node.text().unwrap_or_default().trim()
+            }
+            fn children<'a, 'input>(node: Node<'a, 'input>) -> Vec<Node<'a, 'input>> {
+                node.children()
+                    .filter(|child| child.is_element() || (child.is_text() && !text(*child).is_empty()))
+                    .collect()
+            }
+            fn compare(actual: Node<'_, '_>, expected: Node<'_, '_>, path: &str) -> Option<String> {
+                let (found, wanted) = (attributes(actual), attributes(expected));
+                for (attribute, value) in &wanted {
+                    match found.get(attribute) {
+                        Option::None => {
+                            return Option::Some(format!("at `{}`: attribute `{}` is missing", path, attribute));
+                        }
+                        Option::Some(found) if found != value => {
+                            return Option::Some(format!(
+                                "at `{}`: attribute `{}` is {:?}, expected {:?}",
+                                path, attribute, found, value,
+                            ));
+                        }
+                        Option::Some(_) => (),
+                    }
+                }
+                if let Option::Some(attribute) = found.keys().find(|name| !wanted.contains_key(*name)) {
+                    return Option::Some(format!("at `{}`: attribute `{}` is unexpected", path, attribute));
+                }
+
+                let (found, wanted) = (children(actual), children(expected));
+                // elements are told apart from their siblings of the same name by their position
+                let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+                for (i, wanted) in wanted.iter().enumerate() {
+                    let child = if wanted.is_element() {
+                        let n = seen.entry(name(*wanted)).or_insert(0);
+                        *n += 1;
+                        format!("{}/{}[{}]", path, name(*wanted), n)
+                    } else {
+                        format!("{}/text()", path)
+                    };
+                    let found = match found.get(i) {
+                        Option::Some(found) => *found,
+                        Option::None => {
+                            return Option::Some(format!("at `{}`: missing, as `{}` has {} children", child, path, found.len()));
+                        }
+                    };
+                    let mismatch = match (found.is_element(), wanted.is_element()) {
+                        (true, true) if name(found) != name(*wanted) => Option::Some(format!(
+                            "at `{}`: found element `{}`",
+                            child,
+                            name(found),
+                        )),
+                        (true, true) => compare(found, *wanted, &child),
+                        (false, false) if text(found) != text(*wanted) => Option::Some(format!(
+                            "at `{}`: text is {:?}, expected {:?}",
+                            child,
+                            text(found),
+                            text(*wanted),
+                        )),
+                        (false, false) => Option::None,
+                        (true, false) => Option::Some(format!(
+                            "at `{}`: found element `{}`",
+                            child,
+                            name(found),
+                        )),
+                        (false, true) => Option::Some(format!(
+                            "at `{}`: found text {:?}",
+                            child,
+                            text(found),
+                        )),
+                    };
+                    if mismatch.is_some() {
+                        return mismatch;
+                    }
+                }
+                if found.len() > wanted.len() {
+                    return Option::Some(format!(
+                        "at `{}`: {} children, expected {}",
+                        path,
+                        found.len(),
+                        wanted.len(),
+                    ));
+                }
+                Option::None
+            }
+
+            #compare_prologs
+            let parse = |s, side| {
+                Document::parse(s).map_err(|e| format!("in {} output: {}", side, e))
+            };
+            let (found, wanted) = (parse(actual, "actual")?, parse(expected, "expected")?);
+            let (found, wanted) = (found.root_element(), wanted.root_element());
+            if name(found) != name(wanted) {
+                return ::std::result::Result::Ok(Option::Some(format!(
+                    "the root element is `{}`, expected `{}`",
+                    name(found),
+                    name(wanted),
+                )));
+            }
+            ::std::result::Result::Ok(compare(found, wanted, &format!("/{}", name(wanted))))
+        }
+    }
+}
+
+/// A function for the generated code which sorts the lines of a string.
+///
+/// Lines end at `\n` or `\r\n`, and a trailing line break doesn't start an empty line,
+/// but empty lines between others are kept, and sorted first.
+fn sorted_lines_fn() -> TokenStream {
+    quote! {
+        fn __conformance_sorted_lines(s: &str) -> ::std::string::String {
+            let mut lines: ::std::vec::Vec<&str> = s.lines().collect();
+            lines.sort_unstable();
+            lines.join("\n")
+        }
+    }
+}
+
+/// A function for the generated code which removes ANSI escape sequences.
+///
+/// Recognizes CSI (`ESC [ ... final`), OSC (`ESC ] ... BEL` or `ESC ] ... ESC \`),
+/// and two-character escapes. A truncated sequence at the end is dropped.
+fn strip_ansi_fn() -> TokenStream {
+    quote! {
+        fn __conformance_strip_ansi(s: &str) -> ::std::string::String {
+            let mut out = ::std::string::String::with_capacity(s.len());
+            let mut chars = s.chars().peekable();
+            while let ::std::option::Option::Some(c) = chars.next() {
+                if c != '\x1b' {
+                    out.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    ::std::option::Option::Some('[') => {
+                        // parameter and intermediate bytes, then one final byte
+                        while let ::std::option::Option::Some(c) = chars.next() {
+                            if ('\x40'..='\x7e').contains(&c) {
+                                break;
+                            }
+                        }
+                    }
+                    ::std::option::Option::Some(']') => {
+                        while let ::std::option::Option::Some(c) = chars.next() {
+                            if c == '\x07' {
+                                break;
+                            }
+                            if c == '\x1b' && chars.peek() == ::std::option::Option::Some(&'\\') {
+                                chars.next();
+                                break;
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            out
+        }
+    }
+}
+
+/// A function for the generated code which replaces a JSON document
+/// with the pretty-printed value at a JSON Pointer.
+///
+/// If the pointer doesn't resolve, the error names the deepest prefix that does
+/// and what could have followed it.
+fn project_fn(json: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_project(
+            s: &str,
+            pointer: &str,
+        ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            let value: #json::Value = #json::from_str(s).map_err(|e| e.to_string())?;
+            if let ::std::option::Option::Some(value) = value.pointer(pointer) {
+                return #json::to_string_pretty(value).map_err(|e| e.to_string());
+            }
+
+            let mut resolved = ::std::string::String::new();
+            let mut parent = &value;
+            for token in pointer.split('/').skip(1) {
+                let next = ::std::format!("{}/{}", resolved, token);
+                match value.pointer(&next) {
+                    ::std::option::Option::Some(value) => parent = value,
+                    ::std::option::Option::None => break,
+                }
+                resolved = next;
+            }
+            let available = match parent {
+                #json::Value::Object(map) => {
+                    let keys: ::std::vec::Vec<_> = map.keys().map(|key| ::std::format!("`{}`", key)).collect();
+                    ::std::format!("has keys {}", keys.join(", "))
+                }
+                #json::Value::Array(array) => ::std::format!("has indices 0..{}", array.len()),
+                _ => ::std::string::String::from("is not an object or array"),
+            };
+            ::std::result::Result::Err(::std::format!(
+                "JSON Pointer `{}` does not resolve; the value at `{}` {}",
+                pointer, resolved, available,
+            ))
+        }
+    }
+}
+
+/// A function replacing the strings of a JSON document which are numbers or booleans as
+/// written, at any depth, with those, for `loose_scalars`.
+///
+/// Only `true` and `false`, and strings which are a JSON number whole, without surrounding
+/// whitespace, are replaced. The keys of objects are left as they are.
+fn loose_scalars_fn(json: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_loose_scalars(
+            s: &str,
+        ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            fn coerce(value: &mut #json::Value) {
+                match value {
+                    #json::Value::String(s) => {
+                        let coerced = match s.as_str() {
+                            "true" => ::std::option::Option::Some(#json::Value::Bool(true)),
+                            "false" => ::std::option::Option::Some(#json::Value::Bool(false)),
+                            // a number may start with `-` and a digit, and may only contain
+                            // digits, `.`, `e`, `E`, `+` and `-`, so never whitespace
+                            s if s.starts_with(|c: char| c == '-' || c.is_ascii_digit())
+                                && s.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)) =>
+                            {
+                                #json::from_str::<#json::Number>(s).ok().map(#json::Value::Number)
+                            }
+                            _ => ::std::option::Option::None,
+                        };
+                        if let ::std::option::Option::Some(coerced) = coerced {
+                            *value = coerced;
+                        }
+                    }
+                    #json::Value::Array(array) => {
+                        for element in array.iter_mut() {
+                            coerce(element);
+                        }
+                    }
+                    #json::Value::Object(map) => {
+                        for (_, value) in map.iter_mut() {
+                            coerce(value);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            let mut value: #json::Value = #json::from_str(s).map_err(|e| e.to_string())?;
+            coerce(&mut value);
+            #json::to_string_pretty(&value).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// A function for the generated code which pretty-prints a JSON document
+/// with the elements of every array sorted, so that arrays compare as multisets.
+///
+/// Elements are sorted by their compact serialization, after sorting any arrays within them.
+fn sort_arrays_fn(json: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_sort_arrays(
+            s: &str,
+        ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            fn sort(value: &mut #json::Value) {
+                match value {
+                    #json::Value::Array(array) => {
+                        for element in array.iter_mut() {
+                            sort(element);
+                        }
+                        array.sort_by_cached_key(|element| #json::to_string(element).unwrap_or_default());
+                    }
+                    #json::Value::Object(map) => {
+                        for (_, value) in map.iter_mut() {
+                            sort(value);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            let mut value: #json::Value = #json::from_str(s).map_err(|e| e.to_string())?;
+            sort(&mut value);
+            #json::to_string_pretty(&value).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// A function for the generated code which pretty-prints a JSON document
+/// without the members of any object whose key is in `keys`, however deeply nested.
+fn ignore_keys_fn(json: &syn::ExprPath, keys: &[syn::LitStr]) -> TokenStream {
+    quote! {
+        fn __conformance_ignore_keys(
+            s: &str,
+        ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            const KEYS: &[&str] = &[#(#keys),*];
+            fn remove(value: &mut #json::Value) {
+                match value {
+                    #json::Value::Array(array) => {
+                        for element in array.iter_mut() {
+                            remove(element);
+                        }
+                    }
+                    #json::Value::Object(map) => {
+                        // rebuilt rather than removed from, which may reorder the kept keys
+                        *map = ::std::mem::take(map)
+                            .into_iter()
+                            .filter(|(key, _)| !KEYS.contains(&key.as_str()))
+                            .collect();
+                        for (_, value) in map.iter_mut() {
+                            remove(value);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            let mut value: #json::Value = #json::from_str(s).map_err(|e| e.to_string())?;
+            remove(&mut value);
+            #json::to_string_pretty(&value).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// A function for the generated code which parses both outputs as JSON documents,
+/// and asks `value_cmp` whether they match.
+fn values_match_fn(json: &syn::ExprPath, value_cmp: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_values_match(
+            actual: &str,
+            expected: &str,
+        ) -> ::std::result::Result<bool, ::std::string::String> {
+            let actual: #json::Value = #json::from_str(actual)
+                .map_err(|e| ::std::format!("in actual output: {}", e))?;
+            let expected: #json::Value = #json::from_str(expected)
+                .map_err(|e| ::std::format!("in expected output: {}", e))?;
+            ::std::result::Result::Ok(#value_cmp(&actual, &expected))
+        }
+    }
+}
+
+/// A function for the generated code which splits both outputs with `tokenize`,
+/// and describes where their token sequences first differ, if they do.
+fn token_mismatch_fn(tokenize: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_token_mismatch(actual: &str, expected: &str) -> ::std::option::Option<::std::string::String> {
+            let (found, wanted) = (#tokenize(actual), #tokenize(expected));
+            let i = found
+                .iter()
+                .zip(&wanted)
+                .position(|(found, wanted)| found != wanted)
+                .unwrap_or_else(|| found.len().min(wanted.len()));
+            if i == found.len() && i == wanted.len() {
+                return ::std::option::Option::None;
+            }
+            let show = |token: ::std::option::Option<_>| match token {
+                ::std::option::Option::Some(token) => ::std::format!("{:?}", token),
+                ::std::option::Option::None => ::std::string::String::from("no more tokens"),
+            };
+            ::std::option::Option::Some(::std::format!(
+                "the outputs differ at token {}: actual has {}, expected has {}",
+                i,
+                show(found.get(i)),
+                show(wanted.get(i)),
+            ))
+        }
+    }
+}
+
+/// A function for the generated code which decodes the bytes of a binary document,
+/// written in hex, or in base64 with `base64`.
+fn binary_decode_fn(base64: bool) -> TokenStream {
+    let decode = if base64 {
+        quote! {
+            let s: ::std::string::String = s.chars().filter(|c| !c.is_whitespace()).collect();
+            let mut bytes = ::std::vec::Vec::new();
+            let (mut acc, mut bits) = (0_u32, 0);
+            for c in s.trim_end_matches('=').chars() {
+                let digit = match c {
+                    'A'..='Z' => c as u32 - 'A' as u32,
+                    'a'..='z' => c as u32 - 'a' as u32 + 26,
+                    '0'..='9' => c as u32 - '0' as u32 + 52,
+                    '+' | '-' => 62,
+                    '/' | '_' => 63,
+                    _ => return ::std::result::Result::Err(::std::format!("`{}` is not base64", c)),
+                };
+                acc = acc << 6 | digit;
+                bits += 6;
+                if bits >= 8 {
+                    bits -= 8;
+                    bytes.push((acc >> bits) as u8);
+                    acc &= (1 << bits) - 1;
+                }
+            }
+            ::std::result::Result::Ok(bytes)
+        }
+    } else {
+        quote! {
+            let digits: ::std::vec::Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+            if digits.len() % 2 != 0 {
+                return ::std::result::Result::Err(::std::string::String::from("odd number of hex digits"));
+            }
+            digits
+                .chunks(2)
+                .map(|pair| {
+                    let pair: ::std::string::String = pair.iter().collect();
+                    u8::from_str_radix(&pair, 16).map_err(|_| ::std::format!("`{}` is not hex", pair))
+                })
+                .collect()
+        }
+    };
+    quote! {
+        fn __conformance_decode(s: &str) -> ::std::result::Result<::std::vec::Vec<u8>, ::std::string::String> {
+            #decode
+        }
+    }
+}
+
+/// A function for the generated code which decodes two MessagePack documents, written in hex,
+/// or in base64 with `base64`, and describes where their values first differ, if they do.
+///
+/// Integers are compared by value and `f32`s as `f64`s, and maps regardless of the order of
+/// their keys, so that encodings differing in width or order alone are the same.
+fn msgpack_mismatch_fn(base64: bool) -> TokenStream {
+    let decode = binary_decode_fn(base64);
+    quote! {
+        fn __conformance_msgpack_mismatch(
+            actual: &str,
+            expected: &str,
+        ) -> ::std::result::Result<::std::option::Option<::std::string::String>, ::std::string::String> {
+            use ::rmpv::Value;
+
+            #decode
+            fn read(s: &str, side: &str) -> ::std::result::Result<Value, ::std::string::String> {
+                let bytes = __conformance_decode(s).map_err(|e| ::std::format!("in {} output: {}", side, e))?;
+                let mut rest = &bytes[..];
+                let value = ::rmpv::decode::read_value(&mut rest)
+                    .map_err(|e| ::std::format!("in {} output: {}", side, e))?;
+                if !rest.is_empty() {
+                    return ::std::result::Result::Err(::std::format!(
+                        "in {} output: {} bytes after the value",
+                        side,
+                        rest.len(),
+                    ));
+                }
+                ::std::result::Result::Ok(value)
+            }
+            fn mismatch(path: &str, actual: &Value, expected: &Value) -> ::std::option::Option<::std::string::String> {
+                let differ = || {
+                    ::std::option::Option::Some(::std::format!(
+                        "at `{}`, expected {}, found {}",
+                        path, expected, actual,
+                    ))
+                };
+                match (actual, expected) {
+                    (Value::Array(actual), Value::Array(expected)) if actual.len() == expected.len() => actual
+                        .iter()
+                        .zip(expected)
+                        .enumerate()
+                        .find_map(|(i, (actual, expected))| {
+                            mismatch(&::std::format!("{}[{}]", path, i), actual, expected)
+                        }),
+                    (Value::Map(actual), Value::Map(expected)) if actual.len() == expected.len() => {
+                        expected.iter().find_map(|(key, expected)| {
+                            let path = ::std::format!("{}[{}]", path, key);
+                            match actual.iter().find(|(other, _)| mismatch("", other, key).is_none()) {
+                                ::std::option::Option::Some((_, actual)) => mismatch(&path, actual, expected),
+                                ::std::option::Option::None => ::std::option::Option::Some(::std::format!("at `{}`, expected {}, found nothing", path, expected)),
+                            }
+                        })
+                    }
+                    (Value::F32(actual), Value::F64(expected)) if f64::from(*actual) == *expected => ::std::option::Option::None,
+                    (Value::F64(actual), Value::F32(expected)) if *actual == f64::from(*expected) => ::std::option::Option::None,
+                    _ if actual == expected => ::std::option::Option::None,
+                    _ => differ(),
+                }
+            }
+
+            let actual = read(actual, "actual")?;
+            let expected = read(expected, "expected")?;
+            ::std::result::Result::Ok(mismatch("$", &actual, &expected))
+        }
+    }
+}
+
+/// A function for the generated code which decodes two CBOR documents, written in hex,
+/// or in base64 with `base64`, and describes where their values first differ, if they do.
+///
+/// `ciborium` decodes integers of any width and floats of any precision alike, and items of
+/// definite and indefinite length alike, so encodings differing in those alone are the same.
+/// Maps are compared regardless of the order of their keys, as canonical CBOR sorts them
+/// but other encoders needn't. Values are described in CBOR's diagnostic notation.
+fn cbor_mismatch_fn(base64: bool) -> TokenStream {
+    let decode = binary_decode_fn(base64);
+    quote! {
+        fn __conformance_cbor_mismatch(
+            actual: &str,
+            expected: &str,
+        ) -> ::std::result::Result<::std::option::Option<::std::string::String>, ::std::string::String> {
+            use ::ciborium::value::Value;
+
+            #decode
+            fn read(s: &str, side: &str) -> ::std::result::Result<Value, ::std::string::String> {
+                let bytes = __conformance_decode(s).map_err(|e| ::std::format!("in {} output: {}", side, e))?;
+                let mut rest = &bytes[..];
+                let value: Value = ::ciborium::de::from_reader(&mut rest)
+                    .map_err(|e| ::std::format!("in {} output: {:?}", side, e))?;
+                if !rest.is_empty() {
+                    return ::std::result::Result::Err(::std::format!(
+                        "in {} output: {} bytes after the value",
+                        side,
+                        rest.len(),
+                    ));
+                }
+                ::std::result::Result::Ok(value)
+            }
+            fn show(value: &Value) -> ::std::string::String {
+                let join = |items: ::std::vec::Vec<::std::string::String>| items.join(", ");
+                #[allow(unreachable_patterns)]
+                match value {
+                    Value::Integer(n) => i128::from(*n).to_string(),
+                    Value::Float(f) => ::std::format!("{:?}", f),
+                    Value::Bytes(bytes) => ::std::format!(
+                        "h'{}'",
+                        bytes.iter().map(|b| ::std::format!("{:02x}", b)).collect::<::std::string::String>(),
+                    ),
+                    Value::Text(s) => ::std::format!("{:?}", s),
+                    Value::Bool(b) => b.to_string(),
+                    Value::Null => ::std::string::String::from("null"),
+                    Value::Tag(tag, value) => ::std::format!("{}({})", tag, show(value)),
+                    Value::Array(items) => ::std::format!("[{}]", join(items.iter().map(show).collect())),
+                    Value::Map(entries) => ::std::format!(
+                        "{{{}}}",
+                        join(entries.iter().map(|(k, v)| ::std::format!("{}: {}", show(k), show(v))).collect()),
+                    ),
+                    _ => ::std::format!("{:?}", value),
+                }
+            }
+            fn mismatch(path: &str, actual: &Value, expected: &Value) -> ::std::option::Option<::std::string::String> {
+                match (actual, expected) {
+                    (Value::Array(actual), Value::Array(expected)) if actual.len() == expected.len() => actual
+                        .iter()
+                        .zip(expected)
+                        .enumerate()
+                        .find_map(|(i, (actual, expected))| {
+                            mismatch(&::std::format!("{}[{}]", path, i), actual, expected)
+                        }),
+                    (Value::Map(actual), Value::Map(expected)) if actual.len() == expected.len() => {
+                        expected.iter().find_map(|(key, expected)| {
+                            let path = ::std::format!("{}[{}]", path, show(key));
+                            match actual.iter().find(|(other, _)| mismatch("", other, key).is_none()) {
+                                ::std::option::Option::Some((_, actual)) => mismatch(&path, actual, expected),
+                                ::std::option::Option::None => ::std::option::Option::Some(::std::format!("at `{}`, expected {}, found nothing", path, show(expected))),
+                            }
+                        })
+                    }
+                    (Value::Tag(tag, actual), Value::Tag(other, expected)) if tag == other => mismatch(path, actual, expected),
+                    _ if actual == expected => ::std::option::Option::None,
+                    _ => ::std::option::Option::Some(::std::format!(
+                        "at `{}`, expected {}, found {}",
+                        path, show(expected), show(actual),
+                    )),
+                }
+            }
+
+            let actual = read(actual, "actual")?;
+            let expected = read(expected, "expected")?;
+            ::std::result::Result::Ok(mismatch("$", &actual, &expected))
+        }
+    }
+}
+
+/// The `tolerance` of `approx` mode when none is given.
+const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+/// A function for the generated code which parses two JSON documents and describes where they
+/// first differ, if they do, for `approx` mode.
+///
+/// Numbers are the same if they are at most `tolerance` apart, as `f64`s; everything else,
+/// including the keys of objects, in any order, and the lengths of arrays, must be equal.
+fn approx_mismatch_fn(json: &syn::ExprPath, tolerance: f64) -> TokenStream {
+    let tolerance = proc_macro2::Literal::f64_suffixed(tolerance);
+    quote! {
+        fn __conformance_approx_mismatch(
+            actual: &str,
+            expected: &str,
+        ) -> ::std::result::Result<::std::option::Option<::std::string::String>, ::std::string::String> {
+            use #json::Value;
+            const TOLERANCE: f64 = #tolerance;
+
+            fn mismatch(path: &str, actual: &Value, expected: &Value) -> ::std::option::Option<::std::string::String> {
+                match (actual, expected) {
+                    (Value::Number(found), Value::Number(wanted)) => match (found.as_f64(), wanted.as_f64()) {
+                        (::std::option::Option::Some(found), ::std::option::Option::Some(wanted))
+                            if (found - wanted).abs() <= TOLERANCE => ::std::option::Option::None,
+                        _ => ::std::option::Option::Some(::std::format!(
+                            "at `{}`, expected {}, found {}, more than {:e} apart",
+                            path, expected, actual, TOLERANCE,
+                        )),
+                    },
+                    (Value::Array(actual), Value::Array(expected)) if actual.len() == expected.len() => actual
+                        .iter()
+                        .zip(expected)
+                        .enumerate()
+                        .find_map(|(i, (actual, expected))| {
+                            mismatch(&::std::format!("{}[{}]", path, i), actual, expected)
+                        }),
+                    (Value::Object(actual), Value::Object(expected)) => {
+                        let missing = expected.iter().find_map(|(key, expected)| {
+                            let path = ::std::format!("{}.{}", path, key);
+                            match actual.get(key) {
+                                ::std::option::Option::Some(actual) => mismatch(&path, actual, expected),
+                                ::std::option::Option::None => ::std::option::Option::Some(
+                                    ::std::format!("at `{}`, expected {}, found nothing", path, expected),
+                                ),
+                            }
+                        });
+                        missing.or_else(|| {
+                            actual.iter().find(|(key, _)| !expected.contains_key(*key)).map(|(key, actual)| {
+                                ::std::format!("at `{}.{}`, expected nothing, found {}", path, key, actual)
+                            })
+                        })
+                    }
+                    _ if actual == expected => ::std::option::Option::None,
+                    _ => ::std::option::Option::Some(::std::format!(
+                        "at `{}`, expected {}, found {}",
+                        path, expected, actual,
+                    )),
+                }
+            }
+
+            let read = |s: &str, side: &str| {
+                #json::from_str::<Value>(s).map_err(|e| ::std::format!("in {} output: {}", side, e))
+            };
+            let actual = read(actual, "actual")?;
+            let expected = read(expected, "expected")?;
+            ::std::result::Result::Ok(mismatch("$", &actual, &expected))
+        }
+    }
+}
+
+/// A function for the generated code which parses the actual output and the expected template
+/// as JSON documents, and describes where the output first differs, if it does.
+///
+/// Each `<<name>>` of the template outside of a string is a placeholder matching any value,
+/// read as a string no output holds; those of the same name must match the same value,
+/// except for `<<_>>`.
+fn template_mismatch_fn(json: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_template_mismatch(
+            actual: &str,
+            template: &str,
+        ) -> ::std::result::Result<::std::option::Option<::std::string::String>, ::std::string::String> {
+            use #json::Value;
+
+            fn substitute(template: &str) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+                let mut substituted = ::std::string::String::with_capacity(template.len());
+                let (mut in_string, mut escaped) = (false, false);
+                let mut rest = template;
+                while let ::std::option::Option::Some(c) = rest.chars().next() {
+                    if !in_string && rest.starts_with("<<") {
+                        let end = rest[2..].find(">>").ok_or("in expected template: `<<` has no `>>`")?;
+                        let name = &rest[2..2 + end];
+                        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                            return ::std::result::Result::Err(::std::format!(
+                                "in expected template: `<<{}>>` isn't a placeholder",
+                                name,
+                            ));
+                        }
+                        substituted.push_str("\"\\u0000");
+                        substituted.push_str(name);
+                        substituted.push('"');
+                        rest = &rest[2 + end + 2..];
+                        continue;
+                    }
+                    if in_string {
+                        in_string = escaped || c != '"';
+                        escaped = !escaped && c == '\\';
+                    } else {
+                        in_string = c == '"';
+                    }
+                    substituted.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+                ::std::result::Result::Ok(substituted)
+            }
+            fn mismatch(
+                path: &str,
+                actual: &Value,
+                template: &Value,
+                captures: &mut ::std::collections::BTreeMap<::std::string::String, Value>,
+            ) -> ::std::option::Option<::std::string::String> {
+                match (actual, template) {
+                    (_, Value::String(hole)) if hole.starts_with('\0') => {
+                        let name = &hole[1..];
+                        match captures.get(name) {
+                            _ if name == "_" => ::std::option::Option::None,
+                            ::std::option::Option::Some(earlier) if earlier != actual => {
+                                ::std::option::Option::Some(::std::format!(
+                                    "at `{}`, `<<{}>>` is {}, but was {} before",
+                                    path, name, actual, earlier,
+                                ))
+                            }
+                            ::std::option::Option::Some(_) => ::std::option::Option::None,
+                            ::std::option::Option::None => {
+                                captures.insert(::std::string::ToString::to_string(name), actual.clone());
+                                ::std::option::Option::None
+                            }
+                        }
+                    }
+                    (Value::Array(actual), Value::Array(template)) if actual.len() == template.len() => actual
+                        .iter()
+                        .zip(template)
+                        .enumerate()
+                        .find_map(|(i, (actual, template))| {
+                            mismatch(&::std::format!("{}[{}]", path, i), actual, template, captures)
+                        }),
+                    (Value::Object(actual), Value::Object(template)) => {
+                        let missing = template.iter().find_map(|(key, template)| {
+                            let path = ::std::format!("{}.{}", path, key);
+                            match actual.get(key) {
+                                ::std::option::Option::Some(actual) => mismatch(&path, actual, template, captures),
+                                ::std::option::Option::None => ::std::option::Option::Some(
+                                    ::std::format!("at `{}`, expected {}, found nothing", path, template),
+                                ),
+                            }
+                        });
+                        missing.or_else(|| {
+                            actual.iter().find(|(key, _)| !template.contains_key(*key)).map(|(key, actual)| {
+                                ::std::format!("at `{}.{}`, expected nothing, found {}", path, key, actual)
+                            })
+                        })
+                    }
+                    _ if actual == template => ::std::option::Option::None,
+                    _ => ::std::option::Option::Some(::std::format!(
+                        "at `{}`, expected {}, found {}",
+                        path, template, actual,
+                    )),
+                }
+            }
+
+            let substituted = substitute(template)?;
+            let template: Value = #json::from_str(&substituted)
+                .map_err(|e| ::std::format!("in expected template: {}", e))?;
+            let actual: Value = #json::from_str(actual)
+                .map_err(|e| ::std::format!("in actual output: {}", e))?;
+            let mut captures = ::std::collections::BTreeMap::new();
+            ::std::result::Result::Ok(mismatch("$", &actual, &template, &mut captures))
+        }
+    }
+}
+
+/// A function for the generated code which pretty-prints a JSON document
+/// with the keys of every object sorted, however deeply nested.
+///
+/// A parsed `Value` only keeps keys in their original order with `preserve_order`,
+/// so they are sorted explicitly rather than relying on the map type.
+fn sort_keys_fn(json: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_sort_keys(
+            s: &str,
+        ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            fn sort(value: &mut #json::Value) {
+                match value {
+                    #json::Value::Array(array) => {
+                        for element in array.iter_mut() {
+                            sort(element);
+                        }
+                    }
+                    #json::Value::Object(map) => {
+                        let mut entries: ::std::vec::Vec<_> = ::std::mem::take(map).into_iter().collect();
+                        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                        for (_, value) in entries.iter_mut() {
+                            sort(value);
+                        }
+                        *map = entries.into_iter().collect();
+                    }
+                    _ => (),
+                }
+            }
+
+            let mut value: #json::Value = #json::from_str(s).map_err(|e| e.to_string())?;
+            sort(&mut value);
+            #json::to_string_pretty(&value).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// A function for the generated code which parses a JSON document, mutates it in place with
+/// `value_mut`, a `fn(&mut Value)`, and reserializes it.
+fn value_mut_fn(json: &syn::ExprPath, value_mut: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_value_mut(
+            s: &str,
+        ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            let mut value: #json::Value = #json::from_str(s).map_err(|e| e.to_string())?;
+            #value_mut(&mut value);
+            #json::to_string_pretty(&value).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// A function for the generated code which runs a jq program over a JSON document with `jaq`,
+/// giving its outputs compactly, each on its own line.
+fn jq_fn() -> TokenStream {
+    quote! {
+        fn __conformance_jq(
+            json: &str,
+            program: &str,
+        ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            use ::jaq_core::load::{Arena, Error, File, Loader};
+            let arena = Arena::default();
+            let compile = |code: &str| {
+                let loader = Loader::new(::jaq_std::defs().chain(::jaq_json::defs()));
+                let modules = loader.load(&arena, File { code, path: () }).map_err(|errs| {
+                    let errs: ::std::vec::Vec<::std::string::String> = errs
+                        .into_iter()
+                        .flat_map(|(_, e)| -> ::std::vec::Vec<::std::string::String> {
+                            let expected = |(expected, found): (&str, &str)| {
+                                ::std::format!("expected {} at `{}`", expected, found)
+                            };
+                            match e {
+                                Error::Io(errs) => errs.into_iter().map(|(_, e)| e).collect(),
+                                Error::Lex(errs) => errs
+                                    .into_iter()
+                                    .map(|(e, found)| expected((e.as_str(), found)))
+                                    .collect(),
+                                Error::Parse(errs) => errs
+                                    .into_iter()
+                                    .map(|(e, found)| expected((e.as_str(), found)))
+                                    .collect(),
+                            }
+                        })
+                        .collect();
+                    errs.join("; ")
+                })?;
+                ::jaq_core::Compiler::default()
+                    .with_funs(::jaq_std::funs().chain(::jaq_json::funs()))
+                    .compile(modules)
+                    .map_err(|errs| {
+                        let errs: ::std::vec::Vec<::std::string::String> = errs
+                            .into_iter()
+                            .flat_map(|(_, errs)| errs)
+                            .map(|(name, undefined)| {
+                                ::std::format!("undefined {} `{}`", undefined.as_str(), name)
+                            })
+                            .collect();
+                        errs.join("; ")
+                    })
+            };
+            let run = |filter: &::jaq_core::Filter<::jaq_core::Native<::jaq_json::Val>>, value| {
+                let inputs = ::jaq_core::RcIter::new(::std::iter::empty());
+                filter
+                    .run((::jaq_core::Ctx::new([], &inputs), value))
+                    .map(|output| output.map_err(|e| e.to_string()))
+                    .collect::<::std::result::Result<::std::vec::Vec<_>, _>>()
+            };
+
+            let filter = compile(program)
+                .map_err(|e| ::std::format!("invalid jq program `{}`: {}", program, e))?;
+            // the document is parsed by jaq itself, as a string given to `fromjson`
+            let parse = compile("fromjson").expect("`fromjson` is defined");
+            let values = run(&parse, ::jaq_json::Val::from(json.to_string()))?;
+            let mut outputs = ::std::vec::Vec::new();
+            for value in values {
+                let output = run(&filter, value)
+                    .map_err(|e| ::std::format!("jq program `{}` failed: {}", program, e))?;
+                outputs.extend(output.iter().map(|output| output.to_string()));
+            }
+            ::std::result::Result::Ok(outputs.join("\n"))
+        }
+    }
+}
+
+/// A function for the generated code which applies the `preprocess` function to an input.
+///
+/// The function may return either a `String` or a `Result<String, E>`.
+fn preprocess_fn(preprocess: &syn::ExprPath) -> TokenStream {
+    quote! {
+        fn __conformance_preprocess(
+            input: &str,
+        ) -> ::std::result::Result<::std::string::String, ::std::boxed::Box<dyn ::std::error::Error>> {
+            trait Preprocessed {
+                fn into_result(
+                    self,
+                ) -> ::std::result::Result<::std::string::String, ::std::string::String>;
+            }
+            impl Preprocessed for ::std::string::String {
+                fn into_result(
+                    self,
+                ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+                    ::std::result::Result::Ok(self)
+                }
+            }
+            impl<E: ::std::fmt::Display> Preprocessed for ::std::result::Result<::std::string::String, E> {
+                fn into_result(
+                    self,
+                ) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+                    self.map_err(|e| ::std::string::ToString::to_string(&e))
+                }
+            }
+
+            Preprocessed::into_result(#preprocess(input)).map_err(|e| {
+                ::std::convert::From::from(::std::format!("`preprocess` failed: {}", e))
+            })
+        }
+    }
+}
+
+/// A function for the generated code which describes why an output didn't match a pattern.
+///
+/// Where the pattern has a line for each line of the output, and each can be matched on its own,
+/// the first line that doesn't match is pointed out.
+fn regex_mismatch_fn() -> TokenStream {
+    quote! {
+        fn __conformance_regex_mismatch(pattern: &str, actual: &str) -> ::std::string::String {
+            let mut message = ::std::format!(
+                "output does not match the pattern\n  actual: {}\n pattern: {}",
+                actual, pattern,
+            );
+            let patterns: ::std::vec::Vec<_> = pattern.lines().collect();
+            let actuals: ::std::vec::Vec<_> = actual.lines().collect();
+            if patterns.len() != actuals.len() {
+                message.push_str(&::std::format!(
+                    "\nthe output has {} lines, the pattern {}",
+                    actuals.len(),
+                    patterns.len(),
+                ));
+                return message;
+            }
+            for (i, (pattern, actual)) in patterns.iter().zip(&actuals).enumerate() {
+                let line = match ::regex::Regex::new(&::std::format!("^(?:{})$", pattern)) {
+                    ::std::result::Result::Ok(line) => line,
+                    // the pattern has constructs spanning lines
+                    ::std::result::Result::Err(_) => break,
+                };
+                if !line.is_match(actual) {
+                    message.push_str(&::std::format!(
+                        "\nfirst mismatch on line {}\n  actual: {}\n pattern: {}",
+                        i + 1,
+                        actual,
+                        pattern,
+                    ));
+                    break;
+                }
+            }
+            message
+        }
+    }
+}
+
+/// A function for the generated code which describes where two strings diverge:
+/// the byte offset, line, and column, with a caret under the surrounding context.
+///
+/// It is only called once a comparison has already failed.
+fn first_difference_fn() -> TokenStream {
+    quote! {
+        fn __conformance_first_difference(actual: &str, expected: &str) -> ::std::string::String {
+            const CONTEXT: usize = 40;
+
+            let mut offset = actual
+                .bytes()
+                .zip(expected.bytes())
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| actual.len().min(expected.len()));
+            while !actual.is_char_boundary(offset) || !expected.is_char_boundary(offset) {
+                offset -= 1;
+            }
+
+            let line_start = actual[..offset].rfind('\n').map_or(0, |i| i + 1);
+            let line = actual[..offset].matches('\n').count() + 1;
+            let column = actual[line_start..offset].chars().count() + 1;
+
+            let skip = (column - 1).saturating_sub(CONTEXT);
+            let context = |s: &str| -> ::std::string::String {
+                let rest = &s[line_start..];
+                let rest = &rest[..rest.find('\n').unwrap_or(rest.len())];
+                let mut context: ::std::string::String = rest.chars().skip(skip).take(2 * CONTEXT).collect();
+                if skip > 0 {
+                    context.insert_str(0, "...");
+                }
+                if rest.chars().count() > skip + 2 * CONTEXT {
+                    context.push_str("...");
+                }
+                context
+            };
+            let caret = column - 1 - skip + if skip > 0 { 3 } else { 0 };
+
+            ::std::format!(
+                "first difference at byte {} (line {}, col {})\n  actual: {}\nexpected: {}\n          {}^",
+                offset,
+                line,
+                column,
+                context(actual),
+                context(expected),
+                " ".repeat(caret),
+            )
+        }
+    }
+}
+
+/// A function for the generated code which renders the lines that differ between two strings,
+/// with no more than `max_lines` lines of the differing region.
+///
+/// Lines common to the start and end of both are skipped; there's no finer alignment than that.
+/// It is only called once a comparison has already failed.
+fn line_diff_fn() -> TokenStream {
+    quote! {
+        fn __conformance_line_diff(actual: &str, expected: &str, max_lines: usize) -> ::std::string::String {
+            let actual: ::std::vec::Vec<&str> = actual.lines().collect();
+            let expected: ::std::vec::Vec<&str> = expected.lines().collect();
+            let prefix = actual
+                .iter()
+                .zip(&expected)
+                .take_while(|(a, b)| a == b)
+                .count();
+            let suffix = actual[prefix..]
+                .iter()
+                .rev()
+                .zip(expected[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            let removed = expected[prefix..expected.len() - suffix].iter().map(|line| ('-', line));
+            let added = actual[prefix..actual.len() - suffix].iter().map(|line| ('+', line));
+            let lines: ::std::vec::Vec<_> = removed.chain(added).collect();
+            let mut diff = ::std::format!("--- expected\n+++ actual\n@@ line {} @@", prefix + 1);
+            for (sign, line) in lines.iter().take(max_lines) {
+                diff.push_str(&::std::format!("\n{}{}", sign, line));
+            }
+            if lines.len() > max_lines {
+                diff.push_str(&::std::format!("\n... ({} more lines)", lines.len() - max_lines));
+            }
+            diff
+        }
+    }
+}
+
+/// A function for the generated code which runs the `oracle` command on an input,
+/// returning its standard output without trailing newlines.
+///
+/// `{input_file}` is replaced with the path of a temporary file containing the input,
+/// and `{input}` with the input itself. The command is run in the consuming crate's directory.
+fn oracle_fn(oracle: &Oracle) -> TokenStream {
+    let Oracle { command, args } = oracle;
+    quote! {
+        fn __conformance_oracle(input: &str) -> ::std::result::Result<::std::string::String, ::std::string::String> {
+            // tests run in parallel, so each input gets a file of its own
+            static FILES: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+            let file = ::std::env::temp_dir().join(::std::format!(
+                "conformance-oracle-{}-{}",
+                ::std::process::id(),
+                FILES.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed),
+            ));
+            ::std::fs::write(&file, input).map_err(|e| {
+                ::std::format!("failed to write the oracle's input to `{}`: {}", file.display(), e)
+            })?;
+            let args: ::std::vec::Vec<::std::string::String> = [#(#args),*]
+                .iter()
+                .map(|arg| arg.replace("{input_file}", &file.to_string_lossy()).replace("{input}", input))
+                .collect();
+            let output = ::std::process::Command::new(&args[0])
+                .args(&args[1..])
+                .current_dir(::std::env!("CARGO_MANIFEST_DIR"))
+                .output();
+            let _ = ::std::fs::remove_file(&file);
+
+            let output = output.map_err(|e| ::std::format!("failed to run oracle `{}`: {}", #command, e))?;
+            if !output.status.success() {
+                return ::std::result::Result::Err(::std::format!(
+                    "oracle `{}` failed with {}:\n{}",
+                    #command,
+                    output.status,
+                    ::std::string::String::from_utf8_lossy(&output.stderr),
+                ));
+            }
+            let stdout = ::std::string::String::from_utf8(output.stdout)
+                .map_err(|e| ::std::format!("oracle `{}` printed invalid UTF-8: {}", #command, e))?;
+            ::std::result::Result::Ok(stdout.trim_end_matches(|c| c == '\n' || c == '\r').to_string())
+        }
+    }
+}
+
+/// Invert the outcome of `body`, running `test`, if it is `@xfail`: it then passes if the test
+/// fails, with an error or a panic, and fails if the test passes.
+fn expect_failure(body: TokenStream, test: &Test, options: &Options) -> TokenStream {
+    let reason = match &test.xfail {
+        Some(reason) => reason,
+        None => return body,
+    };
+    let Test {
+        title, file, line, ..
+    } = test;
+    // a panic aborts, rather than unwinds, on `wasm32-unknown-unknown`
+    if options.wasm {
+        let e = format!(
+            "{}:{}: test `{}` is `@xfail`, which `wasm` tests can't be, \
+             as a failure may panic, which they can't catch",
+            file, line, title
+        );
+        return compile_error(&e, Span::call_site());
+    }
+    let e = match reason.as_str() {
+        "" => format!(
+            "{}:{}: test `{}`: unexpected pass, remove `@xfail`",
+            file, line, title
+        ),
+        reason => format!(
+            "{}:{}: test `{}`: unexpected pass, remove `@xfail` ({})",
+            file, line, title, reason
+        ),
+    };
+    quote! {
+        match ::std::panic::catch_unwind(|| -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> { #body }) {
+            ::std::result::Result::Ok(::std::result::Result::Ok(())) => {
+                ::std::result::Result::Err(::std::convert::From::from(#e))
+            }
+            _ => ::std::result::Result::Ok(()),
+        }
+    }
+}
+
+/// The attribute making a generated function a test: `#[test]`, or with `wasm`,
+/// `wasm-bindgen-test`'s, which runs it in a WebAssembly runtime instead.
+fn test_attr(options: &Options) -> TokenStream {
+    if options.wasm {
+        quote!(#[::wasm_bindgen_test::wasm_bindgen_test])
+    } else {
+        quote!(#[test])
+    }
+}
+
+/// Generate the `#[test]` function `test_name` running `test` through `testing_fn`.
+///
+/// `contents` is the constant holding the included test file.
+fn test_fn(
+    test_name: &syn::Ident,
+    testing_fn: &syn::Ident,
+    contents: &syn::Ident,
+    test: &Test,
+    options: &Options,
+) -> TokenStream {
+    let body = expect_failure(
+        case_body(testing_fn, contents, test, options),
+        test,
+        options,
+    );
+
+    // an `@ignore`d test is ignored whatever the features, and may only be ignored once
+    let ignore = match (&test.ignore, &options.ignore_unless_feature) {
+        (Some(reason), _) if reason.is_empty() => quote!(#[ignore]),
+        (Some(reason), _) => quote!(#[ignore = #reason]),
+        (None, Some(feature)) => quote!(#[cfg_attr(not(feature = #feature), ignore)]),
+        (None, None) => TokenStream::new(),
+    };
+    let test = test_attr(options);
+
+    quote! {
+        #test
+        #ignore
+        fn #test_name() -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+            #body
+        }
+    }
+}
+
+/// Generate the `#[test]` function `test_name` running every case of `tests` in turn,
+/// which reports all of those failing at once, rather than stopping at the first.
+///
+/// `@ignore`d cases are skipped and counted.
+fn aggregate_test_fn(
+    test_name: &syn::Ident,
+    testing_fn: &syn::Ident,
+    contents: &syn::Ident,
+    tests: &[Test],
+    options: &Options,
+) -> TokenStream {
+    let ignored = tests.iter().filter(|test| test.ignore.is_some()).count();
+    let run = tests.iter().filter(|test| test.ignore.is_none());
+    let total = run.clone().count();
+    let cases = run.map(|test| {
+        let body = expect_failure(case_body(testing_fn, contents, test, options), test, options);
+        let Test {
+            title, file, line, ..
+        } = test;
+        quote! {
+            match ::std::panic::catch_unwind(|| -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> { #body }) {
+                ::std::result::Result::Ok(::std::result::Result::Ok(())) => (),
+                ::std::result::Result::Ok(::std::result::Result::Err(e)) => failures.push(e.to_string()),
+                ::std::result::Result::Err(panic) => {
+                    let message = panic
+                        .downcast_ref::<::std::string::String>()
+                        .map(::std::string::String::as_str)
+                        .or_else(|| panic.downcast_ref::<&str>().copied())
+                        .unwrap_or("panicked");
+                    failures.push(::std::format!("{}:{}: case `{}`: {}", #file, #line, #title, message));
+                }
+            }
+        }
+    });
+    let ignore = match &options.ignore_unless_feature {
+        Some(feature) => quote!(#[cfg_attr(not(feature = #feature), ignore)]),
+        None => TokenStream::new(),
+    };
+    let test_attr = test_attr(options);
+
+    quote! {
+        #test_attr
+        #ignore
+        fn #test_name() {
+            let mut failures: ::std::vec::Vec<::std::string::String> = ::std::vec::Vec::new();
+            #(#cases)*
+            if !failures.is_empty() {
+                ::std::panic!(
+                    "{} of {} cases failed ({} passed; {} ignored):\n{}",
+                    failures.len(),
+                    #total,
+                    #total - failures.len(),
+                    #ignored,
+                    failures.join("\n"),
+                );
+            }
+        }
+    }
+}
+
+/// Generate the `#[test]` function `test_name` printing a table of `tests` to stderr, with the
+/// line each starts on, its name, and whether it is run or `@ignore`d, and why.
+///
+/// It asserts nothing, so it passes whatever the cases do; the table is built when the tests
+/// are generated, and only printed when they run.
+pub(crate) fn index_test_fn(
+    test_name: &syn::Ident,
+    tests: &[Test],
+    options: &Options,
+) -> TokenStream {
+    let rows: Vec<_> = tests
+        .iter()
+        .map(|test| {
+            let status = match test.ignore.as_deref() {
+                None => String::from("run"),
+                Some("") => String::from("ignored"),
+                Some(reason) => format!("ignored: {}", reason),
+            };
+            (test.line.to_string(), test.case_name(), status)
+        })
+        .collect();
+    let width = |column: fn(&(String, &str, String)) -> usize, title: &str| {
+        rows.iter()
+            .map(column)
+            .chain(Some(title.len()))
+            .max()
+            .unwrap_or(0)
+    };
+    let line_width = width(|row| row.0.len(), "line");
+    let name_width = width(|row| row.1.chars().count(), "case");
+    let mut table = format!(
+        "{:>3$}  {:4$}  {}\n",
+        "line", "case", "status", line_width, name_width
+    );
+    for (line, name, status) in &rows {
+        table.push_str(&format!(
+            "{:>3$}  {:4$}  {}\n",
+            line, name, status, line_width, name_width
+        ));
+    }
+    let file = tests.first().map_or("", |test| &test.file);
+    let ignored = tests.iter().filter(|test| test.ignore.is_some()).count();
+    let summary = format!(
+        "{}: {} case{}, {} run, {} ignored\n",
+        file,
+        tests.len(),
+        if tests.len() == 1 { "" } else { "s" },
+        tests.len() - ignored,
+        ignored
+    );
+    let test_attr = test_attr(options);
+
+    quote! {
+        #test_attr
+        fn #test_name() {
+            ::std::eprint!("{}{}", #summary, #table);
+        }
+    }
+}
+
+/// Generate the `#[test]` function `test_name` checking that the tests of `tests` with the same
+/// input but differently written outputs expect the same, as `testing_fn` compares them:
+/// through `de` and `ser` in `exact` mode, and structurally in the structural modes.
+///
+/// Outputs aren't compared in modes where they aren't expected values, nor those of tests
+/// which aren't run by `testing_fn` itself, with an `@expr` output or their own `@ser`.
+fn consistency_test_fn(
+    test_name: &syn::Ident,
+    testing_fn: &syn::Ident,
+    contents: &syn::Ident,
+    tests: &[Test],
+    options: &Options,
+) -> TokenStream {
+    let compared = |test: &Test| {
+        !test.error
+            && !test.incomplete
+            && test.expr.is_none()
+            && test.override_fn(testing_fn).is_none()
+            && !test.takes_snapshot(options)
+    };
+    let pairs: Vec<_> = same_subjects(tests)
+        .into_iter()
+        .filter(|(previous, test)| compared(previous) && compared(test))
+        .filter(|(previous, test)| previous.output.text != test.output.text)
+        .map(|(previous, test)| {
+            let (first, second) = (
+                previous.output.tokens(contents),
+                test.output.tokens(contents),
+            );
+            let (first_name, second_name) = (previous.case_name(), test.case_name());
+            quote!((#first, #second, (#first_name, #second_name)))
+        })
+        .collect();
+    if pairs.is_empty() || !testing_fn_compares_outputs(options) {
+        return TokenStream::new();
+    }
+    let consistent_fn = consistent_fn(testing_fn);
+    let test = test_attr(options);
+    quote! {
+        #test
+        fn #test_name() -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+            for (first, second, names) in [#(#pairs),*].iter().copied() {
+                #consistent_fn(first, second, names)?;
+            }
+            ::std::result::Result::Ok(())
+        }
+    }
+}
+
+/// The function registering a Criterion benchmark of each case in `tests` with `bench`,
+/// timing its input through the `actual_fn` of `testing_fn`.
+///
+/// `@error` and `@incomplete` cases have no output to time, and `@ignore`d cases are left out,
+/// as they are of the tests.
+fn bench_fn_of(
+    bench_fn: &syn::Ident,
+    testing_fn: &syn::Ident,
+    contents: &syn::Ident,
+    tests: &[Test],
+) -> TokenStream {
+    if !cfg!(feature = "bench") {
+        return compile_error(
+            "`bench` requires the `bench` feature of `conformance`",
+            Span::call_site(),
+        );
+    }
+    let benches = tests
+        .iter()
+        .filter(|test| test.ignore.is_none() && !test.error && !test.incomplete)
+        .map(|test| {
+            let override_fn = test.override_fn(testing_fn);
+            let actual_fn = actual_fn(override_fn.as_ref().unwrap_or(testing_fn));
+            let input = test.input.tokens(contents);
+            let name = test.case_name();
+            quote! {
+                c.bench_function(#name, |b| {
+                    b.iter(|| #actual_fn(::criterion::black_box(#input)).unwrap())
+                });
+            }
+        });
+    quote! {
+        /// Registers a benchmark of each case in the file, for `criterion_group!`.
+        #[allow(dead_code)]
+        pub fn #bench_fn(c: &mut ::criterion::Criterion) {
+            #(#benches)*
+        }
+    }
+}
+
+/// The block running `test` through `testing_fn`, which evaluates to whether it passed.
+///
+/// `contents` is the constant holding the included test file.
+fn case_body(
+    testing_fn: &syn::Ident,
+    contents: &syn::Ident,
+    test: &Test,
+    options: &Options,
+) -> TokenStream {
+    let Test { repeat, .. } = test;
+    // a test with its own `@ser` or `@de` is run by testing functions of its own
+    let override_fn = test.override_fn(testing_fn);
+    let testing_fn = override_fn.as_ref().unwrap_or(testing_fn);
+    let input = test.input.tokens(contents);
+    // an expression is evaluated in the test, to be serialized by the testing function
+    let output = match &test.expr {
+        Some(expr) => quote!({ #expr }),
+        None => test.output.tokens(contents),
+    };
+
+    let callee = test.testing_fn(testing_fn);
+    let call = |expected: &TokenStream, actual: &TokenStream| {
+        if (callee == *testing_fn || test.expr.is_some())
+            && options.message.is_some()
+            && options.predicate.is_none()
+        {
+            let Test {
+                title, file, line, ..
+            } = test;
+            quote!(#callee(#expected, #actual, #title, #file, #line))
+        } else if test.error && (options.error_kind.is_some() || options.error_pos.is_some()) {
+            let kind = options.error_kind.as_ref().map(|_| match &test.error_kind {
+                Some(kind) => quote!(, ::std::option::Option::Some(#kind)),
+                None => quote!(, ::std::option::Option::None),
+            });
+            let at = options.error_pos.as_ref().map(|_| match test.error_at {
+                Some((line, column)) => quote!(, ::std::option::Option::Some((#line, #column))),
+                None => quote!(, ::std::option::Option::None),
+            });
+            quote!(#callee(#expected, #actual #kind #at))
+        } else {
+            quote!(#callee(#expected, #actual))
+        }
+    };
+    let mut run = if !test.modes.is_empty() {
+        // every mode is checked, so that all of those failing are reported
+        let checks = test.modes.iter().map(|mode| {
+            let name = mode.name();
+            let call = match mode {
+                conformance_format::Mode::Idempotent => call(&input, &input),
+                conformance_format::Mode::Roundtrip => call(&output, &output),
+                // `exact`
+                _ => call(&output, &input),
+            };
+            quote! {
+                match ::std::panic::catch_unwind(|| #call) {
+                    ::std::result::Result::Ok(::std::result::Result::Ok(())) => (),
+                    ::std::result::Result::Ok(::std::result::Result::Err(e)) => {
+                        failures.push(::std::format!("mode `{}`: {}", #name, e));
+                    }
+                    ::std::result::Result::Err(panic) => {
+                        let message = panic
+                            .downcast_ref::<::std::string::String>()
+                            .map(::std::string::String::as_str)
+                            .or_else(|| panic.downcast_ref::<&str>().copied())
+                            .unwrap_or("panicked");
+                        failures.push(::std::format!("mode `{}`: {}", #name, message));
+                    }
+                }
+            }
+        });
+        quote! {{
+            let mut failures: ::std::vec::Vec<::std::string::String> = ::std::vec::Vec::new();
+            #(#checks)*
+            if failures.is_empty() {
+                ::std::result::Result::Ok(())
+            } else {
+                let e = failures.join("\n");
+                ::std::result::Result::Err(::std::convert::From::from(e))
+            }
+        }}
+    } else if test.takes_snapshot(options) {
+        let snapshot = test.snapshot_path(options);
+        let actual_fn = actual_fn(testing_fn);
+        let compare = call(&quote!(&snapshot), &input);
+        quote! {{
+            let snapshot = #snapshot;
+            match ::std::fs::read_to_string(snapshot) {
+                ::std::result::Result::Ok(snapshot) => #compare,
+                ::std::result::Result::Err(e) if e.kind() != ::std::io::ErrorKind::NotFound => {
+                    let e = ::std::format!("failed to read snapshot `{}`: {}", snapshot, e);
+                    ::std::result::Result::Err(::std::convert::From::from(e))
+                }
+                ::std::result::Result::Err(_) if ::std::env::var_os("CONFORMANCE_ACCEPT").is_some() => {
+                    let actual = #actual_fn(#input)?;
+                    // written whole and renamed into place, so that concurrent runs
+                    // never see part of a snapshot
+                    let tmp = ::std::format!("{}.{}.tmp", snapshot, ::std::process::id());
+                    ::std::fs::write(&tmp, actual)
+                        .and_then(|()| ::std::fs::rename(&tmp, snapshot))
+                        .map_err(|e| ::std::format!("failed to write snapshot `{}`: {}", snapshot, e))?;
+                    ::std::eprintln!("wrote snapshot `{}`", snapshot);
+                    ::std::result::Result::Ok(())
+                }
+                ::std::result::Result::Err(_) => {
+                    let e = ::std::format!(
+                        "snapshot `{}` does not exist; run with `CONFORMANCE_ACCEPT=1` to write it",
+                        snapshot,
+                    );
+                    ::std::result::Result::Err(::std::convert::From::from(e))
+                }
+            }
+        }}
+    } else if test.equivalents.is_empty() {
+        call(&output, &input)
+    } else {
+        let equivalents = test.equivalents.iter().map(|body| body.tokens(contents));
+        quote!(#callee(&[#input, #(#equivalents),*]))
+    };
+    if !test.warnings.is_empty() {
+        // checked once the output is, for the warnings of the (first) input's value
+        let warnings_fn = warnings_fn(testing_fn);
+        let expected = &test.warnings;
+        run = quote! {{
+            let checked: ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> = #run;
+            checked.and_then(|()| {
+                let warnings = #warnings_fn(#input)?;
+                let expected: &[&str] = &[#(#expected),*];
+                match expected.iter().find(|expected| !warnings.iter().any(|warning| warning.contains(*expected))) {
+                    ::std::option::Option::Some(missing) => {
+                        let e = ::std::format!("expected a warning containing {:?}, but the warnings were {:?}", missing, warnings);
+                        ::std::result::Result::Err(::std::convert::From::from(e))
+                    }
+                    ::std::option::Option::None => ::std::result::Result::Ok(()),
+                }
+            })
+        }};
+    }
+    if let (true, Some(remaining)) = (options.consume_all, &options.remaining) {
+        // rejected and cut short inputs aren't read whole, nor is it `de` reading a `@de` test's
+        if !test.error && !test.incomplete && test.de.is_none() {
+            let equivalents = test.equivalents.iter().map(|body| body.tokens(contents));
+            run = quote! {{
+                let checked: ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> = #run;
+                checked.and_then(|()| {
+                    let inputs: &[&str] = &[#input, #(#equivalents),*];
+                    for input in inputs {
+                        let remaining: usize = #remaining(input);
+                        if remaining > 0 {
+                            let unread = input.get(input.len().saturating_sub(remaining)..).unwrap_or(input);
+                            let e = ::std::format!("the input wasn't read whole: {} byte(s) were left, {:?}", remaining, unread);
+                            return ::std::result::Result::Err(::std::convert::From::from(e));
+                        }
+                    }
+                    ::std::result::Result::Ok(())
+                })
+            }};
+        }
+    }
+    if let Some(id) = &test.id {
+        // name the spec case on failure, as the test name may have lost its spelling
+        run = quote! {
+            match ::std::panic::catch_unwind(|| #run) {
+                ::std::result::Result::Ok(::std::result::Result::Ok(())) => ::std::result::Result::Ok(()),
+                ::std::result::Result::Ok(::std::result::Result::Err(e)) => {
+                    let e = ::std::format!("case `{}`: {}", #id, e);
+                    ::std::result::Result::Err(::std::convert::From::from(e))
+                }
+                ::std::result::Result::Err(panic) => {
+                    ::std::eprintln!("case `{}` failed", #id);
+                    ::std::panic::resume_unwind(panic)
+                }
+            }
+        };
+    }
+    if options.before_each.is_some() || options.after_each.is_some() {
+        run = each_hooks(&run, options);
+    }
+
+    let body = if *repeat == 1 {
+        run
+    } else {
+        quote! {
+            for iteration in 1..=#repeat {
+                match ::std::panic::catch_unwind(|| #run) {
+                    ::std::result::Result::Ok(::std::result::Result::Ok(())) => (),
+                    ::std::result::Result::Ok(::std::result::Result::Err(e)) => {
+                        let e = ::std::format!("iteration {} of {}: {}", iteration, #repeat, e);
+                        return ::std::result::Result::Err(::std::convert::From::from(e));
+                    }
+                    ::std::result::Result::Err(panic) => {
+                        ::std::eprintln!("failed on iteration {} of {}", iteration, #repeat);
+                        ::std::panic::resume_unwind(panic)
+                    }
+                }
+            }
+            ::std::result::Result::Ok(())
+        }
+    };
+    // point at the case in the test file, where an editor can jump to it
+    let Test {
+        title, file, line, ..
+    } = test;
+    // and at the version its output was captured from, which may be stale
+    let location = match &test.baseline_version {
+        Some(version) => quote! {
+            ::std::format!(
+                "{}:{}: baseline from {} differs in {}",
+                #file,
+                #line,
+                #version,
+                ::std::env!("CARGO_PKG_VERSION"),
+            )
+        },
+        None => quote!(::std::format!("{}:{}", #file, #line)),
+    };
+    let (annotate, annotate_error, annotate_panic) = match ci_annotation_fn(options.ci, file, *line)
+    {
+        Some(annotate) => (
+            annotate,
+            quote!(__conformance_annotate(&e.to_string());),
+            quote! {
+                __conformance_annotate(
+                    panic
+                        .downcast_ref::<::std::string::String>()
+                        .map(::std::string::String::as_str)
+                        .or_else(|| panic.downcast_ref::<&str>().copied())
+                        .unwrap_or("panicked"),
+                );
+            },
+        ),
+        None => (TokenStream::new(), TokenStream::new(), TokenStream::new()),
+    };
+    let body = quote! {
+        #annotate
+        match ::std::panic::catch_unwind(|| -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> { #body }) {
+            ::std::result::Result::Ok(::std::result::Result::Ok(())) => ::std::result::Result::Ok(()),
+            ::std::result::Result::Ok(::std::result::Result::Err(e)) => {
+                #annotate_error
+                let e = ::std::format!("{}: {}", #location, e);
+                ::std::result::Result::Err(::std::convert::From::from(e))
+            }
+            ::std::result::Result::Err(panic) => {
+                #annotate_panic
+                ::std::eprintln!("{}: case `{}` failed", #location, #title);
+                ::std::panic::resume_unwind(panic)
+            }
+        }
+    };
+
+    // printed before the case runs, so that a case which hangs is named
+    let progress = if options.verbose {
+        quote! {
+            if ::std::env::var_os("CONFORMANCE_VERBOSE").is_some() {
+                ::std::eprintln!("running {}::{}", #file, #title);
+            }
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    quote! {{
+        #progress
+        #body
+    }}
+}
+
+/// A function for the generated code which prints the failure `message` of the case at `file`
+/// and `line` in the format `ci` annotates, if the tests are running on it, or `None` without `ci`.
+///
+/// It is printed to stdout, where GitHub Actions reads workflow commands, as the test runner
+/// prints what a failing test printed. The path is made relative to the checked out repository,
+/// as both services expect, which the manifest directory of a workspace member isn't.
+fn ci_annotation_fn(ci: Ci, file: &str, line: usize) -> Option<TokenStream> {
+    let (running, root, print) = match ci {
+        Ci::None => return None,
+        Ci::Github => (
+            "GITHUB_ACTIONS",
+            "GITHUB_WORKSPACE",
+            quote! {
+                let escape = |s: &str| s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+                let file = escape(&path.display().to_string()).replace(':', "%3A").replace(',', "%2C");
+                ::std::println!("::error file={},line={}::{}", file, #line, escape(message));
+            },
+        ),
+        Ci::Gitlab => (
+            "GITLAB_CI",
+            "CI_PROJECT_DIR",
+            quote! {
+                // the job log colors each line anew
+                for message in message.lines() {
+                    ::std::println!("\x1b[31;1m{}:{}: {}\x1b[0m", path.display(), #line, message);
+                }
+            },
+        ),
+    };
+    Some(quote! {
+        fn __conformance_annotate(message: &str) {
+            if ::std::env::var_os(#running).is_none() {
+                return;
+            }
+            let path = ::std::path::Path::new(::std::env!("CARGO_MANIFEST_DIR")).join(#file);
+            let path = ::std::env::var_os(#root)
+                .and_then(|root| path.strip_prefix(root).ok().map(::std::path::Path::to_path_buf))
+                .unwrap_or(path);
+            #print
+        }
+    })
+}
+
+/// Wrap `run`, one run of a case, in the `before_each` and `after_each` hooks of `options`.
+///
+/// `after_each` is run by a guard dropped once `run` is done, so that it runs even if `run`
+/// panics; it isn't run if `before_each` panics, as the case never ran.
+fn each_hooks(run: &TokenStream, options: &Options) -> TokenStream {
+    // a closure is called, and any other expression is evaluated
+    let hook = |expr: &syn::Expr| match expr {
+        syn::Expr::Closure(_) => quote!({ (#expr)(); }),
+        _ => quote!({ #expr; }),
+    };
+    let before = options.before_each.as_ref().map(hook);
+    let after = options.after_each.as_ref().map(|expr| {
+        let after = hook(expr);
+        quote! {
+            struct AfterEach<F: ::std::ops::FnMut()>(F);
+            impl<F: ::std::ops::FnMut()> ::std::ops::Drop for AfterEach<F> {
+                fn drop(&mut self) {
+                    (self.0)()
+                }
+            }
+            let _after_each = AfterEach(|| #after);
+        }
+    });
+    quote! {{
+        #before
+        #after
+        #run
+    }}
+}
+
+/// The function comparing the outputs of equivalent inputs, beside `testing_fn`.
+pub(crate) fn equivalent_fn(testing_fn: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("{}_equivalent", testing_fn)
+}
+
+/// The function comparing the expected outputs of tests with the same input, beside `testing_fn`.
+fn consistent_fn(testing_fn: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("{}_consistent", testing_fn)
+}
+
+/// The function giving the normalized output of an input, beside `testing_fn`.
+fn actual_fn(testing_fn: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("{}_actual", testing_fn)
+}
+
+/// The function giving the warnings of the value of an input, beside `testing_fn`.
+fn warnings_fn(testing_fn: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("{}_warnings", testing_fn)
+}
+
+/// The function checking that `de` needs more data than inputs give, beside `testing_fn`.
+pub(crate) fn incomplete_fn(testing_fn: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("{}_incomplete", testing_fn)
+}
+
+/// The function serializing the value of an `@expr` output to compare, beside `testing_fn`.
+pub(crate) fn expr_fn(testing_fn: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("{}_expr", testing_fn)
+}
+
+/// The function checking that `de` rejects inputs, beside `testing_fn`.
+pub(crate) fn error_fn(testing_fn: &syn::Ident) -> syn::Ident {
+    quote::format_ident!("{}_error", testing_fn)
+}
+
+/// Replace each generic parameter named in `params` with its argument, throughout `tokens`.
+fn substitute_generics(tokens: TokenStream, params: &BTreeMap<String, TokenStream>) -> TokenStream {
+    tokens
+        .into_iter()
+        .flat_map(|tt| match tt {
+            proc_macro2::TokenTree::Ident(ident) if params.contains_key(&ident.to_string()) => {
+                params[&ident.to_string()].clone()
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut substituted = proc_macro2::Group::new(
+                    group.delimiter(),
+                    substitute_generics(group.stream(), params),
+                );
+                substituted.set_span(group.span());
+                quote!(#substituted)
+            }
+            tt => quote!(#tt),
+        })
+        .collect()
+}
+
+/// Replace each lifetime named in `lifetimes` with `'_`, throughout `tokens`,
+/// so that a type borrowing from the tested function's arguments can be named outside of it.
+fn erase_lifetimes(tokens: TokenStream, lifetimes: &BTreeSet<String>) -> TokenStream {
+    let mut erased = TokenStream::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(tt) = tokens.next() {
+        match tt {
+            proc_macro2::TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+                match tokens.peek() {
+                    Some(proc_macro2::TokenTree::Ident(ident))
+                        if lifetimes.contains(&ident.to_string()) =>
+                    {
+                        let lifetime = syn::Lifetime::new("'_", ident.span());
+                        tokens.next();
+                        erased.extend(quote!(#lifetime));
+                    }
+                    _ => erased.extend(quote!(#punct)),
+                }
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut substituted = proc_macro2::Group::new(
+                    group.delimiter(),
+                    erase_lifetimes(group.stream(), lifetimes),
+                );
+                substituted.set_span(group.span());
+                erased.extend(quote!(#substituted));
+            }
+            tt => erased.extend(quote!(#tt)),
+        }
+    }
+    erased
+}
+
+/// The path to call the tested function by, and its return type,
+/// with any generic parameters instantiated by `turbofish`, as given by `given_by`.
+///
+/// The lifetime parameters of the function are erased from the return type, as `'_`,
+/// so that a zero-copy type borrowing from the input, such as `Token<'a>`, is inferred
+/// wherever the generated code names it.
+fn instantiate(
+    fun: &syn::ItemFn,
+    turbofish: Option<&syn::AngleBracketedGenericArguments>,
+    given_by: &str,
+) -> Result<(TokenStream, syn::Type), TokenStream> {
+    let fn_name = &fun.sig.ident;
+    let tested_type = match &fun.sig.output {
+        syn::ReturnType::Type(_, r#type) => (**r#type).clone(),
+        syn::ReturnType::Default => syn::parse_str("()").unwrap(),
+    };
+    let lifetimes: BTreeSet<String> = fun
+        .sig
+        .generics
+        .lifetimes()
+        .map(|param| param.lifetime.ident.to_string())
+        .collect();
+    let tested_type = if lifetimes.is_empty() {
+        tested_type
+    } else {
+        syn::parse2(erase_lifetimes(quote!(#tested_type), &lifetimes))
+            .map_err(|e| e.to_compile_error())?
+    };
+    // lifetimes are inferred, and can't be given in a turbofish if late-bound
+    let params: Vec<&syn::Ident> = fun
+        .sig
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            syn::GenericParam::Type(param) => Some(&param.ident),
+            syn::GenericParam::Const(param) => Some(&param.ident),
+            syn::GenericParam::Lifetime(_) => None,
+        })
+        .collect();
+
+    match (turbofish, params.is_empty()) {
+        (None, true) => Ok((quote!(#fn_name), tested_type)),
+        (None, false) => Err(compile_error(
+            &format!(
+                "`{}` is generic, so its generic arguments must be given with `transform_turbofish = <...>` \
+                 or the frontmatter `type` of each test file",
+                fn_name,
+            ),
+            fun.sig.generics.span(),
+        )),
+        (Some(turbofish), true) => Err(compile_error(
+            &format!("{} was given, but `{}` isn't generic", given_by, fn_name),
+            turbofish.span(),
+        )),
+        (Some(turbofish), false) if turbofish.args.len() != params.len() => Err(compile_error(
+            &format!(
+                "`{}` has {} generic parameters, but {} gives {} arguments",
+                fn_name,
+                params.len(),
+                given_by,
+                turbofish.args.len(),
+            ),
+            turbofish.span(),
+        )),
+        (Some(turbofish), false) => {
+            let args = params
+                .iter()
+                .map(|param| param.to_string())
+                .zip(turbofish.args.iter().map(|arg| quote!(#arg)))
+                .collect();
+            let tested_type = substitute_generics(quote!(#tested_type), &args);
+            let tested_type = syn::parse2(tested_type).map_err(|e| e.to_compile_error())?;
+            let args = &turbofish.args;
+            Ok((quote!(#fn_name::<#args>), tested_type))
+        }
+    }
+}
+
+/// [`instantiate`] the tested function with the frontmatter `type` of a test file.
+fn instantiate_frontmatter(
+    fun: &syn::ItemFn,
+    r#type: &syn::Type,
+) -> Result<(TokenStream, syn::Type), TokenStream> {
+    let turbofish = syn::parse_quote!(<#r#type>);
+    instantiate(fun, Some(&turbofish), "the frontmatter `type`")
+}
+
+/// Turn a file or directory name into a valid identifier.
+pub(crate) fn sanitize_ident(name: &str) -> syn::Ident {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_numeric()) {
+        ident.insert(0, '_');
+    }
+    syn::parse_str(&ident).unwrap_or_else(|_| quote::format_ident!("_{}", ident))
+}
+
+/// The testing functions of `args` calling the tested function by `fn_name`,
+/// which returns `tested_type`, as a function of the name to give them.
+pub(crate) fn testing_fns<'a>(
+    args: &'a AttrArgs,
+    fn_name: &TokenStream,
+    tested_type: &syn::Type,
+) -> Result<impl Fn(&syn::Ident) -> TokenStream + 'a, TokenStream> {
+    testing_fns_with(args, &args.ser, &args.de, fn_name, tested_type)
+}
+
+/// The testing functions of the tests of `tests` with their own `@ser`, `@de` or
+/// `@tolerance`, one set for each of the distinct overrides, named by [`Test::override_fn`].
+fn override_testing_fns(
+    args: &AttrArgs,
+    fn_name: &TokenStream,
+    tested_type: &syn::Type,
+    testing_fn: &syn::Ident,
+    tests: &[Test],
+) -> Result<TokenStream, TokenStream> {
+    let overrides: BTreeMap<_, _> = tests
+        .iter()
+        .filter_map(|test| {
+            let override_fn = test.override_fn(testing_fn)?;
+            Some((override_fn.to_string(), (override_fn, test)))
+        })
+        .collect();
+    overrides
+        .values()
+        .map(|(override_fn, test)| {
+            let mut overridden = args.clone();
+            overridden.options.tolerance = test.tolerance.or(args.options.tolerance);
+            let ser = test.ser.as_ref().unwrap_or(&args.ser);
+            let de = test.de.as_ref().unwrap_or(&args.de);
+            let testing_fns = testing_fns_with(&overridden, ser, de, fn_name, tested_type)?;
+            Ok(testing_fns(override_fn))
+        })
+        .collect()
+}
+
+/// [`testing_fns`], serializing with `ser` and deserializing with `de` rather than
+/// with those of `args`.
+fn testing_fns_with<'a>(
+    args: &'a AttrArgs,
+    ser: &'a syn::ExprPath,
+    de: &'a syn::ExprPath,
+    fn_name: &TokenStream,
+    tested_type: &syn::Type,
+) -> Result<impl Fn(&syn::Ident) -> TokenStream + 'a, TokenStream> {
+    let AttrArgs {
+        value,
+        format,
+        options,
+        ..
+    } = args;
+    // what `de` parses to, where the tested type would be
+    let parsed_type = options.r#type.as_ref().unwrap_or(tested_type);
+    let de_type = value.clone().unwrap_or_else(|| parsed_type.clone());
+    // a borrowed value can be serialized, but not deserialized from each test's own strings
+    if let (None, None, syn::Type::Reference(_)) = (value, &options.seed, parsed_type) {
+        let e = "the tested function returns a reference, but as no `value` type is given, \
+                 `de` deserializes to the tested type, which must be owned; \
+                 return an owned type (such as `String` for `&str`, or `Vec<T>` for `&[T]`), \
+                 or give a `value` type to deserialize to";
+        return Err(compile_error(e, tested_type.span()));
+    }
+
+    // Check serde bounds up front, so that a mismatch is reported against the tested type
+    // rather than deep inside the generated testing function.
+    // what is serialized is parsed, if the tested function only gives its type
+    let serialized_type = if options.direction == Direction::Serialize || options.identity {
+        parsed_type
+    } else {
+        tested_type
+    };
+    let assert_serde = if cfg!(feature = "serde") && format.is_some() {
+        // `check!` doesn't know the tested type
+        let assert_ser = match serialized_type {
+            syn::Type::Infer(_) => TokenStream::new(),
+            _ => quote_spanned! {serialized_type.span()=>
+                assert_serialize::<#serialized_type>();
+            },
+        };
+        // with a predicate or pattern, the expected output is never deserialized,
+        // and with a seed, it is deserialized to the seed's `Value`
+        let assert_de = if options.predicate.is_none()
+            && options.mode != Mode::Regex
+            && !options.mode.is_structural()
+            && options.seed.is_none()
+        {
+            quote_spanned! {de_type.span()=>
+                assert_serialize::<#de_type>();
+                assert_deserialize::<#de_type>();
+            }
+        } else {
+            TokenStream::new()
+        };
+        quote! {
+            const _: fn() = || {
+                fn assert_serialize<T: ?Sized + ::serde::Serialize>() {}
+                fn assert_deserialize<T: for<'de> ::serde::Deserialize<'de>>() {}
+                #assert_ser
+                #assert_de
+            };
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    // checked up front too, so that a missing `Clone` is reported against the type
+    let assert_clone = match serialized_type {
+        syn::Type::Infer(_) => TokenStream::new(),
+        _ if options.clone_stable => {
+            let cloned_type = if options.stream {
+                quote!(::std::vec::Vec<#de_type>)
+            } else {
+                quote!(#serialized_type)
+            };
+            quote_spanned! {serialized_type.span()=>
+                const _: fn() = || {
+                    fn assert_clone<T: ?Sized + ::std::clone::Clone>() {}
+                    assert_clone::<#cloned_type>();
+                };
+            }
+        }
+        _ => TokenStream::new(),
+    };
+
+    let ser_try = if options.ser_infallible {
+        quote!()
+    } else {
+        quote!(?)
+    };
+    // the call deserializing a string `input`
+    let de_args = &options.de_args;
+    let deserialize = |input: TokenStream| match &options.seed {
+        Some(seed) => quote! {
+            ::serde::de::DeserializeSeed::deserialize(
+                #seed,
+                &mut #format::Deserializer::from_str(#input),
+            )
+        },
+        None => {
+            let de = with_turbofish(de, &de_type);
+            quote!(#de(#input #(, #de_args)*))
+        }
+    };
+    let de_try = if options.de_infallible {
+        quote!()
+    } else if options.de_option {
+        quote!(.ok_or("`de` returned `None`")?)
+    } else {
+        quote!(?)
+    };
+    // adjustments made to the serialized strings before they are compared,
+    // with the helper functions they use
+    let mut helpers = TokenStream::new();
+    let mut normalize_actual = TokenStream::new();
+    let mut normalize_expected = TokenStream::new();
+    if options.strip_ansi {
+        helpers.extend(strip_ansi_fn());
+        normalize_actual.extend(quote!(let actual = __conformance_strip_ansi(&actual);));
+        normalize_expected.extend(quote!(let expected = __conformance_strip_ansi(&expected);));
+    }
+    if options.strip_bom {
+        normalize_actual.extend(quote! {
+            let actual = match actual.strip_prefix('\u{feff}') {
+                ::std::option::Option::Some(rest) => ::std::string::ToString::to_string(rest),
+                ::std::option::Option::None => actual,
+            };
+        });
+        normalize_expected.extend(quote! {
+            let expected = match expected.strip_prefix('\u{feff}') {
+                ::std::option::Option::Some(rest) => ::std::string::ToString::to_string(rest),
+                ::std::option::Option::None => expected,
+            };
+        });
+    }
+
+    if options.normalize_newlines {
+        normalize_actual
+            .extend(quote!(let actual = actual.replace("\r\n", "\n").replace('\r', "\n");));
+        normalize_expected
+            .extend(quote!(let expected = expected.replace("\r\n", "\n").replace('\r', "\n");));
+    }
+
+    // the JSON crate for options which compare outputs structurally
+    let json = options.json.as_ref().or(format.as_ref());
+    let require_json = |option: &str, span: Span| {
+        json.ok_or_else(|| {
+            let e = format!(
+                "`{}` requires a JSON crate; use `serde = json` or the `json` option",
+                option,
+            );
+            compile_error(&e, span)
+        })
+    };
+
+    if let Some(pointer) = &options.project {
+        let json = require_json("project", pointer.span())?;
+        helpers.extend(project_fn(json));
+        normalize_actual.extend(quote! {
+            let actual = __conformance_project(&actual, #pointer)
+                .map_err(|e| ::std::format!("in actual output: {}", e))?;
+        });
+        normalize_expected.extend(quote! {
+            let expected = __conformance_project(&expected, #pointer)
+                .map_err(|e| ::std::format!("in expected output: {}", e))?;
+        });
+    }
+
+    // before sorting arrays, as the removed members would change the order of their elements
+    if let Some(key) = options.ignore_keys.first() {
+        let json = require_json("ignore_keys", key.span())?;
+        helpers.extend(ignore_keys_fn(json, &options.ignore_keys));
+        normalize_actual.extend(quote! {
+            let actual = __conformance_ignore_keys(&actual).map_err(|e| ::std::format!("in actual output: {}", e))?;
+        });
+        normalize_expected.extend(quote! {
+            let expected = __conformance_ignore_keys(&expected)
+                .map_err(|e| ::std::format!("in expected output: {}", e))?;
+        });
+    }
+
+    // before sorting arrays, as a coerced element sorts differently
+    if options.loose_scalars {
+        let json = require_json("loose_scalars", Span::call_site())?;
+        helpers.extend(loose_scalars_fn(json));
+        normalize_actual.extend(quote! {
+            let actual = __conformance_loose_scalars(&actual).map_err(|e| ::std::format!("in actual output: {}", e))?;
+        });
+        normalize_expected.extend(quote! {
+            let expected = __conformance_loose_scalars(&expected)
+                .map_err(|e| ::std::format!("in expected output: {}", e))?;
+        });
+    }
+
+    if options.unordered_arrays {
+        let json = require_json("unordered_arrays", Span::call_site())?;
+        helpers.extend(sort_arrays_fn(json));
+        normalize_actual.extend(quote! {
+            let actual = __conformance_sort_arrays(&actual).map_err(|e| ::std::format!("in actual output: {}", e))?;
+        });
+        normalize_expected.extend(quote! {
+            let expected = __conformance_sort_arrays(&expected)
+                .map_err(|e| ::std::format!("in expected output: {}", e))?;
+        });
+    }
+
+    if options.sort_keys {
+        let json = require_json("sort_keys", Span::call_site())?;
+        helpers.extend(sort_keys_fn(json));
+        normalize_actual.extend(quote! {
+            let actual = __conformance_sort_keys(&actual).map_err(|e| ::std::format!("in actual output: {}", e))?;
+        });
+        normalize_expected.extend(quote! {
+            let expected = __conformance_sort_keys(&expected)
+                .map_err(|e| ::std::format!("in expected output: {}", e))?;
+        });
+    }
+
+    // last, so that the mutator sees what the other options leave of the outputs
+    if let Some(value_mut) = &options.value_mut {
+        let json = require_json("value_mut", value_mut.span())?;
+        helpers.extend(value_mut_fn(json, value_mut));
+        normalize_actual.extend(quote! {
+            let actual = __conformance_value_mut(&actual).map_err(|e| ::std::format!("in actual output: {}", e))?;
+        });
+        normalize_expected.extend(quote! {
+            let expected = __conformance_value_mut(&expected)
+                .map_err(|e| ::std::format!("in expected output: {}", e))?;
+        });
+    }
+
+    // after the JSON options, which parse the outputs, so that the lines they give are sorted
+    if options.sorted_lines {
+        helpers.extend(sorted_lines_fn());
+        normalize_actual.extend(quote!(let actual = __conformance_sorted_lines(&actual);));
+        normalize_expected.extend(quote!(let expected = __conformance_sorted_lines(&expected);));
+    }
+
+    // the expected output is what the program gives, so only the actual output is filtered
+    let mut filter_first = TokenStream::new();
+    if let Some(program) = &options.jq {
+        if !cfg!(feature = "jq") {
+            return Err(compile_error(
+                "the `jq` option requires the `jq` feature of `conformance`",
+                program.span(),
+            ));
+        }
+        helpers.extend(jq_fn());
+        normalize_actual.extend(quote! {
+            let actual = __conformance_jq(&actual, #program).map_err(|e| ::std::format!("in actual output: {}", e))?;
+        });
+        // but the outputs of equivalent inputs are both actual
+        filter_first.extend(quote! {
+            let expected = __conformance_jq(&expected, #program)
+                .map_err(|e| ::std::format!("in the output of input 1: {}", e))?;
+        });
+    }
+
+    // after the built-in normalizations, so that it sees what would be compared
+    if let Some(normalize) = &options.normalize {
+        normalize_actual.extend(quote!(let actual = #normalize(&actual);));
+        normalize_expected.extend(quote!(let expected = #normalize(&expected);));
+    }
+
+    // the comparison of the normalized outputs, which the `value_cmp` function may loosen
+    let compared = |assert: TokenStream| match &options.value_cmp {
+        Some(value_cmp) => quote! {
+            if !__conformance_values_match(&actual, &expected)? {
+                #assert
+                // the outputs are the same text, which the function still found different
+                ::std::panic!(
+                    "`{}` found the outputs different: {}",
+                    ::std::stringify!(#value_cmp),
+                    actual,
+                );
+            }
+        },
+        None => assert,
+    };
+    if let Some(value_cmp) = &options.value_cmp {
+        let json = require_json("value_cmp", value_cmp.span())?;
+        helpers.extend(values_match_fn(json, value_cmp));
+    }
+    if let Some(tokenize) = &options.tokenize {
+        helpers.extend(token_mismatch_fn(tokenize));
+    }
+
+    // the call serializing a value `value` to a string
+    let ser_args = &options.ser_args;
+    let serialize = |value: TokenStream| match &options.ser_writer {
+        // output which isn't UTF-8 fails, unless it is allowed
+        Some(ser_writer) if options.checks_utf8() => quote! {{
+            let mut buf = ::std::vec::Vec::<u8>::new();
+            #ser_writer(&mut buf, &#value #(, #ser_args)*)#ser_try;
+            match ::std::string::String::from_utf8(buf) {
+                ::std::result::Result::Ok(output) => output,
+                ::std::result::Result::Err(e) => {
+                    let e = ::std::format!(
+                        "`{}` wrote invalid UTF-8 at byte {}: b\"{}\"",
+                        ::std::stringify!(#ser_writer),
+                        e.utf8_error().valid_up_to(),
+                        e.as_bytes().escape_ascii(),
+                    );
+                    return ::std::result::Result::Err(::std::convert::From::from(e));
+                }
+            }
+        }},
+        // and is then compared as bytes, escaped in a byte string literal
+        Some(ser_writer) => quote! {{
+            let mut buf = ::std::vec::Vec::<u8>::new();
+            #ser_writer(&mut buf, &#value #(, #ser_args)*)#ser_try;
+            ::std::string::String::from_utf8(buf).unwrap_or_else(|e| {
+                ::std::format!("b\"{}\"", e.as_bytes().escape_ascii())
+            })
+        }},
+        None => quote!(#ser(&#value #(, #ser_args)*)#ser_try),
+    };
+    // the values of a `stream`, stopping at the first error
+    let collect = if options.de_infallible {
+        quote!(.collect::<::std::vec::Vec<#de_type>>())
+    } else {
+        quote!(.collect::<::std::result::Result<::std::vec::Vec<#de_type>, _>>())
+    };
+    // the tested input deserialized to the tested type, for a function which only gives the type
+    let deserialized = {
+        let de = match &options.seed {
+            Some(_) => deserialize(quote!(actual)),
+            None => {
+                let de = with_turbofish(de, parsed_type);
+                quote!(#de(actual #(, #de_args)*))
+            }
+        };
+        quote! {{
+            let _ = #fn_name;
+            #de #de_try
+        }}
+    };
+    let de_stream = with_turbofish(de, &de_type);
+    // the values of a `batch`, as the tested function takes them
+    let de_batch = with_turbofish(de, &syn::parse_quote!(_));
+    let collect_batch = if options.de_infallible {
+        quote!(.collect::<::std::vec::Vec<_>>())
+    } else if options.de_option {
+        quote!(.collect::<::std::option::Option<::std::vec::Vec<_>>>())
+    } else {
+        quote!(.collect::<::std::result::Result<::std::vec::Vec<_>, _>>())
+    };
+    let batch = |input| quote!(#input.split("\n,,,\n").map(|input| #de_batch(input #(, #de_args)*))#collect_batch);
+    // the value the tested input gives, to be serialized
+    let value = match options.direction {
+        // the tested function only gives the type of each value
+        Direction::Deserialize if options.stream => quote! {{
+            let _ = #fn_name;
+            #de_stream(actual #(, #de_args)*)#collect #de_try
+        }},
+        Direction::Deserialize if options.batch => {
+            let values = batch(quote!(actual));
+            quote!(#fn_name(#values #de_try))
+        }
+        // the tested function only names the type each input is deserialized to
+        Direction::Deserialize if options.identity => deserialized,
+        // an `async` function is run to completion on a runtime of its own
+        Direction::Deserialize => match &options.runtime {
+            Some(runtime) => quote!(#runtime().block_on(#fn_name(actual))),
+            None => quote!(#fn_name(actual)),
+        },
+        // the tested function only gives the type the input is deserialized to
+        Direction::Serialize => deserialized,
+    };
+    // the value is serialized to the second format and read back before it is serialized
+    let value = match (&options.ser_b, &options.de_b) {
+        (Some(ser_b), Some(de_b)) => {
+            let de_b = with_turbofish(de_b, serialized_type);
+            quote! {{
+                let value = #value;
+                let via = match #ser_b(&value) {
+                    ::std::result::Result::Ok(via) => via,
+                    ::std::result::Result::Err(e) => {
+                        let e = ::std::format!("`{}` failed: {}", ::std::stringify!(#ser_b), e);
+                        return ::std::result::Result::Err(::std::convert::From::from(e));
+                    }
+                };
+                match #de_b(&via) {
+                    ::std::result::Result::Ok(value) => value,
+                    ::std::result::Result::Err(e) => {
+                        let e = ::std::format!(
+                            "`{}` rejected what `{}` gave: {}\nvia: {}",
+                            ::std::stringify!(#de_b),
+                            ::std::stringify!(#ser_b),
+                            e,
+                            via,
+                        );
+                        return ::std::result::Result::Err(::std::convert::From::from(e));
+                    }
+                }
+            }}
+        }
+        _ => value,
+    };
+    // the warnings are of the value the input gives, before it is serialized
+    let warnings_fn_body = options.warnings.as_ref().map(|warnings| {
+        quote! {
+            let value = #value;
+            ::std::result::Result::Ok(#warnings(&value))
+        }
+    });
+    let actual = if options.ser_stable || options.clone_stable {
+        let first = serialize(quote!(value));
+        let second = options.ser_stable.then(|| {
+            let second = serialize(quote!(value));
+            quote! {
+                let second = #second;
+                if first != second {
+                    let e = ::std::format!(
+                        "serializing the same value twice gave different outputs:\nfirst:  {:?}\nsecond: {:?}",
+                        first,
+                        second,
+                    );
+                    return ::std::result::Result::Err(::std::convert::From::from(e));
+                }
+            }
+        });
+        let of_clone = options.clone_stable.then(|| {
+            let of_clone = serialize(quote!(cloned));
+            quote! {
+                let cloned = ::std::clone::Clone::clone(&value);
+                let of_clone = #of_clone;
+                if first != of_clone {
+                    let e = ::std::format!(
+                        "serializing a clone of the value gave a different output:\nvalue: {:?}\nclone: {:?}",
+                        first,
+                        of_clone,
+                    );
+                    return ::std::result::Result::Err(::std::convert::From::from(e));
+                }
+            }
+        });
+        quote! {{
+            let value = #value;
+            let first = #first;
+            #second
+            #of_clone
+            first
+        }}
+    } else {
+        serialize(value)
+    };
+    let first_difference = first_difference_fn();
+    // the budget is checked as soon as the output is serialized, before any comparison
+    let actual = match options.max_bytes {
+        Some(max_bytes) => quote! {{
+            let actual = #actual;
+            if actual.len() > #max_bytes {
+                let e = ::std::format!(
+                    "serialized output is {} bytes, over the budget of {} bytes",
+                    actual.len(),
+                    #max_bytes,
+                );
+                return ::std::result::Result::Err(::std::convert::From::from(e));
+            }
+            actual
+        }},
+        None => actual,
+    };
+    let actual = if options.deterministic {
+        quote! {{
+            #first_difference
+            let first = #actual;
+            let second = #actual;
+            if first != second {
+                let e = ::std::format!(
+                    "the second parse of the input serialized differently from the first; {}",
+                    __conformance_first_difference(&second, &first),
+                );
+                return ::std::result::Result::Err(::std::convert::From::from(e));
+            }
+            first
+        }}
+    } else {
+        actual
+    };
+    // the reference parser sees the output as serialized, before any normalization
+    let actual = match &options.de_ref {
+        Some(de_ref) => {
+            let serialized_type: syn::Type = if options.stream {
+                syn::parse_quote!(::std::vec::Vec<#de_type>)
+            } else {
+                de_type.clone()
+            };
+            let de_ref_call = with_turbofish(de_ref, &serialized_type);
+            quote! {{
+                let actual = #actual;
+                if let ::std::result::Result::Err(e) = #de_ref_call(&actual) {
+                    let e = ::std::format!(
+                        "`{}` rejected the serialized output: {}\noutput: {}",
+                        ::std::stringify!(#de_ref),
+                        e,
+                        actual,
+                    );
+                    return ::std::result::Result::Err(::std::convert::From::from(e));
+                }
+                actual
+            }}
+        }
+        None => actual,
+    };
+    // the output is compared once it stops changing, if it does within the bound
+    let actual = if options.fixpoint {
+        let max = options.fixpoint_max.map_or(2, |(max, _)| max);
+        let reparsed = deserialize(quote!(&actual));
+        let reserialized = serialize(quote!(reparsed));
+        quote! {{
+            let mut actual = #actual;
+            let mut reparses = 0;
+            loop {
+                let reparsed = #reparsed #de_try;
+                let next = #reserialized;
+                if next == actual {
+                    break actual;
+                }
+                reparses += 1;
+                if reparses == #max {
+                    let e = ::std::format!(
+                        "the serialized output didn't reach a fixed point within {} re-parses \
+                         through `de`:\nbefore: {:?}\n after: {:?}",
+                        #max,
+                        actual,
+                        next,
+                    );
+                    return ::std::result::Result::Err(::std::convert::From::from(e));
+                }
+                actual = next;
+            }
+        }}
+    } else {
+        actual
+    };
+    if options.template {
+        let json = require_json("template", Span::call_site())?;
+        helpers.extend(template_mismatch_fn(json));
+    }
+    if options.mode == Mode::Msgpack {
+        if !cfg!(feature = "msgpack") {
+            return Err(compile_error(
+                "`msgpack` mode requires the `msgpack` feature of `conformance`",
+                Span::call_site(),
+            ));
+        }
+        helpers.extend(msgpack_mismatch_fn(options.base64));
+    }
+    if options.mode == Mode::Cbor {
+        if !cfg!(feature = "cbor") {
+            return Err(compile_error(
+                "`cbor` mode requires the `cbor` feature of `conformance`",
+                Span::call_site(),
+            ));
+        }
+        helpers.extend(cbor_mismatch_fn(options.base64));
+    }
+    if options.mode == Mode::Xml {
+        if !cfg!(feature = "xml") {
+            return Err(compile_error(
+                "`xml` mode requires the `xml` feature of `conformance`",
+                Span::call_site(),
+            ));
+        }
+        helpers.extend(xml_mismatch_fn(options.strict_prolog));
+    }
+    if options.mode == Mode::Approx {
+        let json = require_json("approx", Span::call_site())?;
+        helpers.extend(approx_mismatch_fn(
+            json,
+            options.tolerance.unwrap_or(DEFAULT_TOLERANCE),
+        ));
+    }
+    if options.mode == Mode::YamlToplevelUnordered {
+        if !cfg!(feature = "yaml") {
+            return Err(compile_error(
+                "`yaml_toplevel_unordered` mode requires the `yaml` feature of `conformance`",
+                Span::call_site(),
+            ));
+        }
+        let yaml = match format {
+            Some(format) => quote!(#format),
+            None => quote!(::serde_yaml),
+        };
+        helpers.extend(yaml_mismatch_fn(yaml));
+    }
+    let structural_mismatch = match options.mode {
+        Mode::Cbor => quote!(__conformance_cbor_mismatch),
+        Mode::Xml => quote!(__conformance_xml_mismatch),
+        Mode::Approx => quote!(__conformance_approx_mismatch),
+        Mode::YamlToplevelUnordered => quote!(__conformance_yaml_mismatch),
+        _ => quote!(__conformance_msgpack_mismatch),
+    };
+    // outputs which should be the same are compared as the testing function compares them,
+    // failing with what they should be the same as
+    let assert_same = |what: TokenStream| {
+        if options.mode.is_structural() {
+            quote! {
+                if let ::std::option::Option::Some(e) = #structural_mismatch(&actual, &expected)? {
+                    ::std::panic!("{}; {}", #what, e);
+                }
+            }
+        } else if options.tokenize.is_some() {
+            quote! {
+                if let ::std::option::Option::Some(e) = __conformance_token_mismatch(&actual, &expected) {
+                    ::std::panic!("{}; {}", #what, e);
+                }
+            }
+        } else {
+            compared(quote! {
+                ::std::assert_eq!(
+                    actual,
+                    expected,
+                    "{}; {}",
+                    #what,
+                    __conformance_first_difference(&actual, &expected),
+                );
+            })
+        }
+    };
+    // cases with inputs separated by `&&&` are compared to each other instead
+    let equivalent_assert = assert_same(quote!(::std::format!(
+        "input {} serializes differently from input 1",
+        i + 1
+    )));
+    let equivalent_fn_body = quote! {
+        #helpers
+        #first_difference
+        let expected = {
+            let actual = inputs[0];
+            #actual
+        };
+        #filter_first
+        #normalize_expected
+        for (i, actual) in inputs.iter().enumerate().skip(1) {
+            let actual = #actual;
+            #normalize_actual
+            #equivalent_assert
+        }
+        ::std::result::Result::Ok(())
+    };
+    // the expected output, as the actual one is compared to
+    let expected = if options.oracle.is_some() {
+        quote! {{
+            let _ = expected;
+            __conformance_oracle(input)?
+        }}
+    } else if options.expected_as_written()
+        || options.direction == Direction::Serialize
+        || options.stream
+        || options.jq.is_some()
+    {
+        quote!(::std::string::String::from(expected))
+    } else {
+        // the trusted pair, if given, canonicalizes the expected output in their place
+        let de = match &options.canon_de {
+            Some(canon_de) => {
+                let canon_de = with_turbofish(canon_de, &de_type);
+                quote!(#canon_de(expected)?)
+            }
+            None => {
+                let de = deserialize(quote!(expected));
+                quote!(#de #de_try)
+            }
+        };
+        match &options.canon_ser {
+            Some(canon_ser) => quote!(#canon_ser(&#de)?),
+            None => serialize(de),
+        }
+    };
+    // `check_consistency` compares the expected outputs of tests with the same input,
+    // unless they're not what the actual output is compared to
+    let consistent_fn_body = match options.mode {
+        _ if !options.check_consistency || !testing_fn_compares_outputs(options) => None,
+        // which are compared as written
+        mode if mode.is_structural() => Some(quote!(::std::string::String::from(expected))),
+        _ => Some(quote! {
+            let expected = #expected;
+            #normalize_expected
+            expected
+        }),
+    }
+    .map(|canonical| {
+        let assert = assert_same(quote! {
+            ::std::format!(
+                "tests `{}` and `{}` have the same input but expect different outputs",
+                __names.0,
+                __names.1,
+            )
+        });
+        quote! {
+            #helpers
+            #first_difference
+            let __canonical = |expected: &str| -> ::std::result::Result<::std::string::String, ::std::boxed::Box<dyn ::std::error::Error>> {
+                ::std::result::Result::Ok({ #canonical })
+            };
+            let expected = __canonical(__first)?;
+            let actual = __canonical(__second)?;
+            #assert
+            ::std::result::Result::Ok(())
+        }
+    });
+    let testing_fn_body = if options.mode == Mode::Regex {
+        if !cfg!(feature = "regex") {
+            return Err(compile_error(
+                "`regex` mode requires the `regex` feature of `conformance`",
+                Span::call_site(),
+            ));
+        }
+        let regex_mismatch = regex_mismatch_fn();
+        quote! {
+            #helpers
+            #regex_mismatch
+            let actual = #actual;
+            #normalize_actual
+            let pattern = ::regex::Regex::new(&::std::format!("^(?:{})$", expected))
+                .map_err(|e| ::std::format!("invalid pattern: {}", e))?;
+            if !pattern.is_match(&actual) {
+                return ::std::result::Result::Err(::std::convert::From::from(
+                    __conformance_regex_mismatch(expected, &actual),
+                ));
+            }
+            ::std::result::Result::Ok(())
+        }
+    } else if options.mode.is_structural() {
+        // the output section is compared as written, as its encoding is what may differ
+        quote! {
+            #helpers
+            let actual = #actual;
+            #normalize_actual
+            if let ::std::option::Option::Some(e) = #structural_mismatch(&actual, expected)? {
+                let e = ::std::format!("{}\nexpected: {}\nactual: {}", e, expected, actual);
+                return ::std::result::Result::Err(::std::convert::From::from(e));
+            }
+            ::std::result::Result::Ok(())
+        }
+    } else if options.template {
+        // the output section is a template, which `de` can't normalize
+        quote! {
+            #helpers
+            let actual = #actual;
+            #normalize_actual
+            if let ::std::option::Option::Some(e) = __conformance_template_mismatch(&actual, expected)? {
+                let e = ::std::format!("{}\nexpected: {}\nactual: {}", e, expected, actual);
+                return ::std::result::Result::Err(::std::convert::From::from(e));
+            }
+            ::std::result::Result::Ok(())
+        }
+    } else if let Some(predicate) = &options.predicate {
+        // the output section only describes what the predicate checks
+        quote! {
+            #helpers
+            let actual = #actual;
+            #normalize_actual
+            if let ::std::result::Result::Err(e) = #predicate(&actual) {
+                let mut message = ::std::format!("predicate `{}` failed: {}", ::std::stringify!(#predicate), e);
+                if !expected.is_empty() {
+                    message.push_str("\nexpected: ");
+                    message.push_str(expected);
+                }
+                return ::std::result::Result::Err(::std::convert::From::from(message));
+            }
+            ::std::result::Result::Ok(())
+        }
+    } else {
+        let assert = match &options.message {
+            Some(Message {
+                template,
+                placeholders,
+            }) => {
+                let placeholders = placeholders
+                    .iter()
+                    .map(|name| syn::Ident::new(name, Span::call_site()));
+                quote! {
+                    let _ = (name, file, line);
+                    if actual != expected {
+                        ::std::panic!("{}", ::std::format!(#template, #(#placeholders = #placeholders),*));
+                    }
+                }
+            }
+            None if options.max_diff_lines.is_some() => {
+                let line_diff = line_diff_fn();
+                let max_diff_lines = options.max_diff_lines.unwrap();
+                // the outputs themselves may be huge, so unlike `assert_eq!` they aren't printed
+                let preprocessed = if options.preprocess.is_some() {
+                    quote!(message.push_str(&::std::format!("\npreprocessed input:\n{}", preprocessed));)
+                } else {
+                    TokenStream::new()
+                };
+                quote! {
+                    #first_difference
+                    #line_diff
+                    if actual != expected {
+                        let mut message = __conformance_first_difference(&actual, &expected);
+                        message.push('\n');
+                        message.push_str(&__conformance_line_diff(&actual, &expected, #max_diff_lines));
+                        #preprocessed
+                        ::std::panic!("{}", message);
+                    }
+                }
+            }
+            None if options.preprocess.is_some() => quote! {
+                #first_difference
+                ::std::assert_eq!(
+                    actual,
+                    expected,
+                    "{}\npreprocessed input:\n{}",
+                    __conformance_first_difference(&actual, &expected),
+                    preprocessed,
+                );
+            },
+            None => quote! {
+                #first_difference
+                ::std::assert_eq!(actual, expected, "{}", __conformance_first_difference(&actual, &expected));
+            },
+        };
+        // the tokens, rather than the text, of the outputs must be the same
+        let assert = if options.tokenize.is_some() {
+            quote! {
+                if let ::std::option::Option::Some(e) = __conformance_token_mismatch(&actual, &expected) {
+                    ::std::panic!("{}\n  actual: {}\nexpected: {}", e, actual, expected);
+                }
+            }
+        } else {
+            compared(assert)
+        };
+        let oracle = match &options.oracle {
+            Some(oracle) => {
+                if !cfg!(feature = "oracle") {
+                    return Err(compile_error(
+                        "the `oracle` option requires the `oracle` feature of `conformance`",
+                        oracle.command.span(),
+                    ));
+                }
+                oracle_fn(oracle)
+            }
+            None => TokenStream::new(),
+        };
+        if options.retry == 0 {
+            // the oracle is given the input, which `actual` no longer is
+            let input = if options.oracle.is_some() {
+                quote!(let input = actual;)
+            } else {
+                TokenStream::new()
+            };
+            quote! {
+                #helpers
+                #oracle
+                #input
+                let actual = #actual;
+                let expected = #expected;
+                #normalize_actual
+                #normalize_expected
+                #assert
+                ::std::result::Result::Ok(())
+            }
+        } else {
+            // a workaround for nondeterministic serializers: try again until the output matches
+            let retry = options.retry;
+            quote! {
+                #helpers
+                #oracle
+                let input = actual;
+                let expected = #expected;
+                #normalize_expected
+                let mut retries = 0;
+                let actual = loop {
+                    let actual = input;
+                    let actual = #actual;
+                    #normalize_actual
+                    if actual == expected || retries == #retry {
+                        break actual;
+                    }
+                    retries += 1;
+                };
+                if retries > 0 && actual == expected {
+                    ::std::eprintln!("output matched after {} of {} retries", retries, #retry);
+                }
+                #assert
+                ::std::result::Result::Ok(())
+            }
+        }
+    };
+    // the `message` template can refer to the test, so the testing function is told which
+    let (location_params, location_args) =
+        if options.message.is_some() && options.predicate.is_none() {
+            (
+                quote!(, name: &str, file: &str, line: usize),
+                quote!(, name, file, line),
+            )
+        } else {
+            Default::default()
+        };
+
+    // `@error kind=<tag>` cases check the kind before the message
+    let (kind_param, check_kind) = match &options.error_kind {
+        Some(error_kind) => (
+            quote!(, __kind: ::std::option::Option<&str>),
+            quote! {
+                if let ::std::option::Option::Some(__kind) = __kind {
+                    let __found: &str = #error_kind(&__e);
+                    if __found != __kind {
+                        let __e = ::std::format!(
+                            "`de` error {:?} is of kind `{}`, not `{}`",
+                            ::std::string::ToString::to_string(&__e),
+                            __found,
+                            __kind,
+                        );
+                        return ::std::result::Result::Err(::std::convert::From::from(__e));
+                    }
+                }
+            },
+        ),
+        None => Default::default(),
+    };
+    // and `@error_at line:column` cases the position
+    let (at_param, check_at) = match &options.error_pos {
+        Some(error_pos) => (
+            quote!(, __at: ::std::option::Option<(usize, usize)>),
+            quote! {
+                if let ::std::option::Option::Some(__at) = __at {
+                    let __found: (usize, usize) = #error_pos(&__e);
+                    if __found != __at {
+                        let __e = ::std::format!(
+                            "`de` error {:?} is at {}:{}, not {}:{}",
+                            ::std::string::ToString::to_string(&__e),
+                            __found.0,
+                            __found.1,
+                            __at.0,
+                            __at.1,
+                        );
+                        return ::std::result::Result::Err(::std::convert::From::from(__e));
+                    }
+                }
+            },
+        ),
+        None => Default::default(),
+    };
+    // the error is either serialized and compared whole, or its message searched
+    let check_error = match &options.error_ser {
+        Some(error_ser) => quote! {
+            let __found: ::std::string::String = #error_ser(&__e);
+            // without an output section, the input only has to be rejected
+            if expected.is_empty() || __found.trim() == expected {
+                ::std::result::Result::Ok(())
+            } else {
+                let __e = ::std::format!(
+                    "`de` error serialized by `{}` to {:?}, not {:?}",
+                    ::std::stringify!(#error_ser),
+                    __found.trim(),
+                    expected,
+                );
+                ::std::result::Result::Err(::std::convert::From::from(__e))
+            }
+        },
+        None => quote! {
+            let __e = ::std::string::ToString::to_string(&__e);
+            if __e.contains(expected) {
+                ::std::result::Result::Ok(())
+            } else {
+                let __e = ::std::format!("`de` error {:?} does not contain {:?}", __e, expected);
+                ::std::result::Result::Err(::std::convert::From::from(__e))
+            }
+        },
+    };
+    // `@error` cases check that `de` rejects the input
+    let reject = if options.de_option {
+        quote! {
+            ::std::option::Option::Some(_) => {
+                ::std::result::Result::Err(::std::convert::From::from("`de` accepted the input"))
+            }
+            ::std::option::Option::None => {
+                let _ = expected;
+                ::std::result::Result::Ok(())
+            }
+        }
+    } else {
+        quote! {
+            ::std::result::Result::Ok(_) => {
+                ::std::result::Result::Err(::std::convert::From::from("`de` accepted the input"))
+            }
+            ::std::result::Result::Err(__e) => {
+                #check_kind
+                #check_at
+                #check_error
+            }
+        }
+    };
+    let de = if options.stream {
+        let de = with_turbofish(de, &de_type);
+        quote!(#de(input #(, #de_args)*)#collect)
+    } else if options.batch {
+        // the values are given to the tested function for their type, if all are accepted
+        let values = batch(quote!(input));
+        quote!(#values.map(#fn_name))
+    } else {
+        deserialize(quote!(input))
+    };
+    let error_fn_body = quote! {
+        match #de {
+            #reject
+        }
+    };
+    // `@incomplete` cases check what `de` returns with the `incomplete` classifier
+    let incomplete_fn_body = options.incomplete.as_ref().map(|incomplete| {
+        quote! {
+            let __result = #de;
+            if #incomplete(&__result) {
+                ::std::result::Result::Ok(())
+            } else {
+                let mut __e = ::std::format!(
+                    "`{}` found that `de` didn't need more data",
+                    ::std::stringify!(#incomplete),
+                );
+                if !expected.is_empty() {
+                    __e.push_str("\nexpected: ");
+                    __e.push_str(expected);
+                }
+                ::std::result::Result::Err(::std::convert::From::from(__e))
+            }
+        }
+    });
+    // `@expr` outputs are compared as what they serialize to, as if written out
+    let expr_fn_body = match serialized_type {
+        // `check!` has no test file, so no `@expr` tests, nor a type to give them
+        syn::Type::Infer(_) => None,
+        _ if options.stream => Some((
+            quote!(::std::vec::Vec<#de_type>),
+            serialize(quote!(expected)),
+        )),
+        _ => Some((quote!(#serialized_type), serialize(quote!(expected)))),
+    };
+    // each function preprocesses its inputs up front, keeping them for failure messages
+    let (preprocess_actual, preprocess_inputs, preprocess_input) = match &options.preprocess {
+        Some(preprocess) => {
+            let preprocess = preprocess_fn(preprocess);
+            // in `idempotent` mode, the expected output is the input itself
+            let preprocess_expected = if options.mode == Mode::Idempotent {
+                quote!(let expected: &str = &preprocessed;)
+            } else {
+                TokenStream::new()
+            };
+            (
+                quote! {
+                    #preprocess
+                    let preprocessed = __conformance_preprocess(actual)?;
+                    let actual: &str = &preprocessed;
+                    #preprocess_expected
+                },
+                quote! {
+                    #preprocess
+                    let preprocessed = inputs
+                        .iter()
+                        .map(|input| __conformance_preprocess(input))
+                        .collect::<::std::result::Result<::std::vec::Vec<_>, _>>()?;
+                    let inputs: ::std::vec::Vec<&str> = preprocessed.iter().map(|input| &**input).collect();
+                },
+                quote! {
+                    #preprocess
+                    let preprocessed = __conformance_preprocess(input)?;
+                    let input: &str = &preprocessed;
+                },
+            )
+        }
+        None => Default::default(),
+    };
+    Ok(move |testing_fn: &syn::Ident| {
+        let equivalent_fn = equivalent_fn(testing_fn);
+        let consistent_fn = consistent_fn_body.as_ref().map(|body| {
+            let consistent_fn = consistent_fn(testing_fn);
+            quote! {
+                #[allow(dead_code)]
+                fn #consistent_fn(__first: &str, __second: &str, __names: (&str, &str)) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+                    #body
+                }
+            }
+        });
+        let incomplete_fn = incomplete_fn_body.as_ref().map(|body| {
+            let incomplete_fn = incomplete_fn(testing_fn);
+            quote! {
+                #[allow(dead_code)]
+                #[track_caller]
+                fn #incomplete_fn(expected: &str, input: &str) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+                    #preprocess_input
+                    #body
+                }
+            }
+        });
+        let expr_fn = expr_fn_body.as_ref().map(|(value_type, expected)| {
+            let expr_fn = expr_fn(testing_fn);
+            quote! {
+                #[allow(dead_code)]
+                #[track_caller]
+                fn #expr_fn(expected: #value_type, actual: &str #location_params) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+                    let expected = #expected;
+                    #testing_fn(&expected, actual #location_args)
+                }
+            }
+        });
+        let warnings_fn = warnings_fn_body.as_ref().map(|body| {
+            let warnings_fn = warnings_fn(testing_fn);
+            quote! {
+                #[allow(dead_code)]
+                fn #warnings_fn(actual: &str) -> ::std::result::Result<::std::vec::Vec<::std::string::String>, ::std::boxed::Box<dyn ::std::error::Error>> {
+                    #preprocess_actual
+                    #body
+                }
+            }
+        });
+        // snapshots are written with what would be compared to them,
+        // and benchmarks time the same
+        let actual_fn = if options.snapshots || options.bench {
+            let actual_fn = actual_fn(testing_fn);
+            quote! {
+                #[allow(dead_code)]
+                fn #actual_fn(actual: &str) -> ::std::result::Result<::std::string::String, ::std::boxed::Box<dyn ::std::error::Error>> {
+                    #preprocess_actual
+                    #helpers
+                    let actual = #actual;
+                    #normalize_actual
+                    ::std::result::Result::Ok(actual)
+                }
+            }
+        } else {
+            TokenStream::new()
+        };
+        // an infallible `de` can't reject anything, so there are no `@error` tests to run
+        let error_fn = if options.de_infallible {
+            TokenStream::new()
+        } else {
+            let error_fn = error_fn(testing_fn);
+            quote! {
+                #[allow(dead_code)]
+                #[track_caller]
+                fn #error_fn(expected: &str, input: &str #kind_param #at_param) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+                    #preprocess_input
+                    #error_fn_body
+                }
+            }
+        };
+        quote! {
+            #assert_serde
+            #assert_clone
+
+            // failures are reported at the call in the generated test function
+            #[allow(dead_code)]
+            #[track_caller]
+            #[must_use = "the test only fails if its `Err` is returned or unwrapped"]
+            fn #testing_fn(expected: &str, actual: &str #location_params) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+                #preprocess_actual
+                #testing_fn_body
+            }
+
+            #[allow(dead_code)]
+            #[track_caller]
+            fn #equivalent_fn(inputs: &[&str]) -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+                #preprocess_inputs
+                #equivalent_fn_body
+            }
+
+            #error_fn
+
+            #consistent_fn
+
+            #incomplete_fn
+
+            #expr_fn
+
+            #actual_fn
+
+            #warnings_fn
+        }
+    })
+}
+
+/// A module named after the test file at `path`, documented with each of `tests` as an example.
+///
+/// The examples are `text` blocks, as the module can't know how to call the tested function,
+/// and so are shown by `rustdoc` but not run.
+fn doc_examples(
+    path: &Path,
+    tests: &[Test],
+    fn_name: &TokenStream,
+    options: &Options,
+) -> TokenStream {
+    let file = tests.first().map_or_else(
+        || path.to_string_lossy().to_string(),
+        |test| test.file.clone(),
+    );
+    let mut doc = format!("Examples from `{}`.\n", file);
+    for test in tests.iter().filter(|test| test.ignore.is_none()) {
+        doc.push_str(&format!("\n# {}\n\n", test.title));
+        doc.push_str(&fenced(&test.input.text));
+        for equivalent in &test.equivalents {
+            doc.push_str("\nis the same as\n\n");
+            doc.push_str(&fenced(&equivalent.text));
+        }
+        // equivalent inputs have no output to show
+        let output = match options.mode {
+            _ if test.error => "is rejected with an error containing",
+            _ if test.incomplete => "is incomplete, needing more data",
+            _ if !test.equivalents.is_empty() => continue,
+            Mode::Exact => "gives",
+            Mode::Idempotent => "is unchanged",
+            Mode::Regex => "gives output matching",
+            Mode::Msgpack => "gives the MessagePack value of",
+            Mode::Cbor => "gives the CBOR value of",
+            Mode::Xml => "gives the XML document",
+            Mode::Approx => "gives approximately",
+            Mode::YamlToplevelUnordered => "gives the YAML value",
+        };
+        if (options.mode == Mode::Idempotent && !test.error) || test.incomplete {
+            doc.push_str(&format!("\n{}.\n", output));
+        } else {
+            doc.push_str(&format!("\n{}\n\n", output));
+            doc.push_str(&fenced(&test.output.text));
+        }
+    }
+
+    let module = match &options.mod_name {
+        Some(mod_name) => mod_name.clone(),
+        None => sanitize_ident(&format!(
+            "{}_examples",
+            path.file_stem().unwrap().to_string_lossy()
+        )),
+    };
+    let filepath = path.to_string_lossy().to_string();
+    quote! {
+        #[doc = #doc]
+        pub mod #module {}
+        const _: &[u8] = ::std::include_bytes!(#filepath);
+        // the tested function is otherwise unused
+        const _: () = { let _ = #fn_name; };
+    }
+}
+
+/// `body` as a Markdown `text` block, fenced with more backticks than any run of them in `body`,
+/// so that none of its backticks or fences end the block.
+fn fenced(body: &str) -> String {
+    let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{}text\n{}\n{}\n", fence, body, fence)
+}
+
+/// The tests of `fun`, whose `file` or `dir` is relative to `base_dir`.
+pub(crate) fn build_tests(mut args: AttrArgs, fun: syn::ItemFn, base_dir: PathBuf) -> TokenStream {
+    args.options.identity = names_tested_type(&fun);
+    if args.options.wasm && !cfg!(feature = "wasm") {
+        return compile_error(
+            "`wasm` requires the `wasm` feature of `conformance`",
+            Span::call_site(),
+        );
+    }
+    if args.options.identity && args.options.batch {
+        return compile_error(
+            "`batch` passes the values of each input to the tested function, \
+             so it needs a body which transforms them",
+            fun.sig.ident.span(),
+        );
+    }
+    match (&args.options.runtime, &fun.sig.asyncness) {
+        (None, Some(asyncness)) => {
+            return compile_error(
+                "the tested function is `async`; give a `runtime` option, \
+                 a function building the runtime to run it on",
+                asyncness.span(),
+            );
+        }
+        (Some(runtime), None) => {
+            let e = format!(
+                "`runtime` runs an `async` tested function, but `{}` isn't `async`",
+                fun.sig.ident
+            );
+            return compile_error(&e, runtime.span());
+        }
+        (Some(runtime), Some(_)) if args.options.batch || args.options.identity => {
+            return compile_error(
+                "`runtime` runs the tested function on each input, \
+                 so it can't be used with `batch`, or a function which only names a type",
+                runtime.span(),
+            );
+        }
+        _ => (),
+    }
+    let AttrArgs {
+        source, options, ..
+    } = &args;
+    // files with a frontmatter `type` are tested with an instantiation of their own
+    let instantiated = instantiate(
+        &fun,
+        options.transform_turbofish.as_ref(),
+        "`transform_turbofish`",
+    );
+    let (path, span, argument) = match source {
+        Source::File(path) => (path, path.span(), "file"),
+        Source::Dir(path) => (path, path.span(), "dir"),
+        Source::Pairs { input_dir, .. } => (input_dir, input_dir.span(), "input_dir"),
+        Source::Cases { name, .. } => (name, name.span(), "cases"),
+    };
+    let (path, mut env_vars) = match expand_env_vars(&path.value(), argument) {
+        Ok(it) => it,
+        Err(e) => return compile_error(&e, span),
+    };
+    let path = base_dir.join(path);
+    let output_dir = match source {
+        Source::Pairs { output_dir, .. } => {
+            match expand_env_vars(&output_dir.value(), "output_dir") {
+                Ok((output_dir, vars)) => {
+                    env_vars.extend(vars);
+                    Some(base_dir.join(output_dir))
+                }
+                Err(e) => return compile_error(&e, output_dir.span()),
+            }
+        }
+        _ => None,
+    };
+    // `env!` makes changing the variables rebuild the tests, like editing the file does,
+    // and `option_env!` does so for `CONFORMANCE_MODE`, which needn't be set
+    let mut track_env_vars = quote! {
+        #(const _: &str = ::std::env!(#env_vars);)*
+        const _: ::std::option::Option<&str> = ::std::option_env!("CONFORMANCE_MODE");
+    };
+    if let Some((attribute_mode, mode_span)) = options.attribute_mode {
+        track_env_vars.extend(compile_warning(
+            &format!(
+                "`CONFORMANCE_MODE` overrides the `{}` mode of the attribute with `{}`",
+                attribute_mode.name(),
+                options.mode.name()
+            ),
+            mode_span,
+        ));
+    }
+    // a broken symlink isn't missing, but a mistake to report
+    if options.skip_if_missing && fs::symlink_metadata(&path).is_err() {
+        let mut tts = compile_warning(
+            &format!(
+                "`{}` does not exist, so no tests were generated",
+                path.display()
+            ),
+            span,
+        );
+        let fn_name = match instantiated {
+            Ok((fn_name, _)) => fn_name,
+            Err(e) => return e,
+        };
+        // the tested function is otherwise unused
+        tts.extend(quote!(const _: () = { let _ = #fn_name; };));
+        tts.extend(track_env_vars);
+        return tts;
+    }
+
+    if !options.allow_outside {
+        let outside = std::iter::once(path.as_path())
+            .chain(output_dir.as_deref())
+            .find_map(outside_manifest_dir);
+        if let Some(e) = outside {
+            return compile_error(&e, span);
+        }
+    }
+
+    // opening a directory fails with an error which doesn't say what to do instead
+    if let Source::File(file) = source {
+        if path.is_dir() {
+            let e = format!(
+                "`file` names `{}`, which is a directory; to test each file in it, \
+                 use `dir = {:?}` instead, with `extension` for files not ending in `.test`",
+                path.display(),
+                file.value(),
+            );
+            return compile_error(&e, span);
+        }
+    }
+
+    let mut tts = match source {
+        Source::File(_) | Source::Pairs { .. } | Source::Cases { .. } => {
+            let tests_path = path;
+            // the pairs' files are included one by one, with no file of tests to slice,
+            // and inline cases have no file at all
+            let read = match (&output_dir, source) {
+                (_, Source::Cases { cases, .. }) => inline_tests(cases, &tests_path, span, options)
+                    .map(|tests| (tests, None, Some(TokenStream::new()))),
+                (Some(output_dir), _) => pair_tests(&tests_path, output_dir, span, options)
+                    .map(|(tests, include)| (tests, None, Some(include))),
+                (None, _) => read_tests(&tests_path, span, options)
+                    .map(|(tests, r#type)| (tests, r#type, None)),
+            };
+            let (tests, r#type, include) = match read {
+                Ok(it) => it,
+                Err(e) => return e,
+            };
+            let instantiated = match r#type {
+                Some(r#type) => instantiate_frontmatter(&fun, &r#type),
+                None => instantiated,
+            };
+            if options.doc_examples {
+                return match instantiated {
+                    Ok((fn_name, _)) => {
+                        let mut tts = doc_examples(&tests_path, &tests, &fn_name, options);
+                        tts.extend(track_env_vars);
+                        tts
+                    }
+                    Err(e) => e,
+                };
+            }
+            let (fn_name, tested_type) = match instantiated {
+                Ok(it) => it,
+                Err(e) => return e,
+            };
+            let testing_fns = match testing_fns(&args, &fn_name, &tested_type) {
+                Ok(testing_fns) => testing_fns,
+                Err(e) => return e,
+            };
+
+            let filename = match &options.mod_name {
+                Some(mod_name) => mod_name.to_string(),
+                None if output_dir.is_some() => {
+                    sanitize_ident(&tests_path.file_name().unwrap_or_default().to_string_lossy())
+                        .to_string()
+                }
+                None => tests_path
+                    .file_stem()
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('.', "_"),
+            };
+            let testing_fn = syn::Ident::new(&filename, Span::call_site());
+            let mut testing_fns = testing_fns(&testing_fn);
+            match override_testing_fns(&args, &fn_name, &tested_type, &testing_fn, &tests) {
+                Ok(override_fns) => testing_fns.extend(override_fns),
+                Err(e) => return e,
+            }
+            let contents = quote::format_ident!("{}_CONTENTS", filename.to_uppercase());
+            let include = include
+                .unwrap_or_else(|| include_test_file(&tests_path, &contents, &tests, options));
+            let mut warnings = identical_warnings(&tests, options, span);
+            warnings.extend(similar_name_warnings(&tests, options, span));
+            warnings.extend(oversized_warning(&tests, options, span));
+            warnings.extend(duplicate_warnings(&tests, span));
+            let found = case_names_of(&tests);
+            warnings.extend(unknown_exclusions(&found, options));
+            match required_cases(&found, &base_dir, options) {
+                Ok(required) => warnings.extend(required),
+                Err(e) => return e,
+            }
+            let case_names = quote::format_ident!("{}_CASE_NAMES", filename.to_uppercase());
+            let case_count = quote::format_ident!("{}_CASE_COUNT", filename.to_uppercase());
+            let case_meta = quote::format_ident!("{}_CASE_META", filename.to_uppercase());
+            let names = tests.iter().map(Test::case_name);
+            let meta = tests.iter().map(Test::case_meta);
+            let count = tests.len();
+
+            let mut tts = quote! {
+                #warnings
+
+                #include
+                /// The name, or `@id` if given, of each case in the file.
+                #[allow(dead_code)]
+                const #case_names: &[&str] = &[#(#names),*];
+                /// The `@meta` pairs of each case in the file, in the order of its names.
+                #[allow(dead_code)]
+                const #case_meta: &[&[(&str, &str)]] = &[#(#meta),*];
+                /// The number of cases in the file.
+                #[allow(dead_code)]
+                const #case_count: usize = #count;
+
+                #testing_fns
+            };
+
+            let test_fns = if options.aggregate {
+                let test_name = quote::format_ident!("{}_all_cases", filename);
+                Ok(aggregate_test_fn(
+                    &test_name,
+                    &testing_fn,
+                    &contents,
+                    &tests,
+                    options,
+                ))
+            } else {
+                group_tests(&tests, span, options, |test| {
+                    // the prefix separates the file's name from the test's, unless it is a word
+                    let separator = if test.name.to_string().starts_with('_') {
+                        ""
+                    } else {
+                        "_"
+                    };
+                    let test_name = quote::format_ident!("{}{}{}", filename, separator, test.name);
+                    test_fn(&test_name, &testing_fn, &contents, test, options)
+                })
+            };
+            match test_fns {
+                Ok(test_fns) => tts.extend(test_fns),
+                Err(e) => return e,
+            }
+            if options.index {
+                let index_name = quote::format_ident!("{}_index", filename);
+                tts.extend(index_test_fn(&index_name, &tests, options));
+            }
+            if options.check_consistency {
+                let consistency_name = quote::format_ident!("{}_consistency", filename);
+                tts.extend(consistency_test_fn(
+                    &consistency_name,
+                    &testing_fn,
+                    &contents,
+                    &tests,
+                    options,
+                ));
+            }
+            if options.bench {
+                let bench_fn = quote::format_ident!("{}_bench", filename);
+                tts.extend(bench_fn_of(&bench_fn, &testing_fn, &contents, &tests));
+            }
+
+            tts
+        }
+        Source::Dir(dir) => {
+            let dir_path = path;
+            // each file's module is still named after it
+            let testing_fn = match &options.mod_name {
+                Some(mod_name) => mod_name.clone(),
+                None => sanitize_ident(
+                    &dir_path
+                        .file_name()
+                        .map_or_else(|| dir.value(), |name| name.to_string_lossy().to_string()),
+                ),
+            };
+            let extension = options
+                .extension
+                .as_deref()
+                .unwrap_or(match options.file_format {
+                    FileFormat::Native => "test",
+                    FileFormat::Csv => "csv",
+                    FileFormat::Tsv => "tsv",
+                    FileFormat::Jsonl => "jsonl",
+                });
+            let walker = DirWalker {
+                testing_fn: &testing_fn,
+                extension,
+                args: &args,
+                fun: &fun,
+                instantiated: &instantiated,
+                span: dir.span(),
+            };
+            let mut found = BTreeSet::new();
+            let tests = match walker.walk(&dir_path, 1, &mut found) {
+                Ok(it) => it,
+                Err(e) => return e,
+            };
+            let unknown = unknown_exclusions(&found, options);
+            if !unknown.is_empty() {
+                return unknown;
+            }
+            let required = match required_cases(&found, &base_dir, options) {
+                Ok(required) => required,
+                Err(e) => return e,
+            };
+
+            // which is only an error if some file has no frontmatter `type`, found by the walker
+            let testing_fns = match &instantiated {
+                Ok((fn_name, tested_type)) => match testing_fns(&args, fn_name, tested_type) {
+                    Ok(testing_fns) => testing_fns(&testing_fn),
+                    Err(e) => return e,
+                },
+                Err(_) => TokenStream::new(),
+            };
+            quote! {
+                #required
+                #testing_fns
+
+                mod #testing_fn {
+                    #tests
+                }
+            }
+        }
+    };
+    tts.extend(track_env_vars);
+    hide_from_docs(tts)
+}
+
+/// Mark each item of `generated` `#[doc(hidden)]`, so that tests don't show up in the docs of
+/// crates documenting private items. Macro calls, such as `compile_error!`, are left as they are.
+fn hide_from_docs(generated: TokenStream) -> TokenStream {
+    match syn::parse2::<syn::File>(generated.clone()) {
+        Ok(file) => file
+            .items
+            .into_iter()
+            .map(|item| match item {
+                syn::Item::Macro(_) => quote!(#item),
+                item => quote!(#[doc(hidden)] #item),
+            })
+            .collect(),
+        Err(_) => generated,
+    }
+}
+
+/// Expand the `${VAR}` segments of the `argument` path from the environment at compile time,
+/// giving the expanded path and the names of the variables it used.
+pub(crate) fn expand_env_vars(path: &str, argument: &str) -> Result<(String, Vec<String>), String> {
+    let mut expanded = String::new();
+    let mut vars = Vec::new();
+    let mut rest = path;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("`{}` has a `${{` without a closing `}}`", argument))?;
+        let var = &rest[start + 2..start + end];
+        if var.is_empty() {
+            return Err(format!(
+                "`{}` has a `${{}}` without a variable name",
+                argument
+            ));
+        }
+        let value = env::var(var).map_err(|e| {
+            format!(
+                "`{}` uses the environment variable `{}`, but it is {}",
+                argument,
+                var,
+                match e {
+                    env::VarError::NotPresent => "not set",
+                    env::VarError::NotUnicode(_) => "not valid unicode",
+                }
+            )
+        })?;
+        expanded.push_str(&value);
+        if !vars.iter().any(|v| v == var) {
+            vars.push(var.to_string());
+        }
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok((expanded, vars))
+}
+
+/// Generate the test functions of `tests` with `test_fn`,
+/// putting those in a `@group` in a module named after it.
+///
+/// With `sort_cases`, the functions are emitted sorted by their identifiers, and those of the
+/// same identifier in the order of the file; otherwise, all are in the order of the file.
+fn group_tests(
+    tests: &[Test],
+    span: Span,
+    options: &Options,
+    test_fn: impl Fn(&Test) -> TokenStream,
+) -> Result<TokenStream, TokenStream> {
+    let mut tts = TokenStream::new();
+    let mut groups: BTreeMap<String, (&str, TokenStream)> = BTreeMap::new();
+    let mut errs = TokenStream::new();
+
+    let mut ordered: Vec<&Test> = tests.iter().collect();
+    if options.sort_cases {
+        ordered.sort_by_cached_key(|test| test.name.to_string());
+    }
+    for test in ordered {
+        let group = match &test.group {
+            Some(group) => group,
+            None => {
+                tts.extend(test_fn(test));
+                continue;
+            }
+        };
+        let module = sanitize_ident(group).to_string();
+        let (previous, group_tts) = groups
+            .entry(module.clone())
+            .or_insert((group, TokenStream::new()));
+        if previous != group {
+            errs.extend(compile_error(
+                &format!(
+                    "groups `{}` and `{}` both map to module `{}`",
+                    previous, group, module,
+                ),
+                span,
+            ));
+            continue;
+        }
+        group_tts.extend(test_fn(test));
+    }
+
+    for (module, (_, group_tts)) in groups {
+        let module = syn::Ident::new(&module, Span::call_site());
+        tts.extend(quote! {
+            mod #module {
+                use super::*;
+                #group_tts
+            }
+        });
+    }
+
+    if errs.is_empty() {
+        Ok(tts)
+    } else {
+        Err(errs)
+    }
+}
+
+/// Recursive discovery of test files for `dir` mode.
+///
+/// Each subdirectory and test file becomes a module named after it.
+/// Entries are visited in sorted order, so output is reproducible.
+struct DirWalker<'a> {
+    testing_fn: &'a syn::Ident,
+    extension: &'a str,
+    args: &'a AttrArgs,
+    fun: &'a syn::ItemFn,
+    /// The instantiation of the tested function for files without a frontmatter `type`.
+    instantiated: &'a Result<(TokenStream, syn::Type), TokenStream>,
+    span: Span,
+}
+
+impl DirWalker<'_> {
+    /// Generate the contents of the module for `dir` at module `depth`,
+    /// adding the names and `@id`s of its cases to `found`.
+    fn walk(
+        &self,
+        dir: &Path,
+        depth: usize,
+        found: &mut BTreeSet<(String, Option<String>)>,
+    ) -> Result<TokenStream, TokenStream> {
+        let entries = fs::read_dir(dir).map_err(|e| {
+            let e = format!("failed to read directory `{}`: {}", dir.display(), e);
+            compile_error(&e, self.span)
+        })?;
+        let mut paths = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                let e = format!("failed to read directory `{}`: {}", dir.display(), e);
+                compile_error(&e, self.span)
+            })?;
+        paths.sort();
+
+        let suffix = format!(".{}", self.extension);
+        let mut names = BTreeMap::new();
+        let mut tts = TokenStream::new();
+        let mut errs = TokenStream::new();
+
+        for path in paths {
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            let (name, contents) = if path.is_dir() {
+                match self.walk(&path, depth + 1, found) {
+                    Ok(contents) if contents.is_empty() => continue,
+                    Ok(contents) => (sanitize_ident(&file_name), contents),
+                    Err(e) => {
+                        errs.extend(e);
+                        continue;
+                    }
+                }
+            } else if file_name.ends_with(&suffix) {
+                match self.file(&path, depth + 1, found) {
+                    Ok(contents) => (
+                        sanitize_ident(&file_name[..file_name.len() - suffix.len()]),
+                        contents,
+                    ),
+                    Err(e) => {
+                        errs.extend(e);
+                        continue;
+                    }
+                }
+            } else {
+                continue;
+            };
+
+            if let Some(previous) = names.insert(name.to_string(), file_name.clone()) {
+                errs.extend(compile_error(
+                    &format!(
+                        "`{}` and `{}` in `{}` both map to module `{}`",
+                        previous,
+                        file_name,
+                        dir.display(),
+                        name,
+                    ),
+                    self.span,
+                ));
+                continue;
+            }
+
+            tts.extend(quote! {
+                pub(crate) mod #name {
+                    #contents
+                }
+            });
+        }
+
+        if errs.is_empty() {
+            Ok(tts)
+        } else {
+            Err(errs)
+        }
+    }
+
+    /// Generate the contents of the module for test file `path` at module `depth`,
+    /// adding the names and `@id`s of its cases to `found`.
+    fn file(
+        &self,
+        path: &Path,
+        depth: usize,
+        found: &mut BTreeSet<(String, Option<String>)>,
+    ) -> Result<TokenStream, TokenStream> {
+        let options = &self.args.options;
+        let (tests, r#type) = read_tests(path, self.span, options)?;
+        found.extend(case_names_of(&tests));
+        let testing_fn = self.testing_fn;
+        let supers: Vec<_> = (0..depth).map(|_| quote!(super::)).collect();
+        // the file's own testing functions need everything the directory's can name
+        let import_all = quote! {
+            #[allow(unused_imports)]
+            use #(#supers)* *;
+        };
+        let contents = quote::format_ident!("CONTENTS");
+        let consistency = if options.check_consistency {
+            let consistency_name = quote::format_ident!("{}_consistency", testing_fn);
+            consistency_test_fn(&consistency_name, testing_fn, &contents, &tests, options)
+        } else {
+            TokenStream::new()
+        };
+        let testing_fns = match r#type {
+            Some(r#type) => {
+                let (fn_name, tested_type) = instantiate_frontmatter(self.fun, &r#type)?;
+                let testing_fns = testing_fns(self.args, &fn_name, &tested_type)?(testing_fn);
+                let override_fns =
+                    override_testing_fns(self.args, &fn_name, &tested_type, testing_fn, &tests)?;
+                quote! {
+                    #import_all
+                    #testing_fns
+                    #override_fns
+                }
+            }
+            None => {
+                let (fn_name, tested_type) = self.instantiated.as_ref().map_err(Clone::clone)?;
+                // import only the testing functions used, to avoid unused import warnings
+                let shared = tests
+                    .iter()
+                    .filter(|test| test.override_fn(testing_fn).is_none());
+                let used: BTreeSet<_> = shared
+                    .clone()
+                    .map(|test| test.testing_fn(testing_fn))
+                    .chain(
+                        shared
+                            .filter(|test| test.takes_snapshot(options))
+                            .map(|_| actual_fn(testing_fn)),
+                    )
+                    .chain(Some(consistent_fn(testing_fn)).filter(|_| !consistency.is_empty()))
+                    .collect();
+                let mut tts: TokenStream = used
+                    .iter()
+                    .map(|used| quote!(use #(#supers)* #used;))
+                    .collect();
+                let override_fns =
+                    override_testing_fns(self.args, fn_name, tested_type, testing_fn, &tests)?;
+                if !override_fns.is_empty() {
+                    tts.extend(import_all);
+                    tts.extend(override_fns);
+                }
+                tts
+            }
+        };
+
+        let include = include_test_file(path, &contents, &tests, options);
+        let mut warnings = identical_warnings(&tests, options, self.span);
+        warnings.extend(similar_name_warnings(&tests, options, self.span));
+        warnings.extend(oversized_warning(&tests, options, self.span));
+        warnings.extend(duplicate_warnings(&tests, self.span));
+        let names = tests.iter().map(Test::case_name);
+        let meta = tests.iter().map(Test::case_meta);
+        let count = tests.len();
+
+        let index = if options.index {
+            index_test_fn(&quote::format_ident!("index"), &tests, options)
+        } else {
+            TokenStream::new()
+        };
+        let mut tts = quote! {
+            #warnings
+            #include
+            #index
+            #consistency
+            /// The name, or `@id` if given, of each case in the file.
+            #[allow(dead_code)]
+            pub(crate) const CASE_NAMES: &[&str] = &[#(#names),*];
+            /// The `@meta` pairs of each case in the file, in the order of its names.
+            #[allow(dead_code)]
+            pub(crate) const CASE_META: &[&[(&str, &str)]] = &[#(#meta),*];
+            /// The number of cases in the file.
+            #[allow(dead_code)]
+            pub(crate) const CASE_COUNT: usize = #count;
+            #testing_fns
+        };
+        if options.aggregate {
+            let test_name = quote::format_ident!("all_cases");
+            tts.extend(aggregate_test_fn(
+                &test_name, testing_fn, &contents, &tests, options,
+            ));
+            return Ok(tts);
+        }
+        tts.extend(group_tests(&tests, self.span, options, |test| {
+            test_fn(&test.name, testing_fn, &contents, test, options)
+        })?);
+        Ok(tts)
+    }
+}
@@ -1,308 +1,2649 @@
 extern crate proc_macro;
 
 use {
+    crate::{
+        codegen::{build_tests, expand_env_vars, sanitize_ident, testing_fns},
+        options::{
+            AttrArgs, CheckArgs, IncludeArgs, Options, QuickcheckArgs, Source, ValidateArgs,
+        },
+        read::{is_gzip, outside_manifest_dir, read_tests},
+    },
     proc_macro2::{Span, TokenStream},
     quote::{quote, quote_spanned},
     std::{
+        collections::BTreeMap,
         env,
-        fs::File,
-        io::prelude::*,
+        ffi::OsStr,
+        fs,
         path::{Path, PathBuf},
     },
-    syn::parse::Parse,
+    syn::spanned::Spanned,
 };
 
+mod codegen;
+mod options;
+mod read;
+
 fn compile_error(s: &str, span: Span) -> TokenStream {
     quote_spanned!(span=> compile_error! { #s })
 }
 
-struct AttrArgs {
-    ser: syn::ExprPath,
-    de: syn::ExprPath,
-    value: Option<syn::Type>,
-    file: syn::LitStr,
+/// Emit a warning at `span` from stable Rust, by referring to a deprecated item.
+fn compile_warning(s: &str, span: Span) -> TokenStream {
+    let warning = quote::format_ident!("WARNING", span = span);
+    quote_spanned! {span=>
+        const _: () = {
+            #[deprecated(note = #s)]
+            const #warning: () = ();
+            #warning
+        };
+    }
 }
 
-impl Parse for AttrArgs {
-    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
-        mod kw {
-            syn::custom_keyword!(exact);
-            syn::custom_keyword!(file);
-            syn::custom_keyword!(ser);
-            syn::custom_keyword!(de);
-            syn::custom_keyword!(value);
-            syn::custom_keyword!(serde);
-        }
-
-        // TODO: add `superset` mode where actual is "at least" expected
-        let _: kw::exact = input.parse()?;
-        let _: syn::Token![,] = input.parse()?;
+/// The name of the file the tests of `args` for `fun` are written to with `generated_file`,
+/// which is unique as long as functions tested with the same file have different names.
+fn generated_file_name(args: &AttrArgs, fun: &syn::ItemFn) -> String {
+    format!("{}_tests.rs", invocation_name(args, fun))
+}
 
-        let la = input.lookahead1();
-        let (ser, de, value) = if la.peek(kw::serde) {
-            let _: kw::serde = input.parse()?;
-            let _: syn::Token![=] = input.parse()?;
-            let format: syn::ExprPath = input.parse()?;
-            let _: syn::Token![,] = input.parse()?;
+/// A name for the invocation of `conformance::tests` with `args` on `fun`, for its files.
+fn invocation_name(args: &AttrArgs, fun: &syn::ItemFn) -> String {
+    // an unset variable is reported by `build_tests`
+    let expanded = |path: &syn::LitStr| {
+        let path = path.value();
+        expand_env_vars(&path, "").map_or(path, |(expanded, _)| expanded)
+    };
+    let stem = match &args.source {
+        Source::File(file) => Path::new(&expanded(file))
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().replace('.', "_")),
+        Source::Cases { name, .. } => name.value(),
+        Source::Dir(dir) | Source::Pairs { input_dir: dir, .. } => {
+            let dir = expanded(dir);
+            let name = Path::new(&dir)
+                .file_name()
+                .map_or_else(|| dir.clone(), |name| name.to_string_lossy().to_string());
+            sanitize_ident(&name).to_string()
+        }
+    };
+    format!("{}_{}", stem, fun.sig.ident)
+}
 
-            // FUTURE(rust-lang/rust#64797): use #[cfg(accessible)] to prefer `to_string_pretty`
-            // as well as fall back to the `::ser::to_string`/`::de::from_str`/`::value::Value`
+/// The directory of the counts recorded with `count_total`, in `out_dir`.
+fn counts_dir(out_dir: &OsStr) -> PathBuf {
+    Path::new(out_dir).join("conformance").join("counts")
+}
 
-            // for errors
-            let la = input.lookahead1();
-            if !(la.peek(kw::ser) || la.peek(kw::de) || la.peek(kw::value) | la.peek(kw::file)) {
-                return Err(la.error());
+/// The number of `#[test]` functions in `generated`.
+fn count_tests(generated: TokenStream) -> usize {
+    let mut count = 0;
+    let mut after_pound = false;
+    for tt in generated {
+        match tt {
+            proc_macro2::TokenTree::Group(group) => {
+                let is_test = after_pound
+                    && group.delimiter() == proc_macro2::Delimiter::Bracket
+                    && group.stream().to_string() == "test";
+                if is_test {
+                    count += 1;
+                } else {
+                    count += count_tests(group.stream());
+                }
+                after_pound = false;
             }
+            proc_macro2::TokenTree::Punct(punct) => after_pound = punct.as_char() == '#',
+            _ => after_pound = false,
+        }
+    }
+    count
+}
 
-            let ser: syn::ExprPath = if input.peek(kw::ser) {
-                let _: kw::ser = input.parse()?;
-                let _: syn::Token![=] = input.parse()?;
-                let ser: syn::ExprPath = input.parse()?;
-                let _: syn::Token![,] = input.parse()?;
-
-                // for errors
-                let la = input.lookahead1();
-                if !(la.peek(kw::de) || la.peek(kw::value) | la.peek(kw::file)) {
-                    return Err(la.error());
-                }
+/// Record the number of tests in `generated`, read from `source`, for `conformance::total!()`,
+/// in `$OUT_DIR/conformance/counts/<crate>/name`.
+///
+/// Each invocation has a file of its own, so that expanding it again replaces its count.
+fn record_count(name: &str, source: &syn::LitStr, generated: &TokenStream) -> TokenStream {
+    let out_dir = match env::var_os("OUT_DIR") {
+        Some(out_dir) => out_dir,
+        None => {
+            return compile_error(
+                "`count_total` needs $OUT_DIR; add a build script, even an empty one",
+                source.span(),
+            )
+        }
+    };
+    // the integration tests of a package are crates of their own, sharing its `OUT_DIR`
+    let krate = env::var("CARGO_CRATE_NAME").unwrap_or_default();
+    let dir = counts_dir(&out_dir).join(krate);
+    let path = dir.join(name);
+    let count = count_tests(generated.clone()).to_string();
+    let written = fs::create_dir_all(&dir).and_then(|()| {
+        if fs::read(&path).ok().as_deref() != Some(count.as_bytes()) {
+            fs::write(&path, &count)?;
+        }
+        Ok(())
+    });
+    match written {
+        Ok(()) => TokenStream::new(),
+        Err(e) => {
+            let e = format!("failed to write to `{}`: {}", path.display(), e);
+            compile_error(&e, source.span())
+        }
+    }
+}
 
-                ser
-            } else {
-                syn::parse_quote!(#format::to_string)
-            };
+/// The sum of the counts recorded in `dir` and its subdirectories.
+fn sum_counts(dir: &Path) -> Result<usize, String> {
+    let error = |e: std::io::Error| format!("failed to read `{}`: {}", dir.display(), e);
+    let mut total = 0;
+    for entry in fs::read_dir(dir).map_err(error)? {
+        let path = entry.map_err(error)?.path();
+        if path.is_dir() {
+            total += sum_counts(&path)?;
+            continue;
+        }
+        let count = fs::read_to_string(&path).map_err(error)?;
+        total += count
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| format!("`{}` is not a count: {}", path.display(), e))?;
+    }
+    Ok(total)
+}
 
-            let de: syn::ExprPath = if input.peek(kw::de) {
-                let _: kw::de = input.parse()?;
-                let _: syn::Token![=] = input.parse()?;
-                let de: syn::ExprPath = input.parse()?;
-                let _: syn::Token![,] = input.parse()?;
+/// Write `generated`, the tests read from `source`, to `$OUT_DIR/conformance/name`,
+/// returning the `include!` of it.
+///
+/// The file is left untouched if it is up to date, so as not to invalidate the build.
+/// The generated code still `include_str!`s the test file, so editing it rebuilds the tests.
+fn include_generated(name: &str, source: &syn::LitStr, generated: TokenStream) -> TokenStream {
+    let out_dir = match env::var_os("OUT_DIR") {
+        Some(out_dir) => out_dir,
+        None => {
+            return compile_error(
+                "`generated_file` needs $OUT_DIR; add a build script, even an empty one",
+                source.span(),
+            )
+        }
+    };
+    // the code is no longer from a macro expansion, so it is linted as if written by hand
+    let generated = match syn::parse2::<syn::File>(generated.clone()) {
+        Ok(file) => {
+            let items = file.items;
+            quote!(#(#[allow(clippy::all)] #items)*)
+        }
+        Err(_) => generated,
+    };
+    let dir = Path::new(&out_dir).join("conformance");
+    let path = dir.join(name);
+    let contents = format!(
+        "// Generated by `conformance::tests` from `{}`. Do not edit.\n{}\n",
+        source.value(),
+        generated,
+    );
+    let written = fs::create_dir_all(&dir).and_then(|()| {
+        if fs::read(&path).ok().as_deref() != Some(contents.as_bytes()) {
+            fs::write(&path, &contents)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = written {
+        let e = format!("failed to write to `{}`: {}", path.display(), e);
+        return compile_error(&e, source.span());
+    }
 
-                // for errors
-                let la = input.lookahead1();
-                if !(la.peek(kw::value) | la.peek(kw::file)) {
-                    return Err(la.error());
-                }
+    let include = format!("/conformance/{}", name);
+    quote!(::std::include!(::std::concat!(::std::env!("OUT_DIR"), #include));)
+}
 
-                de
-            } else {
-                syn::parse_quote!(#format::from_str)
-            };
+#[proc_macro_attribute]
+pub fn tests(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    // we want to re-emit the notated item in all cases
+    let mut tts: TokenStream = item.clone().into();
 
-            let value: syn::Type = if input.peek(kw::value) {
-                let _: kw::value = input.parse()?;
-                let _: syn::Token![=] = input.parse()?;
-                let value: syn::Type = input.parse()?;
-                let _: syn::Token![,] = input.parse()?;
-                value
-            } else {
-                syn::parse_quote!(#format::Value)
-            };
+    // emit as many environment compile errors as possible in one place
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .map_err(|e| {
+            let e = format!("expected $CARGO_MANIFEST_DIR; {}", e);
+            compile_error(&e, Span::call_site())
+        });
+    let args = syn::parse::<AttrArgs>(attr).map_err(|e| e.to_compile_error());
+    let fun = syn::parse::<syn::ItemFn>(item).map_err(|e| e.to_compile_error());
 
-            (ser, de, Some(value))
-        } else if la.peek(kw::ser) {
-            let _: kw::ser = input.parse()?;
-            let _: syn::Token![=] = input.parse()?;
-            let ser: syn::ExprPath = input.parse()?;
-            let _: syn::Token![,] = input.parse()?;
-
-            let _: kw::de = input.parse()?;
-            let _: syn::Token![=] = input.parse()?;
-            let de: syn::ExprPath = input.parse()?;
-            let _: syn::Token![,] = input.parse()?;
-
-            let value = if input.peek(kw::value) {
-                let _: kw::value = input.parse()?;
-                let _: syn::Token![=] = input.parse()?;
-                let value: syn::Type = input.parse()?;
-                let _: syn::Token![,] = input.parse()?;
-                Some(value)
-            } else {
-                None
-            };
+    match (args, fun, manifest_dir) {
+        (Ok(args), Ok(fun), Ok(manifest_dir)) => tts = expand_tests(args, fun, manifest_dir, tts),
+        (Err(a), Err(b), Err(c)) => tts.extend(vec![a, b, c]),
+        (Err(a), Err(b), _) | (Err(a), _, Err(b)) | (_, Err(a), Err(b)) => tts.extend(vec![a, b]),
+        (Err(a), _, _) | (_, Err(a), _) | (_, _, Err(a)) => tts.extend(vec![a]),
+    }
 
-            (ser, de, value)
-        } else {
-            return Err(la.error());
-        };
+    tts.into()
+}
 
-        let _: kw::file = input.parse()?;
-        let _: syn::Token![=] = input.parse()?;
-        let file: syn::LitStr = input.parse()?;
+/// `tts`, the tested function `fun` as written, followed by the tests of `fun`.
+fn expand_tests(
+    args: AttrArgs,
+    fun: syn::ItemFn,
+    manifest_dir: PathBuf,
+    mut tts: TokenStream,
+) -> TokenStream {
+    // without a source file on disk, paths fall back to being relative to the manifest
+    let base_dir = if args.options.relative_to_source {
+        source_dir().unwrap_or(manifest_dir)
+    } else {
+        manifest_dir
+    };
+    if names_tested_type(&fun) {
+        tts = type_naming_fn(&fun);
+    }
+    let name = invocation_name(&args, &fun);
+    let file_name = generated_file_name(&args, &fun);
+    let source = match &args.source {
+        Source::File(path)
+        | Source::Dir(path)
+        | Source::Pairs {
+            input_dir: path, ..
+        }
+        | Source::Cases { name: path, .. } => path.clone(),
+    };
+    let Options {
+        generated_file,
+        count_total,
+        ..
+    } = args.options;
+    let cfgs = generated_cfgs(&fun, &args.options);
+    if !args.options.always {
+        // the tested function may only be called by its tests
+        tts = quote!(#[cfg_attr(not(test), allow(dead_code))] #tts);
+    }
+    let generated = if args.options.types.is_empty() {
+        build_tests(args, fun, base_dir)
+    } else {
+        typed_tests(args, fun, base_dir)
+    };
+    let generated = with_cfgs(&cfgs, generated);
+    if count_total {
+        tts.extend(record_count(&name, &source, &generated));
+    }
+    if generated_file {
+        tts.extend(include_generated(&file_name, &source, generated));
+    } else {
+        tts.extend(generated);
+    }
+    tts
+}
 
-        Ok(AttrArgs {
-            ser,
-            de,
-            value,
-            file,
-        })
+/// The tests of `fun`, generic over one type, for each of the `types` of `args`, each in a
+/// module named after the type, in snake case.
+fn typed_tests(args: AttrArgs, fun: syn::ItemFn, base_dir: PathBuf) -> TokenStream {
+    let mut tts = TokenStream::new();
+    // the type of each module
+    let mut modules: BTreeMap<String, &syn::Type> = BTreeMap::new();
+    for r#type in &args.options.types {
+        let module = type_module(r#type);
+        if let Some(previous) = modules.insert(module.to_string(), r#type) {
+            let e = format!(
+                "types `{}` and `{}` both map to module `{}`",
+                quote!(#previous),
+                quote!(#r#type),
+                module
+            );
+            tts.extend(compile_error(&e, r#type.span()));
+            continue;
+        }
+        let mut args = args.clone();
+        args.options.types = Vec::new();
+        args.options.transform_turbofish = Some(syn::parse_quote!(<#r#type>));
+        let tests = build_tests(args, fun.clone(), base_dir.clone());
+        tts.extend(quote! {
+            mod #module {
+                #[allow(unused_imports)]
+                use super::*;
+                #tests
+            }
+        });
     }
+    tts
 }
 
-struct Test {
-    name: syn::Ident,
-    input: String,
-    output: String,
+/// The name of the module `types` puts the tests of `r#type` in: its tokens in snake case,
+/// as `hash_map_string_u32` for `HashMap<String, u32>`.
+fn type_module(r#type: &syn::Type) -> syn::Ident {
+    let name = quote!(#r#type).to_string();
+    let chars: Vec<char> = name.chars().collect();
+    let mut module = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !module.is_empty() && !module.ends_with('_') {
+                module.push('_');
+            }
+            continue;
+        }
+        // a word starts at an uppercase letter after a lowercase one or a digit, or before one
+        // ending a run of uppercase letters, as the `T` of `BTreeMap`
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let starts_word = c.is_uppercase()
+            && match previous {
+                Some(previous) if previous.is_lowercase() || previous.is_numeric() => true,
+                Some(previous) if previous.is_uppercase() => next.is_some_and(|c| c.is_lowercase()),
+                _ => false,
+            };
+        if starts_word && !module.ends_with('_') {
+            module.push('_');
+        }
+        module.extend(c.to_lowercase());
+    }
+    sanitize_ident(module.trim_end_matches('_'))
 }
 
-fn read_tests(file_path: &Path, span: Span) -> Result<Vec<Test>, TokenStream> {
-    let source = {
-        let mut f = File::open(file_path)
-            .map_err(|e| compile_error(&format!("failed to open file: {}", e), span))?;
-        let mut s = String::with_capacity(f.metadata().map(|m| m.len() as usize + 1).unwrap_or(0));
-        f.read_to_string(&mut s)
-            .map_err(|e| compile_error(&format!("failed to read file: {}", e), span))?;
-        s
+/// Generate the tests of a file at module scope, without a tested function, for the type
+/// each input is deserialized to and serialized back from:
+///
+/// ```rust,ignore
+/// conformance::include_tests! {
+///     file = "tests/tokens.json.test", ser = serde_json::to_string, de = serde_json::from_str,
+///     type = Vec<Token>,
+/// }
+/// ```
+///
+/// The arguments are those of `conformance::tests`, in any order, with the type in place of the
+/// tested function's return type; without a mode, the tests are in `exact` mode.
+#[proc_macro]
+pub fn include_tests(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let IncludeArgs { args, r#type } = match syn::parse::<IncludeArgs>(input) {
+        Ok(it) => it,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let manifest_dir = match env::var("CARGO_MANIFEST_DIR") {
+        Ok(manifest_dir) => PathBuf::from(manifest_dir),
+        Err(e) => {
+            let e = format!("expected $CARGO_MANIFEST_DIR; {}", e);
+            return compile_error(&e, Span::call_site()).into();
+        }
     };
+    let fun = included_fn(&args, &r#type);
+    expand_tests(args, fun, manifest_dir, TokenStream::new()).into()
+}
 
-    if !source.ends_with('\n') {
-        return Err(compile_error("file needs to have trailing newline", span));
+/// The function `include_tests!` is expanded with, which only names `r#type`.
+///
+/// It is named after the source, so that two invocations in a module don't collide.
+fn included_fn(args: &AttrArgs, r#type: &syn::Type) -> syn::ItemFn {
+    let source = match &args.source {
+        Source::File(path)
+        | Source::Dir(path)
+        | Source::Pairs {
+            input_dir: path, ..
+        }
+        | Source::Cases { name: path, .. } => path.value(),
+    };
+    let name = quote::format_ident!("included_{}", sanitize_ident(&source));
+    syn::parse_quote! {
+        #[doc(hidden)]
+        fn #name() -> #r#type {}
     }
+}
 
-    let (s, trailing) = source.split_at(source.rfind("\n...\n").map_or(0, |i| i + 5));
-    if !trailing.trim().is_empty() {
-        return Err(compile_error(
-            "file has disallowed content after final `...`",
-            span,
-        ));
+/// The directory of the source file invoking the macro, if it is a file on disk.
+///
+/// The path `rustc` was given may be relative to its working directory, which the macro shares.
+fn source_dir() -> Option<PathBuf> {
+    let file = proc_macro::Span::call_site().local_file()?;
+    let file = env::current_dir().ok()?.join(file);
+    file.parent().map(Path::to_path_buf)
+}
+
+/// The function `path`, called with the type it parses to given as `::<r#type>`.
+///
+/// A qualified path such as `<Foo as FromStr>::from_str`, or one already given generic
+/// arguments, parses to its own type, so it is called as is.
+fn with_turbofish(path: &syn::ExprPath, r#type: &syn::Type) -> TokenStream {
+    let has_arguments = path
+        .path
+        .segments
+        .last()
+        .is_some_and(|segment| !segment.arguments.is_empty());
+    if path.qself.is_some() || has_arguments {
+        quote!(#path)
+    } else {
+        quote!(#path::<#r#type>)
     }
+}
 
-    let mut tests = Vec::new();
-    let mut errs = TokenStream::new();
+/// `fun`, which only names the tested type, given a body returning it, as its empty body doesn't.
+fn type_naming_fn(fun: &syn::ItemFn) -> TokenStream {
+    let mut fun = fun.clone();
+    fun.attrs
+        .push(syn::parse_quote!(#[allow(unused_variables)]));
+    fun.block = syn::parse_quote!({
+        ::std::unreachable!("this function only names the type `conformance` tests")
+    });
+    quote!(#fun)
+}
 
-    for (i, test) in s.split_terminator("\n...\n").enumerate() {
-        let i: usize = i;
-        let test: &str = test;
+/// Whether `fun` has an empty body and a return type, which it only names:
+/// the tests deserialize each input to it and serialize it back, without calling `fun`.
+fn names_tested_type(fun: &syn::ItemFn) -> bool {
+    fun.block.stmts.is_empty() && matches!(fun.sig.output, syn::ReturnType::Type(..))
+}
 
-        let (name, rest) = match test.find("\n===\n") {
-            Some(ix) => (&test[0..ix], &test[ix + 5..]),
-            None => {
-                errs.extend(compile_error(
-                    &format!("test {} does not have `===` after name", i),
-                    span,
-                ));
-                continue;
-            }
-        };
-        let name = name.trim().replace(' ', "_");
-
-        let (input, output) = match rest.rfind("\n---\n") {
-            Some(ix) => (&rest[0..ix], &rest[ix + 5..]),
-            None => {
-                errs.extend(compile_error(
-                    &format!("test `{}` does not have `---` after input", name),
-                    span,
-                ));
-                continue;
-            }
-        };
-        let input = input.trim().to_string();
-        let output = output.trim().to_string();
-
-        let name = match syn::parse_str::<syn::Ident>(&format!("_{}", name)) {
-            Ok(name) => name,
-            Err(_) => {
-                errs.extend(compile_error(
-                    &format!("`{}` is not a valid test name identifier", name),
-                    span,
-                ));
-                continue;
-            }
-        };
+/// The `#[cfg]`s the items generated for `fun` are given: those of `fun`, and unless `always`
+/// is given, `#[cfg(test)]`, so that the tests aren't built into the crate itself.
+///
+/// Documentation examples are generated in every build, for `rustdoc` to see, as are
+/// benchmarks, which `cargo bench` doesn't build with `cfg(test)`.
+fn generated_cfgs(fun: &syn::ItemFn, options: &Options) -> Vec<syn::Attribute> {
+    let mut cfgs: Vec<_> = fun
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("cfg"))
+        .cloned()
+        .collect();
+    if !options.always && !options.doc_examples && !options.bench {
+        cfgs.push(syn::parse_quote!(#[cfg(test)]));
+    }
+    cfgs
+}
+
+/// Give each item of `generated` the `#[cfg]`s of [`generated_cfgs`],
+/// so that a function compiled out takes its tests with it rather than leaving them dangling.
+fn with_cfgs(cfgs: &[syn::Attribute], generated: TokenStream) -> TokenStream {
+    if cfgs.is_empty() {
+        return generated;
+    }
+    match syn::parse2::<syn::File>(generated.clone()) {
+        Ok(file) => file
+            .items
+            .into_iter()
+            .map(|item| quote!(#(#cfgs)* #item))
+            .collect(),
+        // the errors are reported, whatever the `cfg`
+        Err(_) => generated,
+    }
+}
 
-        tests.push(Test {
-            name,
-            input,
-            output,
-        })
+/// Check a single case inline, without a test file, evaluating to the `Result` the
+/// generated tests return, as in a hand-written test:
+///
+/// ```rust,ignore
+/// conformance::check!(exact, serde = serde_json, fn = lex_tokens, input = "a=b", expected = "...")?;
+/// ```
+///
+/// The arguments are those of `conformance::tests`, with the tested function and the case
+/// in place of `file` or `dir`; there is no `expected` in `idempotent` mode.
+///
+/// As with the checking functions of the generated tests, the case only fails if the `Err` is
+/// returned or unwrapped:
+///
+/// ```rust
+/// # extern crate json as serde_json;
+/// fn words(s: &str) -> Vec<&str> {
+///     s.split_whitespace().collect()
+/// }
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     conformance::check!(exact, serde = serde_json, fn = words, input = "a b",
+///         expected = r#"["a", "b"]"#)?;
+///     Ok(())
+/// }
+/// ```
+///
+/// so dropping the `Result` is an `unused_must_use` warning:
+///
+/// ```rust,compile_fail
+/// #![deny(unused_must_use)]
+/// # extern crate json as serde_json;
+/// # fn words(s: &str) -> Vec<&str> {
+/// #     s.split_whitespace().collect()
+/// # }
+/// fn main() {
+///     conformance::check!(exact, serde = serde_json, fn = words, input = "a b",
+///         expected = r#"["a", "b"]"#);
+/// }
+/// ```
+#[proc_macro]
+pub fn check(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let CheckArgs {
+        args,
+        fun,
+        input,
+        expected,
+    } = match syn::parse::<CheckArgs>(input) {
+        Ok(it) => it,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let testing_fns = match testing_fns(&args, &quote!((#fun)), &syn::parse_quote!(_)) {
+        Ok(testing_fns) => testing_fns,
+        Err(e) => return e.into(),
+    };
+    let testing_fn = syn::Ident::new("check", Span::call_site());
+    let testing_fns = testing_fns(&testing_fn);
+    let expected = match expected {
+        Some(expected) => quote!(#expected),
+        None => quote!(input),
+    };
+    quote!({
+        #testing_fns
+        let input: &str = #input;
+        #testing_fn(#expected, input)
+    })
+    .into()
+}
+
+/// Check that a test file is well-formed, without generating its tests:
+///
+/// ```rust,ignore
+/// conformance::validate!("tests/corpus.json.test");
+/// conformance::validate!(idempotent, "tests/formatted.json.test", allow_trailing_content);
+/// ```
+///
+/// The file is read as `conformance::tests` would read it with the same mode, `exact` by
+/// default, and options, and each problem is a compile error. Nothing else is generated,
+/// so this is an item, which includes the file to be rebuilt when it changes.
+#[proc_macro]
+pub fn validate(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let args = match syn::parse::<ValidateArgs>(input) {
+        Ok(it) => it,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    match env::var("CARGO_MANIFEST_DIR") {
+        Ok(manifest_dir) => validate_file(&args, Path::new(&manifest_dir)).into(),
+        Err(e) => {
+            let e = format!("expected $CARGO_MANIFEST_DIR; {}", e);
+            compile_error(&e, Span::call_site()).into()
+        }
     }
+}
 
-    if errs.is_empty() {
-        Ok(tests)
+/// Read the test file of `args`, relative to `base_dir`, giving its errors, if any,
+/// or else the items including it.
+fn validate_file(args: &ValidateArgs, base_dir: &Path) -> TokenStream {
+    let ValidateArgs { path, options } = args;
+    let (expanded, env_vars) = match expand_env_vars(&path.value(), "validate!") {
+        Ok(it) => it,
+        Err(e) => return compile_error(&e, path.span()),
+    };
+    let file_path = base_dir.join(expanded);
+    if let Some(e) = outside_manifest_dir(&file_path).filter(|_| !options.allow_outside) {
+        return compile_error(&e, path.span());
+    }
+    if let Err(e) = read_tests(&file_path, path.span(), options) {
+        return e;
+    }
+    let filepath = file_path.to_string_lossy().to_string();
+    let include = if is_gzip(&file_path) {
+        quote!(
+            const _: &[u8] = ::std::include_bytes!(#filepath);
+        )
     } else {
-        Err(errs)
+        quote!(
+            const _: &str = ::std::include_str!(#filepath);
+        )
+    };
+    quote! {
+        #include
+        #(const _: &str = ::std::env!(#env_vars);)*
+    }
+}
+
+/// The total number of tests generated by the invocations of `conformance::tests`
+/// with the `count_total` option, as a `usize` literal:
+///
+/// ```rust,ignore
+/// const TOTAL: usize = conformance::total!();
+/// ```
+///
+/// Proc macros can't share state, so the counts are read from `OUT_DIR`, where they are
+/// recorded as each invocation is expanded. Only the invocations expanded before this one
+/// are counted: those earlier in the same crate, and those of other crates of the package
+/// which were compiled first.
+#[proc_macro]
+pub fn total(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = TokenStream::from(input);
+    if !input.is_empty() {
+        return compile_error("`conformance::total!()` takes no arguments", input.span()).into();
     }
+    let out_dir = match env::var_os("OUT_DIR") {
+        Some(out_dir) => out_dir,
+        None => {
+            let e = "`conformance::total!()` needs $OUT_DIR; add a build script, even an empty one";
+            return compile_error(e, Span::call_site()).into();
+        }
+    };
+    let dir = counts_dir(&out_dir);
+    let total = if dir.exists() {
+        match sum_counts(&dir) {
+            Ok(total) => total,
+            Err(e) => return compile_error(&e, Span::call_site()).into(),
+        }
+    } else {
+        0
+    };
+    let total = proc_macro2::Literal::usize_suffixed(total);
+    quote!(#total).into()
 }
 
+/// Check with `quickcheck` that the tested function never panics on inputs grown from the
+/// inputs of a test file, and that what it gives round-trips:
+///
+/// ```rust,ignore
+/// #[conformance::quickcheck(serde = serde_json, file = "tests/tokens.json.test", tests = 1000)]
+/// fn lex_tokens(s: &str) -> Vec<Token> {
+///     lex(s)
+/// }
+/// ```
+///
+/// The tested function takes the place of `de`, or with an empty body, names the type `de`
+/// deserializes to. The generated test first checks the inputs of the file as written, then
+/// `tests` inputs made by editing them a few characters at a time, 100 by default.
 #[proc_macro_attribute]
-pub fn tests(
+pub fn quickcheck(
     attr: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    // we want to re-emit the notated item in all cases
     let mut tts: TokenStream = item.clone().into();
-
-    // emit as many environment compile errors as possible in one place
-    let manifest_dir = env::var("CARGO_MANIFEST_DIR")
-        .map(PathBuf::from)
-        .map_err(|e| {
+    let args = match syn::parse::<QuickcheckArgs>(attr) {
+        Ok(it) => it,
+        Err(e) => {
+            tts.extend(e.to_compile_error());
+            return tts.into();
+        }
+    };
+    let fun = match syn::parse::<syn::ItemFn>(item) {
+        Ok(it) => it,
+        Err(e) => {
+            tts.extend(e.to_compile_error());
+            return tts.into();
+        }
+    };
+    let manifest_dir = match env::var("CARGO_MANIFEST_DIR") {
+        Ok(manifest_dir) => PathBuf::from(manifest_dir),
+        Err(e) => {
             let e = format!("expected $CARGO_MANIFEST_DIR; {}", e);
-            compile_error(&e, Span::call_site())
-        });
-    let args = syn::parse::<AttrArgs>(attr).map_err(|e| e.to_compile_error());
-    let fun = syn::parse::<syn::ItemFn>(item).map_err(|e| e.to_compile_error());
-
-    match (args, fun, manifest_dir) {
-        (Ok(args), Ok(fun), Ok(manifest_dir)) => tts.extend(build_tests(args, fun, manifest_dir)),
-        (Err(a), Err(b), Err(c)) => tts.extend(vec![a, b, c]),
-        (Err(a), Err(b), _) | (Err(a), _, Err(b)) | (_, Err(a), Err(b)) => tts.extend(vec![a, b]),
-        (Err(a), _, _) | (_, Err(a), _) | (_, _, Err(a)) => tts.extend(vec![a]),
+            tts.extend(compile_error(&e, Span::call_site()));
+            return tts.into();
+        }
+    };
+    if names_tested_type(&fun) {
+        tts = type_naming_fn(&fun);
     }
-
+    tts = quote!(#[cfg_attr(not(test), allow(dead_code))] #tts);
+    tts.extend(quickcheck_test(&args, &fun, &manifest_dir));
     tts.into()
 }
 
-fn build_tests(args: AttrArgs, fun: syn::ItemFn, manifest_dir: PathBuf) -> TokenStream {
-    let AttrArgs {
+/// The `#[test]` checking the property of `conformance::quickcheck` for `fun`,
+/// seeded from the file of `args`, relative to `base_dir`.
+///
+/// `@ignore`d cases don't seed it; the inputs of `@error` cases do, as `de` mustn't panic on
+/// those either.
+fn quickcheck_test(args: &QuickcheckArgs, fun: &syn::ItemFn, base_dir: &Path) -> TokenStream {
+    let QuickcheckArgs {
         ser,
         de,
-        value,
         file,
+        tests,
     } = args;
-    let fn_name = &fun.sig.ident;
-    let tested_type = match &fun.sig.output {
-        syn::ReturnType::Type(_, r#type) => (**r#type).clone(),
-        syn::ReturnType::Default => syn::parse_str("()").unwrap(),
+    if !cfg!(feature = "quickcheck") {
+        return compile_error(
+            "`conformance::quickcheck` requires the `quickcheck` feature of `conformance`",
+            Span::call_site(),
+        );
+    }
+    let r#type = match &fun.sig.output {
+        syn::ReturnType::Type(_, r#type) => r#type,
+        syn::ReturnType::Default => return compile_error(
+            "`conformance::quickcheck` needs the tested function to return the value to serialize",
+            fun.sig.span(),
+        ),
     };
-    let de_type = value.unwrap_or(tested_type);
-
-    let tests_path = manifest_dir.join(file.value());
-    let tests = match read_tests(&tests_path, file.span()) {
-        Ok(it) => it,
+    let file_path = base_dir.join(file.value());
+    if let Some(e) = outside_manifest_dir(&file_path) {
+        return compile_error(&e, file.span());
+    }
+    let tests_read = match read_tests(&file_path, file.span(), &Options::default()) {
+        Ok((tests_read, _)) => tests_read,
         Err(e) => return e,
     };
+    let seeds = tests_read
+        .iter()
+        .filter(|test| test.ignore.is_none())
+        .flat_map(|test| Some(&test.input).into_iter().chain(&test.equivalents))
+        .map(|body| &body.text);
+    let filepath = file_path.to_string_lossy().to_string();
+
+    let fun_name = &fun.sig.ident;
+    let de_call = with_turbofish(de, r#type);
+    // the tested function parses the input, unless it only names the type
+    let parse = if names_tested_type(fun) {
+        quote!(#de_call(input).map_err(|e| e.to_string()))
+    } else {
+        quote!(::std::result::Result::<_, ::std::string::String>::Ok(#fun_name(input)))
+    };
+    let test_name = quote::format_ident!("{}_quickcheck", fun_name);
+
+    quote! {
+        #[cfg(test)]
+        #[test]
+        fn #test_name() {
+            use ::quickcheck::{Arbitrary, Gen, QuickCheck, TestResult};
+
+            // included to rebuild the test when the file is edited
+            const _: &str = ::std::include_str!(#filepath);
+            const SEEDS: &[&str] = &[#(#seeds),*];
+
+            /// An input made by editing a seed, which shrinks as any string does.
+            #[derive(Clone, Debug)]
+            struct Seeded(::std::string::String);
+
+            impl Arbitrary for Seeded {
+                fn arbitrary(g: &mut Gen) -> Self {
+                    let seed = g.choose(SEEDS).copied().unwrap_or_default();
+                    let mut chars: ::std::vec::Vec<char> = seed.chars().collect();
+                    for _ in 0..usize::arbitrary(g) % 4 {
+                        let at = usize::arbitrary(g) % (chars.len() + 1);
+                        match u8::arbitrary(g) % 3 {
+                            0 if at < chars.len() => {
+                                chars.remove(at);
+                            }
+                            1 if at < chars.len() => chars[at] = char::arbitrary(g),
+                            _ => chars.insert(at, char::arbitrary(g)),
+                        }
+                    }
+                    Seeded(chars.into_iter().collect())
+                }
+
+                fn shrink(&self) -> ::std::boxed::Box<dyn ::std::iter::Iterator<Item = Self>> {
+                    ::std::boxed::Box::new(self.0.shrink().map(Seeded))
+                }
+            }
+
+            /// Whether `input`, if parsed, serializes to what its serialization reparses to.
+            fn __conformance_round_trips(input: &str) -> ::std::result::Result<(), ::std::string::String> {
+                let parsed = ::std::panic::catch_unwind(|| #parse).map_err(|panic| {
+                    let message = panic
+                        .downcast_ref::<::std::string::String>()
+                        .map(::std::string::String::as_str)
+                        .or_else(|| panic.downcast_ref::<&str>().copied())
+                        .unwrap_or("panicked");
+                    ::std::format!("`{}` panicked: {}", ::std::stringify!(#fun_name), message)
+                })?;
+                // an input may be rejected, as long as it is rejected without panicking
+                let value = match parsed {
+                    ::std::result::Result::Ok(value) => value,
+                    ::std::result::Result::Err(_) => return ::std::result::Result::Ok(()),
+                };
+                let serialized = #ser(&value).map_err(|e| ::std::format!("`ser` failed: {}", e))?;
+                let reparsed: #r#type = #de_call(&serialized).map_err(|e| {
+                    ::std::format!("`de` rejected the serialized {:?}: {}", serialized, e)
+                })?;
+                let reserialized = #ser(&reparsed).map_err(|e| {
+                    ::std::format!("`ser` failed on the reparsed value: {}", e)
+                })?;
+                if reserialized == serialized {
+                    ::std::result::Result::Ok(())
+                } else {
+                    ::std::result::Result::Err(::std::format!(
+                        "serialized to {:?}, which reparses and serializes to {:?}",
+                        serialized,
+                        reserialized,
+                    ))
+                }
+            }
+
+            fn __conformance_property(input: Seeded) -> TestResult {
+                match __conformance_round_trips(&input.0) {
+                    ::std::result::Result::Ok(()) => TestResult::passed(),
+                    ::std::result::Result::Err(e) => TestResult::error(e),
+                }
+            }
+
+            for seed in SEEDS {
+                if let ::std::result::Result::Err(e) = __conformance_round_trips(seed) {
+                    ::std::panic!("seed {:?} of `{}`: {}", seed, #file, e);
+                }
+            }
+            QuickCheck::new()
+                .tests(#tests)
+                .quickcheck(__conformance_property as fn(Seeded) -> TestResult);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            codegen::index_test_fn,
+            options::{mode_from_env, Mode},
+            read::{open_tests, trim_body},
+        },
+        std::collections::BTreeSet,
+    };
+
+    fn expand(attr: &str) -> String {
+        let args: AttrArgs = syn::parse_str(attr).unwrap();
+        let fun = syn::parse_str("fn lines(s: &str) -> Vec<String> { unimplemented!() }").unwrap();
+        build_tests(args, fun, PathBuf::from(env!("CARGO_MANIFEST_DIR"))).to_string()
+    }
+
+    fn read_error(path: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+        match read_tests(&path, Span::call_site(), &Options::default()) {
+            Ok(_) => panic!("`{}` was read successfully", path.display()),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn separators_only() {
+        let e = read_error("tests/invalid/separator_only.test");
+        assert!(e.contains("file has no tests, only `...` separators"));
+    }
+
+    #[test]
+    fn leading_separator() {
+        let e = read_error("tests/invalid/leading_separator.test");
+        assert!(e.contains("file starts with `...`, but a separator must follow a test"));
+    }
+
+    #[test]
+    fn empty_sections_are_forbidden() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/invalid/empty_sections.test");
+        let options = Options {
+            forbid_empty: true,
+            ..Options::default()
+        };
+        let e = match read_tests(&path, Span::call_site(), &options) {
+            Ok(_) => panic!("`{}` was read successfully", path.display()),
+            Err(e) => e.to_string(),
+        };
+        assert!(e.contains("test `no output` (line 7) has an empty output"));
+        assert!(e.contains("test `no input` (line 13) has an empty input"));
+        // `@allow_empty` tests, and the output of `@error` tests, aren't checked
+        assert_eq!(e.matches("forbid_empty").count(), 2);
+        assert!(read_tests(&path, Span::call_site(), &Options::default()).is_ok());
+    }
+
+    #[test]
+    fn missing_meta_is_rejected() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/invalid/missing_meta.test");
+        let options = Options {
+            require_meta: vec![syn::parse_quote!("spec"), syn::parse_quote!("section")],
+            ..Options::default()
+        };
+        let e = match read_tests(&path, Span::call_site(), &options) {
+            Ok(_) => panic!("`{}` was read successfully", path.display()),
+            Err(e) => e.to_string(),
+        };
+        assert!(e.contains(
+            "tests/invalid/missing_meta.test:9: test `uncited` has no `@meta spec=...`, \
+             which `require_meta` requires"
+        ));
+        assert!(e.contains(
+            "tests/invalid/missing_meta.test:17: test `ignored` has no `@meta spec=...` \
+             or `@meta section=...`, which `require_meta` requires"
+        ));
+        assert_eq!(e.matches("require_meta").count(), 2);
+        let e =
+            syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", require_meta = []"#)
+                .err()
+                .unwrap();
+        assert_eq!(e.to_string(), "`require_meta` needs a key");
+    }
+
+    #[test]
+    fn empty_test() {
+        let e = read_error("tests/invalid/empty_test.test");
+        assert!(e.contains("test 1 is empty; is there an extra `...`?"));
+    }
+
+    #[test]
+    fn expr_must_parse() {
+        let e = read_error("tests/invalid/expr.test");
+        assert!(e.contains(
+            "test `unclosed` (line 1) is `@expr`, but its output isn't a Rust expression"
+        ));
+        let expanded = expand(r#"exact, serde=json, file="tests/expr.json.test""#);
+        assert!(expanded.contains(
+            "expr_json_expr ( { vec ! [ String :: from ( \"one\" ) , String :: from ( \"two\" ) ] } , \"one\\ntwo\" )"
+        ));
+        assert!(
+            expanded.contains("fn expr_json_expr ( expected : Vec < String > , actual : & str )")
+        );
+        let expanded = expand(r#"regex, serde=json, file="tests/expr.json.test""#);
+        assert!(expanded.contains("which can only be used in `exact` mode"));
+    }
+
+    #[test]
+    fn near_miss() {
+        let e = read_error("tests/invalid/near_miss.test");
+        assert!(e.contains("does not have `===` after name; found `==`, did you mean `===`?"));
+    }
+
+    #[test]
+    fn identical_input_and_output_warns() {
+        let attr = r#"exact, ser=s, de=d, file="tests/raw.txt.test", raw"#;
+        assert!(expand(attr).contains("has identical input and output"));
+        let attr = r#"exact, ser=s, de=d, file="tests/raw.txt.test", raw, allow_identical"#;
+        assert!(!expand(attr).contains("has identical input and output"));
+    }
+
+    #[test]
+    fn reference_return_types() {
+        let args: AttrArgs =
+            syn::parse_str(r#"exact, ser=s, de=d, file="tests/raw.txt.test""#).unwrap();
+        let fun = syn::parse_str("fn first(s: &str) -> &str { s }").unwrap();
+        let expanded = build_tests(args, fun, PathBuf::from(env!("CARGO_MANIFEST_DIR")));
+        assert!(expanded
+            .to_string()
+            .contains("the tested function returns a reference"));
+        // with a `value`, the tested type is only serialized
+        let args: AttrArgs =
+            syn::parse_str(r#"exact, serde=json, file="tests/raw.txt.test""#).unwrap();
+        let fun = syn::parse_str("fn first(s: &str) -> &str { s }").unwrap();
+        let expanded = build_tests(args, fun, PathBuf::from(env!("CARGO_MANIFEST_DIR")));
+        assert!(!expanded.to_string().contains("compile_error"));
+    }
+
+    #[test]
+    fn modes_need_exact_mode() {
+        let expanded = expand(r#"regex, ser=s, de=d, file="tests/modes.txt.test""#);
+        assert!(expanded.contains(
+            "test `sorted` has `@modes`, which can only be used in `exact` mode \
+             without `predicate` or `oracle`"
+        ));
+    }
+
+    #[test]
+    fn similar_names_warn() {
+        let expanded = expand(r#"exact, serde=json, file="tests/invalid/similar_names.test""#);
+        assert!(expanded.contains("fn similar_names_a_b ("));
+        assert!(expanded.contains("fn similar_names_a_b_2 ("));
+        assert!(expanded.contains(
+            "tests `a b` and `a_b` have names which give the same identifier, \
+             so the latter is named `_a_b_2`"
+        ));
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/invalid/similar_names.test", allow_similar_names"#,
+        );
+        assert!(!expanded.contains("the same identifier"));
+    }
+
+    #[test]
+    fn message_placeholders_are_checked() {
+        let attr = |message| {
+            format!(
+                r#"exact, serde=json, file="tests/message.json.test", message={:?}"#,
+                message
+            )
+        };
+        let error = syn::parse_str::<AttrArgs>(&attr("{actual:?} {output}"))
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("unknown placeholder `{output}`"));
+        assert!(syn::parse_str::<AttrArgs>(&attr("{{output}}")).is_ok());
+        assert!(syn::parse_str::<AttrArgs>(&attr("{name")).is_err());
+        assert!(syn::parse_str::<AttrArgs>(&attr("name}")).is_err());
+    }
+
+    #[test]
+    fn split_includes_needs_out_dir() {
+        let attr = r#"exact, serde=json, file="tests/large.json.test", split_includes"#;
+        assert!(expand(attr).contains("`split_includes` needs $OUT_DIR"));
+    }
+
+    #[test]
+    fn tests_are_counted() {
+        let expanded: TokenStream =
+            expand(r#"exact, serde=json, file="tests/cases/groups.json.test""#)
+                .parse()
+                .unwrap();
+        assert_eq!(count_tests(expanded.clone()), 4);
+        let source = syn::parse_str("\"tests/cases/groups.json.test\"").unwrap();
+        assert!(record_count("groups_json_lines", &source, &expanded)
+            .to_string()
+            .contains("`count_total` needs $OUT_DIR"));
+    }
+
+    #[test]
+    fn generated_file_needs_out_dir() {
+        let attr = r#"exact, serde=json, file="tests/repeat.json.test", generated_file"#;
+        let args: AttrArgs = syn::parse_str(attr).unwrap();
+        let fun = syn::parse_str("fn lines(s: &str) -> Vec<String> { unimplemented!() }").unwrap();
+        assert_eq!(
+            generated_file_name(&args, &fun),
+            "repeat_json_lines_tests.rs"
+        );
+        let source = syn::parse_str("\"tests/repeat.json.test\"").unwrap();
+        let included = include_generated("repeat_json_lines_tests.rs", &source, TokenStream::new());
+        assert!(included
+            .to_string()
+            .contains("`generated_file` needs $OUT_DIR"));
+    }
+
+    #[test]
+    fn retry_is_checked() {
+        let parse = |options| {
+            let attr = format!(
+                r#"exact, serde=json, file="tests/retry.json.test", {}"#,
+                options
+            );
+            syn::parse_str::<AttrArgs>(&attr)
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert_eq!(parse("retry = 0").unwrap(), "`retry` must be at least 1");
+        assert!(parse("retry = 2, predicate = p")
+            .unwrap()
+            .contains("with `predicate`"));
+        assert!(parse("retry = 2").is_none());
+        assert_eq!(
+            parse("max_diff_lines = 0").unwrap(),
+            "`max_diff_lines` must be at least 1"
+        );
+    }
 
-    let filepath = tests_path.to_string_lossy().to_string();
-    let filename = tests_path
-        .file_stem()
+    #[test]
+    fn generic_functions_need_turbofish() {
+        let expand = |attr: &str| {
+            let args: AttrArgs = syn::parse_str(attr).unwrap();
+            let fun = syn::parse_str("fn parse<'a, T>(s: &'a str) -> Vec<T> { unimplemented!() }")
+                .unwrap();
+            build_tests(args, fun, PathBuf::from(env!("CARGO_MANIFEST_DIR"))).to_string()
+        };
+        let attr = r#"exact, serde=json, file="tests/generic.json.test""#;
+        assert!(expand(attr).contains("must be given with `transform_turbofish"));
+        let attr =
+            r#"exact, serde=json, file="tests/generic.json.test", transform_turbofish = <i64, u8>"#;
+        assert!(expand(attr).contains("has 1 generic parameters, but"));
+        let attr =
+            r#"exact, serde=json, file="tests/generic.json.test", transform_turbofish = <i64>"#;
+        let expanded = expand(attr);
+        assert!(expanded.contains("parse :: < i64 > ( actual )"));
+        assert!(expanded.contains("Vec < i64 >"));
+    }
+
+    #[test]
+    fn types_are_tested_in_modules_of_their_own() {
+        let expand = |attr: &str| {
+            let args: AttrArgs = syn::parse_str(attr).unwrap();
+            let fun =
+                syn::parse_str("fn parse<T>(s: &str) -> Vec<T> { unimplemented!() }").unwrap();
+            typed_tests(args, fun, PathBuf::from(env!("CARGO_MANIFEST_DIR"))).to_string()
+        };
+        let attr = r#"exact, serde=json, file="tests/generic.json.test", types = [i64, BTreeMap<String, u8>]"#;
+        let expanded = expand(attr);
+        assert!(expanded.contains("mod i64 {"));
+        assert!(expanded.contains("parse :: < i64 > ( actual )"));
+        assert!(expanded.contains("mod b_tree_map_string_u8 {"));
+        assert!(expanded.contains("parse :: < BTreeMap < String , u8 > > ( actual )"));
+        let attr = r#"exact, serde=json, file="tests/generic.json.test", types = [HashMap<u8>, HashMap<U8>]"#;
+        assert!(expand(attr).contains(
+            "types `HashMap < u8 >` and `HashMap < U8 >` both map to module `hash_map_u8`"
+        ));
+        let e = syn::parse_str::<AttrArgs>(
+            r#"exact, serde=json, file="a.test", types = [i64], transform_turbofish = <u8>"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`types` gives the generic argument of each module's tested function, \
+             so it can't be used with `transform_turbofish`"
+        );
+    }
+
+    #[test]
+    fn oracle_is_checked() {
+        let parse = |mode: &str, oracle: &str| {
+            let attr = format!(
+                r#"{}, serde=json, file="tests/oracle.txt.test", oracle = "{}""#,
+                mode, oracle
+            );
+            syn::parse_str::<AttrArgs>(&attr)
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert!(parse("exact", "cat {input_file}").is_none());
+        assert_eq!(parse("exact", " ").unwrap(), "`oracle` needs a command");
+        assert!(parse("exact", "cat {output}")
+            .unwrap()
+            .starts_with("unknown placeholder `{output}` in `oracle`"));
+        assert_eq!(
+            parse("exact", "cat {input").unwrap(),
+            "unclosed `{` in `oracle`"
+        );
+        assert!(parse("idempotent", "cat {input_file}")
+            .unwrap()
+            .contains("can only be used in `exact` mode"));
+    }
+
+    #[test]
+    fn expected_case_counts() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/cases/groups.json.test", expect_cases = 4"#);
+        assert!(expanded.contains("const GROUPS_JSON_CASE_COUNT : usize = 4usize ;"));
+        assert!(!expanded.contains("compile_error"));
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/cases/groups.json.test", expect_cases = 5"#);
+        assert!(
+            expanded.contains("expected 5 cases in `tests/cases/groups.json.test`, but found 4")
+        );
+    }
+
+    #[test]
+    fn cross_format_round_trips_need_both_pairs() {
+        let expanded = expand(
+            r#"idempotent, ser_a=json::to_string, de_a=json::from_str, file="tests/cross_format.json.test", ser_b = yaml::to_string, de_b = yaml::from_str"#,
+        );
+        assert!(expanded.contains("let via = match yaml :: to_string ( & value )"));
+        assert!(expanded.contains("match yaml :: from_str :: < Vec < String > > ( & via )"));
+        // the first pair is `ser` and `de` by either name
+        let plain = expand(
+            r#"idempotent, ser=json::to_string, de=json::from_str, file="tests/cross_format.json.test", ser_b = yaml::to_string, de_b = yaml::from_str"#,
+        );
+        assert_eq!(plain, expanded);
+        let error = |attr: &str| syn::parse_str::<AttrArgs>(attr).err().unwrap().to_string();
+        assert!(
+            error(r#"exact, serde=json, file="a.test", ser_b = yaml::to_string"#)
+                .ends_with("together; give both")
+        );
+        assert!(
+            error(r#"exact, ser_a=json::to_string, de_a=json::from_str, file="a.test""#)
+                .starts_with("`ser_a` and `de_a` name the first of two formats")
+        );
+        assert!(error(
+            r#"exact, serde=json, file="a.test", stream, ser_b = yaml::to_string, de_b = yaml::from_str"#
+        )
+        .ends_with("so they can't be used with `stream`"));
+    }
+
+    #[test]
+    fn async_functions_are_run_on_a_runtime() {
+        let expand_async = |attr: &str, fun: &str| {
+            let args: AttrArgs = syn::parse_str(attr).unwrap();
+            let fun = syn::parse_str(fun).unwrap();
+            build_tests(args, fun, PathBuf::from(env!("CARGO_MANIFEST_DIR"))).to_string()
+        };
+        let attr = r#"exact, serde=json, file="tests/fallibility.json.test", runtime = rt"#;
+        let expanded = expand_async(attr, "async fn lines(s: &str) -> Vec<String> { todo!() }");
+        assert!(expanded.contains("rt ( ) . block_on ( lines ( actual ) )"));
+        let expanded = expand_async(attr, "fn lines(s: &str) -> Vec<String> { todo!() }");
+        assert!(expanded.contains("`runtime` runs an `async` tested function, but `lines` isn't"));
+        let expanded = expand_async(
+            r#"exact, serde=json, file="tests/fallibility.json.test""#,
+            "async fn lines(s: &str) -> Vec<String> { todo!() }",
+        );
+        assert!(expanded.contains("the tested function is `async`; give a `runtime` option"));
+    }
+
+    #[test]
+    fn included_tests_take_their_arguments_in_any_order() {
+        let IncludeArgs { args, r#type } = syn::parse_str(
+            r#"sort_keys, type = HashMap<String, u32>, file = "a.test", de = a::de, ser = a::ser"#,
+        )
+        .unwrap();
+        assert!(args.options.mode == Mode::Exact && args.options.sort_keys);
+        assert_eq!(quote!(#r#type).to_string(), "HashMap < String , u32 >");
+        assert!(matches!(args.source, Source::File(file) if file.value() == "a.test"));
+        let e = syn::parse_str::<IncludeArgs>(r#"idempotent, serde = json, file = "a.test""#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`include_tests!` needs the type its tests are of, as `type = Foo`"
+        );
+    }
+
+    #[test]
+    fn duplicates_are_dropped_with_a_warning() {
+        let expanded = expand(r#"exact, serde=json, file="tests/dedup.json.test", dedup"#);
+        assert!(expanded.contains(
+            "test `two lines` has the same input and expected output as `pair of lines`, \
+             `copied again`, which `dedup` generates no tests for"
+        ));
+        assert!(expanded.contains("fn dedup_json_two_lines"));
+        assert!(!expanded.contains("fn dedup_json_pair_of_lines"));
+        assert!(expanded.contains("fn dedup_json_other_output"));
+        assert!(expanded.contains("const DEDUP_JSON_CASE_COUNT : usize = 3usize ;"));
+        let expanded = expand(r#"exact, serde=json, file="tests/dedup.json.test""#);
+        assert!(expanded.contains("fn dedup_json_pair_of_lines"));
+        assert!(!expanded.contains("dedup` generates"));
+        // tests checking something more of the same input and output are kept
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/dedup_checks.json.test", dedup, error_kind = kind"#,
+        );
+        assert!(!expanded.contains("dedup` generates"));
+        assert!(expanded.contains("fn dedup_checks_json_truncated_otherwise"));
+        assert!(expanded.contains("fn dedup_checks_json_read_once"));
+    }
+
+    #[test]
+    fn sorted_lines_need_text_outputs() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/fallibility.json.test", sorted_lines"#);
+        assert!(expanded.contains("let actual = __conformance_sorted_lines ( & actual ) ;"));
+        let e = syn::parse_str::<AttrArgs>(r#"regex, serde=json, file="a.test", sorted_lines"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`sorted_lines` compares the outputs as sets of lines, so it can't be used in `regex` mode"
+        );
+    }
+
+    #[test]
+    fn fixpoint_bounds_its_re_parses() {
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/fallibility.json.test", fixpoint, fixpoint_max = 1"#,
+        );
+        assert!(expanded.contains("if reparses == 1usize {"));
+        let error = |attr: &str| syn::parse_str::<AttrArgs>(attr).err().unwrap().to_string();
+        assert_eq!(
+            error(r#"exact, serde=json, file="a.test", fixpoint_max = 3"#),
+            "`fixpoint_max` bounds the re-parses of `fixpoint`; give `fixpoint`"
+        );
+        assert!(
+            error(r#"exact, serde=json, file="a.test", fixpoint, fixpoint_max = 0"#)
+                .ends_with("must be at least 1")
+        );
+        assert!(
+            error(r#"exact, serde=json, file="a.test", fixpoint, stream"#)
+                .ends_with("so it can't be used with `stream`")
+        );
+    }
+
+    #[test]
+    fn files_must_not_be_directories() {
+        let expanded = expand(r#"exact, serde=json, file="tests/cases""#);
+        assert!(expanded.contains(
+            "which is a directory; to test each file in it, \
+                                   use `dir = \\\"tests/cases\\\"` instead"
+        ));
+    }
+
+    #[test]
+    fn each_case_runs_between_its_hooks() {
+        let expanded = expand(
+            r#"exact, serde = json, file = "tests/repeat.json.test", before_each = registry::clear(), after_each = || registry::check()"#,
+        );
+        assert!(expanded.contains(
+            "{ { registry :: clear ( ) ; } struct AfterEach < F : :: std :: ops :: FnMut ( ) >"
+        ));
+        // the closure is called, rather than evaluated and dropped
+        assert!(expanded.contains("registry :: check ( ) ) ( ) ; } ) ;"));
+        // within the loop over the repetitions, so that each gets hooks of its own
+        let hooked = expanded.find("registry :: clear").unwrap();
+        assert!(expanded.find("for iteration in 1 ..= 100").unwrap() < hooked);
+        assert!(
+            !expand(r#"exact, serde = json, file = "tests/repeat.json.test""#)
+                .contains("AfterEach")
+        );
+    }
+
+    #[test]
+    fn quickcheck_is_seeded_from_the_inputs() {
+        let args: QuickcheckArgs =
+            syn::parse_str(r#"serde = json, file = "tests/error.json.test", tests = 500"#).unwrap();
+        assert_eq!(args.tests, 500);
+        let fun = syn::parse_str("fn tokens() -> Vec<String> {}").unwrap();
+        let base_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let generated = quickcheck_test(&args, &fun, base_dir).to_string();
+        assert!(generated.contains("fn tokens_quickcheck ( )"));
+        assert!(generated.contains("json :: from_str :: < Vec < String > > ( input )"));
+        assert!(generated.contains(". tests ( 500u64 )"));
+        let seeds = &generated[generated.find("const SEEDS").unwrap()..];
+        let seeds = &seeds[..seeds.find(';').unwrap()];
+        let (tests, _) = read_tests(
+            &base_dir.join("tests/error.json.test"),
+            Span::call_site(),
+            &Options::default(),
+        )
+        .unwrap();
+        for test in &tests {
+            assert!(seeds.contains(&format!("{:?}", test.input.text)));
+        }
+
+        let fun = syn::parse_str("fn lex(s: &str) -> Vec<String> { lines(s) }").unwrap();
+        let generated = quickcheck_test(&args, &fun, base_dir).to_string();
+        assert!(generated.contains("Ok ( lex ( input ) )"));
+        let e = syn::parse_str::<QuickcheckArgs>(r#"serde = json"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`quickcheck` needs the test file whose inputs seed it, as `file = \"...\"`"
+        );
+    }
+
+    #[test]
+    fn indexes_list_each_case() {
+        let expanded = expand(r#"exact, serde = json, file = "tests/ignore.json.test", index"#);
+        assert!(expanded.contains("fn ignore_json_index ( )"));
+        let tests = read_tests(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ignore.json.test"),
+            Span::call_site(),
+            &Options::default(),
+        )
+        .unwrap()
+        .0;
+        let index =
+            index_test_fn(&quote::format_ident!("index"), &tests, &Options::default()).to_string();
+        assert!(index.contains("\"tests/ignore.json.test: 3 cases, 1 run, 2 ignored\\n\""));
+        assert!(index.contains(concat!(
+            "\"line  case    status\\n",
+            "   1  slow    run\\n",
+            "   7  broken  ignored\\n",
+            "  14  flaky   ignored: fails on leap days\\n\"",
+        )));
+        assert!(
+            !expand(r#"exact, serde = json, file = "tests/ignore.json.test""#).contains("_index")
+        );
+    }
+
+    #[test]
+    fn failures_are_annotated_for_ci() {
+        let file = r#"exact, serde = json, file = "tests/ignore.json.test""#;
+        let expanded = expand(&format!("{}, ci = github", file));
+        assert!(expanded.contains("var_os ( \"GITHUB_ACTIONS\" ) . is_none ( )"));
+        assert!(expanded.contains("\"::error file={},line={}::{}\" , file , 7usize"));
+        assert!(expanded.contains("__conformance_annotate ( & e . to_string ( ) ) ;"));
+        let expanded = expand(&format!("{}, ci = gitlab", file));
+        assert!(expanded.contains("var_os ( \"GITLAB_CI\" ) . is_none ( )"));
+        assert!(expanded.contains("var_os ( \"CI_PROJECT_DIR\" )"));
+        assert!(!expand(&format!("{}, ci = none", file)).contains("fn annotate"));
+        let e = syn::parse_str::<AttrArgs>(&format!("{}, ci = jenkins", file))
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "expected `ci = github`, `ci = gitlab`, or `ci = none`"
+        );
+    }
+
+    #[test]
+    fn consume_all_checks_the_remaining_input() {
+        let file = r#"exact, serde = json, file = "tests/error.json.test""#;
+        let expanded = expand(&format!("{}, consume_all, remaining = rest", file));
+        assert!(expanded.contains("let remaining : usize = rest ( input ) ;"));
+        assert!(!expand(&format!("{}, consume_all", file)).contains("read whole"));
+        let e = syn::parse_str::<AttrArgs>(&format!("{}, remaining = rest", file))
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`remaining` is only called with `consume_all`"
+        );
+    }
+
+    #[test]
+    fn testing_fn_is_must_use() {
+        let expanded = expand(r#"exact, serde=json, file="tests/error.json.test""#);
+        assert!(expanded.contains(
+            "# [ must_use = \"the test only fails if its `Err` is returned or unwrapped\" ] \
+             fn error_json ("
+        ));
+    }
+
+    #[test]
+    fn yaml_mode_compares_top_level_keys_unordered() {
+        let expanded = expand(
+            r#"yaml_toplevel_unordered, ser = y::to_string, de = y::from_str, file = "tests/toplevel.yaml.test""#,
+        );
+        assert!(expanded.contains("use :: serde_yaml :: Value ;"));
+        assert!(expanded.contains("__conformance_yaml_mismatch ( & actual , expected ) ?"));
+        let expanded =
+            expand(r#"yaml_toplevel_unordered, serde = yaml, file = "tests/toplevel.yaml.test""#);
+        assert!(expanded.contains("use yaml :: Value ;"));
+        let e = syn::parse_str::<AttrArgs>(
+            r#"yaml_toplevel_unordered, serde = yaml, file = "a.test", predicate = p"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`yaml_toplevel_unordered` mode compares the outputs as YAML values, \
+             so it can't be used with `predicate`"
+        );
+    }
+
+    #[test]
+    fn loose_scalars_needs_json() {
+        let expanded = expand(r#"exact, serde=json, file="tests/error.json.test", loose_scalars"#);
+        assert!(expanded.contains("let expected = __conformance_loose_scalars ( & expected )"));
+        let expanded = expand(
+            r#"exact, ser = y::to_string, de = y::from_str, file="tests/error.json.test", loose_scalars"#,
+        );
+        assert!(expanded.contains(
+            "`loose_scalars` requires a JSON crate; use `serde = json` or the `json` option"
+        ));
+    }
+
+    #[test]
+    fn required_cases_are_checked() {
+        let file = r#"exact, serde=json, file="tests/ids.json.test""#;
+        let expanded = expand(&format!(
+            r#"{}, require_cases = "tests/invalid/missing_cases.txt""#,
+            file
+        ));
+        assert!(expanded.contains(
+            "no case is named `exponent`, which `tests/invalid/missing_cases.txt` requires"
+        ));
+        let expanded = expand(&format!(
+            r#"{}, require_cases = "tests/invalid/extra_cases.txt", warn_extra_cases"#,
+            file
+        ));
+        assert!(
+            expanded.contains("test `plain name` isn't listed in `tests/invalid/extra_cases.txt`")
+        );
+        assert!(!expanded.contains("test `number with fraction` isn't listed"));
+        assert!(!expand(&format!(
+            r#"{}, require_cases = "tests/invalid/extra_cases.txt""#,
+            file
+        ))
+        .contains("isn't listed"));
+        assert_eq!(
+            syn::parse_str::<AttrArgs>(&format!("{}, warn_extra_cases", file))
+                .err()
+                .unwrap()
+                .to_string(),
+            "`warn_extra_cases` warns of cases missing from `require_cases`, \
+             so it can only be used with it"
+        );
+    }
+
+    #[test]
+    fn tokenize_compares_token_sequences() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/fallibility.json.test", tokenize = t"#);
+        assert!(expanded.contains("let ( found , wanted ) = ( t ( actual ) , t ( expected ) ) ;"));
+        assert!(expanded.contains("__conformance_token_mismatch ( & actual , & expected )"));
+        let e = syn::parse_str::<AttrArgs>(r#"xml, serde=json, file="a.test", tokenize = t"#)
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            e,
+            "`tokenize` compares the outputs as token sequences, so it can't be used with `xml` mode"
+        );
+        let e = syn::parse_str::<AttrArgs>(
+            r#"exact, serde=json, file="a.test", tokenize = t, value_cmp = c"#,
+        )
+        .err()
         .unwrap()
-        .to_string_lossy()
-        .replace('.', "_");
-    let testing_fn = syn::Ident::new(&filename, Span::call_site());
-
-    let mut tts = quote! {
-        fn #testing_fn(expected: &str, actual: &str) -> Result<(), Box<dyn ::std::error::Error>> {
-            const _: &str = include_str!(#filepath);
-            let actual = #ser(&#fn_name(actual))?;
-            let expected = #ser(&#de::<#de_type>(expected)?)?; // normalize
-            assert_eq!(actual, expected);
+        .to_string();
+        assert!(e.ends_with("so it can't be used with `value_cmp`"), "{}", e);
+    }
+
+    #[test]
+    fn xfail_cases_must_fail() {
+        let file = r#"exact, serde=json, file="tests/xfail.json.test""#;
+        for attr in [String::from(file), format!("{}, aggregate", file)].iter() {
+            let expanded = expand(attr);
+            assert!(expanded.contains(
+                "tests/xfail.json.test:1: test `known bug`: unexpected pass, \
+                 remove `@xfail` (words aren't split yet)"
+            ));
+        }
+        // but `aggregate` skips the `@ignore`d case
+        assert!(expand(file).contains("test `fixed bug`: unexpected pass, remove `@xfail`\""));
+        let expanded = expand(r#"exact, serde=json, file="tests/ids.json.test""#);
+        assert!(!expanded.contains("unexpected pass"));
+    }
+
+    #[test]
+    fn wasm_tests_use_wasm_bindgen_test() {
+        let file = r#"exact, serde=json, file="tests/ids.json.test""#;
+        let expanded = expand(&format!("{}, wasm", file));
+        assert!(expanded
+            .contains("# [ :: wasm_bindgen_test :: wasm_bindgen_test ] fn ids_json_plain_name"));
+        assert!(!expanded.contains("# [ test ]"));
+        assert_eq!(
+            syn::parse_str::<AttrArgs>(&format!("{}, wasm, snapshots", file))
+                .err()
+                .unwrap()
+                .to_string(),
+            "`wasm` tests run without files, processes, environment variables, stderr, or \
+             unwinding, so it can't be used with `snapshots`"
+        );
+        let e = expand(r#"exact, serde=json, file="tests/xfail.json.test", wasm"#);
+        assert!(e.contains("test `known bug` is `@xfail`, which `wasm` tests can't be"));
+    }
+
+    #[test]
+    fn oversized_files_are_warned_about() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/cases/groups.json.test", warn_over = 3"#);
+        assert!(expanded.contains(
+            "`tests/cases/groups.json.test` has 4 cases, more than the 3 of `warn_over`"
+        ));
+        assert!(!expanded.contains("compile_error"));
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/cases/groups.json.test", warn_over = 4"#);
+        assert!(!expanded.contains("deprecated"));
+    }
+
+    #[test]
+    fn env_vars_in_paths() {
+        assert_eq!(
+            expand_env_vars("${CARGO_PKG_NAME}/${CARGO_PKG_NAME}.test", "file"),
+            Ok((
+                String::from("conformance/conformance.test"),
+                vec![String::from("CARGO_PKG_NAME")]
+            ))
+        );
+        assert_eq!(
+            expand_env_vars("a/${b", "dir").unwrap_err(),
+            "`dir` has a `${` without a closing `}`"
+        );
+        let expanded = expand(
+            r#"exact, serde=json, file="${CARGO_MANIFEST_DIR}/tests/cases/groups.json.test""#,
+        );
+        assert!(!expanded.contains("compile_error"));
+        assert!(
+            expanded.contains(r#"const _ : & str = :: std :: env ! ( "CARGO_MANIFEST_DIR" ) ;"#)
+        );
+        let expanded = expand(r#"exact, serde=json, file="${CONFORMANCE_UNSET}/a.test""#);
+        assert!(expanded.contains(
+            "`file` uses the environment variable `CONFORMANCE_UNSET`, but it is not set"
+        ));
+    }
+
+    #[test]
+    fn streams_are_checked() {
+        let parse = |attr: &str| {
+            syn::parse_str::<AttrArgs>(attr)
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert!(parse(r#"exact, serde=json, file="a.test", stream"#).is_none());
+        assert!(parse(r#"idempotent, serde=json, file="a.test", stream"#)
+            .unwrap()
+            .ends_with(
+                "can't be used with `idempotent` mode, as the input isn't a serialized array"
+            ));
+        assert!(
+            parse(r#"exact, serde=json, file="a.test", direction = serialize, stream"#)
+                .unwrap()
+                .ends_with("can't be used with `direction = serialize`")
+        );
+
+        let expanded = expand(
+            r#"exact, ser=json::to_string, de=from_str, file="tests/stream.json.test", stream"#,
+        );
+        assert!(expanded.contains(
+            "from_str :: < Vec < String > > ( actual ) . collect :: < :: std :: result :: \
+             Result < :: std :: vec :: Vec < Vec < String > > , _ > > ( ) ?"
+        ));
+        assert!(expanded.contains(":: std :: string :: String :: from ( expected )"));
+    }
+
+    #[test]
+    fn incomplete_tests_need_a_classifier() {
+        let expanded = expand(r#"exact, serde=json, file="tests/incomplete.json.test""#);
+        assert!(expanded
+            .contains("test `truncated array` is `@incomplete`, but no `incomplete` option tells"));
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/incomplete.json.test", incomplete = needs_more"#,
+        );
+        assert!(expanded.contains(
+            "let __result = json :: from_str :: < json :: Value > ( input ) ; if needs_more ( & __result )"
+        ));
+        assert!(expanded.contains(r#"_incomplete ( "" , "[\"one\"," )"#));
+    }
+
+    #[test]
+    fn error_kinds_need_a_classifier() {
+        let expanded = expand(r#"exact, serde=json, file="tests/error_kind.json.test""#);
+        assert!(expanded.contains(
+            "test `truncated` has `@error kind=...`, but no `error_kind` option gives the kinds"
+        ));
+        let e = syn::parse_str::<AttrArgs>(
+            r#"exact, serde=json, file="a.test", de_option, error_kind = kind"#,
+        )
+        .err()
+        .unwrap();
+        assert!(e
+            .to_string()
+            .ends_with("can't be used with `de_option`, as `None` has no error"));
+    }
+
+    #[test]
+    fn error_serializers_replace_the_substring_check() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/error_ser.json.test", error_ser = describe"#);
+        assert!(expanded.contains("describe ( & __e ) ;"));
+        assert!(!expanded.contains("does not contain"));
+        let e = syn::parse_str::<AttrArgs>(
+            r#"exact, serde=json, file="a.test", de_option, error_ser = describe"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`error_ser` serializes the errors of `de`, so it can't be used with `de_option`, \
+             as `None` has no error"
+        );
+    }
+
+    #[test]
+    fn error_positions_need_a_locator() {
+        let expanded = expand(r#"exact, serde=json, file="tests/error_at.json.test""#);
+        assert!(expanded.contains(
+            "test `double comma` has `@error_at`, but no `error_pos` option gives the positions"
+        ));
+        let e = syn::parse_str::<AttrArgs>(
+            r#"exact, serde=json, file="a.test", de_infallible, error_pos = at"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`error_pos` locates the errors of `de`, \
+             so it can't be used with `de_infallible`, as there are no errors"
+        );
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/error_at.json.test", error_pos = at"#);
+        assert!(expanded.contains(":: std :: option :: Option :: Some ( ( 2usize , 9usize ) )"));
+    }
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn table_columns_are_checked() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/invalid/columns.csv", format = csv"#);
+        assert!(expanded.contains(
+            "`tests/invalid/columns.csv` has unknown column `output`; \
+             expected `name`, `input`, and `expected`"
+        ));
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.csv", format = xml"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "expected `format = csv`, `format = tsv`, or `format = jsonl`"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "jsonl")]
+    fn jsonl_lines_are_checked() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/invalid/cases.jsonl", format = jsonl"#);
+        assert!(expanded.contains("failed to read line 3 of `tests/invalid/cases.jsonl`: "));
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/lines.json.jsonl", format = jsonl"#);
+        assert!(expanded.contains("fn lines_json_two_lines ( )"));
+    }
+
+    #[test]
+    fn check_args() {
+        let parse = |args: &str| {
+            syn::parse_str::<CheckArgs>(args)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            parse(r#"exact, serde=json, fn = f, input = "a", expected = "b", sort_keys"#),
+            Ok(())
+        );
+        assert_eq!(
+            parse(r#"idempotent, serde=json, fn = f, input = "a""#),
             Ok(())
+        );
+        assert_eq!(
+            parse(r#"exact, serde=json, fn = f, input = "a""#).unwrap_err(),
+            "`check!` needs an `expected = \"...\"`, except in `idempotent` mode"
+        );
+        assert!(
+            parse(r#"exact, ser=s, de=d, fn = f, input = "a", expected = "b""#)
+                .unwrap_err()
+                .ends_with("so it needs a `value` type; use `serde` or give `value`")
+        );
+    }
+
+    #[test]
+    fn canon_normalizes_expected() {
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/canon.json.test", canon_ser = canon::to_string"#,
+        );
+        assert!(expanded.contains(
+            "let expected = canon :: to_string ( & json :: from_str :: < json :: Value > ( expected ) ? ) ? ;"
+        ));
+        let e = syn::parse_str::<AttrArgs>(r#"regex, serde=json, file="a.test", canon_de = d"#)
+            .err()
+            .unwrap();
+        assert!(e
+            .to_string()
+            .ends_with("which isn't done with `regex` mode"));
+    }
+
+    #[test]
+    fn env_mode_must_name_a_mode() {
+        let mode = |value| mode_from_env(value, Span::call_site()).map_err(|e| e.to_string());
+        assert!(matches!(mode(None), Ok(None)));
+        assert!(matches!(mode(Some(" ")), Ok(None)));
+        assert!(matches!(
+            mode(Some("idempotent")),
+            Ok(Some(Mode::Idempotent))
+        ));
+        let error = mode(Some("superset")).err().unwrap();
+        assert!(error.starts_with("`CONFORMANCE_MODE` is `superset`, which isn't a mode"));
+        for mode in &Mode::ALL {
+            assert!(error.contains(&format!("`{}`", mode.name())));
         }
-    };
+    }
 
-    for test in tests {
-        let Test {
-            name,
-            input,
-            output,
-        } = test;
-        let test_name = quote::format_ident!("{}{}", filename, name);
-        tts.extend(quote! {
-            #[test]
-            fn #test_name() -> Result<(), Box<dyn ::std::error::Error>> {
-                #testing_fn(#output, #input)
-            }
-        })
+    #[test]
+    fn inline_cases_are_tuples_of_strings() {
+        let expanded = expand(r#"exact, serde=json, cases = [("a", "x", "[\"x\"]")]"#);
+        assert!(expanded.contains("const CASES_CASE_NAMES : & [ & str ] = & [ \"a\" ] ;"));
+        assert!(expanded.contains("fn cases_a ( )"));
+        assert!(!expanded.contains("include_str"));
+        let parse = |attr: &str| {
+            syn::parse_str::<AttrArgs>(attr)
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert_eq!(
+            parse(r#"exact, serde=json, cases = [("a", "x")]"#).unwrap(),
+            "each of `cases` is a tuple `(name, input, expected)` of string literals"
+        );
+        assert_eq!(
+            parse(r#"idempotent, serde=json, cases = [("a", "x")]"#),
+            None
+        );
+        assert_eq!(
+            parse(r#"exact, serde=json, cases = [(" ", "x", "y")]"#).unwrap(),
+            "a case of `cases` needs a name"
+        );
+        assert!(
+            parse(r#"exact, serde=json, cases = [("a", "x", "y")], single"#)
+                .unwrap()
+                .ends_with("so it can't be used with `single`")
+        );
     }
 
-    tts
+    #[test]
+    fn sort_cases_only_reorders_the_tests() {
+        let test_fns = |expanded: &str| -> Vec<String> {
+            expanded
+                .match_indices("fn sort_cases_json_")
+                .map(|(ix, _)| {
+                    let name = &expanded[ix + 3..];
+                    name[..name.find(' ').unwrap()].to_string()
+                })
+                // the testing functions are emitted first
+                .filter(|name| {
+                    !["equivalent", "error", "expr"]
+                        .iter()
+                        .any(|f| name.ends_with(f))
+                })
+                .collect()
+        };
+        let in_file_order = expand(r#"exact, serde=json, file="tests/sort_cases.json.test""#);
+        let sorted = expand(r#"exact, serde=json, file="tests/sort_cases.json.test", sort_cases"#);
+        assert_eq!(
+            test_fns(&in_file_order),
+            [
+                "sort_cases_json_zebra",
+                "sort_cases_json_apple",
+                "sort_cases_json_mango"
+            ]
+        );
+        assert_eq!(
+            test_fns(&sorted),
+            [
+                "sort_cases_json_apple",
+                "sort_cases_json_mango",
+                "sort_cases_json_zebra"
+            ]
+        );
+        // the cases are still listed in the order of the file
+        let names = "const SORT_CASES_JSON_CASE_NAMES : & [ & str ] = & [ \"zebra\" , \"apple\" , \"mango\" ] ;";
+        assert!(in_file_order.contains(names));
+        assert!(sorted.contains(names));
+    }
+
+    #[test]
+    fn value_mut_runs_after_the_other_normalizations() {
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/value_mut.json.test", value_mut = round, sort_keys"#,
+        );
+        assert!(expanded.contains("round ( & mut value ) ;"));
+        let sorted = expanded
+            .find("let actual = __conformance_sort_keys ( & actual )")
+            .unwrap();
+        let mutated = expanded
+            .find("let actual = __conformance_value_mut ( & actual )")
+            .unwrap();
+        assert!(sorted < mutated);
+        let e = expand(
+            r#"exact, ser=a::to_string, de=a::from_str, file="tests/value_mut.json.test", value_mut = round"#,
+        );
+        assert!(e.contains("`value_mut` requires a JSON crate"));
+    }
+
+    #[test]
+    fn trusted_goldens_are_not_normalized() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/canon.json.test", trusted_goldens"#);
+        assert!(
+            expanded.contains("let expected = :: std :: string :: String :: from ( expected ) ;")
+        );
+        assert!(!expanded.contains("json :: from_str :: < json :: Value > ( expected )"));
+        let e = syn::parse_str::<AttrArgs>(
+            r#"exact, serde=json, file="a.test", trusted_goldens, canon_ser = c"#,
+        )
+        .err()
+        .unwrap();
+        assert!(e
+            .to_string()
+            .ends_with("which isn't done with `trusted_goldens`"));
+    }
+
+    #[test]
+    fn mod_name_replaces_the_derived_name() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/error.json.test", mod_name = rejected"#);
+        assert!(expanded.contains("fn rejected_valid ( )"));
+        assert!(expanded.contains("const REJECTED_CASE_NAMES"));
+        let expanded = expand(r#"exact, serde=json, dir="tests/cases", mod_name = corpus"#);
+        assert!(expanded.contains("mod corpus {"));
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/doc_examples.json.test", doc_examples, mod_name = examples"#,
+        );
+        assert!(expanded.contains("pub mod examples { }"));
+        for name in &["fn", "r#fn", "\"x\""] {
+            let attr = format!(r#"exact, serde=json, file="a.test", mod_name = {}"#, name);
+            assert!(syn::parse_str::<AttrArgs>(&attr).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn doc_examples_are_fenced() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/doc_examples.json.test", doc_examples"#);
+        assert!(expanded.contains("pub mod doc_examples_json_examples { }"));
+        assert!(!expanded.contains("# [test]"));
+        assert!(expanded
+            .contains(r#"# keeps backticks\n\n````text\n\"```rust\\n`x`\"\n````\n\ngives\n"#));
+        assert!(expanded.contains(
+            r#"# rejects numbers\n\n```text\n1\n```\n\nis rejected with an error containing\n"#
+        ));
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, dir="tests", doc_examples"#)
+            .err()
+            .unwrap();
+        assert!(e.to_string().ends_with("so it can't be used with `dir`"));
+    }
+
+    #[test]
+    fn ignore_keys_need_json() {
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", ignore_keys = []"#)
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "`ignore_keys` needs a key");
+        let expanded = expand(
+            r#"exact, ser=a::to_string, de=a::from_str, file="tests/ignore_keys.json.test", ignore_keys = ["id"]"#,
+        );
+        assert!(expanded.contains("`ignore_keys` requires a JSON crate"));
+    }
+
+    #[test]
+    fn empty_bodies_only_name_the_type() {
+        let args = syn::parse_str(r#"exact, serde=json, file="tests/identity.json.test""#).unwrap();
+        let fun = syn::parse_str("fn lines() -> Vec<String> {}").unwrap();
+        let expanded =
+            build_tests(args, fun, PathBuf::from(env!("CARGO_MANIFEST_DIR"))).to_string();
+        assert!(expanded.contains(
+            "json :: to_string ( & { let _ = lines ; json :: from_str :: < Vec < String > > ( actual ) ? } ) ?"
+        ));
+    }
+
+    #[test]
+    fn verbose_is_gated_on_the_environment() {
+        let expanded = expand(r#"exact, serde=json, file="tests/repeat.json.test", verbose"#);
+        assert!(expanded.contains(
+            "if :: std :: env :: var_os ( \"CONFORMANCE_VERBOSE\" ) . is_some ( ) { :: std :: eprintln ! ( \"running {}::{}\" , \"tests/repeat.json.test\""
+        ));
+        let quiet = expand(r#"exact, serde=json, file="tests/repeat.json.test""#);
+        assert!(!quiet.contains("CONFORMANCE_VERBOSE"));
+    }
+
+    #[test]
+    fn value_cmp_conflicts() {
+        let e = syn::parse_str::<AttrArgs>(r#"regex, serde=json, file="a.test", value_cmp = c"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`value_cmp` decides whether the outputs match, so it can't be used with `regex` mode"
+        );
+        let expanded = expand(
+            r#"exact, ser=a::to_string, de=a::from_str, file="tests/value_cmp.json.test", value_cmp = c"#,
+        );
+        assert!(expanded.contains("`value_cmp` requires a JSON crate"));
+    }
+
+    #[test]
+    fn snapshots_are_named_after_cases() {
+        let e = syn::parse_str::<AttrArgs>(r#"regex, serde=json, file="a.test", snapshots"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`snapshots` records the output of cases without one, so it can't be used with `regex` mode"
+        );
+        let expanded = expand(r#"exact, serde=json, file="tests/snapshots.json.test", snapshots"#);
+        assert!(expanded.contains("tests/snapshots.json.recorded.snap"));
+        // cases with an output are compared to it
+        assert!(!expanded.contains("snapshots.json.written_out.snap"));
+    }
+
+    #[test]
+    fn benches_register_each_case() {
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, dir="tests", bench"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`bench` registers the cases of one file as benchmarks, so it can't be used with `dir`"
+        );
+        let expanded = expand(r#"exact, serde=json, file="tests/error.json.test", bench"#);
+        assert!(
+            expanded.contains("pub fn error_json_bench ( c : & mut :: criterion :: Criterion )")
+        );
+        assert!(expanded.contains("fn error_json_actual ( actual : & str )"));
+        // `@error` cases have no output to time
+        assert_eq!(expanded.matches("c . bench_function").count(), 1);
+        assert!(expanded.contains("c . bench_function ( \"valid\""));
+    }
+
+    #[test]
+    fn jq_filters_the_actual_output() {
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", jq = " ""#)
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "`jq` needs a program");
+        let e =
+            syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", jq = ".a", sort_keys"#)
+                .err()
+                .unwrap();
+        assert!(e
+            .to_string()
+            .ends_with("so it can't be used with `sort_keys`"));
+        let expanded = expand(r#"exact, serde=json, file="tests/identity.json.test", jq = ".[0]""#);
+        assert!(expanded.contains("let actual = __conformance_jq ( & actual , \".[0]\" )"));
+        assert!(
+            expanded.contains("let expected = :: std :: string :: String :: from ( expected ) ;")
+        );
+    }
+
+    #[test]
+    fn generated_items_are_hidden_from_docs() {
+        let expanded = expand(r#"exact, serde=json, file="tests/identity.json.test""#);
+        assert!(expanded
+            .contains("# [ doc ( hidden ) ] # [ doc = r\" The number of cases in the file.\" ]"));
+        assert!(expanded.contains("# [ doc ( hidden ) ] # [ test ] fn identity_json_origin ( )"));
+        assert!(expanded.contains(
+            "# [ doc ( hidden ) ] # [ allow ( dead_code ) ] # [ track_caller ] # [ must_use ="
+        ));
+    }
+
+    #[test]
+    fn exclusions_must_name_a_case() {
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/exclude.json.test", exclude = ["known failure", "passng"]"#,
+        );
+        assert!(expanded.contains("`exclude` names `passng`, but no case has that name"));
+        assert!(expanded.contains(
+            "# [ ignore = \"excluded by the `exclude` option\" ] fn exclude_json_known_failure"
+        ));
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", exclude = []"#)
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "`exclude` needs a case name");
+        // in any file of a directory
+        let expanded = expand(r#"exact, serde=json, dir="tests/cases", exclude = ["nested"]"#);
+        assert!(!expanded.contains("`exclude` names"));
+        assert!(expanded.contains("# [ ignore = \"excluded by the `exclude` option\" ] fn _nested"));
+    }
+
+    #[test]
+    fn unpaired_files_are_errors() {
+        let expanded = expand(
+            r#"exact, serde=json, input_dir="tests/invalid/pairs/in", output_dir="tests/invalid/pairs/out""#,
+        );
+        assert!(expanded.contains(
+            "input `tests/invalid/pairs/in/orphan.txt` has no output `orphan.*` in `tests/invalid/pairs/out`"
+        ));
+        assert!(expanded.contains(
+            "output `tests/invalid/pairs/out/stray.json` has no input `stray.*` in `tests/invalid/pairs/in`"
+        ));
+        let expanded = expand(
+            r#"exact, serde=json, input_dir="tests/pairs/in", output_dir="tests/pairs/out""#,
+        );
+        assert!(expanded.contains("const _ : & str = :: std :: include_str ! ("));
+        let e = syn::parse_str::<AttrArgs>(
+            r#"idempotent, serde=json, input_dir="tests/pairs/in", output_dir="tests/pairs/out""#,
+        )
+        .err()
+        .unwrap();
+        assert!(e
+            .to_string()
+            .ends_with("so it can't be used with `idempotent` mode"));
+    }
+
+    #[test]
+    fn reference_parser_sees_the_serialized_output() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/de_ref.json.test", de_ref = r::from_str"#);
+        assert!(expanded.contains(
+            "if let :: std :: result :: Result :: Err ( e ) = r :: from_str :: < json :: Value > ( & actual )"
+        ));
+    }
+
+    #[test]
+    fn ident_prefix_must_start_an_identifier() {
+        let parse = |prefix: &str| {
+            let attr = format!(
+                r#"exact, serde=json, file="a.test", ident_prefix = "{}""#,
+                prefix
+            );
+            syn::parse_str::<AttrArgs>(&attr)
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert_eq!(
+            parse("1").unwrap(),
+            "`ident_prefix` must start an identifier, as `case_` does"
+        );
+        assert!(parse("").is_some());
+        assert!(parse("case_").is_none());
+        let expanded = expand(r#"exact, serde=json, dir="tests/cases", ident_prefix = "case_""#);
+        assert!(expanded.contains("fn case_nested ( )"));
+    }
+
+    #[test]
+    fn validate_reads_without_generating() {
+        let validate = |input: &str| {
+            let args: ValidateArgs = syn::parse_str(input).unwrap();
+            validate_file(&args, Path::new(env!("CARGO_MANIFEST_DIR"))).to_string()
+        };
+        let expanded = validate(r#""tests/identity.json.test""#);
+        assert!(expanded.starts_with("const _ : & str = :: std :: include_str ! ("));
+        assert!(!expanded.contains("fn "));
+        let e = validate(r#""tests/invalid/near_miss.test""#);
+        assert!(e.contains("did you mean `===`?"));
+        // the mode and options decide how the file is read
+        assert!(validate(r#""tests/idempotent.json.test""#).contains("compile_error"));
+        assert!(!validate(r#"idempotent, "tests/idempotent.json.test""#).contains("compile_error"));
+        let e = validate(r#""tests/incomplete.json.test""#);
+        assert!(e.contains("no `incomplete` option tells"));
+        let e = validate(r#""tests/incomplete.json.test", incomplete = needs_more"#);
+        assert!(!e.contains("compile_error"));
+    }
+
+    #[test]
+    fn overrides_have_testing_fns_of_their_own() {
+        let e = read_error("tests/invalid/override.test");
+        assert!(e.contains("test `spaced` (line 1) has `@de not a path`, but that isn't a path"));
+        let expanded = expand(r#"exact, serde=json, file="tests/overrides.json.test""#);
+        // the two cases overriding `de` alike share a testing function, unlike the third
+        let override_fns: BTreeSet<_> = expanded
+            .split_whitespace()
+            .filter(|token| token.starts_with("overrides_json_override_") && token.len() == 28)
+            .collect();
+        assert_eq!(override_fns.len(), 2);
+        assert!(
+            expanded.contains("crate :: overrides :: from_lines :: < json :: Value > ( expected )")
+        );
+    }
+
+    #[test]
+    fn warnings_are_checked_after_the_output() {
+        let e = read_error("tests/warn.json.test");
+        assert!(e.contains(
+            "test `blank line` (line 8) has `@warn`, but no `warnings` option gives the warnings of a value"
+        ));
+        let expanded = expand(r#"exact, serde=json, file="tests/warn.json.test", warnings = lint"#);
+        assert!(expanded.contains("fn warn_json_warnings ( actual : & str )"));
+        assert!(expanded.contains(":: std :: result :: Result :: Ok ( lint ( & value ) )"));
+        assert!(expanded.contains(
+            "let expected : & [ & str ] = & [ \"line 2 is blank\" , \"line 3 is blank\" ] ;"
+        ));
+    }
+
+    #[test]
+    fn paths_outside_the_manifest_dir_are_rejected() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/../../conformance-format/README.md""#);
+        assert!(
+            expanded.contains("conformance-format/README.md` is outside of the manifest directory")
+        );
+        assert!(expanded.contains("give `allow_outside` to read it anyway"));
+        // `..` within the manifest directory is fine
+        let expanded = expand(r#"exact, serde=json, file="tests/../tests/identity.json.test""#);
+        assert!(!expanded.contains("outside of the manifest directory"));
+        let expanded =
+            expand(r#"exact, serde=json, file="../conformance-format/README.md", allow_outside"#);
+        assert!(!expanded.contains("outside of the manifest directory"));
+    }
+
+    #[test]
+    fn templates_are_compared_as_written() {
+        let e = syn::parse_str::<AttrArgs>(r#"idempotent, serde=json, file="a.test", template"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`template` compares the outputs with a template as written, \
+             so it can't be used with `idempotent` mode"
+        );
+        let expanded = expand(
+            r#"exact, ser=a::to_string, de=a::from_str, file="tests/template.json.test", template"#,
+        );
+        assert!(expanded.contains("`template` requires a JSON crate"));
+        let expanded = expand(r#"exact, serde=json, file="tests/template.json.test", template"#);
+        assert!(expanded.contains("__conformance_template_mismatch ( & actual , expected ) ?"));
+        // the template isn't deserialized
+        assert!(!expanded.contains("json :: from_str :: < json :: Value > ( expected )"));
+    }
+
+    #[test]
+    fn msgpack_compares_decoded_values() {
+        let e = syn::parse_str::<AttrArgs>(
+            r#"msgpack, ser = hex::ser, de = hex::de, file="a.test", predicate = ok"#,
+        )
+        .err()
+        .unwrap();
+        assert!(e
+            .to_string()
+            .ends_with("so it can't be used with `predicate`"));
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", base64"#)
+            .err()
+            .unwrap();
+        assert!(e
+            .to_string()
+            .ends_with("so it can only be used in `msgpack` or `cbor` mode"));
+        let expanded =
+            expand(r#"msgpack, ser = hex::ser, de = hex::de, file="tests/msgpack.test""#);
+        assert!(expanded.contains("__conformance_msgpack_mismatch ( & actual , expected ) ?"));
+        assert!(expanded.contains("odd number of hex digits"));
+        let expanded =
+            expand(r#"msgpack, ser = hex::ser, de = hex::de, file="tests/msgpack.test", base64"#);
+        assert!(expanded.contains("is not base64"));
+    }
+
+    #[test]
+    fn utf8_is_checked_in_text_modes() {
+        let writer = |mode: &str, option: &str| {
+            expand(&format!(
+                r#"{}, ser = hex::ser, de = hex::de, file="tests/msgpack.test", ser_writer = write{}"#,
+                mode, option
+            ))
+        };
+        let checked = "wrote invalid UTF-8 at byte";
+        assert!(writer("exact", "").contains(checked));
+        assert!(!writer("exact", ", allow_invalid_utf8").contains(checked));
+        assert!(!writer("msgpack", "").contains(checked));
+        assert!(writer("msgpack", ", utf8").contains(checked));
+        let e = syn::parse_str::<AttrArgs>(
+            r#"exact, serde=json, file="a.test", ser_writer = write, utf8, allow_invalid_utf8"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`utf8` fails output which isn't UTF-8, so it can't be used with `allow_invalid_utf8`"
+        );
+        let e =
+            syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", allow_invalid_utf8"#)
+                .err()
+                .unwrap();
+        assert!(e
+            .to_string()
+            .starts_with("`allow_invalid_utf8` checks what `ser_writer` writes"));
+    }
+
+    #[test]
+    fn cbor_compares_decoded_values() {
+        let e = syn::parse_str::<AttrArgs>(
+            r#"cbor, ser = hex::ser, de = hex::de, file="a.test", stream"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`cbor` mode compares the outputs as CBOR values, so it can't be used with `stream`"
+        );
+        let expanded = expand(r#"cbor, ser = hex::ser, de = hex::de, file="tests/cbor.test""#);
+        assert!(expanded.contains("__conformance_cbor_mismatch ( & actual , expected ) ?"));
+        assert!(expanded.contains(":: ciborium :: de :: from_reader ( & mut rest )"));
+        assert!(!expanded.contains("rmpv"));
+    }
+
+    #[test]
+    fn xml_compares_parsed_trees() {
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde = json, file="a.test", strict_prolog"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`strict_prolog` compares the XML declarations of the outputs, \
+             so it can only be used in `xml` mode"
+        );
+        let e = syn::parse_str::<AttrArgs>(r#"xml, serde = json, file="a.test", sorted_lines"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`sorted_lines` compares the outputs as sets of lines, so it can't be used in `xml` mode"
+        );
+        let expanded = expand(r#"xml, serde = json, file="tests/xml.test""#);
+        assert!(expanded.contains("__conformance_xml_mismatch ( & actual , expected ) ?"));
+        assert!(expanded.contains("use :: roxmltree :: { Document , Node }"));
+        assert!(!expanded.contains("fn declaration"));
+        let expanded = expand(r#"xml, serde = json, file="tests/xml.test", strict_prolog"#);
+        assert!(expanded.contains("declaration ( actual )"));
+    }
+
+    #[test]
+    fn approx_compares_numbers_within_a_tolerance() {
+        let e =
+            syn::parse_str::<AttrArgs>(r#"exact, serde = json, file="a.test", tolerance = 0.1"#)
+                .err()
+                .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`tolerance` bounds how far apart the numbers of `approx` mode may be, \
+             so it can only be used in `approx` mode"
+        );
+        let e =
+            syn::parse_str::<AttrArgs>(r#"approx, serde = json, file="a.test", tolerance = -0.1"#)
+                .err()
+                .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`tolerance` must be a finite, non-negative float"
+        );
+        let e =
+            syn::parse_str::<AttrArgs>(r#"approx, serde = json, file="a.test", tolerance = 1e999"#)
+                .err()
+                .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "`tolerance` must be a finite, non-negative float"
+        );
+        let expanded = expand(r#"approx, serde = json, file="tests/approx.json.test""#);
+        assert!(expanded.contains("__conformance_approx_mismatch ( & actual , expected ) ?"));
+        assert!(expanded.contains("const TOLERANCE : f64 = 0.000000001f64 ;"));
+        // `thirds` has its own `@tolerance`
+        assert!(expanded.contains("const TOLERANCE : f64 = 0.01f64 ;"));
+        let expanded =
+            expand(r#"approx, serde = json, file="tests/approx.json.test", tolerance = 0.5"#);
+        assert!(expanded.contains("const TOLERANCE : f64 = 0.5f64 ;"));
+        assert!(!expanded.contains("0.000000001f64"));
+    }
+
+    #[test]
+    fn tests_are_only_built_for_tests() {
+        let fun =
+            syn::parse_str("#[cfg(unix)] fn lines(s: &str) -> Vec<String> { unimplemented!() }")
+                .unwrap();
+        let cfgs = |attr: &str| {
+            let args: AttrArgs = syn::parse_str(attr).unwrap();
+            let cfgs = generated_cfgs(&fun, &args.options);
+            quote!(#(#cfgs)*).to_string()
+        };
+        let file = r#"exact, serde=json, file="tests/identity.json.test""#;
+        assert_eq!(cfgs(file), "# [ cfg ( unix ) ] # [ cfg ( test ) ]");
+        assert_eq!(cfgs(&format!("{}, always", file)), "# [ cfg ( unix ) ]");
+        assert_eq!(
+            cfgs(&format!("{}, doc_examples", file)),
+            "# [ cfg ( unix ) ]"
+        );
+        // `cargo bench` doesn't build with `cfg(test)`
+        assert_eq!(cfgs(&format!("{}, bench", file)), "# [ cfg ( unix ) ]");
+        let generated = with_cfgs(
+            &[syn::parse_quote!(#[cfg(test)])],
+            quote!(
+                fn a() {}
+                const B: u8 = 0;
+            ),
+        );
+        assert_eq!(
+            generated.to_string(),
+            "# [ cfg ( test ) ] fn a ( ) { } # [ cfg ( test ) ] const B : u8 = 0 ;"
+        );
+    }
+
+    #[test]
+    fn batches_are_split_into_sub_inputs() {
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", batch, stream"#)
+            .err()
+            .unwrap();
+        assert!(e.to_string().ends_with("so it can't be used with `stream`"));
+        let expanded = expand(r#"exact, serde=json, file="tests/batch.json.test", batch"#);
+        assert!(expanded.contains(
+            "lines ( actual . split ( \"\\n,,,\\n\" ) . map ( | input | json :: from_str :: < _ > ( input ) )"
+        ));
+    }
+
+    #[test]
+    fn clones_are_checked_for_clone() {
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/identity.json.test", clone_stable"#);
+        assert!(expanded.contains("assert_clone :: < Vec < String > > ( ) ;"));
+        assert!(expanded.contains("let cloned = :: std :: clone :: Clone :: clone ( & value ) ;"));
+    }
+
+    #[test]
+    fn newlines_are_normalized() {
+        let options = Options {
+            normalize_newlines: true,
+            ..Options::default()
+        };
+        assert_eq!(trim_body("\r\na\r\nb\rc\r\n", &options), "a\nb\nc");
+        assert_eq!(trim_body("a\r\nb", &Options::default()), "a\r\nb");
+    }
+
+    #[test]
+    fn types_override_the_return_type() {
+        let expanded = expand(
+            r#"exact, ser=json::to_string, de=json::from_str, file="tests/ids.json.test", type = Vec<&'static str>"#,
+        );
+        assert!(expanded.contains("json :: from_str :: < Vec < & 'static str > > ( expected )"));
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", type = 1"#)
+            .err()
+            .unwrap();
+        assert!(e.to_string().starts_with("expected one of"));
+    }
+
+    #[test]
+    fn golden_files_are_checked() {
+        let e = read_error("tests/invalid/golden.json.test");
+        assert!(
+            e.contains("test `missing` has `@file.gz missing.json.gz`, but failed to open file"),
+            "{}",
+            e
+        );
+        let e = read_error("tests/invalid/bad_golden.json.test");
+        assert!(e.contains("not_gzip.json.gz` is not valid gzip"), "{}", e);
+    }
+
+    #[test]
+    fn binary_goldens_are_written_as_output_sections() {
+        let expanded = expand(r#"msgpack, ser = hex::ser, de = hex::de, file = "tests/bin.test""#);
+        assert!(expanded.contains("\"92 01 02\""));
+        assert!(expanded.contains("tests/golden/pair.bin\""));
+        let expanded =
+            expand(r#"msgpack, ser = hex::ser, de = hex::de, file = "tests/bin.test", base64"#);
+        assert!(expanded.contains("\"kgEC\""));
+
+        let e = expand(r#"exact, serde = json, file = "tests/bin.test""#);
+        assert!(e.contains(
+            "test `pair` has `@bin golden/pair.bin`, but `exact` mode doesn't compare outputs as bytes"
+        ));
+        let e = expand(r#"cbor, ser = hex::ser, de = hex::de, file = "tests/invalid/bin.test""#);
+        assert!(
+            e.contains("has `@bin golden/missing.bin`, but `tests/invalid/golden/missing.bin` doesn't exist"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn strict_format_rejects_untidy_files() {
+        let strict = Options {
+            strict_format: true,
+            ..Options::default()
+        };
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/invalid/untidy.test");
+        let e = match read_tests(&path, Span::call_site(), &strict) {
+            Ok(_) => panic!("`{}` was read successfully", path.display()),
+            Err(e) => e.to_string(),
+        };
+        assert!(e.contains("tests/invalid/untidy.test:2: separator `===` has trailing whitespace, so it isn't read as one"), "{}", e);
+        assert!(
+            e.contains("tests/invalid/untidy.test:9: tab in the name or directives of a test"),
+            "{}",
+            e
+        );
+        assert!(
+            e.contains("tests/invalid/untidy.test:13: separator `---` has leading whitespace"),
+            "{}",
+            e
+        );
+        // tabs in bodies are content
+        assert!(!e.contains(":11:"), "{}", e);
+    }
+
+    #[test]
+    fn qualified_paths_have_no_turbofish() {
+        let expanded = expand(
+            r#"exact, ser=ToString::to_string, de=<Vec<String> as FromStr>::from_str, file="tests/ids.json.test""#,
+        );
+        assert!(expanded.contains("< Vec < String > as FromStr > :: from_str ( expected ) ?"));
+        let expanded = expand(
+            r#"exact, ser=ToString::to_string, de=parse::<Vec<String>>, file="tests/ids.json.test""#,
+        );
+        assert!(expanded.contains("parse :: < Vec < String > > ( expected ) ?"));
+    }
+
+    #[test]
+    fn relative_to() {
+        let e =
+            syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", relative_to = cwd"#)
+                .err()
+                .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "expected `relative_to = source` or `relative_to = manifest`"
+        );
+    }
+
+    #[test]
+    fn directions() {
+        let e = syn::parse_str::<AttrArgs>(r#"exact, serde=json, file="a.test", direction = up"#)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "expected `direction = deserialize` or `direction = serialize`"
+        );
+        let expanded =
+            expand(r#"exact, serde=json, file="tests/serialize.json.test", direction = serialize"#);
+        assert!(expanded.contains(
+            "json :: to_string ( & { let _ = lines ; json :: from_str :: < Vec < String > > \
+             ( actual ) ? } ) ? ; let expected = :: std :: string :: String :: from ( expected ) ;"
+        ));
+    }
+
+    #[test]
+    fn seed_is_checked() {
+        let parse = |attr: &str| {
+            syn::parse_str::<AttrArgs>(attr)
+                .err()
+                .map(|e| e.to_string())
+        };
+        assert!(parse(r#"exact, serde=json, file="a.test", seed = Seed"#).is_none());
+        assert!(parse(
+            r#"exact, ser=json::to_string, de=json::from_str, file="a.test", seed = Seed"#
+        )
+        .unwrap()
+        .ends_with("can't be used with the `ser` and `de` arguments; use `serde`"));
+        assert!(
+            parse(r#"exact, serde=json, file="a.test", de_option, seed = Seed"#)
+                .unwrap()
+                .ends_with("can't be used with `de_option`")
+        );
+
+        let expanded = expand(r#"exact, serde=json, file="tests/seed.json.test", seed = Seed"#);
+        assert!(expanded.contains(
+            ":: serde :: de :: DeserializeSeed :: deserialize ( Seed , & mut json :: Deserializer \
+             :: from_str ( expected ) , )"
+        ));
+        assert!(!expanded.contains(" json :: from_str"));
+    }
+
+    #[test]
+    fn inconsistent_outputs() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/invalid/inconsistent.test");
+        let read = |check_consistency| {
+            let options = Options {
+                check_consistency,
+                error_kind: Some(syn::parse_quote!(kind)),
+                ..Options::default()
+            };
+            read_tests(&path, Span::call_site(), &options).map(|_| ())
+        };
+        assert!(read(false).is_ok());
+        let e = read(true).unwrap_err().to_string();
+        assert!(e.contains(
+            "tests `first` and `error` have the same input but expect different outcomes"
+        ));
+        assert!(e.contains(
+            "tests `eof` and `syntax` have the same input but expect different error kinds"
+        ));
+        // what's written differently is compared by the tests, once read
+        assert!(!e.contains("`copied`"));
+        assert!(!e.contains("`unclassified`"));
+        assert!(!e.contains("`failing`"));
+    }
+
+    #[test]
+    fn outputs_are_compared_as_the_tests_compare_them() {
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/consistency/lines.json.test", check_consistency"#,
+        );
+        assert!(expanded.contains("fn lines_json_consistency ( )"));
+        assert!(expanded.contains(r#"lines_json_consistent ( first , second , names ) ? ; } "#));
+        assert!(expanded
+            .contains(r#"[ ( "[\"a\", \"b\"]" , "[\"a\",\"b\"]" , ( "spaced" , "compact" ) ) ]"#));
+        let expanded = expand(
+            r#"idempotent, serde=json, file="tests/consistency/lines.json.test", check_consistency"#,
+        );
+        assert!(!expanded.contains("fn lines_json_consistency"));
+    }
+
+    #[test]
+    fn ignored_tests() {
+        let expanded = expand(
+            r#"exact, serde=json, file="tests/ignore.json.test", ignore_unless_feature = "slow-tests""#,
+        );
+        let attrs = |test: &str| {
+            let end = expanded.find(&format!("fn ignore_json_{} ", test)).unwrap();
+            let start = expanded[..end].rfind("# [ test ]").unwrap();
+            expanded[start..end].to_string()
+        };
+        assert_eq!(
+            attrs("slow"),
+            r#"# [ test ] # [ cfg_attr ( not ( feature = "slow-tests" ) , ignore ) ] "#
+        );
+        assert_eq!(attrs("broken"), "# [ test ] # [ ignore ] ");
+        assert_eq!(
+            attrs("flaky"),
+            r#"# [ test ] # [ ignore = "fails on leap days" ] "#
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn broken_symlinks_are_explained() {
+        let dir = env::temp_dir().join(format!("conformance-symlinks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let link = dir.join("broken.test");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(dir.join("missing.test"), &link).unwrap();
+
+        let e = open_tests(&link).err().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(e.starts_with(&format!("failed to open file `{}`: ", link.display())));
+        assert!(e.contains(&format!(
+            "; it is a broken symlink to `{}`, which can't be resolved: ",
+            dir.join("missing.test").display()
+        )));
+    }
+
+    #[test]
+    fn frontmatter_type() {
+        let e = read_error("tests/invalid/frontmatter_type.test");
+        assert!(e.contains(
+            "frontmatter `type = \\\"Vec<\\\"` of `tests/invalid/frontmatter_type.test` is not a type"
+        ));
+        // a frontmatter `type` instantiates a generic tested function in its file only
+        let expanded = expand(r#"exact, serde=json, file="tests/typed/floats.json.test""#);
+        assert!(expanded.contains("the frontmatter `type` was given, but `lines` isn't generic"));
+    }
+
+    #[test]
+    fn single_files() {
+        let expanded = expand(r#"exact, serde=json, file="tests/invalid/single.test", single"#);
+        assert!(expanded.contains(
+            "`single` file `tests/invalid/single.test` has no separators, \
+             so its expected output is read from its sibling `single.expected`"
+        ));
+        // the input is its own expected output in `idempotent` mode
+        let expanded =
+            expand(r#"idempotent, serde=json, file="tests/invalid/single.test", single"#);
+        assert!(expanded.contains("fn single_single"));
+        // a file with separators is as usual
+        let expanded = expand(r#"exact, serde=json, file="tests/cases/groups.json.test", single"#);
+        assert!(expanded.contains("fn groups_json_ungrouped"));
+    }
+
+    #[test]
+    fn groups_are_modules() {
+        let expanded = expand(r#"exact, serde=json, file="tests/cases/groups.json.test""#);
+        let ungrouped = expanded.find("fn groups_json_ungrouped").unwrap();
+        let first = expanded.find("mod first_group { use super :: * ;").unwrap();
+        let second = expanded.find("mod second { use super :: * ;").unwrap();
+        assert!(ungrouped < first && first < second);
+        assert!(expanded[first..second].contains("fn groups_json_two"));
+        assert!(expanded[second..].contains("fn groups_json_three"));
+
+        let expanded = expand(r#"exact, serde=json, dir="tests/cases""#);
+        assert!(expanded.contains("mod groups_json {"));
+        assert!(expanded.contains("mod first_group { use super :: * ;"));
+    }
+
+    #[test]
+    fn expansion_is_deterministic() {
+        for attr in &[
+            r#"exact, serde=json, file="tests/dedent.json.test""#,
+            r#"exact, serde=json, dir="tests/cases""#,
+        ] {
+            assert_eq!(expand(attr), expand(attr));
+        }
+    }
+
+    #[test]
+    fn large_bodies_are_sliced() {
+        let expanded = expand(r#"exact, serde=json, file="tests/large.json.test""#);
+        assert!(expanded.contains("& LARGE_JSON_CONTENTS [ 10usize .. 1569usize ]"));
+        assert!(expanded.contains("& LARGE_JSON_CONTENTS [ 1574usize .. 3436usize ]"));
+        assert!(!expanded.contains("line 30 of the large case"));
+
+        let expanded = expand(r#"exact, serde=json, file="tests/dedent.json.test""#);
+        assert!(!expanded.contains("DEDENT_JSON_CONTENTS ["));
+    }
+
+    #[test]
+    fn dir_is_expanded_in_sorted_order() {
+        let expanded = expand(r#"exact, serde=json, dir="tests/cases""#);
+        let position = |module: &str| expanded.find(&format!("mod {} {{", module)).unwrap();
+        assert!(position("nested") < position("deeper"));
+        assert!(position("deeper") < position("inner_json"));
+        assert!(position("inner_json") < position("top_json"));
+    }
 }
@@ -0,0 +1,2069 @@
+use {
+    proc_macro2::{Span, TokenStream},
+    quote::quote,
+    std::{collections::BTreeSet, env},
+    syn::{parse::Parse, spanned::Spanned},
+};
+
+#[derive(Clone)]
+pub(crate) struct AttrArgs {
+    pub(crate) ser: syn::ExprPath,
+    pub(crate) de: syn::ExprPath,
+    pub(crate) value: Option<syn::Type>,
+    /// The path given as the `serde` shorthand, if it was used.
+    pub(crate) format: Option<syn::ExprPath>,
+    pub(crate) source: Source,
+    pub(crate) options: Options,
+}
+
+/// Where the tests are read from.
+#[derive(Clone)]
+pub(crate) enum Source {
+    /// A single test file.
+    File(syn::LitStr),
+    /// A directory searched recursively for test files.
+    Dir(syn::LitStr),
+    /// A directory of input files, each paired with the output file of the same stem
+    /// in another directory.
+    Pairs {
+        input_dir: syn::LitStr,
+        output_dir: syn::LitStr,
+    },
+    /// Cases given in the attribute, with `"cases"` at the `cases` key standing in for the
+    /// path of a file where one is shown.
+    Cases {
+        name: syn::LitStr,
+        cases: Vec<InlineCase>,
+    },
+}
+
+/// A case given in the attribute as `(name, input, expected)`, or `(name, input)` in
+/// `idempotent` mode.
+#[derive(Clone)]
+pub(crate) struct InlineCase {
+    pub(crate) name: syn::LitStr,
+    pub(crate) input: syn::LitStr,
+    pub(crate) expected: Option<syn::LitStr>,
+}
+
+/// How the serialized output of each test is checked.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum Mode {
+    /// Compare against the test's output section.
+    #[default]
+    Exact,
+    /// Compare against the test's own input; tests have no output section.
+    Idempotent,
+    /// Match against the test's output section as a regular expression.
+    Regex,
+    /// Compare against the test's output section as MessagePack values, decoded from text.
+    Msgpack,
+    /// Compare against the test's output section as CBOR values, decoded from text.
+    Cbor,
+    /// Compare against the test's output section as XML trees, parsed from text.
+    Xml,
+    /// Compare against the test's output section as JSON values, numbers within a tolerance.
+    Approx,
+    /// Compare against the test's output section as YAML values, the keys of a top-level
+    /// mapping in any order.
+    YamlToplevelUnordered,
+}
+
+impl Mode {
+    pub(crate) const ALL: [Mode; 8] = [
+        Mode::Exact,
+        Mode::Idempotent,
+        Mode::Regex,
+        Mode::Msgpack,
+        Mode::Cbor,
+        Mode::Xml,
+        Mode::Approx,
+        Mode::YamlToplevelUnordered,
+    ];
+
+    /// The name of the mode, as written in the attribute.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Mode::Exact => "exact",
+            Mode::Idempotent => "idempotent",
+            Mode::Regex => "regex",
+            Mode::Msgpack => "msgpack",
+            Mode::Cbor => "cbor",
+            Mode::Xml => "xml",
+            Mode::Approx => "approx",
+            Mode::YamlToplevelUnordered => "yaml_toplevel_unordered",
+        }
+    }
+
+    /// Whether the outputs are binary documents, written as text, which are compared decoded.
+    pub(crate) fn is_binary(self) -> bool {
+        self == Mode::Msgpack || self == Mode::Cbor
+    }
+
+    /// Whether the outputs are compared as the documents they are parsed or decoded to,
+    /// with the output section as written, rather than as strings.
+    pub(crate) fn is_structural(self) -> bool {
+        self.is_binary()
+            || self == Mode::Xml
+            || self == Mode::Approx
+            || self == Mode::YamlToplevelUnordered
+    }
+}
+
+/// The mode which `CONFORMANCE_MODE`, if set to `value`, gives in place of the attribute's.
+///
+/// An empty value is taken as unset, so that `CONFORMANCE_MODE=` turns the override off.
+pub(crate) fn mode_from_env(value: Option<&str>, span: Span) -> syn::Result<Option<Mode>> {
+    let value = match value.map(str::trim) {
+        Some(value) if !value.is_empty() => value,
+        _ => return Ok(None),
+    };
+    match Mode::ALL.iter().find(|mode| mode.name() == value) {
+        Some(&mode) => Ok(Some(mode)),
+        None => Err(syn::Error::new(
+            span,
+            format!(
+                "`CONFORMANCE_MODE` is `{}`, which isn't a mode; expected one of {}",
+                value,
+                Mode::ALL
+                    .iter()
+                    .map(|mode| format!("`{}`", mode.name()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        )),
+    }
+}
+
+/// Which side of the format the tests exercise, given with the `direction` option.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum Direction {
+    /// The tested function turns each input into a value, which is serialized
+    /// and compared to the output deserialized and reserialized.
+    #[default]
+    Deserialize,
+    /// `de` turns each input, a value literal, into the tested type,
+    /// which is serialized and compared to the output as written.
+    Serialize,
+}
+
+/// How test files are written, given with the `format` option.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum FileFormat {
+    /// Tests separated by `===`, `---`, and `...` lines.
+    #[default]
+    Native,
+    /// A table with `name`, `input`, and `expected` columns, one test per row.
+    Csv,
+    /// As `Csv`, with cells separated by tabs.
+    Tsv,
+    /// A JSON object with `name`, `input`, and `expected` strings on each line, one test per line.
+    Jsonl,
+}
+
+/// The CI service failures are annotated for, given with the `ci` option.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum Ci {
+    /// Failures are only reported by the panic.
+    #[default]
+    None,
+    /// Failures are also printed as GitHub Actions `::error` workflow commands.
+    Github,
+    /// Failures are also printed as red `file:line` lines of the GitLab CI job log.
+    Gitlab,
+}
+
+/// Optional flags that may follow the `file` argument.
+#[derive(Clone, Default)]
+pub(crate) struct Options {
+    /// The mode given before the serialization arguments, or by `CONFORMANCE_MODE`.
+    pub(crate) mode: Mode,
+    /// The mode given before the serialization arguments and where,
+    /// if `CONFORMANCE_MODE` overrides it with another.
+    pub(crate) attribute_mode: Option<(Mode, Span)>,
+    /// Strip common leading whitespace from each body,
+    /// optionally removing at most the given number of characters.
+    pub(crate) dedent: Option<Option<usize>>,
+    /// `de` returns the value directly rather than a `Result`.
+    pub(crate) de_infallible: bool,
+    /// `de` returns an `Option` rather than a `Result`.
+    pub(crate) de_option: bool,
+    /// `ser` returns the string directly rather than a `Result`.
+    pub(crate) ser_infallible: bool,
+    /// Extra arguments passed to `de` after the input.
+    pub(crate) de_args: Vec<syn::Expr>,
+    /// Extra arguments passed to `ser` after the value.
+    pub(crate) ser_args: Vec<syn::Expr>,
+    /// Take section bodies verbatim, without trimming surrounding whitespace.
+    pub(crate) raw: bool,
+    /// Compare against the expected section as written, without normalizing it.
+    pub(crate) literal_expected: bool,
+    /// Trust the expected section to be canonical, skipping its normalization for speed.
+    pub(crate) trusted_goldens: bool,
+    /// Read the expected section as a JSON template, whose `<<name>>` placeholders match any value.
+    pub(crate) template: bool,
+    /// In `msgpack` or `cbor` mode, the outputs are written in base64 rather than hex.
+    pub(crate) base64: bool,
+    /// In `xml` mode, compare the XML declarations of the outputs too.
+    pub(crate) strict_prolog: bool,
+    /// In `approx` mode, how far apart numbers may be, unless a test gives its own `@tolerance`.
+    pub(crate) tolerance: Option<f64>,
+    /// Remove ANSI escape sequences from the serialized output before comparing.
+    pub(crate) strip_ansi: bool,
+    /// Sort the lines of both outputs before comparing, for output which is a set of lines.
+    pub(crate) sorted_lines: bool,
+    /// Remove a leading byte order mark from both outputs before comparing.
+    pub(crate) strip_bom: bool,
+    /// Convert `\r\n` and lone `\r` to `\n` in bodies, and in both outputs before comparing.
+    pub(crate) normalize_newlines: bool,
+    /// The extension of test files discovered in `dir` mode.
+    pub(crate) extension: Option<String>,
+    /// The JSON crate used to parse outputs for structural comparison,
+    /// if not the `serde` format.
+    pub(crate) json: Option<syn::ExprPath>,
+    /// Compare only the value at this JSON Pointer.
+    pub(crate) project: Option<syn::LitStr>,
+    /// Compare only what this jq program gives for the actual output.
+    pub(crate) jq: Option<syn::LitStr>,
+    /// Generate no tests, with a warning, if the file or directory doesn't exist.
+    pub(crate) skip_if_missing: bool,
+    /// Generate the tests in every build, rather than only under `#[cfg(test)]`.
+    pub(crate) always: bool,
+    /// Check the serialized output with this function instead of comparing it.
+    pub(crate) predicate: Option<syn::ExprPath>,
+    /// The type `de` parses to in place of the tested function's return type (`type = Foo`).
+    pub(crate) r#type: Option<syn::Type>,
+    /// Decide whether the outputs match with this function, given both parsed as JSON values.
+    pub(crate) value_cmp: Option<syn::ExprPath>,
+    /// Compare the token sequences this function splits the outputs into, rather than their text.
+    pub(crate) tokenize: Option<syn::ExprPath>,
+    /// Sort the elements of JSON arrays before comparing.
+    pub(crate) unordered_arrays: bool,
+    /// Read JSON strings which are numbers or booleans as those before comparing.
+    pub(crate) loose_scalars: bool,
+    /// Append a hash of each test's input to its name.
+    pub(crate) name_includes_hash: bool,
+    /// Don't warn about `exact` tests whose input and output are identical.
+    pub(crate) allow_identical: bool,
+    /// Ignore anything after the final `...` of a file, rather than rejecting it.
+    pub(crate) allow_trailing_content: bool,
+    /// Read a final test without its `...` as a test, rather than as trailing content.
+    pub(crate) lenient_terminator: bool,
+    /// Reject separator lines with surrounding whitespace, and tabs in names and directives.
+    pub(crate) strict_format: bool,
+    /// Reject cases with an empty input or output, unless they are `@allow_empty`.
+    pub(crate) forbid_empty: bool,
+    /// Reject cases without `@meta` pairs of these keys.
+    pub(crate) require_meta: Vec<syn::LitStr>,
+    /// Document the cases on a generated module instead of generating tests.
+    pub(crate) doc_examples: bool,
+    /// Run every case in one test per file, which reports all of those failing.
+    pub(crate) aggregate: bool,
+    /// Generate a test per file printing a table of its cases, and whether each is ignored.
+    pub(crate) index: bool,
+    /// Compare cases with an empty output against a `.snap` file of their serialized output,
+    /// written when `CONFORMANCE_ACCEPT` is set.
+    pub(crate) snapshots: bool,
+    /// Also generate a function registering a Criterion benchmark of each case.
+    pub(crate) bench: bool,
+    /// The name to give what is generated in place of one derived from the file or directory.
+    pub(crate) mod_name: Option<syn::Ident>,
+    /// Print the name of each case to stderr as it starts, if `CONFORMANCE_VERBOSE` is set.
+    pub(crate) verbose: bool,
+    /// The CI service to annotate failures for, when the tests run on it.
+    pub(crate) ci: Ci,
+    /// Generate `wasm-bindgen-test` tests, for `wasm32` targets, rather than `#[test]`s.
+    pub(crate) wasm: bool,
+    /// Resolve `file` or `dir` against the directory of the invoking source file
+    /// (`relative_to = source`), rather than the manifest directory.
+    pub(crate) relative_to_source: bool,
+    /// Read `file` or `dir` even if it resolves to outside of the manifest directory.
+    pub(crate) allow_outside: bool,
+    /// The template of the message for a mismatched output.
+    pub(crate) message: Option<Message>,
+    /// Write long bodies to `OUT_DIR` and include them separately.
+    pub(crate) split_includes: bool,
+    /// How many times to reserialize a mismatched output before failing.
+    pub(crate) retry: usize,
+    /// Remove the members of JSON objects with these keys, at any depth, before comparing.
+    pub(crate) ignore_keys: Vec<syn::LitStr>,
+    /// The names, or `@id`s, of cases to `#[ignore]` without editing the file.
+    pub(crate) exclude: Vec<syn::LitStr>,
+    /// Sort the keys of JSON objects before comparing.
+    pub(crate) sort_keys: bool,
+    /// A function mutating both outputs, parsed as JSON values, before comparing.
+    pub(crate) value_mut: Option<syn::ExprPath>,
+    /// The generic arguments to call a generic tested function with.
+    pub(crate) transform_turbofish: Option<syn::AngleBracketedGenericArguments>,
+    /// The types to call a generic tested function with, each in a module of its own.
+    pub(crate) types: Vec<syn::Type>,
+    /// A function transforming each input before it is given to `de` or the tested function.
+    pub(crate) preprocess: Option<syn::ExprPath>,
+    /// Check that the input serializes the same when tested twice.
+    pub(crate) deterministic: bool,
+    /// Re-parse the serialized output with `de` and serialize it again until it stops changing.
+    pub(crate) fixpoint: bool,
+    /// How many times `fixpoint` may re-parse the output before it must have stopped changing.
+    pub(crate) fixpoint_max: Option<(usize, Span)>,
+    /// Report a mismatch with a line diff of at most this many lines.
+    pub(crate) max_diff_lines: Option<usize>,
+    /// The command whose output each input is compared to, instead of the output section.
+    pub(crate) oracle: Option<Oracle>,
+    /// Reject tests with the same input but different expected outputs.
+    pub(crate) check_consistency: bool,
+    /// Ignore the tests unless the consuming crate enables this feature.
+    pub(crate) ignore_unless_feature: Option<syn::LitStr>,
+    /// A `fn(&str) -> String` applied to both outputs last, right before they are compared.
+    pub(crate) normalize: Option<syn::ExprPath>,
+    /// Write the generated tests to a file in `OUT_DIR`, and `include!` it.
+    pub(crate) generated_file: bool,
+    /// The most bytes a serialized output may have.
+    pub(crate) max_bytes: Option<usize>,
+    /// Treat a file without separators as one case, expecting its sibling `.expected` file.
+    pub(crate) single: bool,
+    /// The `DeserializeSeed` to deserialize with, in place of `de`.
+    pub(crate) seed: Option<syn::Expr>,
+    /// Evaluated before each run of a case, or called if it is a closure.
+    pub(crate) before_each: Option<syn::Expr>,
+    /// Evaluated after each run of a case, even one which panicked, or called if it is a closure.
+    pub(crate) after_each: Option<syn::Expr>,
+    /// How many cases each test file must have.
+    pub(crate) expect_cases: Option<usize>,
+    /// How many cases a test file may have before a warning suggests splitting it.
+    pub(crate) warn_over: Option<usize>,
+    /// Whether the tests exercise the tested function or `ser`.
+    pub(crate) direction: Direction,
+    /// A serializer writing to an `io::Write`, used in place of `ser`.
+    pub(crate) ser_writer: Option<syn::ExprPath>,
+    /// Fail when `ser_writer` writes invalid UTF-8 in a binary mode, as in the others.
+    pub(crate) utf8: bool,
+    /// Compare what `ser_writer` writes as bytes when it isn't UTF-8, rather than failing.
+    pub(crate) allow_invalid_utf8: bool,
+    /// Don't warn about tests whose different names give the same identifier.
+    pub(crate) allow_similar_names: bool,
+    /// Generate one test for each set of cases with the same input and expected output.
+    pub(crate) dedup: bool,
+    /// Emit the tests sorted by their identifiers, rather than in the order of the file.
+    pub(crate) sort_cases: bool,
+    /// Record the number of tests in `OUT_DIR`, for `conformance::total!()`.
+    pub(crate) count_total: bool,
+    /// Check that serializing the tested value twice gives the same output.
+    pub(crate) ser_stable: bool,
+    /// Check that serializing a clone of the tested value gives the same output as the value.
+    pub(crate) clone_stable: bool,
+    /// `de` returns an iterator of the values in an input, which are collected into a `Vec`.
+    pub(crate) stream: bool,
+    /// Each input is a list of sub-inputs separated by `,,,` lines, each deserialized by `de`,
+    /// which are passed to the tested function together, as a `Vec`.
+    pub(crate) batch: bool,
+    /// The tested function has an empty body, and only names the type inputs are deserialized to.
+    /// Set from the function rather than by an option.
+    pub(crate) identity: bool,
+    /// Builds the runtime an `async` tested function is run on, with its `block_on`.
+    pub(crate) runtime: Option<syn::ExprPath>,
+    /// Classifies the errors of `de`, for `@error kind=<tag>` tests.
+    pub(crate) error_kind: Option<syn::ExprPath>,
+    /// Gives the line and column of the errors of `de`, for `@error_at` tests.
+    pub(crate) error_pos: Option<syn::ExprPath>,
+    /// Serializes the errors of `de`, for `@error` tests to compare with their output exactly.
+    pub(crate) error_ser: Option<syn::ExprPath>,
+    /// The serializer of a second format, through which each value makes a round trip.
+    pub(crate) ser_b: Option<syn::ExprPath>,
+    /// The deserializer of the second format, reading back what `ser_b` gives.
+    pub(crate) de_b: Option<syn::ExprPath>,
+    /// Tells whether what `de` returns says it needs more data, for `@incomplete` tests.
+    pub(crate) incomplete: Option<syn::ExprPath>,
+    /// Gives the warnings of the tested value, for `@warn` tests.
+    pub(crate) warnings: Option<syn::ExprPath>,
+    /// Check that `de` reads each input whole, if `remaining` is given.
+    pub(crate) consume_all: bool,
+    /// Gives how many bytes of an input `de` leaves unread, for `consume_all`.
+    pub(crate) remaining: Option<syn::ExprPath>,
+    /// How the test files are written.
+    pub(crate) file_format: FileFormat,
+    /// The trusted serializer normalizing the expected output, in place of `ser`.
+    pub(crate) canon_ser: Option<syn::ExprPath>,
+    /// The trusted deserializer normalizing the expected output, in place of `de`.
+    pub(crate) canon_de: Option<syn::ExprPath>,
+    /// A reference deserializer which must accept the serialized output.
+    pub(crate) de_ref: Option<syn::ExprPath>,
+    /// What test names are prefixed with to make identifiers, `_` by default.
+    pub(crate) ident_prefix: Option<String>,
+    /// A file listing a case name, or `@id`, per line, each of which some case must have.
+    pub(crate) require_cases: Option<syn::LitStr>,
+    /// Warn of each case missing from `require_cases`.
+    pub(crate) warn_extra_cases: bool,
+}
+
+impl Options {
+    /// What test names are prefixed with to make identifiers.
+    pub(crate) fn ident_prefix(&self) -> &str {
+        self.ident_prefix.as_deref().unwrap_or("_")
+    }
+
+    /// Whether the expected section is compared as written, rather than normalized.
+    pub(crate) fn expected_as_written(&self) -> bool {
+        self.literal_expected || self.trusted_goldens
+    }
+
+    /// Whether what `ser_writer` writes must be UTF-8: in the text modes unless it's allowed
+    /// not to be, and in the binary modes with `utf8`.
+    pub(crate) fn checks_utf8(&self) -> bool {
+        self.utf8 || !(self.mode.is_binary() || self.allow_invalid_utf8)
+    }
+}
+
+/// The placeholders which may appear in the `message` option.
+const MESSAGE_PLACEHOLDERS: &[&str] = &["name", "actual", "expected", "file", "line"];
+
+/// The `message` option: a `format!` string using some of `MESSAGE_PLACEHOLDERS`.
+#[derive(Clone)]
+pub(crate) struct Message {
+    pub(crate) template: syn::LitStr,
+    pub(crate) placeholders: BTreeSet<String>,
+}
+
+impl Parse for Message {
+    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
+        let template: syn::LitStr = input.parse()?;
+        let value = template.value();
+        let error = |e: String| syn::Error::new(template.span(), e);
+
+        let mut placeholders = BTreeSet::new();
+        let mut rest = &*value;
+        while let Some(ix) = rest.find(['{', '}']) {
+            let (brace, after) = (&rest[ix..=ix], &rest[ix + 1..]);
+            if after.starts_with(brace) {
+                rest = &after[1..];
+                continue;
+            }
+            if brace == "}" {
+                return Err(error(String::from("unmatched `}` in `message`; use `}}`")));
+            }
+            let end = after
+                .find('}')
+                .ok_or_else(|| error(String::from("unclosed `{` in `message`; use `{{`")))?;
+            // allow a format spec, as in `{actual:?}`
+            let name = after[..end].split(':').next().unwrap();
+            if !MESSAGE_PLACEHOLDERS.contains(&name) {
+                return Err(error(format!(
+                    "unknown placeholder `{{{}}}` in `message`; expected one of {}",
+                    name,
+                    MESSAGE_PLACEHOLDERS
+                        .iter()
+                        .map(|name| format!("`{{{}}}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )));
+            }
+            placeholders.insert(name.to_string());
+            rest = &after[end + 1..];
+        }
+
+        Ok(Message {
+            template,
+            placeholders,
+        })
+    }
+}
+
+/// The placeholders which may appear in the arguments of the `oracle` option.
+const ORACLE_PLACEHOLDERS: &[&str] = &["input_file", "input"];
+
+/// The `oracle` option: a command, split into its program and arguments at whitespace,
+/// with some of `ORACLE_PLACEHOLDERS` in its arguments.
+#[derive(Clone)]
+pub(crate) struct Oracle {
+    pub(crate) command: syn::LitStr,
+    pub(crate) args: Vec<String>,
+}
+
+impl Parse for Oracle {
+    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
+        let command: syn::LitStr = input.parse()?;
+        let value = command.value();
+        let error = |e: String| syn::Error::new(command.span(), e);
+
+        let args: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+        if args.is_empty() {
+            return Err(error(String::from("`oracle` needs a command")));
+        }
+        for arg in &args[1..] {
+            let mut rest = &**arg;
+            while let Some(ix) = rest.find('{') {
+                let after = &rest[ix + 1..];
+                let end = after
+                    .find('}')
+                    .ok_or_else(|| error(String::from("unclosed `{` in `oracle`")))?;
+                let name = &after[..end];
+                if !ORACLE_PLACEHOLDERS.contains(&name) {
+                    return Err(error(format!(
+                        "unknown placeholder `{{{}}}` in `oracle`; expected `{{input_file}}` or `{{input}}`",
+                        name,
+                    )));
+                }
+                rest = &after[end + 1..];
+            }
+        }
+
+        Ok(Oracle { command, args })
+    }
+}
+
+/// Parse `= (expr, ...)`, the extra arguments of `de_args` or `ser_args`.
+fn parse_args(input: syn::parse::ParseStream<'_>) -> syn::parse::Result<Vec<syn::Expr>> {
+    let _: syn::Token![=] = input.parse()?;
+    let content;
+    syn::parenthesized!(content in input);
+    let args = content.parse_terminated::<_, syn::Token![,]>(syn::Expr::parse)?;
+    Ok(args.into_iter().collect())
+}
+
+impl Parse for Options {
+    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
+        use syn::ext::IdentExt;
+
+        let mut options = Options::default();
+        let mut retry_span = None;
+        while !input.is_empty() {
+            let _: syn::Token![,] = input.parse()?;
+            if input.is_empty() {
+                break;
+            }
+
+            let name = input.call(syn::Ident::parse_any)?;
+            match &*name.to_string() {
+                "dedent" => {
+                    let limit = if input.peek(syn::Token![=]) {
+                        let _: syn::Token![=] = input.parse()?;
+                        let limit: syn::LitInt = input.parse()?;
+                        Some(limit.base10_parse()?)
+                    } else {
+                        None
+                    };
+                    options.dedent = Some(limit);
+                }
+                "de_infallible" => options.de_infallible = true,
+                "de_option" => options.de_option = true,
+                "ser_infallible" => options.ser_infallible = true,
+                "de_args" => options.de_args = parse_args(input)?,
+                "ser_args" => options.ser_args = parse_args(input)?,
+                "raw" => options.raw = true,
+                "literal_expected" => options.literal_expected = true,
+                "trusted_goldens" => options.trusted_goldens = true,
+                "template" => options.template = true,
+                "base64" => options.base64 = true,
+                "strip_ansi" => options.strip_ansi = true,
+                "sorted_lines" => options.sorted_lines = true,
+                "strict_prolog" => options.strict_prolog = true,
+                "strip_bom" => options.strip_bom = true,
+                "normalize_newlines" => options.normalize_newlines = true,
+                "skip_if_missing" => options.skip_if_missing = true,
+                "always" => options.always = true,
+                "unordered_arrays" => options.unordered_arrays = true,
+                "loose_scalars" => options.loose_scalars = true,
+                "sort_keys" => options.sort_keys = true,
+                "ignore_keys" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let content;
+                    let brackets = syn::bracketed!(content in input);
+                    let keys = content
+                        .parse_terminated::<_, syn::Token![,]>(<syn::LitStr as Parse>::parse)?;
+                    if keys.is_empty() {
+                        return Err(syn::Error::new(brackets.span, "`ignore_keys` needs a key"));
+                    }
+                    options.ignore_keys = keys.into_iter().collect();
+                }
+                "require_meta" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let content;
+                    let brackets = syn::bracketed!(content in input);
+                    let keys = content
+                        .parse_terminated::<_, syn::Token![,]>(<syn::LitStr as Parse>::parse)?;
+                    if keys.is_empty() {
+                        return Err(syn::Error::new(brackets.span, "`require_meta` needs a key"));
+                    }
+                    options.require_meta = keys.into_iter().collect();
+                }
+                "exclude" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let content;
+                    let brackets = syn::bracketed!(content in input);
+                    let names = content
+                        .parse_terminated::<_, syn::Token![,]>(<syn::LitStr as Parse>::parse)?;
+                    if names.is_empty() {
+                        return Err(syn::Error::new(
+                            brackets.span,
+                            "`exclude` needs a case name",
+                        ));
+                    }
+                    options.exclude = names.into_iter().collect();
+                }
+                "deterministic" => options.deterministic = true,
+                "fixpoint" => options.fixpoint = true,
+                "fixpoint_max" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let max: syn::LitInt = input.parse()?;
+                    let fixpoint_max = max.base10_parse()?;
+                    if fixpoint_max == 0 {
+                        return Err(syn::Error::new(
+                            max.span(),
+                            "`fixpoint_max` must be at least 1",
+                        ));
+                    }
+                    options.fixpoint_max = Some((fixpoint_max, max.span()));
+                }
+                "ser_stable" => options.ser_stable = true,
+                "clone_stable" => options.clone_stable = true,
+                "stream" => options.stream = true,
+                "batch" => options.batch = true,
+                "name_includes_hash" => options.name_includes_hash = true,
+                "allow_identical" => options.allow_identical = true,
+                "allow_trailing_content" => options.allow_trailing_content = true,
+                "strict_format" => options.strict_format = true,
+                "lenient_terminator" => options.lenient_terminator = true,
+                "forbid_empty" => options.forbid_empty = true,
+                "doc_examples" => options.doc_examples = true,
+                "verbose" => options.verbose = true,
+                "aggregate" => options.aggregate = true,
+                "index" => options.index = true,
+                "snapshots" => options.snapshots = true,
+                "allow_outside" => options.allow_outside = true,
+                "bench" => options.bench = true,
+                "wasm" => options.wasm = true,
+                "mod_name" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let name: syn::Ident = input.parse()?;
+                    // the name is also a prefix, of `CONTENTS` and the like
+                    if name.to_string().starts_with("r#") {
+                        return Err(syn::Error::new(
+                            name.span(),
+                            "`mod_name` can't be a raw identifier",
+                        ));
+                    }
+                    options.mod_name = Some(name);
+                }
+                "allow_similar_names" => options.allow_similar_names = true,
+                "dedup" => options.dedup = true,
+                "sort_cases" => options.sort_cases = true,
+                "split_includes" => options.split_includes = true,
+                "check_consistency" => options.check_consistency = true,
+                "generated_file" => options.generated_file = true,
+                "count_total" => options.count_total = true,
+                "single" => options.single = true,
+                "retry" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let retry: syn::LitInt = input.parse()?;
+                    options.retry = retry.base10_parse()?;
+                    if options.retry == 0 {
+                        return Err(syn::Error::new(retry.span(), "`retry` must be at least 1"));
+                    }
+                    retry_span = Some(retry.span());
+                }
+                "message" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.message = Some(input.parse()?);
+                }
+                "direction" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let direction: syn::Ident = input.parse()?;
+                    options.direction = match &*direction.to_string() {
+                        "deserialize" => Direction::Deserialize,
+                        "serialize" => Direction::Serialize,
+                        _ => {
+                            return Err(syn::Error::new(
+                                direction.span(),
+                                "expected `direction = deserialize` or `direction = serialize`",
+                            ))
+                        }
+                    };
+                }
+                "ci" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let ci: syn::Ident = input.parse()?;
+                    options.ci = match &*ci.to_string() {
+                        "github" => Ci::Github,
+                        "gitlab" => Ci::Gitlab,
+                        "none" => Ci::None,
+                        _ => {
+                            return Err(syn::Error::new(
+                                ci.span(),
+                                "expected `ci = github`, `ci = gitlab`, or `ci = none`",
+                            ))
+                        }
+                    };
+                }
+                "format" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let format: syn::Ident = input.parse()?;
+                    options.file_format = match &*format.to_string() {
+                        "csv" => FileFormat::Csv,
+                        "tsv" => FileFormat::Tsv,
+                        "jsonl" => FileFormat::Jsonl,
+                        _ => {
+                            return Err(syn::Error::new(
+                                format.span(),
+                                "expected `format = csv`, `format = tsv`, or `format = jsonl`",
+                            ))
+                        }
+                    };
+                }
+                "relative_to" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let base: syn::Ident = input.parse()?;
+                    options.relative_to_source = match &*base.to_string() {
+                        "source" => true,
+                        "manifest" => false,
+                        _ => {
+                            return Err(syn::Error::new(
+                                base.span(),
+                                "expected `relative_to = source` or `relative_to = manifest`",
+                            ))
+                        }
+                    };
+                }
+                "expect_cases" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let expect_cases: syn::LitInt = input.parse()?;
+                    options.expect_cases = Some(expect_cases.base10_parse()?);
+                }
+                "tolerance" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let minus: Option<syn::Token![-]> = input.parse()?;
+                    let tolerance: syn::LitFloat = input.parse()?;
+                    let bound: f64 = tolerance.base10_parse()?;
+                    if minus.is_some() || !bound.is_finite() {
+                        return Err(syn::Error::new(
+                            tolerance.span(),
+                            "`tolerance` must be a finite, non-negative float",
+                        ));
+                    }
+                    options.tolerance = Some(bound);
+                }
+                "warn_over" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let warn_over: syn::LitInt = input.parse()?;
+                    options.warn_over = Some(warn_over.base10_parse()?);
+                }
+                "type" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.r#type = Some(input.parse()?);
+                }
+                "value_cmp" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.value_cmp = Some(input.parse()?);
+                }
+                "tokenize" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.tokenize = Some(input.parse()?);
+                }
+                "value_mut" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.value_mut = Some(input.parse()?);
+                }
+                "predicate" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.predicate = Some(input.parse()?);
+                }
+                "ser_writer" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.ser_writer = Some(input.parse()?);
+                }
+                "utf8" => options.utf8 = true,
+                "allow_invalid_utf8" => options.allow_invalid_utf8 = true,
+                "canon_ser" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.canon_ser = Some(input.parse()?);
+                }
+                "canon_de" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.canon_de = Some(input.parse()?);
+                }
+                "de_ref" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.de_ref = Some(input.parse()?);
+                }
+                "ident_prefix" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let prefix: syn::LitStr = input.parse()?;
+                    // a prefix followed by a digit must still be an identifier
+                    if syn::parse_str::<syn::Ident>(&format!("{}0", prefix.value())).is_err() {
+                        return Err(syn::Error::new(
+                            prefix.span(),
+                            "`ident_prefix` must start an identifier, as `case_` does",
+                        ));
+                    }
+                    options.ident_prefix = Some(prefix.value());
+                }
+                "error_kind" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.error_kind = Some(input.parse()?);
+                }
+                "error_pos" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.error_pos = Some(input.parse()?);
+                }
+                "error_ser" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.error_ser = Some(input.parse()?);
+                }
+                "runtime" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.runtime = Some(input.parse()?);
+                }
+                "ser_b" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.ser_b = Some(input.parse()?);
+                }
+                "de_b" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.de_b = Some(input.parse()?);
+                }
+                "incomplete" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.incomplete = Some(input.parse()?);
+                }
+                "warnings" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.warnings = Some(input.parse()?);
+                }
+                "consume_all" => options.consume_all = true,
+                "require_cases" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.require_cases = Some(input.parse()?);
+                }
+                "warn_extra_cases" => options.warn_extra_cases = true,
+                "remaining" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.remaining = Some(input.parse()?);
+                }
+                "seed" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.seed = Some(input.parse()?);
+                }
+                "before_each" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.before_each = Some(input.parse()?);
+                }
+                "after_each" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.after_each = Some(input.parse()?);
+                }
+                "extension" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let extension: syn::LitStr = input.parse()?;
+                    options.extension = Some(extension.value());
+                }
+                "json" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.json = Some(input.parse()?);
+                }
+                "max_diff_lines" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let max: syn::LitInt = input.parse()?;
+                    let max_diff_lines = max.base10_parse()?;
+                    if max_diff_lines == 0 {
+                        return Err(syn::Error::new(
+                            max.span(),
+                            "`max_diff_lines` must be at least 1",
+                        ));
+                    }
+                    options.max_diff_lines = Some(max_diff_lines);
+                }
+                "ignore_unless_feature" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.ignore_unless_feature = Some(input.parse()?);
+                }
+                "max_bytes" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let max: syn::LitInt = input.parse()?;
+                    options.max_bytes = Some(max.base10_parse()?);
+                }
+                "normalize" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.normalize = Some(input.parse()?);
+                }
+                "oracle" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.oracle = Some(input.parse()?);
+                }
+                "preprocess" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.preprocess = Some(input.parse()?);
+                }
+                "transform_turbofish" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    options.transform_turbofish = Some(input.parse()?);
+                }
+                "types" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let content;
+                    let brackets = syn::bracketed!(content in input);
+                    let types = content
+                        .parse_terminated::<_, syn::Token![,]>(<syn::Type as Parse>::parse)?;
+                    if types.is_empty() {
+                        return Err(syn::Error::new(brackets.span, "`types` needs a type"));
+                    }
+                    options.types = types.into_iter().collect();
+                }
+                "project" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let pointer: syn::LitStr = input.parse()?;
+                    let value = pointer.value();
+                    if !value.is_empty() && !value.starts_with('/') {
+                        return Err(syn::Error::new(
+                            pointer.span(),
+                            "a JSON Pointer must be empty or start with `/`",
+                        ));
+                    }
+                    options.project = Some(pointer);
+                }
+                "jq" => {
+                    let _: syn::Token![=] = input.parse()?;
+                    let program: syn::LitStr = input.parse()?;
+                    if program.value().trim().is_empty() {
+                        return Err(syn::Error::new(program.span(), "`jq` needs a program"));
+                    }
+                    options.jq = Some(program);
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        format!("unknown option `{}`", name),
+                    ))
+                }
+            }
+        }
+        if let (Some(span), Some(_)) = (retry_span, &options.predicate) {
+            return Err(syn::Error::new(
+                span,
+                "`retry` compares outputs, so it can't be used with `predicate`",
+            ));
+        }
+        Ok(options)
+    }
+}
+
+/// A mode or option another can't be used with, as its restriction names it.
+enum Setting {
+    Mode(Mode),
+    /// Any of the structural modes, named as the mode given.
+    Structural,
+    /// Either binary mode, named as the mode given.
+    Binary,
+    /// An option, by its name, or where the tests are read from, by its key.
+    Named(&'static str),
+    /// The setting, with the reason it conflicts.
+    As(&'static Setting, &'static str),
+}
+
+impl Setting {
+    /// Where the setting is given, if it is, in `options` read from `source`.
+    fn given(&self, options: &Options, source: Option<&Source>) -> Option<Span> {
+        let flag = |given: bool| if given { Some(Span::call_site()) } else { None };
+        let path = |path: &Option<syn::ExprPath>| path.as_ref().map(Spanned::span);
+        match *self {
+            Setting::Mode(named) => flag(options.mode == named),
+            Setting::Structural => flag(options.mode.is_structural()),
+            Setting::Binary => flag(options.mode.is_binary()),
+            Setting::As(setting, _) => setting.given(options, source),
+            Setting::Named(name) => match name {
+                "dir" => match source {
+                    Some(Source::Dir(dir)) => Some(dir.span()),
+                    _ => None,
+                },
+                "cases" => match source {
+                    Some(Source::Cases { name, .. }) => Some(name.span()),
+                    _ => None,
+                },
+                "input_dir" => match source {
+                    Some(Source::Pairs { input_dir, .. }) => Some(input_dir.span()),
+                    _ => None,
+                },
+                "aggregate" => flag(options.aggregate),
+                "allow_invalid_utf8" => flag(options.allow_invalid_utf8),
+                "base64" => flag(options.base64),
+                "batch" => flag(options.batch),
+                "bench" => flag(options.bench),
+                // either of the pair
+                "canon" => path(&options.canon_ser).or_else(|| path(&options.canon_de)),
+                "ci" => flag(options.ci != Ci::None),
+                "de_args" => flag(!options.de_args.is_empty()),
+                "de_infallible" => flag(options.de_infallible),
+                "de_option" => flag(options.de_option),
+                "direction = serialize" => flag(options.direction == Direction::Serialize),
+                "doc_examples" => flag(options.doc_examples),
+                "error_kind" => path(&options.error_kind),
+                "error_pos" => path(&options.error_pos),
+                "error_ser" => path(&options.error_ser),
+                "fixpoint" => flag(options.fixpoint),
+                "format" => flag(options.file_format != FileFormat::Native),
+                "ignore_keys" => options.ignore_keys.first().map(syn::LitStr::span),
+                "incomplete" => path(&options.incomplete),
+                "index" => flag(options.index),
+                "jq" => options.jq.as_ref().map(syn::LitStr::span),
+                "literal_expected" => flag(options.literal_expected),
+                "loose_scalars" => flag(options.loose_scalars),
+                "max_diff_lines" => flag(options.max_diff_lines.is_some()),
+                "message" => flag(options.message.is_some()),
+                "oracle" => options.oracle.as_ref().map(|oracle| oracle.command.span()),
+                "predicate" => path(&options.predicate),
+                "project" => options.project.as_ref().map(syn::LitStr::span),
+                "retry" => flag(options.retry > 0),
+                "seed" => options.seed.as_ref().map(Spanned::span),
+                // both of the pair, which are only given together
+                "ser_b" => path(&options.de_b).filter(|_| options.ser_b.is_some()),
+                "single" => flag(options.single),
+                "skip_if_missing" => flag(options.skip_if_missing),
+                "snapshots" => flag(options.snapshots),
+                "sort_keys" => flag(options.sort_keys),
+                "sorted_lines" => flag(options.sorted_lines),
+                "stream" => flag(options.stream),
+                "strict_prolog" => flag(options.strict_prolog),
+                "template" => flag(options.template),
+                "tokenize" => path(&options.tokenize),
+                "tolerance" => flag(options.tolerance.is_some()),
+                "transform_turbofish" => options.transform_turbofish.as_ref().map(Spanned::span),
+                "trusted_goldens" => flag(options.trusted_goldens),
+                "types" => options.types.first().map(Spanned::span),
+                "unordered_arrays" => flag(options.unordered_arrays),
+                "utf8" => flag(options.utf8),
+                "value_cmp" => path(&options.value_cmp),
+                "value_mut" => path(&options.value_mut),
+                "verbose" => flag(options.verbose),
+                "wasm" => flag(options.wasm),
+                _ => unreachable!("no setting `{}`", name),
+            },
+        }
+    }
+
+    /// The setting as errors name it, when the tests are in `mode`.
+    fn name(&self, mode: Mode) -> String {
+        match self {
+            Setting::Mode(named) => format!("`{}` mode", named.name()),
+            Setting::Structural | Setting::Binary => format!("`{}` mode", mode.name()),
+            Setting::Named(name) => format!("`{}`", name),
+            Setting::As(setting, reason) => format!("{}, {}", setting.name(mode), reason),
+        }
+    }
+}
+
+/// Where a mode, an option, or a test's directive can be used.
+pub(crate) struct Restriction {
+    /// The only modes it can be used in, or empty if it can be used in any.
+    only_in: &'static [Mode],
+    /// The settings it can't be used with, the first of which given is reported.
+    without: &'static [Setting],
+}
+
+impl Restriction {
+    /// How `options`, read from `source`, break the restriction, if they do: the setting
+    /// given which it can't be used with, or the only modes it can be used in, and without what.
+    pub(crate) fn broken(&self, options: &Options, source: Option<&Source>) -> Option<String> {
+        let mut given = self
+            .without
+            .iter()
+            .filter(|setting| setting.given(options, source).is_some());
+        if self.only_in.is_empty() {
+            return given.next().map(|setting| setting.name(options.mode));
+        }
+        if self.only_in.contains(&options.mode) && given.next().is_none() {
+            return None;
+        }
+        let modes: Vec<_> = self
+            .only_in
+            .iter()
+            .map(|mode| format!("`{}`", mode.name()))
+            .collect();
+        let mut broken = format!("{} mode", modes.join(" or "));
+        if let Some((last, rest)) = self.without.split_last() {
+            let rest: Vec<_> = rest
+                .iter()
+                .map(|setting| setting.name(options.mode))
+                .collect();
+            broken.push_str(" without ");
+            if !rest.is_empty() {
+                broken.push_str(&rest.join(", "));
+                broken.push_str(" or ");
+            }
+            broken.push_str(&last.name(options.mode));
+        }
+        Some(broken)
+    }
+}
+
+/// What modes and options can't be used with, checked in order: what is restricted,
+/// the error explaining why, which the first setting breaking the restriction finishes,
+/// and the restriction.
+const RESTRICTIONS: &[(Setting, &str, Restriction)] = {
+    use Setting::{As, Binary, Named, Structural};
+    const fn without(without: &'static [Setting]) -> Restriction {
+        Restriction {
+            only_in: &[],
+            without,
+        }
+    }
+    const fn only_in(only_in: &'static [Mode], without: &'static [Setting]) -> Restriction {
+        Restriction { only_in, without }
+    }
+    &[
+        (
+            Named("doc_examples"),
+            "`doc_examples` documents the cases of one file, so it can't be used with",
+            without(&[Named("dir")]),
+        ),
+        (
+            Named("bench"),
+            "`bench` registers the cases of one file as benchmarks, so it can't be used with",
+            without(&[Named("dir"), Named("doc_examples"), Named("oracle")]),
+        ),
+        (
+            Named("wasm"),
+            "`wasm` tests run without files, processes, environment variables, stderr, \
+             or unwinding, so it can't be used with",
+            without(&[
+                Named("snapshots"),
+                Named("oracle"),
+                Named("bench"),
+                Named("ci"),
+                Named("verbose"),
+                Named("index"),
+                Named("aggregate"),
+            ]),
+        ),
+        (
+            Named("cases"),
+            "`cases` gives the tests in the attribute rather than a file, \
+             so it can't be used with",
+            without(&[Named("single"), Named("format"), Named("skip_if_missing")]),
+        ),
+        (
+            Named("input_dir"),
+            "`input_dir` pairs each input file with an output file, so it can't be used with",
+            without(&[
+                Setting::Mode(Mode::Idempotent),
+                Named("doc_examples"),
+                Named("single"),
+                Named("format"),
+            ]),
+        ),
+        (
+            Named("seed"),
+            "`seed` deserializes with the `Deserializer` of the `serde` format, \
+             so it can't be used with",
+            without(&[Named("de_args"), Named("de_infallible"), Named("de_option")]),
+        ),
+        (
+            Named("canon"),
+            "`canon_ser` and `canon_de` normalize the expected output, which isn't done with",
+            without(&[
+                Setting::Mode(Mode::Regex),
+                Structural,
+                Named("predicate"),
+                Named("oracle"),
+                Named("literal_expected"),
+                Named("trusted_goldens"),
+                Named("direction = serialize"),
+                Named("stream"),
+            ]),
+        ),
+        (
+            Named("jq"),
+            "`jq` compares what its program gives with the output as written, \
+             so it can't be used with",
+            without(&[
+                Setting::Mode(Mode::Idempotent),
+                Named("oracle"),
+                Named("project"),
+                Named("ignore_keys"),
+                Named("unordered_arrays"),
+                Named("loose_scalars"),
+                Named("sort_keys"),
+                Named("value_mut"),
+            ]),
+        ),
+        (
+            Named("snapshots"),
+            "`snapshots` records the output of cases without one, so it can't be used with",
+            without(&[
+                Setting::Mode(Mode::Regex),
+                Setting::Mode(Mode::Idempotent),
+                Named("predicate"),
+                Named("oracle"),
+            ]),
+        ),
+        (
+            Named("template"),
+            "`template` compares the outputs with a template as written, \
+             so it can't be used with",
+            without(&[
+                Setting::Mode(Mode::Idempotent),
+                Setting::Mode(Mode::Regex),
+                Structural,
+                Named("predicate"),
+                Named("oracle"),
+                Named("value_cmp"),
+                Named("message"),
+                Named("project"),
+                Named("ignore_keys"),
+                Named("unordered_arrays"),
+                Named("loose_scalars"),
+                Named("value_mut"),
+            ]),
+        ),
+        (
+            Named("value_cmp"),
+            "`value_cmp` decides whether the outputs match, so it can't be used with",
+            without(&[
+                Setting::Mode(Mode::Regex),
+                Structural,
+                Named("predicate"),
+                Named("retry"),
+            ]),
+        ),
+        (
+            Named("tokenize"),
+            "`tokenize` compares the outputs as token sequences, so it can't be used with",
+            without(&[
+                Setting::Mode(Mode::Regex),
+                Structural,
+                Named("predicate"),
+                Named("template"),
+                Named("value_cmp"),
+                Named("message"),
+                Named("max_diff_lines"),
+                Named("retry"),
+            ]),
+        ),
+        (
+            Named("error_kind"),
+            "`error_kind` classifies the errors of `de`, so it can't be used with",
+            without(ERRORS_OF_DE),
+        ),
+        (
+            Named("error_pos"),
+            "`error_pos` locates the errors of `de`, so it can't be used with",
+            without(ERRORS_OF_DE),
+        ),
+        (
+            Named("error_ser"),
+            "`error_ser` serializes the errors of `de`, so it can't be used with",
+            without(ERRORS_OF_DE),
+        ),
+        (
+            Named("stream"),
+            "`stream` collects the values `de` returns into a `Vec`, so it can't be used with",
+            without(&[
+                Named("seed"),
+                Named("de_option"),
+                Named("direction = serialize"),
+                As(
+                    &Setting::Mode(Mode::Idempotent),
+                    "as the input isn't a serialized array",
+                ),
+            ]),
+        ),
+        (
+            Named("batch"),
+            "`batch` passes the values of each input's sub-inputs to the tested function \
+             together, so it can't be used with",
+            without(&[
+                Named("stream"),
+                Named("seed"),
+                Named("incomplete"),
+                Named("direction = serialize"),
+                As(
+                    &Setting::Mode(Mode::Idempotent),
+                    "as the input isn't a serialized output",
+                ),
+            ]),
+        ),
+        (
+            Setting::Mode(Mode::Msgpack),
+            "`msgpack` mode compares the outputs as MessagePack values, so it can't be used with",
+            without(STRUCTURAL),
+        ),
+        (
+            Setting::Mode(Mode::Cbor),
+            "`cbor` mode compares the outputs as CBOR values, so it can't be used with",
+            without(STRUCTURAL),
+        ),
+        (
+            Setting::Mode(Mode::Xml),
+            "`xml` mode compares the outputs as XML trees, so it can't be used with",
+            without(STRUCTURAL),
+        ),
+        (
+            Setting::Mode(Mode::Approx),
+            "`approx` mode compares the outputs as JSON values, so it can't be used with",
+            without(STRUCTURAL),
+        ),
+        (
+            Setting::Mode(Mode::YamlToplevelUnordered),
+            "`yaml_toplevel_unordered` mode compares the outputs as YAML values, \
+             so it can't be used with",
+            without(STRUCTURAL),
+        ),
+        (
+            Named("tolerance"),
+            "`tolerance` bounds how far apart the numbers of `approx` mode may be, \
+             so it can only be used in",
+            only_in(&[Mode::Approx], &[]),
+        ),
+        (
+            Named("types"),
+            "`types` gives the generic argument of each module's tested function, \
+             so it can't be used with",
+            without(&[Named("transform_turbofish")]),
+        ),
+        (
+            Named("strict_prolog"),
+            "`strict_prolog` compares the XML declarations of the outputs, \
+             so it can only be used in",
+            only_in(&[Mode::Xml], &[]),
+        ),
+        (
+            Named("base64"),
+            "`base64` tells how the outputs of `msgpack` and `cbor` modes are written, \
+             so it can only be used in",
+            only_in(&[Mode::Msgpack, Mode::Cbor], &[]),
+        ),
+        (
+            Named("ser_b"),
+            "`ser_b` and `de_b` make a round trip through a second format of the value \
+             of each input, so they can't be used with",
+            without(&[Named("stream"), Named("batch"), Named("seed")]),
+        ),
+        (
+            Named("fixpoint"),
+            "`fixpoint` re-parses the serialized output with `de`, so it can't be used with",
+            without(&[Binary, Named("stream"), Named("batch")]),
+        ),
+        (
+            Named("sorted_lines"),
+            "`sorted_lines` compares the outputs as sets of lines, so it can't be used in",
+            without(&[Setting::Mode(Mode::Regex), Structural]),
+        ),
+        (
+            Named("utf8"),
+            "`utf8` fails output which isn't UTF-8, so it can't be used with",
+            without(&[Named("allow_invalid_utf8")]),
+        ),
+        (
+            Named("oracle"),
+            "`oracle` gives the expected output, so it can only be used in",
+            only_in(&[Mode::Exact], &[Named("predicate")]),
+        ),
+    ]
+};
+
+/// What the structural modes, comparing decoded outputs, can't be used with.
+const STRUCTURAL: &[Setting] = &[
+    Setting::Named("predicate"),
+    Setting::Named("jq"),
+    Setting::Named("stream"),
+];
+
+/// What the options handling the errors of `de` can't be used with.
+const ERRORS_OF_DE: &[Setting] = &[
+    Setting::As(&Setting::Named("de_option"), "as `None` has no error"),
+    Setting::As(&Setting::Named("de_infallible"), "as there are no errors"),
+];
+
+/// Where the directives of a test which other modes or options leave nothing to check can be
+/// used: the directive, as the error names it, and its restriction.
+pub(crate) const DIRECTIVE_RESTRICTIONS: [(&str, Restriction); 3] = [
+    // each mode calls the `exact` testing function, only choosing what it's given
+    (
+        "has `@modes`",
+        Restriction {
+            only_in: &[Mode::Exact],
+            without: &[Setting::Named("predicate"), Setting::Named("oracle")],
+        },
+    ),
+    (
+        "has `@tolerance`",
+        Restriction {
+            only_in: &[Mode::Approx],
+            without: &[],
+        },
+    ),
+    (
+        "is `@expr`",
+        Restriction {
+            only_in: &[Mode::Exact],
+            without: &[
+                Setting::Named("predicate"),
+                Setting::Named("oracle"),
+                Setting::Named("jq"),
+            ],
+        },
+    ),
+];
+
+impl Parse for AttrArgs {
+    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
+        mod kw {
+            syn::custom_keyword!(exact);
+            syn::custom_keyword!(idempotent);
+            syn::custom_keyword!(regex);
+            syn::custom_keyword!(msgpack);
+            syn::custom_keyword!(cbor);
+            syn::custom_keyword!(xml);
+            syn::custom_keyword!(approx);
+            syn::custom_keyword!(yaml_toplevel_unordered);
+            syn::custom_keyword!(file);
+            syn::custom_keyword!(dir);
+            syn::custom_keyword!(input_dir);
+            syn::custom_keyword!(output_dir);
+            syn::custom_keyword!(cases);
+            syn::custom_keyword!(ser);
+            syn::custom_keyword!(de);
+            syn::custom_keyword!(ser_a);
+            syn::custom_keyword!(de_a);
+            syn::custom_keyword!(value);
+            syn::custom_keyword!(serde);
+        }
+
+        // TODO: add `superset` mode where actual is "at least" expected
+        let mode_span = input.cursor().span();
+        let la = input.lookahead1();
+        let mode = if la.peek(kw::exact) {
+            let _: kw::exact = input.parse()?;
+            Mode::Exact
+        } else if la.peek(kw::idempotent) {
+            let _: kw::idempotent = input.parse()?;
+            Mode::Idempotent
+        } else if la.peek(kw::regex) {
+            let _: kw::regex = input.parse()?;
+            Mode::Regex
+        } else if la.peek(kw::msgpack) {
+            let _: kw::msgpack = input.parse()?;
+            Mode::Msgpack
+        } else if la.peek(kw::cbor) {
+            let _: kw::cbor = input.parse()?;
+            Mode::Cbor
+        } else if la.peek(kw::xml) {
+            let _: kw::xml = input.parse()?;
+            Mode::Xml
+        } else if la.peek(kw::approx) {
+            let _: kw::approx = input.parse()?;
+            Mode::Approx
+        } else if la.peek(kw::yaml_toplevel_unordered) {
+            let _: kw::yaml_toplevel_unordered = input.parse()?;
+            Mode::YamlToplevelUnordered
+        } else {
+            return Err(la.error());
+        };
+        let _: syn::Token![,] = input.parse()?;
+
+        let la = input.lookahead1();
+        // the span of `ser_a`, if the first of two formats was named as such
+        let mut pair_a = None;
+        let (ser, de, value, format) = if la.peek(kw::serde) {
+            let _: kw::serde = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            let format: syn::ExprPath = input.parse()?;
+            let _: syn::Token![,] = input.parse()?;
+
+            // FUTURE(rust-lang/rust#64797): use #[cfg(accessible)] to prefer `to_string_pretty`
+            // as well as fall back to the `::ser::to_string`/`::de::from_str`/`::value::Value`
+
+            // for errors
+            let la = input.lookahead1();
+            if !(la.peek(kw::ser)
+                || la.peek(kw::de)
+                || la.peek(kw::value)
+                    | la.peek(kw::file)
+                    | la.peek(kw::dir)
+                    | la.peek(kw::input_dir)
+                    | la.peek(kw::cases))
+            {
+                return Err(la.error());
+            }
+
+            let ser: syn::ExprPath = if input.peek(kw::ser) {
+                let _: kw::ser = input.parse()?;
+                let _: syn::Token![=] = input.parse()?;
+                let ser: syn::ExprPath = input.parse()?;
+                let _: syn::Token![,] = input.parse()?;
+
+                // for errors
+                let la = input.lookahead1();
+                if !(la.peek(kw::de)
+                    || la.peek(kw::value)
+                        | la.peek(kw::file)
+                        | la.peek(kw::dir)
+                        | la.peek(kw::input_dir)
+                        | la.peek(kw::cases))
+                {
+                    return Err(la.error());
+                }
+
+                ser
+            } else {
+                syn::parse_quote!(#format::to_string)
+            };
+
+            let de: syn::ExprPath = if input.peek(kw::de) {
+                let _: kw::de = input.parse()?;
+                let _: syn::Token![=] = input.parse()?;
+                let de: syn::ExprPath = input.parse()?;
+                let _: syn::Token![,] = input.parse()?;
+
+                // for errors
+                let la = input.lookahead1();
+                if !(la.peek(kw::value)
+                    | la.peek(kw::file)
+                    | la.peek(kw::dir)
+                    | la.peek(kw::input_dir)
+                    | la.peek(kw::cases))
+                {
+                    return Err(la.error());
+                }
+
+                de
+            } else {
+                syn::parse_quote!(#format::from_str)
+            };
+
+            let value: syn::Type = if input.peek(kw::value) {
+                let _: kw::value = input.parse()?;
+                let _: syn::Token![=] = input.parse()?;
+                let value: syn::Type = input.parse()?;
+                let _: syn::Token![,] = input.parse()?;
+                value
+            } else {
+                syn::parse_quote!(#format::Value)
+            };
+
+            (ser, de, Some(value), Some(format))
+        } else if la.peek(kw::ser) {
+            let _: kw::ser = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            let ser: syn::ExprPath = input.parse()?;
+            let _: syn::Token![,] = input.parse()?;
+
+            let _: kw::de = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            let de: syn::ExprPath = input.parse()?;
+            let _: syn::Token![,] = input.parse()?;
+
+            let value = if input.peek(kw::value) {
+                let _: kw::value = input.parse()?;
+                let _: syn::Token![=] = input.parse()?;
+                let value: syn::Type = input.parse()?;
+                let _: syn::Token![,] = input.parse()?;
+                Some(value)
+            } else {
+                None
+            };
+
+            (ser, de, value, None)
+        } else if la.peek(kw::ser_a) {
+            // `ser` and `de`, named as the first of the two formats of a round trip
+            let key: kw::ser_a = input.parse()?;
+            pair_a = Some(key.span);
+            let _: syn::Token![=] = input.parse()?;
+            let ser: syn::ExprPath = input.parse()?;
+            let _: syn::Token![,] = input.parse()?;
+
+            let _: kw::de_a = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            let de: syn::ExprPath = input.parse()?;
+            let _: syn::Token![,] = input.parse()?;
+
+            let value = if input.peek(kw::value) {
+                let _: kw::value = input.parse()?;
+                let _: syn::Token![=] = input.parse()?;
+                let value: syn::Type = input.parse()?;
+                let _: syn::Token![,] = input.parse()?;
+                Some(value)
+            } else {
+                None
+            };
+
+            (ser, de, value, None)
+        } else {
+            return Err(la.error());
+        };
+
+        let la = input.lookahead1();
+        let source = if la.peek(kw::file) {
+            let _: kw::file = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            Source::File(input.parse()?)
+        } else if la.peek(kw::dir) {
+            let _: kw::dir = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            Source::Dir(input.parse()?)
+        } else if la.peek(kw::input_dir) {
+            let _: kw::input_dir = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            let input_dir = input.parse()?;
+            let _: syn::Token![,] = input.parse()?;
+            let _: kw::output_dir = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            Source::Pairs {
+                input_dir,
+                output_dir: input.parse()?,
+            }
+        } else if la.peek(kw::cases) {
+            let key: kw::cases = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            let content;
+            syn::bracketed!(content in input);
+            let tuples = content.parse_terminated::<_, syn::Token![,]>(syn::Expr::parse)?;
+            let shape = if mode == Mode::Idempotent {
+                "`(name, input)`"
+            } else {
+                "`(name, input, expected)`"
+            };
+            let mut cases = Vec::with_capacity(tuples.len());
+            for tuple in tuples {
+                let strs: Option<Vec<_>> = match &tuple {
+                    syn::Expr::Tuple(tuple) => tuple
+                        .elems
+                        .iter()
+                        .map(|elem| match elem {
+                            syn::Expr::Lit(syn::ExprLit {
+                                lit: syn::Lit::Str(s),
+                                ..
+                            }) => Some(s.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => None,
+                };
+                let mut strs = match strs {
+                    Some(strs) if strs.len() == if mode == Mode::Idempotent { 2 } else { 3 } => {
+                        strs.into_iter()
+                    }
+                    _ => {
+                        return Err(syn::Error::new(
+                            tuple.span(),
+                            format!("each of `cases` is a tuple {} of string literals", shape),
+                        ))
+                    }
+                };
+                let name = strs.next().unwrap();
+                if name.value().trim().is_empty() {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        "a case of `cases` needs a name",
+                    ));
+                }
+                cases.push(InlineCase {
+                    name,
+                    input: strs.next().unwrap(),
+                    expected: strs.next(),
+                });
+            }
+            if cases.is_empty() {
+                return Err(syn::Error::new(key.span, "`cases` gives no cases"));
+            }
+            Source::Cases {
+                name: syn::LitStr::new("cases", key.span),
+                cases,
+            }
+        } else {
+            return Err(la.error());
+        };
+
+        let mut options: Options = input.parse()?;
+        options.mode = mode;
+        let env_mode = env::var("CONFORMANCE_MODE").ok();
+        if let Some(env_mode) = mode_from_env(env_mode.as_deref(), mode_span)? {
+            if env_mode != mode {
+                options.attribute_mode = Some((mode, mode_span));
+                options.mode = env_mode;
+            }
+        }
+        if let (Some(seed), None) = (&options.seed, &format) {
+            return Err(syn::Error::new(
+                seed.span(),
+                "`seed` deserializes with the `Deserializer` of the `serde` format, \
+                 so it can't be used with the `ser` and `de` arguments; use `serde`",
+            ));
+        }
+        for (subject, does, restriction) in RESTRICTIONS {
+            let span = match subject.given(&options, Some(&source)) {
+                Some(span) => span,
+                None => continue,
+            };
+            if let Some(broken) = restriction.broken(&options, Some(&source)) {
+                return Err(syn::Error::new(span, format!("{} {}", does, broken)));
+            }
+        }
+        if options.warn_extra_cases && options.require_cases.is_none() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`warn_extra_cases` warns of cases missing from `require_cases`, \
+                 so it can only be used with it",
+            ));
+        }
+        if let (Some(remaining), false) = (&options.remaining, options.consume_all) {
+            return Err(syn::Error::new(
+                remaining.span(),
+                "`remaining` is only called with `consume_all`",
+            ));
+        }
+        match (&options.ser_b, &options.de_b) {
+            (Some(path), None) | (None, Some(path)) => {
+                return Err(syn::Error::new(
+                    path.span(),
+                    "`ser_b` and `de_b` make a round trip through a second format together; \
+                     give both",
+                ));
+            }
+            (None, None) => {
+                if let Some(span) = pair_a {
+                    return Err(syn::Error::new(
+                        span,
+                        "`ser_a` and `de_a` name the first of two formats; give `ser_b` and \
+                         `de_b` for the second, or name the only format with `ser` and `de`",
+                    ));
+                }
+            }
+            (Some(_), Some(_)) => (),
+        }
+        if let (Some((_, span)), false) = (options.fixpoint_max, options.fixpoint) {
+            return Err(syn::Error::new(
+                span,
+                "`fixpoint_max` bounds the re-parses of `fixpoint`; give `fixpoint`",
+            ));
+        }
+        let utf8_option = match (options.utf8, options.allow_invalid_utf8) {
+            (true, _) => Some("utf8"),
+            (_, true) => Some("allow_invalid_utf8"),
+            _ => None,
+        };
+        if let (Some(option), None) = (utf8_option, &options.ser_writer) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!(
+                    "`{}` checks what `ser_writer` writes, as `ser` gives a `String`, \
+                     which is always UTF-8; give `ser_writer`",
+                    option
+                ),
+            ));
+        }
+
+        Ok(AttrArgs {
+            ser,
+            de,
+            value,
+            format,
+            source,
+            options,
+        })
+    }
+}
+
+/// The arguments of `conformance::validate!`: an optional mode, the test file,
+/// and the options it is read with.
+pub(crate) struct ValidateArgs {
+    pub(crate) path: syn::LitStr,
+    pub(crate) options: Options,
+}
+
+impl Parse for ValidateArgs {
+    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
+        mod kw {
+            syn::custom_keyword!(exact);
+            syn::custom_keyword!(idempotent);
+            syn::custom_keyword!(regex);
+            syn::custom_keyword!(msgpack);
+            syn::custom_keyword!(cbor);
+            syn::custom_keyword!(xml);
+            syn::custom_keyword!(approx);
+            syn::custom_keyword!(yaml_toplevel_unordered);
+        }
+
+        let mode = if input.peek(kw::exact) {
+            let _: kw::exact = input.parse()?;
+            Some(Mode::Exact)
+        } else if input.peek(kw::idempotent) {
+            let _: kw::idempotent = input.parse()?;
+            Some(Mode::Idempotent)
+        } else if input.peek(kw::regex) {
+            let _: kw::regex = input.parse()?;
+            Some(Mode::Regex)
+        } else if input.peek(kw::msgpack) {
+            let _: kw::msgpack = input.parse()?;
+            Some(Mode::Msgpack)
+        } else if input.peek(kw::cbor) {
+            let _: kw::cbor = input.parse()?;
+            Some(Mode::Cbor)
+        } else if input.peek(kw::xml) {
+            let _: kw::xml = input.parse()?;
+            Some(Mode::Xml)
+        } else if input.peek(kw::approx) {
+            let _: kw::approx = input.parse()?;
+            Some(Mode::Approx)
+        } else if input.peek(kw::yaml_toplevel_unordered) {
+            let _: kw::yaml_toplevel_unordered = input.parse()?;
+            Some(Mode::YamlToplevelUnordered)
+        } else {
+            None
+        };
+        if mode.is_some() {
+            let _: syn::Token![,] = input.parse()?;
+        }
+        let path = input.parse()?;
+        let mut options: Options = input.parse()?;
+        options.mode = mode.unwrap_or_default();
+        Ok(ValidateArgs { path, options })
+    }
+}
+
+/// The arguments of `conformance::check!`: those of the attribute, with the tested function
+/// and a single case in place of `file` or `dir`.
+pub(crate) struct CheckArgs {
+    pub(crate) args: AttrArgs,
+    pub(crate) fun: syn::Expr,
+    pub(crate) input: syn::Expr,
+    pub(crate) expected: Option<syn::Expr>,
+}
+
+impl Parse for CheckArgs {
+    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
+        use proc_macro2::TokenTree;
+
+        // the case takes the place of the source, so the rest parses as the attribute would
+        let tokens: TokenStream = input.parse()?;
+        let mut segments = vec![TokenStream::new()];
+        for tt in tokens {
+            match &tt {
+                TokenTree::Punct(punct) if punct.as_char() == ',' => {
+                    segments.push(TokenStream::new())
+                }
+                _ => segments.last_mut().unwrap().extend(Some(tt)),
+            }
+        }
+        let (mut fun, mut case_input, mut expected) = (None, None, None);
+        let mut rest = Vec::new();
+        let mut placed = false;
+        for segment in segments {
+            let mut tts = segment.clone().into_iter();
+            let key = match (tts.next(), tts.next()) {
+                (Some(TokenTree::Ident(key)), Some(TokenTree::Punct(eq)))
+                    if eq.as_char() == '=' =>
+                {
+                    key.to_string()
+                }
+                _ => String::new(),
+            };
+            let slot = match &*key {
+                "fn" => &mut fun,
+                "input" => &mut case_input,
+                "expected" => &mut expected,
+                _ => {
+                    rest.push(segment);
+                    continue;
+                }
+            };
+            if !placed {
+                rest.push(quote!(file = ""));
+                placed = true;
+            }
+            *slot = Some(syn::parse2::<syn::Expr>(tts.collect())?);
+        }
+        let args: AttrArgs = syn::parse2(quote!(#(#rest),*))?;
+
+        let missing = |what: &str| syn::Error::new(Span::call_site(), what);
+        let fun = fun.ok_or_else(|| missing("`check!` needs the tested function, as `fn = f`"))?;
+        let input = case_input.ok_or_else(|| missing("`check!` needs an `input = \"...\"`"))?;
+        if expected.is_none() && args.options.mode != Mode::Idempotent {
+            return Err(missing(
+                "`check!` needs an `expected = \"...\"`, except in `idempotent` mode",
+            ));
+        }
+        let unsupported = if args.value.is_none() {
+            Some("`check!` can't see the tested function's return type, so it needs a `value` type; use `serde` or give `value`")
+        } else if args.options.direction == Direction::Serialize {
+            Some("`check!` can't see the tested function's return type, so it can't use `direction = serialize`")
+        } else if args.options.message.is_some() {
+            Some("`check!` has no test file, so it can't use `message`")
+        } else {
+            None
+        };
+        match unsupported {
+            Some(e) => Err(missing(e)),
+            None => Ok(CheckArgs {
+                args,
+                fun,
+                input,
+                expected,
+            }),
+        }
+    }
+}
+
+/// The arguments of `conformance::include_tests!`: those of the attribute, in any order,
+/// with the `type` the tests are of.
+pub(crate) struct IncludeArgs {
+    pub(crate) args: AttrArgs,
+    pub(crate) r#type: syn::Type,
+}
+
+impl Parse for IncludeArgs {
+    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
+        use proc_macro2::TokenTree;
+
+        const MODES: &[&str] = &[
+            "exact",
+            "idempotent",
+            "regex",
+            "msgpack",
+            "cbor",
+            "xml",
+            "approx",
+            "yaml_toplevel_unordered",
+        ];
+        // the arguments the attribute takes in order, before the options
+        const LEADING: &[&[&str]] = &[
+            &["serde"],
+            &["ser", "ser_a"],
+            &["de", "de_a"],
+            &["value"],
+            &["file", "dir", "input_dir", "cases"],
+            &["output_dir"],
+        ];
+
+        // a comma starts an argument only if a flag or `key =` follows it,
+        // rather than the rest of a type such as `HashMap<K, V>`
+        let tokens: TokenStream = input.parse()?;
+        let mut segments: Vec<(String, TokenStream)> = Vec::new();
+        let mut current = TokenStream::new();
+        let mut tts = tokens.into_iter().peekable();
+        while let Some(tt) = tts.next() {
+            match &tt {
+                TokenTree::Punct(punct) if punct.as_char() == ',' => {
+                    segments.push((String::new(), std::mem::take(&mut current)))
+                }
+                _ => current.extend(Some(tt)),
+            }
+            if tts.peek().is_none() {
+                segments.push((String::new(), std::mem::take(&mut current)));
+            }
+        }
+        let mut args: Vec<(String, TokenStream)> = Vec::new();
+        for (_, segment) in segments {
+            let mut tts = segment.clone().into_iter();
+            let key = match (tts.next(), tts.next()) {
+                (Some(TokenTree::Ident(key)), None) => Some(key.to_string()),
+                (Some(TokenTree::Ident(key)), Some(TokenTree::Punct(eq)))
+                    if eq.as_char() == '=' =>
+                {
+                    Some(key.to_string())
+                }
+                (None, _) => continue,
+                _ => None,
+            };
+            match (key, args.last_mut()) {
+                (Some(key), _) => args.push((key, segment)),
+                (None, Some((_, previous))) => {
+                    previous.extend(quote!(,));
+                    previous.extend(segment);
+                }
+                (None, None) => {
+                    return Err(syn::Error::new(segment.span(), "expected an argument"))
+                }
+            }
+        }
+
+        let r#type = match args.iter().position(|(key, _)| key == "type") {
+            Some(ix) => {
+                let segment = args.remove(ix).1;
+                let mut tts = segment.into_iter();
+                let _ = (tts.next(), tts.next());
+                syn::parse2::<syn::Type>(tts.collect())?
+            }
+            None => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "`include_tests!` needs the type its tests are of, as `type = Foo`",
+                ))
+            }
+        };
+        // the mode comes first, then the arguments the attribute takes in order
+        let rank = |key: &str| {
+            if MODES.contains(&key) {
+                0
+            } else {
+                match LEADING.iter().position(|keys| keys.contains(&key)) {
+                    Some(ix) => ix + 1,
+                    None => LEADING.len() + 1,
+                }
+            }
+        };
+        args.sort_by_key(|(key, _)| rank(key));
+        if !args.iter().any(|(key, _)| MODES.contains(&&**key)) {
+            args.insert(0, (String::from("exact"), quote!(exact)));
+        }
+        let args = args.into_iter().map(|(_, segment)| segment);
+        let args: AttrArgs = syn::parse2(quote!(#(#args),*))?;
+        Ok(IncludeArgs { args, r#type })
+    }
+}
+
+/// The arguments of `conformance::quickcheck`: the format, the test file whose inputs seed
+/// the property, and how many inputs to generate.
+pub(crate) struct QuickcheckArgs {
+    pub(crate) ser: syn::ExprPath,
+    pub(crate) de: syn::ExprPath,
+    pub(crate) file: syn::LitStr,
+    /// How many generated inputs the property is checked on, beside the seeds.
+    pub(crate) tests: u64,
+}
+
+impl Parse for QuickcheckArgs {
+    fn parse(input: &syn::parse::ParseBuffer<'_>) -> syn::parse::Result<Self> {
+        let (mut format, mut ser, mut de, mut file, mut tests) = (None, None, None, None, None);
+        while !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            let _: syn::Token![=] = input.parse()?;
+            match &*key.to_string() {
+                "serde" => format = Some(input.parse::<syn::ExprPath>()?),
+                "ser" => ser = Some(input.parse()?),
+                "de" => de = Some(input.parse()?),
+                "file" => file = Some(input.parse()?),
+                "tests" => {
+                    let n: syn::LitInt = input.parse()?;
+                    tests = Some(n.base10_parse()?);
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "expected `serde`, `ser`, `de`, `file`, or `tests`",
+                    ))
+                }
+            }
+            if !input.is_empty() {
+                let _: syn::Token![,] = input.parse()?;
+            }
+        }
+
+        let missing = |what: &str| syn::Error::new(Span::call_site(), what);
+        let ser = match (ser, &format) {
+            (Some(ser), _) => ser,
+            (None, Some(format)) => syn::parse_quote!(#format::to_string),
+            (None, None) => return Err(missing("`quickcheck` needs `serde = fmt`, or `ser`")),
+        };
+        let de = match (de, &format) {
+            (Some(de), _) => de,
+            (None, Some(format)) => syn::parse_quote!(#format::from_str),
+            (None, None) => return Err(missing("`quickcheck` needs `serde = fmt`, or `de`")),
+        };
+        let file = file.ok_or_else(|| {
+            missing("`quickcheck` needs the test file whose inputs seed it, as `file = \"...\"`")
+        })?;
+        Ok(QuickcheckArgs {
+            ser,
+            de,
+            file,
+            tests: tests.unwrap_or(100),
+        })
+    }
+}
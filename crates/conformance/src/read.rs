@@ -0,0 +1,1683 @@
+use {
+    crate::{
+        codegen::{equivalent_fn, error_fn, expr_fn, incomplete_fn, sanitize_ident},
+        compile_error, compile_warning,
+        options::{FileFormat, InlineCase, Mode, Options, DIRECTIVE_RESTRICTIONS},
+    },
+    proc_macro2::{Span, TokenStream},
+    quote::quote,
+    std::{
+        borrow::Cow,
+        collections::{btree_map::Entry, BTreeMap, BTreeSet},
+        env,
+        fs::{self, File},
+        io::prelude::*,
+        ops::Range,
+        path::{Path, PathBuf},
+    },
+};
+
+pub(crate) struct Test {
+    pub(crate) name: syn::Ident,
+    /// The name as written in the file.
+    pub(crate) title: String,
+    /// The stable identifier given with `@id`, for tracing a test to a specification.
+    pub(crate) id: Option<String>,
+    pub(crate) input: Body,
+    /// Further inputs, separated by `&&&`, which must serialize the same as `input`.
+    pub(crate) equivalents: Vec<Body>,
+    /// `input` must be rejected by `de` (`@error`), with an error containing `output`.
+    pub(crate) error: bool,
+    /// The tag `error_kind` must give the error `input` is rejected with (`@error kind=<tag>`).
+    pub(crate) error_kind: Option<String>,
+    /// The line and column `error_pos` must give the error `input` is rejected with
+    /// (`@error_at line:column`).
+    pub(crate) error_at: Option<(usize, usize)>,
+    /// `input` is cut short, and `incomplete` must find that `de` needs more data (`@incomplete`).
+    pub(crate) incomplete: bool,
+    /// The expression `output` is, giving the value it serializes to (`@expr`).
+    pub(crate) expr: Option<syn::Expr>,
+    /// The reason the test is ignored (`@ignore`), which may be empty.
+    pub(crate) ignore: Option<String>,
+    /// The reason the test is expected to fail (`@xfail`), which may be empty.
+    pub(crate) xfail: Option<String>,
+    /// How far apart the numbers of the outputs may be in `approx` mode instead of the file's
+    /// `tolerance` (`@tolerance`).
+    pub(crate) tolerance: Option<f64>,
+    /// The path of the test file, relative to the manifest directory if possible.
+    pub(crate) file: String,
+    /// The line of the file the test starts on.
+    pub(crate) line: usize,
+    /// The `@group` the test is in, if any.
+    pub(crate) group: Option<String>,
+    pub(crate) output: Body,
+    /// The compressed file `output` was read from (`@file.gz`), included to rebuild on edits.
+    output_file: Option<String>,
+    /// How many times to run the test (`@repeat N`).
+    pub(crate) repeat: usize,
+    /// The differently named earlier test whose identifier this test's `name` would have been,
+    /// before a suffix told them apart.
+    similar_to: Option<String>,
+    /// The checks given with `@modes`, each of which is run.
+    pub(crate) modes: Vec<conformance_format::Mode>,
+    /// The version of the crate the file's outputs were captured from, given by the
+    /// frontmatter `baseline_version`, for failures to name.
+    pub(crate) baseline_version: Option<String>,
+    /// The function serializing this test instead of `ser` (`@ser PATH`).
+    pub(crate) ser: Option<syn::ExprPath>,
+    /// The function deserializing this test instead of `de` (`@de PATH`).
+    pub(crate) de: Option<syn::ExprPath>,
+    /// Substrings of the warnings `warnings` must give for the input's value (`@warn`).
+    pub(crate) warnings: Vec<String>,
+    /// The test's `@meta key=value` pairs, only emitted for reporting.
+    meta: BTreeMap<String, String>,
+    /// The names of the later tests checking the same as this one, which `dedup` dropped.
+    duplicates: Vec<String>,
+}
+
+impl Test {
+    /// The generated function which runs this test, given the main `testing_fn`.
+    pub(crate) fn testing_fn(&self, testing_fn: &syn::Ident) -> syn::Ident {
+        if self.error {
+            error_fn(testing_fn)
+        } else if self.incomplete {
+            incomplete_fn(testing_fn)
+        } else if self.expr.is_some() {
+            expr_fn(testing_fn)
+        } else if !self.equivalents.is_empty() {
+            equivalent_fn(testing_fn)
+        } else {
+            testing_fn.clone()
+        }
+    }
+
+    /// The main testing function of this test's own `@ser`, `@de` or `@tolerance`, if it
+    /// overrides any, named after the main `testing_fn` and the overrides, so that tests
+    /// overriding them alike share it.
+    pub(crate) fn override_fn(&self, testing_fn: &syn::Ident) -> Option<syn::Ident> {
+        let (ser, de) = (&self.ser, &self.de);
+        if ser.is_none() && de.is_none() && self.tolerance.is_none() {
+            return None;
+        }
+        let mut paths = quote!(#ser, #de).to_string();
+        if let Some(tolerance) = self.tolerance {
+            paths.push_str(&format!(", {:e}", tolerance));
+        }
+        Some(quote::format_ident!(
+            "{}_override_{:04x}",
+            testing_fn,
+            short_hash(&paths)
+        ))
+    }
+
+    /// Whether the output of this test is read from its snapshot, with `snapshots`:
+    /// it is compared to an output, but has an empty one.
+    pub(crate) fn takes_snapshot(&self, options: &Options) -> bool {
+        options.snapshots
+            && !self.error
+            && !self.incomplete
+            && self.equivalents.is_empty()
+            && self.modes.is_empty()
+            && self.output.text.is_empty()
+    }
+
+    /// The `.snap` file next to the test file holding this test's snapshot,
+    /// named after the file without its extension and the test, without its `ident_prefix`.
+    pub(crate) fn snapshot_path(&self, options: &Options) -> String {
+        let file = env::var_os("CARGO_MANIFEST_DIR")
+            .map_or_else(PathBuf::new, PathBuf::from)
+            .join(&self.file);
+        let name = self.name.to_string();
+        let name = name.strip_prefix(options.ident_prefix()).unwrap_or(&name);
+        let name = name.trim_start_matches('_');
+        format!("{}.{}.snap", file.with_extension("").display(), name)
+    }
+
+    /// The case's name as exposed in `CASE_NAMES`.
+    pub(crate) fn case_name(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.title)
+    }
+
+    /// What the test checks its expectations against: its inputs, and how they are run.
+    ///
+    /// Tests with the same subject should expect the same of it.
+    fn subject(&self) -> impl Ord {
+        let equivalents: Vec<_> = self
+            .equivalents
+            .iter()
+            .map(|body| body.text.clone())
+            .collect();
+        let modes: Vec<_> = self.modes.iter().map(|mode| mode.name()).collect();
+        let (ser, de) = (&self.ser, &self.de);
+        (
+            self.input.text.clone(),
+            equivalents,
+            modes,
+            quote!(#ser, #de).to_string(),
+            self.tolerance.map(f64::to_bits),
+            self.incomplete,
+            self.xfail.is_some(),
+        )
+    }
+
+    /// Everything the test checks, which an identical test checks the same.
+    fn checked(&self) -> impl Ord {
+        let expr = self.expr.as_ref().map(|expr| quote!(#expr).to_string());
+        (
+            self.subject(),
+            self.output.text.clone(),
+            self.output_file.clone(),
+            (self.error, self.error_kind.clone(), self.error_at),
+            expr,
+            self.warnings.clone(),
+            self.repeat,
+        )
+    }
+
+    /// The case's `@meta` pairs as exposed in `CASE_META`, sorted by key.
+    pub(crate) fn case_meta(&self) -> TokenStream {
+        let (keys, values): (Vec<_>, Vec<_>) = self.meta.iter().unzip();
+        quote!(&[#((#keys, #values)),*])
+    }
+}
+
+/// Bodies longer than this many bytes are sliced out of the `include_str!`ed file
+/// when possible, rather than repeated in the generated code as literals.
+const INLINE_LIMIT: usize = 1024;
+
+/// The input or output section of a test.
+pub(crate) struct Body {
+    pub(crate) text: String,
+    /// Where `text` appears verbatim in the test file, if it is long enough to slice.
+    range: Option<Range<usize>>,
+    /// The file `text` was written to, with `split_includes`.
+    include: Option<String>,
+}
+
+impl Body {
+    /// `text` as produced from `section`, a slice of the file `source`.
+    ///
+    /// If `source` is empty, the body is never sliced.
+    fn new(source: &str, section: &str, text: String) -> Self {
+        let range = if text.len() > INLINE_LIMIT && !source.is_empty() {
+            // a trimmed body starts where the section's content does, without searching for it
+            let start = section.len() - section.trim_start().len();
+            let found = if section[start..].starts_with(&text) {
+                Some(start)
+            } else {
+                section.find(&text)
+            };
+            found.map(|ix| {
+                let start = section.as_ptr() as usize - source.as_ptr() as usize + ix;
+                start..start + text.len()
+            })
+        } else {
+            None
+        };
+        Body {
+            text,
+            range,
+            include: None,
+        }
+    }
+
+    /// Write `text` to `path` if it is long, so it can be included on its own.
+    ///
+    /// The file is left untouched if it is up to date, so as not to invalidate the build.
+    fn split_into(&mut self, path: PathBuf) -> std::io::Result<()> {
+        if self.text.len() <= INLINE_LIMIT {
+            return Ok(());
+        }
+        if fs::read(&path).ok().as_deref() != Some(self.text.as_bytes()) {
+            fs::write(&path, &self.text)?;
+        }
+        self.include = Some(path.to_string_lossy().to_string());
+        Ok(())
+    }
+
+    /// An expression for the body, given the constant holding the included test file.
+    pub(crate) fn tokens(&self, contents: &syn::Ident) -> TokenStream {
+        if let Some(include) = &self.include {
+            return quote!(::std::include_str!(#include));
+        }
+        match &self.range {
+            Some(range) => {
+                let (start, end) = (range.start, range.end);
+                quote!(&#contents[#start..#end])
+            }
+            None => {
+                let text = &self.text;
+                quote!(#text)
+            }
+        }
+    }
+}
+
+/// Remove the whitespace prefix common to all non-blank lines of `s`,
+/// removing no more than `limit` characters from any line if given.
+///
+/// As with Python's `textwrap.dedent`, tabs and spaces are not equivalent:
+/// only an identical run of whitespace characters counts as common.
+/// Lines consisting solely of whitespace do not affect the common prefix
+/// and are emptied.
+fn dedent(s: &str, limit: Option<usize>) -> String {
+    let mut margin: Option<&str> = None;
+    for line in s.lines().filter(|line| !line.trim().is_empty()) {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        margin = Some(match margin {
+            None => indent,
+            Some(margin) => {
+                let common = margin
+                    .char_indices()
+                    .zip(indent.chars())
+                    .find(|&((_, a), b)| a != b)
+                    .map_or(margin.len().min(indent.len()), |((i, _), _)| i);
+                &margin[..common]
+            }
+        });
+    }
+    let mut margin = margin.unwrap_or("");
+    if let Some(limit) = limit {
+        margin = &margin[..margin
+            .char_indices()
+            .nth(limit)
+            .map_or(margin.len(), |(i, _)| i)];
+    }
+
+    s.split('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                ""
+            } else {
+                &line[margin.len()..]
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Trim a section body, first dedenting it if requested.
+///
+/// When dedenting, only surrounding blank lines are removed,
+/// so that indentation of the first line relative to the rest survives.
+/// In `raw` mode, the body is not trimmed at all.
+/// With `normalize_newlines`, its newlines are then all `\n`.
+pub(crate) fn trim_body(body: &str, options: &Options) -> String {
+    let body = match (options.raw, options.dedent) {
+        (true, None) => body.to_string(),
+        (true, Some(limit)) => dedent(body, limit),
+        (false, None) => body.trim().to_string(),
+        (false, Some(limit)) => {
+            let body = dedent(body, limit);
+            let start = body.len() - body.trim_start_matches('\n').len();
+            body[start..].trim_end().to_string()
+        }
+    };
+    if options.normalize_newlines {
+        normalize_newlines(&body)
+    } else {
+        body
+    }
+}
+
+/// `s` with each `\r\n` and lone `\r` replaced by `\n`.
+fn normalize_newlines(s: &str) -> String {
+    s.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// A 16 bit FNV-1a hash of `s`, which unlike `std`'s hashers is stable across releases.
+fn short_hash(s: &str) -> u16 {
+    let hash = s.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    // xor-fold, as recommended for FNV hashes narrower than 32 bits
+    ((hash >> 16) ^ (hash & 0xffff)) as u16
+}
+
+/// Whether the test file at `path` is gzip-compressed.
+pub(crate) fn is_gzip(path: &Path) -> bool {
+    path.extension() == Some("gz".as_ref())
+}
+
+/// Open the test file at `path`, decompressing it if it is gzip-compressed.
+pub(crate) fn open_tests(path: &Path) -> Result<Box<dyn Read>, String> {
+    let f = File::open(path).map_err(|e| open_error(path, e))?;
+    if !is_gzip(path) {
+        return Ok(Box::new(f));
+    }
+    #[cfg(feature = "gzip")]
+    return Ok(Box::new(flate2::read::GzDecoder::new(f)));
+    #[cfg(not(feature = "gzip"))]
+    return Err(String::from(
+        "reading a `.gz` file requires the `gzip` feature of `conformance`",
+    ));
+}
+
+/// The path and decompressed contents of the `@file.gz` output `golden`
+/// of the test file at `file_path`, relative to its directory.
+fn read_golden(file_path: &Path, golden: &str) -> Result<(String, String), String> {
+    let path = file_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(golden);
+    if !is_gzip(&path) {
+        return Err(String::from("it doesn't end with `.gz`"));
+    }
+    if !cfg!(feature = "gzip") {
+        return Err(String::from(
+            "reading it requires the `gzip` feature of `conformance`",
+        ));
+    }
+    let mut bytes = Vec::new();
+    open_tests(&path)?
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("`{}` is not valid gzip: {}", path.display(), e))?;
+    let text = String::from_utf8(bytes)
+        .map_err(|e| format!("`{}` decompresses to invalid UTF-8: {}", path.display(), e))?;
+    Ok((path.to_string_lossy().to_string(), text))
+}
+
+/// Read the binary `golden` of a test in `file_path`, returning its path and its bytes written
+/// as an output section would be, in hex, or in base64 with `base64`.
+///
+/// Only the binary modes compare outputs as bytes, so it is an error in any other.
+fn read_binary_golden(
+    file_path: &Path,
+    golden: &str,
+    options: &Options,
+) -> Result<(String, String), String> {
+    if !options.mode.is_binary() {
+        return Err(format!(
+            "`{}` mode doesn't compare outputs as bytes; use `msgpack` or `cbor` mode",
+            options.mode.name()
+        ));
+    }
+    let path = file_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(golden);
+    if !path.is_file() {
+        return Err(format!("`{}` doesn't exist", display_path(&path)));
+    }
+    let bytes = fs::read(&path).map_err(|e| open_error(&path, e))?;
+    let text = if options.base64 {
+        const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut text = String::new();
+        for chunk in bytes.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0_u32, |n, (i, &byte)| n | u32::from(byte) << (16 - 8 * i));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    text.push(DIGITS[(n >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    text.push('=');
+                }
+            }
+        }
+        text
+    } else {
+        let digits: Vec<_> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        digits.join(" ")
+    };
+    Ok((path.to_string_lossy().to_string(), text))
+}
+
+/// Describe the failure `e` to open `path`, with where it resolves to if it is a symlink,
+/// as permission errors in sandboxed builds are opaque otherwise.
+fn open_error(path: &Path, e: std::io::Error) -> String {
+    let mut message = format!("failed to open file `{}`: {}", path.display(), e);
+    let is_symlink = fs::symlink_metadata(path).is_ok_and(|it| it.file_type().is_symlink());
+    match fs::canonicalize(path) {
+        Ok(resolved) if is_symlink => {
+            message.push_str(&format!("; it is a symlink to `{}`", resolved.display()));
+        }
+        Ok(resolved) if resolved != path => {
+            message.push_str(&format!("; it resolves to `{}`", resolved.display()));
+        }
+        Ok(_) => (),
+        Err(e) if is_symlink => {
+            let target = fs::read_link(path).map_or_else(
+                |_| String::from("an unreadable target"),
+                |target| format!("`{}`", target.display()),
+            );
+            message.push_str(&format!(
+                "; it is a broken symlink to {}, which can't be resolved: {}",
+                target, e
+            ));
+        }
+        Err(_) => (),
+    }
+    message
+}
+
+/// Warn about `exact` tests whose output is the same as their input,
+/// which are likely meant to be `idempotent` instead.
+pub(crate) fn identical_warnings(tests: &[Test], options: &Options, span: Span) -> TokenStream {
+    if options.mode != Mode::Exact || options.allow_identical || options.predicate.is_some() {
+        return TokenStream::new();
+    }
+    tests
+        .iter()
+        // the output of an `@error` test is part of the error, which may well quote the input
+        .filter(|test| {
+            !test.error && test.equivalents.is_empty() && test.input.text == test.output.text
+        })
+        .map(|test| {
+            let e = format!(
+                "test `{}` has identical input and output; if that's intended, \
+                 try `idempotent` mode, or silence this with the `allow_identical` option",
+                test.title,
+            );
+            compile_warning(&e, span)
+        })
+        .collect()
+}
+
+/// Warn about a test file with more cases than `warn_over`, which slows down compiling the tests.
+pub(crate) fn oversized_warning(tests: &[Test], options: &Options, span: Span) -> TokenStream {
+    match (options.warn_over, tests.first()) {
+        (Some(warn_over), Some(first)) if tests.len() > warn_over => {
+            let e = format!(
+                "`{}` has {} cases, more than the {} of `warn_over`; \
+                 each case is compiled into a test, so consider splitting the file",
+                first.file,
+                tests.len(),
+                warn_over,
+            );
+            compile_warning(&e, span)
+        }
+        _ => TokenStream::new(),
+    }
+}
+
+/// The names of `tests` as written, with their `@id`s, which `exclude` and `require_cases` name.
+pub(crate) fn case_names_of(tests: &[Test]) -> BTreeSet<(String, Option<String>)> {
+    tests
+        .iter()
+        .map(|test| (test.title.clone(), test.id.clone()))
+        .collect()
+}
+
+/// Whether some case of `found` has `name` as its name or `@id`.
+fn has_case_named(found: &BTreeSet<(String, Option<String>)>, name: &str) -> bool {
+    found
+        .iter()
+        .any(|(title, id)| title == name || id.as_deref() == Some(name))
+}
+
+/// Reject each name given to `exclude` which isn't among `found`, as it is likely a typo.
+pub(crate) fn unknown_exclusions(
+    found: &BTreeSet<(String, Option<String>)>,
+    options: &Options,
+) -> TokenStream {
+    options
+        .exclude
+        .iter()
+        .filter(|excluded| !has_case_named(found, &excluded.value()))
+        .map(|excluded| {
+            let e = format!(
+                "`exclude` names `{}`, but no case has that name",
+                excluded.value()
+            );
+            compile_error(&e, excluded.span())
+        })
+        .collect()
+}
+
+/// Reject the names listed in the `require_cases` file, relative to `base_dir`, which no case
+/// of `found` has, and warn of each case it doesn't list, if `warn_extra_cases` is given.
+///
+/// Blank lines, and lines starting with `#`, are skipped. The file is included, so that editing
+/// it rebuilds the tests.
+pub(crate) fn required_cases(
+    found: &BTreeSet<(String, Option<String>)>,
+    base_dir: &Path,
+    options: &Options,
+) -> Result<TokenStream, TokenStream> {
+    let list = match &options.require_cases {
+        Some(list) => list,
+        None => return Ok(TokenStream::new()),
+    };
+    let path = base_dir.join(list.value());
+    let contents = std::fs::read_to_string(&path).map_err(|e| {
+        let e = format!("failed to read `{}`: {}", path.display(), e);
+        compile_error(&e, list.span())
+    })?;
+    let required: BTreeSet<&str> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let missing: Vec<_> = required
+        .iter()
+        .filter(|name| !has_case_named(found, name))
+        .map(|name| format!("`{}`", name))
+        .collect();
+    if !missing.is_empty() {
+        let e = format!(
+            "no case is named {}, which `{}` requires",
+            missing.join(", "),
+            list.value()
+        );
+        return Err(compile_error(&e, list.span()));
+    }
+    let filepath = path.display().to_string();
+    let mut tts = quote!(
+        const _: &[u8] = ::std::include_bytes!(#filepath);
+    );
+    if options.warn_extra_cases {
+        let extra = found.iter().filter(|(title, id)| {
+            !required.contains(title.as_str())
+                && !id.as_deref().is_some_and(|id| required.contains(id))
+        });
+        tts.extend(extra.map(|(title, _)| {
+            let w = format!("test `{}` isn't listed in `{}`", title, list.value());
+            compile_warning(&w, list.span())
+        }));
+    }
+    Ok(tts)
+}
+
+/// Drop each test checking the same as an earlier one, recording it as a duplicate of that one.
+///
+/// `@ignore`d tests are kept, as they check nothing.
+fn dedup(tests: &mut Vec<Test>) {
+    let mut first = BTreeMap::new();
+    let mut kept: Vec<Test> = Vec::with_capacity(tests.len());
+    for test in tests.drain(..) {
+        if test.ignore.is_some() {
+            kept.push(test);
+            continue;
+        }
+        match first.entry(test.checked()) {
+            Entry::Occupied(entry) => {
+                let original: &mut Test = &mut kept[*entry.get()];
+                original.duplicates.push(test.title);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(kept.len());
+                kept.push(test);
+            }
+        }
+    }
+    *tests = kept;
+}
+
+/// Warn about each test which `dedup` dropped duplicates of, listing them.
+pub(crate) fn duplicate_warnings(tests: &[Test], span: Span) -> TokenStream {
+    tests
+        .iter()
+        .filter(|test| !test.duplicates.is_empty())
+        .map(|test| {
+            let duplicates: Vec<_> = test
+                .duplicates
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect();
+            let e = format!(
+                "test `{}` has the same input and expected output as {}, \
+                 which `dedup` generates no tests for; remove them from the file",
+                test.title,
+                duplicates.join(", "),
+            );
+            compile_warning(&e, span)
+        })
+        .collect()
+}
+
+/// Warn about tests whose names are different, but not once made identifiers,
+/// as in `a b` and `a_b`, which are likely a naming mistake.
+pub(crate) fn similar_name_warnings(tests: &[Test], options: &Options, span: Span) -> TokenStream {
+    if options.allow_similar_names {
+        return TokenStream::new();
+    }
+    tests
+        .iter()
+        .filter_map(|test| Some((test, test.similar_to.as_ref()?)))
+        .map(|(test, similar_to)| {
+            let e = format!(
+                "tests `{}` and `{}` have names which give the same identifier, \
+                 so the latter is named `{}`; rename one of them, \
+                 or silence this with the `allow_similar_names` option",
+                similar_to,
+                test.case_name(),
+                test.name,
+            );
+            compile_warning(&e, span)
+        })
+        .collect()
+}
+
+/// Each test paired with the first test of the same subject, which it should expect the same as.
+///
+/// `@ignore`d and `@xfail` tests expect nothing, and `&&&` inputs are only compared to each other.
+pub(crate) fn same_subjects(tests: &[Test]) -> Vec<(&Test, &Test)> {
+    let mut first = BTreeMap::new();
+    tests
+        .iter()
+        .filter(|test| test.ignore.is_none() && test.xfail.is_none())
+        .filter(|test| test.equivalents.is_empty())
+        .filter_map(|test| match first.entry(test.subject()) {
+            Entry::Occupied(entry) => Some((*entry.get(), test)),
+            Entry::Vacant(entry) => {
+                entry.insert(test);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Errors for each test expecting a different outcome than an earlier test with the same input,
+/// which is most likely a mistake copying one of them: an `@error` test expects something
+/// different from any other test, as does one naming another error kind or position.
+///
+/// What the outputs of the others are is only known when they're read by the testing function,
+/// so they're compared by `consistency_test_fn`.
+fn inconsistencies(tests: &[Test], span: Span) -> TokenStream {
+    // a test which doesn't check the error's kind or position expects any
+    fn differ<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+        a.is_some() && b.is_some() && a != b
+    }
+    let mut errs = TokenStream::new();
+    for (previous, test) in same_subjects(tests) {
+        let outcome = if previous.error != test.error {
+            "different outcomes"
+        } else if differ(&previous.error_kind, &test.error_kind) {
+            "different error kinds"
+        } else if differ(&previous.error_at, &test.error_at) {
+            "errors at different positions"
+        } else {
+            continue;
+        };
+        let e = format!(
+            "tests `{}` and `{}` have the same input but expect {}",
+            previous.case_name(),
+            test.case_name(),
+            outcome,
+        );
+        errs.extend(compile_error(&e, span));
+    }
+    errs
+}
+
+/// Whether the outputs of tests are values the actual output is compared to, which can be
+/// compared to each other, rather than patterns, descriptions, or unused.
+pub(crate) fn testing_fn_compares_outputs(options: &Options) -> bool {
+    options.predicate.is_none()
+        && options.oracle.is_none()
+        && !options.template
+        && !matches!(options.mode, Mode::Regex | Mode::Idempotent)
+}
+
+/// Generate a constant holding the test file at `path`, so that editing it rebuilds the tests.
+///
+/// A compressed file is included as bytes, so there's nothing for bodies to be sliced from.
+/// The `.expected` file of a `single` file is included too, if there is one,
+/// as are the `@file.gz` files of `tests`.
+pub(crate) fn include_test_file(
+    path: &Path,
+    contents: &syn::Ident,
+    tests: &[Test],
+    options: &Options,
+) -> TokenStream {
+    let filepath = path.to_string_lossy().to_string();
+    let mut tts = if is_gzip(path) {
+        quote!(
+            const _: &[u8] = ::std::include_bytes!(#filepath);
+        )
+    } else {
+        quote! {
+            #[allow(dead_code)]
+            const #contents: &str = ::std::include_str!(#filepath);
+        }
+    };
+    // the outputs read from `@file.gz` files are in the tests, but not the file
+    let goldens = tests.iter().filter_map(|test| test.output_file.as_ref());
+    tts.extend(quote!(#(const _: &[u8] = ::std::include_bytes!(#goldens);)*));
+    let expected_path = path.with_extension("expected");
+    if options.single && expected_path.is_file() {
+        let expected_path = expected_path.to_string_lossy().to_string();
+        tts.extend(quote!(
+            const _: &[u8] = ::std::include_bytes!(#expected_path);
+        ));
+    }
+    tts
+}
+
+/// The `.expected` file of the test file at `path` with contents `source`,
+/// if it is a `single` file: one without separators.
+fn single_expected(path: &Path, source: &str, options: &Options) -> Option<PathBuf> {
+    let has_separators = source.lines().any(|line| line.trim_end() == "===");
+    if options.single && !has_separators {
+        Some(path.with_extension("expected"))
+    } else {
+        None
+    }
+}
+
+/// The one case of the `single` file `file`, whose input is all of `source`
+/// and whose output is the contents of `expected_path`.
+///
+/// The `.expected` file may only be missing if the output is optional or is the input.
+fn single_case<'a>(
+    file: &str,
+    expected_path: &Path,
+    source: &'a str,
+    options: conformance_format::ParseOptions,
+) -> Result<conformance_format::Case<'a>, String> {
+    let output = match fs::read_to_string(expected_path) {
+        Ok(output) => Cow::Owned(output),
+        Err(_) if options.idempotent => Cow::Borrowed(source),
+        Err(_) if options.optional_output => Cow::Borrowed(""),
+        Err(e) => {
+            return Err(format!(
+                "`single` file `{}` has no separators, so its expected output is read from \
+                 its sibling `{}`, but that failed: {}",
+                file,
+                expected_path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                e
+            ))
+        }
+    };
+    let name = Path::new(file)
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+    let name = name.split('.').next().unwrap_or_default();
+    Ok(conformance_format::Case::new(
+        sanitize_ident(name).to_string(),
+        0,
+        1,
+        Cow::Borrowed(source),
+        output,
+    ))
+}
+
+/// The cases of `source`, the table `file` with cells separated by `delimiter`,
+/// whose header names the `name`, `input`, and `expected` columns, in any order.
+///
+/// Cells may be quoted, spanning lines, and the output is that of `idempotent` mode, or
+/// may be omitted as `optional_output` allows, as for the other format.
+#[cfg(feature = "csv")]
+fn table_cases(
+    file: &str,
+    source: &str,
+    delimiter: u8,
+    options: conformance_format::ParseOptions,
+) -> Result<Vec<conformance_format::Case<'static>>, String> {
+    let mut reader = csv_crate::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(source.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("failed to read the header of `{}`: {}", file, e))?
+        .clone();
+    let (mut name, mut input, mut expected) = (None, None, None);
+    for (i, header) in headers.iter().enumerate() {
+        let column = match header.trim() {
+            "name" => &mut name,
+            "input" => &mut input,
+            "expected" => &mut expected,
+            header => {
+                return Err(format!(
+                    "`{}` has unknown column `{}`; expected `name`, `input`, and `expected`",
+                    file, header
+                ))
+            }
+        };
+        *column = Some(i);
+    }
+    let column = |column: Option<usize>, header: &str| {
+        column.ok_or_else(|| format!("`{}` has no `{}` column", file, header))
+    };
+    let (name, input) = (column(name, "name")?, column(input, "input")?);
+    if expected.is_none() && !options.idempotent && !options.optional_output {
+        column(expected, "expected")?;
+    }
+
+    let mut cases = Vec::new();
+    let mut names = BTreeSet::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("failed to read `{}`: {}", file, e))?;
+        let line = record
+            .position()
+            .map_or(0, |position| position.line() as usize);
+        let cell = |column: usize| record.get(column).unwrap_or_default().to_string();
+        let name = cell(name);
+        if name.trim().is_empty() {
+            return Err(format!("row on line {} of `{}` has no name", line, file));
+        }
+        if !names.insert(name.clone()) && !options.allow_duplicate_names {
+            return Err(format!(
+                "test `{}` has the same name as an earlier test",
+                name
+            ));
+        }
+        let input = cell(input);
+        let output = match expected {
+            _ if options.idempotent => input.clone(),
+            Some(expected) => cell(expected),
+            None => String::new(),
+        };
+        cases.push(conformance_format::Case::new(
+            name,
+            index,
+            line,
+            Cow::Owned(input),
+            Cow::Owned(output),
+        ));
+    }
+    Ok(cases)
+}
+
+#[cfg(not(feature = "csv"))]
+fn table_cases(
+    _: &str,
+    _: &str,
+    _: u8,
+    _: conformance_format::ParseOptions,
+) -> Result<Vec<conformance_format::Case<'static>>, String> {
+    Err(String::from(
+        "`format = csv` and `format = tsv` require the `csv` feature of `conformance`",
+    ))
+}
+
+/// The cases of `source`, the JSON Lines file `file`, each non-blank line of which is an object
+/// with `name`, `input`, and `expected` strings.
+///
+/// As for a table, the output is that of `idempotent` mode, or may be omitted as
+/// `optional_output` allows.
+#[cfg(feature = "jsonl")]
+fn jsonl_cases(
+    file: &str,
+    source: &str,
+    options: conformance_format::ParseOptions,
+) -> Result<Vec<conformance_format::Case<'static>>, String> {
+    let mut cases = Vec::new();
+    let mut names = BTreeSet::new();
+    let lines = source.lines().enumerate().map(|(i, line)| (i + 1, line));
+    for (index, (line, text)) in lines
+        .filter(|(_, text)| !text.trim().is_empty())
+        .enumerate()
+    {
+        let object = match json::from_str(text) {
+            Ok(json::Value::Object(object)) => object,
+            Ok(_) => return Err(format!("line {} of `{}` isn't a JSON object", line, file)),
+            Err(e) => return Err(format!("failed to read line {} of `{}`: {}", line, file, e)),
+        };
+        if let Some(key) = object
+            .keys()
+            .find(|key| !["name", "input", "expected"].contains(&key.as_str()))
+        {
+            return Err(format!(
+                "line {} of `{}` has unknown key `{}`; expected `name`, `input`, and `expected`",
+                line, file, key
+            ));
+        }
+        let field = |key: &str| match object.get(key) {
+            Some(json::Value::String(s)) => Ok(Some(s.clone())),
+            Some(_) => Err(format!(
+                "`{}` on line {} of `{}` isn't a string",
+                key, line, file
+            )),
+            None => Ok(None),
+        };
+        let missing = |key: &str| format!("line {} of `{}` has no `{}`", line, file, key);
+        let name = field("name")?.ok_or_else(|| missing("name"))?;
+        if name.trim().is_empty() {
+            return Err(missing("name"));
+        }
+        if !names.insert(name.clone()) && !options.allow_duplicate_names {
+            return Err(format!(
+                "test `{}` has the same name as an earlier test",
+                name
+            ));
+        }
+        let input = field("input")?.ok_or_else(|| missing("input"))?;
+        let output = match field("expected")? {
+            _ if options.idempotent => input.clone(),
+            Some(expected) => expected,
+            None if options.optional_output => String::new(),
+            None => return Err(missing("expected")),
+        };
+        cases.push(conformance_format::Case::new(
+            name,
+            index,
+            line,
+            Cow::Owned(input),
+            Cow::Owned(output),
+        ));
+    }
+    Ok(cases)
+}
+
+#[cfg(not(feature = "jsonl"))]
+fn jsonl_cases(
+    _: &str,
+    _: &str,
+    _: conformance_format::ParseOptions,
+) -> Result<Vec<conformance_format::Case<'static>>, String> {
+    Err(String::from(
+        "`format = jsonl` requires the `jsonl` feature of `conformance`",
+    ))
+}
+
+/// Read the tests of `file_path`, with the `type` given by its frontmatter, if any.
+///
+/// The tests are given the `baseline_version` of the frontmatter.
+pub(crate) fn read_tests(
+    file_path: &Path,
+    span: Span,
+    options: &Options,
+) -> Result<(Vec<Test>, Option<syn::Type>), TokenStream> {
+    let source = {
+        let mut f = open_tests(file_path).map_err(|e| compile_error(&e, span))?;
+        let mut s = String::new();
+        f.read_to_string(&mut s)
+            .map_err(|e| compile_error(&format!("failed to read file: {}", e), span))?;
+        s
+    };
+    if options.strict_format {
+        let errs = format_errors(&display_path(file_path), &source);
+        if !errs.is_empty() {
+            return Err(errs.iter().map(|e| compile_error(e, span)).collect());
+        }
+    }
+    // bodies can only be sliced out of the file if it is included as is
+    let sliceable = if is_gzip(file_path) { "" } else { &source };
+
+    let parse_options = conformance_format::ParseOptions {
+        idempotent: options.mode == Mode::Idempotent,
+        // the output is then only a description, if given
+        optional_output: options.predicate.is_some() || options.oracle.is_some(),
+        // tests of the same name are then told apart by hash
+        allow_duplicate_names: options.name_includes_hash,
+        allow_trailing_content: options.allow_trailing_content,
+        lenient_terminator: options.lenient_terminator,
+    };
+    let parse_errors = |errs: Vec<conformance_format::ParseError>| {
+        errs.iter()
+            .map(|e| compile_error(&e.to_string(), span))
+            .collect::<TokenStream>()
+    };
+    let file = display_path(file_path);
+    let cases = match (
+        options.file_format,
+        single_expected(file_path, &source, options),
+    ) {
+        (FileFormat::Native, Some(expected_path)) => {
+            vec![single_case(&file, &expected_path, &source, parse_options)
+                .map_err(|e| compile_error(&e, span))?]
+        }
+        (FileFormat::Native, None) => {
+            conformance_format::parse_tests_with(&source, parse_options).map_err(parse_errors)?
+        }
+        (FileFormat::Csv, _) => {
+            table_cases(&file, &source, b',', parse_options).map_err(|e| compile_error(&e, span))?
+        }
+        (FileFormat::Tsv, _) => table_cases(&file, &source, b'\t', parse_options)
+            .map_err(|e| compile_error(&e, span))?,
+        (FileFormat::Jsonl, _) => {
+            jsonl_cases(&file, &source, parse_options).map_err(|e| compile_error(&e, span))?
+        }
+    };
+    reject_empty_sections(&cases, span, options)?;
+    reject_missing_meta(&cases, &file, span, options)?;
+    let frontmatter = conformance_format::parse_frontmatter(&source).map_err(parse_errors)?;
+    let r#type = match &frontmatter.r#type {
+        Some(r#type) => Some(syn::parse_str::<syn::Type>(r#type).map_err(|e| {
+            let e = format!(
+                "frontmatter `type = \"{}\"` of `{}` is not a type: {}",
+                r#type, file, e
+            );
+            compile_error(&e, span)
+        })?),
+        None => None,
+    };
+    let mut tests = tests_of(cases, file_path, &file, sliceable, span, options)?;
+    for test in &mut tests {
+        test.baseline_version = frontmatter.baseline_version.clone();
+    }
+    Ok((tests, r#type))
+}
+
+/// The problems `strict_format` finds in `source`, the contents of `file`, each naming the line:
+/// separator lines with whitespace around them, which aren't read as separators, and tabs in
+/// the names and directives of its tests, before their `===`.
+///
+/// A `+++` frontmatter is skipped, and bodies are left alone, whatever they contain.
+fn format_errors(file: &str, source: &str) -> Vec<String> {
+    const SEPARATORS: &[&str] = &["===", "---", "...", "&&&"];
+    let mut errs = Vec::new();
+    let mut lines = source.lines().enumerate().peekable();
+    if lines.peek().is_some_and(|(_, line)| *line == "+++") {
+        lines.next();
+        for (_, line) in lines.by_ref() {
+            if line == "+++" {
+                break;
+            }
+        }
+    }
+    // whether the line is before the `===` of a test, in its name and directives
+    let mut in_header = true;
+    for (ix, line) in lines {
+        let trimmed = line.trim();
+        if SEPARATORS.contains(&trimmed) {
+            if line != trimmed {
+                let problem = if line.trim_end() != line {
+                    "trailing whitespace"
+                } else {
+                    "leading whitespace"
+                };
+                errs.push(format!(
+                    "{}:{}: separator `{}` has {}, so it isn't read as one",
+                    file,
+                    ix + 1,
+                    trimmed,
+                    problem
+                ));
+            }
+            match trimmed {
+                "===" => in_header = false,
+                "..." => in_header = true,
+                _ => (),
+            }
+        } else if in_header && line.contains('\t') {
+            errs.push(format!(
+                "{}:{}: tab in the name or directives of a test",
+                file,
+                ix + 1
+            ));
+        }
+    }
+    errs
+}
+
+/// The errors of each of `cases` which `forbid_empty` rejects, if given.
+fn reject_empty_sections(
+    cases: &[conformance_format::Case<'_>],
+    span: Span,
+    options: &Options,
+) -> Result<(), TokenStream> {
+    if !options.forbid_empty {
+        return Ok(());
+    }
+    let errs: TokenStream = cases
+        .iter()
+        .filter_map(|case| empty_section(case, options))
+        .map(|e| compile_error(&e, span))
+        .collect();
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(errs)
+    }
+}
+
+/// The errors of each of `cases`, read from `file`, without the `@meta` keys of `require_meta`.
+///
+/// Ignored cases are rejected too, as they still belong to the suite.
+fn reject_missing_meta(
+    cases: &[conformance_format::Case<'_>],
+    file: &str,
+    span: Span,
+    options: &Options,
+) -> Result<(), TokenStream> {
+    let mut errs = TokenStream::new();
+    for case in cases {
+        let missing: Vec<_> = options
+            .require_meta
+            .iter()
+            .map(syn::LitStr::value)
+            .filter(|key| !case.meta.contains_key(key))
+            .map(|key| format!("`@meta {}=...`", key))
+            .collect();
+        if !missing.is_empty() {
+            let e = format!(
+                "{}:{}: test `{}` has no {}, which `require_meta` requires",
+                file,
+                case.line,
+                case.name,
+                missing.join(" or ")
+            );
+            errs.extend(compile_error(&e, span));
+        }
+    }
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(errs)
+    }
+}
+
+/// Why `forbid_empty` rejects `case`, if its input, one of its `&&&` inputs, or its output is
+/// empty after trimming, and it isn't `@allow_empty`.
+///
+/// Outputs are only looked at where one is written out: not for `@error` tests, which may accept
+/// any error, `@incomplete` tests, in `idempotent` mode, or when `predicate`, `oracle`, or
+/// `snapshots` make it optional.
+fn empty_section(case: &conformance_format::Case<'_>, options: &Options) -> Option<String> {
+    if case.allow_empty {
+        return None;
+    }
+    let section = if case.input.trim().is_empty() {
+        "input"
+    } else if case.equivalents.iter().any(|input| input.trim().is_empty()) {
+        "`&&&` input"
+    } else if case.output.trim().is_empty()
+        && case.output_file.is_none()
+        && !case.error
+        && !case.incomplete
+        && options.mode != Mode::Idempotent
+        && options.predicate.is_none()
+        && options.oracle.is_none()
+        && !options.snapshots
+    {
+        "output"
+    } else {
+        return None;
+    };
+    Some(format!(
+        "test `{}` (line {}) has an empty {}, which `forbid_empty` rejects; \
+         mark it `@allow_empty` if that is intended",
+        case.name, case.line, section
+    ))
+}
+
+/// Why `path` can't be read without `allow_outside`, if it resolves, through any `..` or
+/// symlinks, to outside of both the manifest directory and `$OUT_DIR`, where a build script
+/// may write tests.
+///
+/// A path which doesn't exist is left for reading it to report.
+pub(crate) fn outside_manifest_dir(path: &Path) -> Option<String> {
+    let resolved = path.canonicalize().ok()?;
+    let manifest_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")?);
+    let out_dir = env::var_os("OUT_DIR").map(PathBuf::from);
+    let within = std::iter::once(&manifest_dir).chain(&out_dir);
+    if within
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| resolved.starts_with(dir))
+    {
+        return None;
+    }
+    Some(format!(
+        "`{}` is outside of the manifest directory `{}`; \
+         give `allow_outside` to read it anyway",
+        resolved.display(),
+        manifest_dir.display(),
+    ))
+}
+
+/// `path`, relative to the manifest directory if possible, as shown in failure messages.
+fn display_path(path: &Path) -> String {
+    env::var_os("CARGO_MANIFEST_DIR")
+        .and_then(|manifest_dir| path.strip_prefix(manifest_dir).ok())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The tests pairing each file of `input_dir` with the file of the same stem in `output_dir`,
+/// named after the stem, and the items including every file, to rebuild on edits.
+///
+/// A file without a counterpart in the other directory is an error. Subdirectories and
+/// hidden files, such as `.gitkeep`, are skipped.
+pub(crate) fn pair_tests(
+    input_dir: &Path,
+    output_dir: &Path,
+    span: Span,
+    options: &Options,
+) -> Result<(Vec<Test>, TokenStream), TokenStream> {
+    let stems = |dir: &Path| -> Result<BTreeMap<String, PathBuf>, TokenStream> {
+        let read_error = |e: std::io::Error| {
+            let e = format!("failed to read directory `{}`: {}", dir.display(), e);
+            compile_error(&e, span)
+        };
+        let mut stems = BTreeMap::new();
+        let mut errs = TokenStream::new();
+        for entry in fs::read_dir(dir).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            if !path.is_file() || file_name.starts_with('.') {
+                continue;
+            }
+            let stem = path.file_stem().unwrap().to_string_lossy().to_string();
+            if let Some(previous) = stems.insert(stem.clone(), path.clone()) {
+                let e = format!(
+                    "`{}` and `{}` in `{}` both have the stem `{}`, so can't be told apart",
+                    previous.file_name().unwrap().to_string_lossy(),
+                    file_name,
+                    dir.display(),
+                    stem,
+                );
+                errs.extend(compile_error(&e, span));
+            }
+        }
+        if errs.is_empty() {
+            Ok(stems)
+        } else {
+            Err(errs)
+        }
+    };
+    let inputs = stems(input_dir)?;
+    let outputs = stems(output_dir)?;
+
+    let mut errs = TokenStream::new();
+    for (stem, path) in &inputs {
+        if !outputs.contains_key(stem) {
+            let e = format!(
+                "input `{}` has no output `{}.*` in `{}`",
+                display_path(path),
+                stem,
+                display_path(output_dir),
+            );
+            errs.extend(compile_error(&e, span));
+        }
+    }
+    for (stem, path) in &outputs {
+        if !inputs.contains_key(stem) {
+            let e = format!(
+                "output `{}` has no input `{}.*` in `{}`",
+                display_path(path),
+                stem,
+                display_path(input_dir),
+            );
+            errs.extend(compile_error(&e, span));
+        }
+    }
+    if !errs.is_empty() {
+        return Err(errs);
+    }
+
+    let read = |path: &Path| {
+        fs::read_to_string(path).map_err(|e| {
+            let e = format!("failed to read `{}`: {}", display_path(path), e);
+            compile_error(&e, span)
+        })
+    };
+    let mut cases = Vec::with_capacity(inputs.len());
+    let mut files = Vec::with_capacity(inputs.len());
+    let mut include = TokenStream::new();
+    for (stem, input_path) in &inputs {
+        let output_path = &outputs[stem];
+        cases.push(conformance_format::Case::new(
+            sanitize_ident(stem).to_string(),
+            cases.len(),
+            1,
+            Cow::Owned(read(input_path)?),
+            Cow::Owned(read(output_path)?),
+        ));
+        files.push(display_path(input_path));
+        for path in &[input_path, output_path] {
+            let path = path.to_string_lossy().to_string();
+            include.extend(quote!(
+                const _: &str = ::std::include_str!(#path);
+            ));
+        }
+    }
+    let mut tests = tests_of(
+        cases,
+        input_dir,
+        &display_path(input_dir),
+        "",
+        span,
+        options,
+    )?;
+    // each test is reported at its input file
+    for (test, file) in tests.iter_mut().zip(files) {
+        test.file = file;
+    }
+    Ok((tests, include))
+}
+
+/// The tests given inline by `cases`, as if read from a file at `file_path` shown as `cases`,
+/// whose lines are the cases, counting from 1.
+pub(crate) fn inline_tests(
+    cases: &[InlineCase],
+    file_path: &Path,
+    span: Span,
+    options: &Options,
+) -> Result<Vec<Test>, TokenStream> {
+    let cases: Vec<_> = cases
+        .iter()
+        .enumerate()
+        .map(|(index, case)| {
+            let input = case.input.value();
+            let output = case
+                .expected
+                .as_ref()
+                .map_or_else(|| input.clone(), |e| e.value());
+            conformance_format::Case::new(
+                case.name.value(),
+                index,
+                index + 1,
+                Cow::Owned(input),
+                Cow::Owned(output),
+            )
+        })
+        .collect();
+    reject_empty_sections(&cases, span, options)?;
+    reject_missing_meta(&cases, "cases", span, options)?;
+    tests_of(cases, file_path, "cases", "", span, options)
+}
+
+/// The tests of `cases`, read from `file_path`, shown as `file`, whose full contents are
+/// `sliceable` if bodies may be sliced out of it.
+fn tests_of(
+    cases: Vec<conformance_format::Case<'_>>,
+    file_path: &Path,
+    file: &str,
+    sliceable: &str,
+    span: Span,
+    options: &Options,
+) -> Result<Vec<Test>, TokenStream> {
+    let mut tests = Vec::with_capacity(cases.len());
+    let mut errs = TokenStream::new();
+    // the case name of the test given each identifier
+    let mut idents: BTreeMap<String, String> = BTreeMap::new();
+
+    for case in cases {
+        // bodies substituted into by a `@table` are no longer slices of the file
+        let body = |section: &Cow<'_, str>| {
+            let source = match section {
+                Cow::Borrowed(_) => sliceable,
+                Cow::Owned(_) => "",
+            };
+            Body::new(source, section, trim_body(section, options))
+        };
+        let input = body(&case.input);
+        let equivalents = case.equivalents.iter().map(body).collect();
+        let (output, output_file) = match (&case.output_file, &case.output_bin) {
+            (Some(golden), _) => match read_golden(file_path, golden) {
+                Ok((path, text)) => (Body::new("", "", trim_body(&text, options)), Some(path)),
+                Err(e) => {
+                    let e = format!("test `{}` has `@file.gz {}`, but {}", case.name, golden, e);
+                    errs.extend(compile_error(&e, span));
+                    continue;
+                }
+            },
+            (None, Some(golden)) => match read_binary_golden(file_path, golden, options) {
+                Ok((path, text)) => (Body::new("", "", text), Some(path)),
+                Err(e) => {
+                    let e = format!("test `{}` has `@bin {}`, but {}", case.name, golden, e);
+                    errs.extend(compile_error(&e, span));
+                    continue;
+                }
+            },
+            (None, None) => (body(&case.output), None),
+        };
+
+        let directives = [!case.modes.is_empty(), case.tolerance.is_some(), case.expr];
+        let broken = DIRECTIVE_RESTRICTIONS
+            .iter()
+            .zip(directives.iter())
+            .filter(|(_, given)| **given)
+            .find_map(|((directive, restriction), _)| {
+                let broken = restriction.broken(options, None)?;
+                Some((directive, broken))
+            });
+        if let Some((directive, broken)) = broken {
+            errs.extend(compile_error(
+                &format!(
+                    "test `{}` {}, which can only be used in {}",
+                    case.name, directive, broken
+                ),
+                span,
+            ));
+            continue;
+        }
+
+        if case.error_at.is_some() && options.error_pos.is_none() {
+            errs.extend(compile_error(
+                &format!(
+                    "test `{}` has `@error_at`, but no `error_pos` option \
+                     gives the positions of errors",
+                    case.name
+                ),
+                span,
+            ));
+            continue;
+        }
+
+        if case.error_kind.is_some() && options.error_kind.is_none() {
+            errs.extend(compile_error(
+                &format!(
+                    "test `{}` has `@error kind=...`, but no `error_kind` option \
+                     gives the kinds of errors",
+                    case.name
+                ),
+                span,
+            ));
+            continue;
+        }
+
+        if case.incomplete && options.incomplete.is_none() {
+            errs.extend(compile_error(
+                &format!(
+                    "test `{}` is `@incomplete`, but no `incomplete` option tells \
+                     whether `de` needs more data",
+                    case.name
+                ),
+                span,
+            ));
+            continue;
+        }
+
+        let expr = if case.expr {
+            match syn::parse_str::<syn::Expr>(&output.text) {
+                Ok(expr) => Some(expr),
+                Err(e) => {
+                    let e = format!(
+                        "test `{}` (line {}) is `@expr`, but its output isn't a Rust expression: {}",
+                        case.name, case.line, e
+                    );
+                    errs.extend(compile_error(&e, span));
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
+        let parse_override = |path: &Option<String>, directive| match path {
+            Some(path) => syn::parse_str::<syn::ExprPath>(path)
+                .map(Some)
+                .map_err(|e| {
+                    format!(
+                        "test `{}` (line {}) has `@{} {}`, but that isn't a path: {}",
+                        case.name, case.line, directive, path, e
+                    )
+                }),
+            None => Ok(None),
+        };
+        let (ser, de) = match (
+            parse_override(&case.ser, "ser"),
+            parse_override(&case.de, "de"),
+        ) {
+            (Ok(ser), Ok(de)) => (ser, de),
+            (Err(e), _) | (_, Err(e)) => {
+                errs.extend(compile_error(&e, span));
+                continue;
+            }
+        };
+
+        if !case.warnings.is_empty() {
+            let conflict = if options.warnings.is_none() {
+                Some("but no `warnings` option gives the warnings of a value")
+            } else if case.error || case.incomplete {
+                Some("but the input has no value to give warnings")
+            } else {
+                None
+            };
+            if let Some(conflict) = conflict {
+                let e = format!(
+                    "test `{}` (line {}) has `@warn`, {}",
+                    case.name, case.line, conflict
+                );
+                errs.extend(compile_error(&e, span));
+                continue;
+            }
+        }
+
+        if case.error && options.de_infallible {
+            errs.extend(compile_error(
+                &format!(
+                    "test `{}` is an `@error` test, but `de` is infallible",
+                    case.name
+                ),
+                span,
+            ));
+            continue;
+        }
+
+        // an explicit id is sanitized, unlike a name, which must already be valid
+        let name = match &case.id {
+            Some(id) => id
+                .chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                .collect(),
+            None => case.name.replace(' ', "_"),
+        };
+        let name = if options.name_includes_hash {
+            format!("{}_{:04x}", name, short_hash(&input.text))
+        } else {
+            name
+        };
+        let name =
+            match syn::parse_str::<syn::Ident>(&format!("{}{}", options.ident_prefix(), name)) {
+                Ok(name) => name,
+                Err(_) => {
+                    errs.extend(compile_error(
+                        &format!("`{}` is not a valid test name identifier", name),
+                        span,
+                    ));
+                    continue;
+                }
+            };
+        let case_name = case.id.as_ref().unwrap_or(&case.name).clone();
+        let (name, similar_to) = match idents.get(&name.to_string()) {
+            None => (name, None),
+            Some(earlier) => {
+                // identical names are only allowed when told apart by hash, and collide by chance
+                let similar_to = Some(earlier.clone()).filter(|earlier| *earlier != case_name);
+                let name = (2_usize..)
+                    .map(|i| quote::format_ident!("{}_{}", name, i))
+                    .find(|name| !idents.contains_key(&name.to_string()))
+                    .unwrap();
+                (name, similar_to)
+            }
+        };
+        idents.insert(name.to_string(), case_name);
+
+        let excluded = options.exclude.iter().any(|excluded| {
+            let excluded = excluded.value();
+            excluded == case.name || case.id.as_ref() == Some(&excluded)
+        });
+        let ignore = case
+            .ignore
+            .or_else(|| excluded.then(|| String::from("excluded by the `exclude` option")));
+
+        tests.push(Test {
+            name,
+            title: case.name,
+            id: case.id,
+            input,
+            equivalents,
+            error: case.error,
+            error_kind: case.error_kind,
+            error_at: case.error_at,
+            incomplete: case.incomplete,
+            expr,
+            ignore,
+            xfail: case.xfail,
+            tolerance: case.tolerance.map(|tolerance| tolerance.parse().unwrap()),
+            file: file.to_string(),
+            line: case.line,
+            group: case.group,
+            output,
+            output_file,
+            repeat: case.repeat,
+            similar_to,
+            modes: case.modes,
+            ser,
+            de,
+            warnings: case.warnings,
+            meta: case.meta,
+            baseline_version: None,
+            duplicates: Vec::new(),
+        })
+    }
+
+    if options.check_consistency {
+        errs.extend(inconsistencies(&tests, span));
+    }
+
+    // a tripwire for a bad separator merging cases, or an edit dropping one
+    match options.expect_cases {
+        Some(expected) if errs.is_empty() && tests.len() != expected => {
+            let e = format!(
+                "expected {} cases in `{}`, but found {}",
+                expected,
+                file,
+                tests.len()
+            );
+            errs.extend(compile_error(&e, span));
+        }
+        _ => (),
+    }
+
+    // after counting, as `expect_cases` counts the cases of the file
+    if options.dedup {
+        dedup(&mut tests);
+    }
+
+    if errs.is_empty() && options.split_includes {
+        split_tests(&mut tests, file, span)?;
+    }
+
+    if errs.is_empty() {
+        Ok(tests)
+    } else {
+        Err(errs)
+    }
+}
+
+/// Write the long bodies of `tests`, read from `file`, to their own files in `OUT_DIR`.
+///
+/// Editing one case then only changes the included file of that case, rather than
+/// the constant of the whole file, which the other cases would otherwise be sliced from.
+fn split_tests(tests: &mut [Test], file: &str, span: Span) -> Result<(), TokenStream> {
+    let out_dir = env::var_os("OUT_DIR").ok_or_else(|| {
+        compile_error(
+            "`split_includes` needs $OUT_DIR; add a build script, even an empty one",
+            span,
+        )
+    })?;
+    let dir = Path::new(&out_dir)
+        .join("conformance")
+        .join(sanitize_ident(file).to_string());
+    let error = |e: std::io::Error| {
+        let e = format!("failed to write to `{}`: {}", dir.display(), e);
+        compile_error(&e, span)
+    };
+    fs::create_dir_all(&dir).map_err(error)?;
+    for test in tests {
+        let name = &test.name;
+        test.input
+            .split_into(dir.join(format!("{}.input", name)))
+            .map_err(error)?;
+        for (i, equivalent) in test.equivalents.iter_mut().enumerate() {
+            equivalent
+                .split_into(dir.join(format!("{}.input{}", name, i + 2)))
+                .map_err(error)?;
+        }
+        test.output
+            .split_into(dir.join(format!("{}.output", name)))
+            .map_err(error)?;
+    }
+    Ok(())
+}
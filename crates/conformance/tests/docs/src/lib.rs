@@ -0,0 +1,12 @@
+//! The docs of this crate, even of its private items, only show `lines` and `split`.
+
+/// The lines of `s`.
+pub fn lines(s: &str) -> Vec<String> {
+    split(s)
+}
+
+/// Split `s` into its lines.
+#[conformance::tests(exact, serde=json, file="lines.json.test", always, index)]
+fn split(s: &str) -> Vec<String> {
+    s.lines().map(String::from).collect()
+}
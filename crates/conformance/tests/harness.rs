@@ -0,0 +1,2131 @@
+fn lines(s: &str) -> Vec<String> {
+    s.lines().map(String::from).collect()
+}
+
+/// Plain text "serialization", for transforms which produce text directly.
+mod text {
+    pub fn to_string<T: ToString>(value: &T) -> String {
+        value.to_string()
+    }
+
+    pub fn from_str<T: for<'a> From<&'a str>>(s: &str) -> T {
+        T::from(s)
+    }
+}
+
+#[conformance::tests(exact, serde=json, file="tests/dedent.json.test", dedent, strict_format)]
+fn dedent_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[conformance::tests(exact, serde=json, file="tests/dedent_limit.json.test", dedent = 4)]
+fn dedent_limit_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+mod fallibility {
+    use serde::{de::DeserializeOwned, Serialize};
+
+    fn to_string<T: Serialize>(value: &T) -> String {
+        json::to_string(value).unwrap()
+    }
+
+    fn from_str<T: DeserializeOwned>(s: &str) -> T {
+        json::from_str(s).unwrap()
+    }
+
+    mod fallible {
+        #[conformance::tests(exact, serde=json, file="tests/fallibility.json.test")]
+        fn lines(s: &str) -> Vec<String> {
+            crate::lines(s)
+        }
+    }
+
+    mod de_infallible {
+        #[conformance::tests(exact, serde=json, de=super::from_str, file="tests/fallibility.json.test", de_infallible)]
+        fn lines(s: &str) -> Vec<String> {
+            crate::lines(s)
+        }
+    }
+
+    mod ser_infallible {
+        #[conformance::tests(exact, serde=json, ser=super::to_string, file="tests/fallibility.json.test", ser_infallible)]
+        fn lines(s: &str) -> Vec<String> {
+            crate::lines(s)
+        }
+    }
+
+    mod infallible {
+        #[conformance::tests(exact, ser=super::to_string, de=super::from_str, file="tests/fallibility.json.test", ser_infallible, de_infallible)]
+        fn lines(s: &str) -> Vec<String> {
+            crate::lines(s)
+        }
+    }
+}
+
+#[conformance::tests(exact, serde=json, dir="tests/cases", check_consistency)]
+fn dir_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+mod extension {
+    #[conformance::tests(exact, serde=json, dir="tests/cases", extension="txt", index)]
+    fn dir_lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+}
+
+#[test]
+fn first_difference_is_reported() {
+    let error =
+        std::panic::catch_unwind(|| dedent_json(r#"["one", "two"]"#, "one\ntwi")).unwrap_err();
+    let message = error.downcast_ref::<String>().unwrap();
+    assert!(message.contains("first difference at byte 10 (line 1, col 11)"));
+    assert!(message.contains(concat!(
+        "\n  actual: [\"one\",\"twi\"]",
+        "\nexpected: [\"one\",\"two\"]",
+        "\n                    ^",
+    )));
+}
+
+#[conformance::tests(exact, serde=json, file="tests/repeat.json.test")]
+fn repeat_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[conformance::tests(exact, serde=json, file="tests/large.json.test")]
+fn large_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[conformance::tests(exact, serde=json, file="tests/table.json.test")]
+fn table_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+mod strip_ansi {
+    fn render(s: &str) -> String {
+        let words = s
+            .split_whitespace()
+            .map(|word| format!("\x1b[1;3{}m{}\x1b[0m", word.len() % 8, word));
+        words.collect::<Vec<_>>().join(" ") + "\x1b]0;title\x07\x1b["
+    }
+
+    #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/strip_ansi.txt.test", ser_infallible, de_infallible, strip_ansi, allow_identical)]
+    fn colored(s: &str) -> String {
+        render(s)
+    }
+}
+
+mod fixpoint {
+    /// Halves each run of spaces, so the output only stops changing once re-parsed enough.
+    fn halve_spaces<T: AsRef<str>>(s: &T) -> String {
+        s.as_ref().replace("  ", " ")
+    }
+
+    #[conformance::tests(exact, ser=halve_spaces, de=crate::text::from_str, file="tests/fixpoint.txt.test", ser_infallible, de_infallible, fixpoint, allow_identical)]
+    fn text() -> String {}
+
+    #[test]
+    fn unstable_output_is_reported() {
+        let e = fixpoint_txt_eight_spaces().unwrap_err().to_string();
+        assert!(
+            e.ends_with(
+                "the serialized output didn't reach a fixed point within 2 re-parses through `de`:\n\
+                 before: \"a  b\"\n after: \"a b\""
+            ),
+            "{}",
+            e
+        );
+    }
+}
+
+mod consume_all {
+    use serde::de::DeserializeOwned;
+
+    /// Deserializes the first JSON value of `s`, ignoring whatever follows it.
+    fn first<T: DeserializeOwned>(s: &str) -> json::Result<T> {
+        let mut values = json::Deserializer::from_str(s).into_iter();
+        values.next().unwrap_or_else(|| json::from_str(s))
+    }
+
+    /// How many bytes of `s` follow its first JSON value, which `first` ignores.
+    fn remaining(s: &str) -> usize {
+        let mut values = json::Deserializer::from_str(s).into_iter::<json::Value>();
+        match values.next() {
+            Some(Ok(_)) => s.len() - values.byte_offset(),
+            _ => 0,
+        }
+    }
+
+    #[conformance::tests(exact, ser=json::to_string, de=first, file="tests/consume_all.json.test", consume_all, remaining = remaining)]
+    fn strings() -> Vec<String> {}
+
+    #[test]
+    fn trailing_input_is_reported() {
+        let e = consume_all_json_trailing_value().unwrap_err().to_string();
+        assert!(
+            e.ends_with("the input wasn't read whole: 6 byte(s) were left, \" [\\\"b\\\"]\""),
+            "{}",
+            e
+        );
+    }
+}
+
+mod yaml_toplevel_unordered {
+    use std::collections::BTreeMap;
+
+    /// Maps the first word of each line to the rest, with the keys serialized in sorted order.
+    #[conformance::tests(yaml_toplevel_unordered, serde=yaml, file="tests/toplevel.yaml.test")]
+    fn words(s: &str) -> BTreeMap<String, Vec<String>> {
+        let lines = s.lines().filter_map(|line| {
+            let mut words = line.split_whitespace().map(String::from);
+            Some((words.next()?, words.collect()))
+        });
+        lines.collect()
+    }
+
+    #[test]
+    fn nested_order_is_reported() {
+        let e = toplevel_yaml_nested_order_matters().unwrap_err().to_string();
+        assert!(e.contains(": top-level key `a` has a different value\n"), "{}", e);
+    }
+
+    #[test]
+    fn missing_key_is_reported() {
+        let e = toplevel_yaml_missing_key().unwrap_err().to_string();
+        assert!(e.contains(": top-level key `b` is missing\n"), "{}", e);
+    }
+}
+
+/// The integers of `tests/msgpack.test` are written wider than `rmpv` writes them.
+mod msgpack {
+    /// MessagePack documents written in hex, as the output sections are.
+    mod hex {
+        fn value(json: &json::Value) -> rmpv::Value {
+            match json {
+                json::Value::Null => rmpv::Value::Nil,
+                json::Value::Bool(b) => rmpv::Value::from(*b),
+                json::Value::Number(n) => match n.as_u64() {
+                    Some(n) => rmpv::Value::from(n),
+                    None => rmpv::Value::from(n.as_f64().unwrap()),
+                },
+                json::Value::String(s) => rmpv::Value::from(s.as_str()),
+                json::Value::Array(array) => rmpv::Value::Array(array.iter().map(value).collect()),
+                json::Value::Object(map) => rmpv::Value::Map(
+                    map.iter()
+                        .map(|(k, v)| (rmpv::Value::from(k.as_str()), value(v)))
+                        .collect(),
+                ),
+            }
+        }
+
+        pub fn ser(json: &json::Value) -> Result<String, String> {
+            let mut bytes = Vec::new();
+            rmpv::encode::write_value(&mut bytes, &value(json)).map_err(|e| e.to_string())?;
+            let hex: Vec<_> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            Ok(hex.join(" "))
+        }
+
+        pub fn de<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, json::Error> {
+            json::from_str(s)
+        }
+    }
+
+    #[conformance::tests(msgpack, ser = hex::ser, de = hex::de, file = "tests/msgpack.test")]
+    fn value(s: &str) -> json::Value {
+        json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn mismatch_is_reported_at_its_path() {
+        let e = msgpack_nested_mismatch().unwrap_err().to_string();
+        assert!(e.contains(": at `$[1][\"id\"]`, expected 4, found 3\n"), "{}", e);
+    }
+}
+
+/// The integers of `tests/cbor.test` are written wider than `ciborium` writes them.
+mod cbor {
+    /// CBOR documents written in hex, as the output sections are.
+    mod hex {
+        pub fn ser<T: serde::Serialize>(value: &T) -> Result<String, String> {
+            let mut bytes = Vec::new();
+            ciborium::ser::into_writer(value, &mut bytes).map_err(|e| e.to_string())?;
+            let hex: Vec<_> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            Ok(hex.join(" "))
+        }
+
+        pub fn de<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, json::Error> {
+            json::from_str(s)
+        }
+    }
+
+    #[conformance::tests(cbor, ser = hex::ser, de = hex::de, file = "tests/cbor.test")]
+    fn value(s: &str) -> json::Value {
+        json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn mismatch_is_reported_at_its_path() {
+        let e = cbor_nested_mismatch().unwrap_err().to_string();
+        assert!(e.contains(": at `$[1][\"id\"]`, expected 4, found 3\n"), "{}", e);
+    }
+}
+
+mod xml {
+    /// An object as an `item` element, with its arrays as child elements named for
+    /// their singular and its other members as attributes.
+    mod item {
+        pub fn ser(item: &json::Value) -> Result<String, String> {
+            let (mut attributes, mut children) = (String::new(), String::new());
+            for (key, value) in item.as_object().ok_or("not an object")? {
+                match value {
+                    json::Value::Array(array) => {
+                        let name = key.trim_end_matches('s');
+                        for child in array {
+                            let text = child.as_str().ok_or("not a string")?;
+                            children.push_str(&format!("<{0}>{1}</{0}>", name, text));
+                        }
+                    }
+                    json::Value::String(s) => attributes.push_str(&format!(" {}=\"{}\"", key, s)),
+                    value => attributes.push_str(&format!(" {}=\"{}\"", key, value)),
+                }
+            }
+            if children.is_empty() {
+                Ok(format!("<item{}/>", attributes))
+            } else {
+                Ok(format!("<item{}>{}</item>", attributes, children))
+            }
+        }
+
+        pub fn de<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, json::Error> {
+            json::from_str(s)
+        }
+    }
+
+    #[conformance::tests(xml, ser = item::ser, de = item::de, file = "tests/xml.test")]
+    fn value(s: &str) -> json::Value {
+        json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn mismatch_is_reported_at_its_path() {
+        let e = xml_different_text().unwrap_err().to_string();
+        assert!(
+            e.contains(": at `/item/tag[2]/text()`: text is \"c\", expected \"b\"\n"),
+            "{}",
+            e
+        );
+    }
+}
+
+/// The words of any input grown from those of the file round-trip through JSON.
+#[conformance::quickcheck(serde = json, file = "tests/ids.json.test", tests = 50)]
+fn quickcheck_words(s: &str) -> Vec<String> {
+    s.split_whitespace().map(String::from).collect()
+}
+
+mod loose_scalars {
+    #[conformance::tests(exact, serde=json, file="tests/loose_scalars.json.test", loose_scalars)]
+    fn value(s: &str) -> json::Value {
+        json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn uncoercible_scalars_differ() {
+        let cases = [
+            loose_scalars_json_leading_zero,
+            loose_scalars_json_whitespace,
+            loose_scalars_json_integer_and_fraction,
+            loose_scalars_json_null,
+            loose_scalars_json_number_and_boolean,
+        ];
+        for case in cases.iter() {
+            assert!(std::panic::catch_unwind(case).is_err());
+        }
+    }
+}
+
+/// The same corpus, with the lengths of words in a `HashMap`, then in a `BTreeMap`.
+mod types {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        iter::FromIterator,
+    };
+
+    #[conformance::tests(exact, serde=json, file="tests/types.json.test", sort_keys, types = [HashMap<String, usize>, BTreeMap<String, usize>])]
+    fn lengths<M: FromIterator<(String, usize)>>(s: &str) -> M {
+        s.split_whitespace().map(|word| (String::from(word), word.len())).collect()
+    }
+}
+
+/// Every case listed in `tests/required.txt` is in the corpus, by name or `@id`.
+mod require_cases {
+    #[conformance::tests(exact, serde=json, file="tests/ids.json.test", require_cases = "tests/required.txt", warn_extra_cases)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+}
+
+/// A function returning nothing is tested as returning `()`, which `serde_json` writes as `null`.
+mod unit {
+    #[conformance::tests(exact, serde=json, file="tests/unit.json.test")]
+    fn validate(s: &str) {
+        assert!(!s.is_empty());
+    }
+
+    #[test]
+    fn other_outputs_are_rejected() {
+        assert!(std::panic::catch_unwind(unit_json_not_null).is_err());
+    }
+}
+
+/// Arithmetic spaced out by the tested function, and written with any spacing in the outputs.
+mod tokenize {
+    /// The numbers and operators of an expression, without the whitespace between them.
+    fn tokens(s: &str) -> Vec<String> {
+        let mut tokens: Vec<String> = Vec::new();
+        let mut in_number = false;
+        for c in s.chars() {
+            match tokens.last_mut() {
+                Some(number) if in_number && c.is_ascii_digit() => number.push(c),
+                _ if c.is_whitespace() => {}
+                _ => tokens.push(String::from(c)),
+            }
+            in_number = c.is_ascii_digit();
+        }
+        tokens
+    }
+
+    #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/tokens.txt.test", ser_infallible, de_infallible, tokenize = tokens)]
+    fn spaced(s: &str) -> String {
+        tokens(s).join(" ")
+    }
+
+    #[test]
+    fn token_mismatches_are_reported() {
+        let e = std::panic::catch_unwind(tokens_txt_different_operator).unwrap_err();
+        let e = e.downcast_ref::<String>().unwrap();
+        assert!(e.contains(r#"the outputs differ at token 1: actual has "+", expected has "-""#), "{}", e);
+        let e = std::panic::catch_unwind(tokens_txt_missing_operand).unwrap_err();
+        let e = e.downcast_ref::<String>().unwrap();
+        assert!(e.contains(r#"at token 2: actual has "2", expected has no more tokens"#), "{}", e);
+    }
+}
+
+/// An `@xfail` case which fails passes, and one which passes fails.
+mod xfail {
+    #[conformance::tests(exact, serde=json, file="tests/xfail.json.test")]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn unexpected_passes_fail() {
+        let e = xfail_json_fixed_bug().unwrap_err().to_string();
+        assert!(e.ends_with(": test `fixed bug`: unexpected pass, remove `@xfail`"), "{}", e);
+    }
+}
+
+mod sorted_lines {
+    use std::collections::HashSet;
+
+    /// The flags of a command line, one per line, in the arbitrary order of a `HashSet`.
+    fn flags(s: &str) -> String {
+        let flags: HashSet<_> = s.split_whitespace().filter(|arg| arg.starts_with('-')).collect();
+        flags.into_iter().collect::<Vec<_>>().join("\n")
+    }
+
+    #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/sorted_lines.txt.test", ser_infallible, de_infallible, sorted_lines, ci = gitlab)]
+    fn command(s: &str) -> String {
+        flags(s)
+    }
+}
+
+mod each {
+    use std::cell::Cell;
+
+    thread_local! {
+        /// How many words have been numbered on this thread, which each case starts over.
+        static NUMBERED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn reset() {
+        NUMBERED.with(|numbered| numbered.set(0));
+    }
+
+    #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/each.txt.test", ser_infallible, de_infallible, before_each = reset(), after_each = || assert!(NUMBERED.with(Cell::get) > 0), ci = github)]
+    fn numbered(s: &str) -> String {
+        let words = s.split_whitespace().map(|word| {
+            let n = NUMBERED.with(|numbered| numbered.get() + 1);
+            NUMBERED.with(|numbered| numbered.set(n));
+            format!("{}. {}", n, word)
+        });
+        words.collect::<Vec<_>>().join("\n")
+    }
+}
+
+#[conformance::tests(exact, ser=text::to_string, de=text::from_str, file="tests/raw.txt.test", ser_infallible, de_infallible, raw, literal_expected, allow_identical)]
+fn raw_text(s: &str) -> String {
+    format!("{}\n", s.trim_end())
+}
+
+mod extra_args {
+    use serde::{de::DeserializeOwned, Serialize};
+
+    struct Config {
+        pretty: bool,
+    }
+
+    const CONFIG: Config = Config { pretty: true };
+
+    fn ser<T: Serialize>(value: &T, config: &Config) -> json::Result<String> {
+        if config.pretty {
+            json::to_string_pretty(value)
+        } else {
+            json::to_string(value)
+        }
+    }
+
+    fn de<T: DeserializeOwned>(s: &str, prompt: &str) -> json::Result<T> {
+        json::from_str(s.trim_start_matches(prompt))
+    }
+
+    #[conformance::tests(exact, ser=ser, de=de, value=json::Value, file="tests/extra_args.json.test", ser_args=(&CONFIG), de_args=("> "))]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+}
+
+mod project {
+    use json::Value;
+
+    #[conformance::tests(exact, serde=json, file="tests/project.json.test", project="/words")]
+    fn summary(s: &str) -> Value {
+        let words: Vec<_> = s.split_whitespace().collect();
+        json::json!({ "words": words, "count": words.len() })
+    }
+
+    #[test]
+    fn unresolved_pointer_is_reported() {
+        let error = project_json(r#"{ "other": [] }"#, "a b c").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "in expected output: JSON Pointer `/words` does not resolve; \
+             the value at `` has keys `other`",
+        );
+    }
+}
+
+mod skip_if_missing {
+    // the warning for each missing source is expected
+    #![allow(deprecated)]
+
+    #[conformance::tests(exact, serde=json, file="tests/missing.json.test", skip_if_missing)]
+    fn file_lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[conformance::tests(exact, serde=json, dir="tests/missing", skip_if_missing)]
+    fn dir_lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+}
+
+mod predicate {
+    fn three_words(actual: &str) -> Result<(), String> {
+        let words: Vec<String> = json::from_str(actual).map_err(|e| e.to_string())?;
+        match words.len() {
+            3 => Ok(()),
+            n => Err(format!("got {} words", n)),
+        }
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/predicate.json.test", predicate=three_words)]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    #[test]
+    fn failure_is_described() {
+        let error = predicate_json("three words", "a b").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "predicate `three_words` failed: got 2 words\nexpected: three words",
+        );
+    }
+}
+
+mod ids {
+    #[conformance::tests(exact, serde=json, file="tests/ids.json.test")]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn case_names_keep_ids() {
+        assert_eq!(IDS_JSON_CASE_NAMES, ["RFC8259-6.1", "plain name"]);
+        assert_eq!(crate::cases::nested::inner_json::CASE_NAMES, ["nested"]);
+    }
+
+    #[test]
+    fn case_meta_parallels_names() {
+        assert_eq!(IDS_JSON_CASE_META.len(), IDS_JSON_CASE_NAMES.len());
+        assert_eq!(IDS_JSON_CASE_META[0], [("section", "6"), ("spec", "RFC8259")]);
+        assert!(IDS_JSON_CASE_META[1].is_empty());
+        assert_eq!(crate::cases::nested::inner_json::CASE_META, [&[]]);
+    }
+
+    #[test]
+    fn id_names_test() {
+        ids_json_RFC8259_6_1().unwrap();
+    }
+}
+
+mod unordered_arrays {
+    use std::collections::HashSet;
+
+    #[conformance::tests(exact, serde=json, file="tests/unordered_arrays.json.test", unordered_arrays)]
+    fn sets(s: &str) -> Vec<HashSet<&str>> {
+        s.lines()
+            .map(|line| line.split_whitespace().collect())
+            .collect()
+    }
+}
+
+mod name_includes_hash {
+    #[conformance::tests(exact, serde=json, file="tests/table.json.test", name_includes_hash)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn name_has_hash_of_input() {
+        table_json_pair_letters_eff4().unwrap();
+    }
+}
+
+mod equivalent {
+    #[conformance::tests(exact, serde=json, file="tests/equivalent.json.test")]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    #[test]
+    fn different_outputs_are_reported() {
+        let error = std::panic::catch_unwind(|| equivalent_json_equivalent(&["a b", "a b", "a c"]))
+            .unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.contains("input 3 serializes differently from input 1"));
+        assert!(message.contains(r#"["a","c"]"#) && message.contains(r#"["a","b"]"#));
+    }
+}
+
+#[conformance::tests(exact, serde=json, file="tests/compressed.json.test.gz")]
+fn compressed_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[conformance::tests(idempotent, serde=json, file="tests/idempotent.json.test")]
+fn parse_json(s: &str) -> json::Value {
+    json::from_str(s).unwrap()
+}
+
+#[conformance::tests(exact, serde=json, file="tests/error.json.test")]
+fn error_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[test]
+fn error_must_contain_expected() {
+    let error = error_json_error("expected value", r#"["one""#).unwrap_err();
+    assert!(error.to_string().starts_with("`de` error"));
+}
+
+mod de_option {
+    fn parse<T: std::str::FromStr>(s: &str) -> Option<T> {
+        s.parse().ok()
+    }
+
+    #[conformance::tests(exact, ser=crate::text::to_string, de=parse, file="tests/de_option.txt.test", ser_infallible, de_option)]
+    fn double(s: &str) -> i64 {
+        2 * parse::<i64>(s).unwrap()
+    }
+
+    #[test]
+    fn none_is_an_error() {
+        let error = de_option_txt("twelve", "6").unwrap_err();
+        assert_eq!(error.to_string(), "`de` returned `None`");
+        let error = de_option_txt_error("", "12").unwrap_err();
+        assert_eq!(error.to_string(), "`de` accepted the input");
+    }
+}
+
+mod message {
+    #[conformance::tests(exact, serde=json, file="tests/message.json.test", message="{name} ({file}:{line}): got {actual}, expected {{{expected}}}")]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    #[test]
+    fn template_is_formatted() {
+        let error = std::panic::catch_unwind(|| {
+            message_json(
+                r#"["one"]"#,
+                "one two",
+                "two words",
+                "tests/message.json.test",
+                1,
+            )
+        })
+        .unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert_eq!(
+            message,
+            r#"two words (tests/message.json.test:1): got ["one","two"], expected {["one"]}"#
+        );
+    }
+}
+
+mod retry {
+    use {serde::Serialize, std::cell::Cell};
+
+    thread_local!(static CALLS: Cell<usize> = const { Cell::new(0) });
+
+    /// Only serializes correctly on every third call, like an unlucky `HashMap` order.
+    fn flaky<T: Serialize>(value: &T) -> json::Result<String> {
+        let calls = CALLS.with(|calls| calls.replace(calls.get() + 1));
+        let s = json::to_string(value)?;
+        Ok(if calls % 3 == 2 {
+            s
+        } else {
+            s.replace(',', ", ")
+        })
+    }
+
+    #[conformance::tests(exact, ser=flaky, de=json::from_str, value=json::Value, file="tests/retry.json.test", literal_expected, retry = 2)]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    #[test]
+    fn retries_are_limited() {
+        CALLS.with(|calls| calls.set(0));
+        retry_json(r#"["a","b"]"#, "a b").unwrap();
+        std::panic::catch_unwind(|| retry_json(r#"["a","c"]"#, "a b")).unwrap_err();
+        assert_eq!(CALLS.with(Cell::get), 6);
+    }
+}
+
+mod ignore_keys {
+    use {
+        serde::Serialize,
+        std::sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// A counter, so that every run gives different `id`s.
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+    fn next_id() -> usize {
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    #[derive(Serialize)]
+    struct Word<'a> {
+        id: usize,
+        text: &'a str,
+    }
+
+    #[derive(Serialize)]
+    struct Document<'a> {
+        id: usize,
+        words: Vec<Word<'a>>,
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/ignore_keys.json.test", ignore_keys = ["id"])]
+    fn identify(s: &str) -> Document<'_> {
+        Document {
+            id: next_id(),
+            words: s
+                .split_whitespace()
+                .map(|text| Word { id: next_id(), text })
+                .collect(),
+        }
+    }
+}
+
+mod value_mut {
+    use {json::Value, serde::Serialize};
+
+    #[derive(Serialize)]
+    struct Stats {
+        mean: f64,
+        count: usize,
+    }
+
+    /// Rounds every float to two decimal places, however deeply nested.
+    fn round(value: &mut Value) {
+        match value {
+            Value::Number(n) if n.is_f64() => {
+                *value = Value::from((n.as_f64().unwrap() * 100.0).round() / 100.0)
+            }
+            Value::Array(array) => array.iter_mut().for_each(round),
+            Value::Object(map) => map.values_mut().for_each(round),
+            _ => (),
+        }
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/value_mut.json.test", value_mut = round)]
+    fn stats(s: &str) -> Stats {
+        let numbers: Vec<f64> = s.split_whitespace().map(|n| n.parse().unwrap()).collect();
+        Stats {
+            mean: numbers.iter().sum::<f64>() / numbers.len() as f64,
+            count: numbers.len(),
+        }
+    }
+
+    /// A `value_mut` function named like the option, and the helper calling it.
+    mod same_name {
+        fn value_mut(value: &mut json::Value) {
+            super::round(value)
+        }
+
+        #[conformance::tests(exact, serde=json, file="tests/value_mut.json.test", value_mut = value_mut)]
+        fn stats(s: &str) -> super::Stats {
+            super::stats(s)
+        }
+    }
+}
+
+/// A complete spec in one file: valid inputs with their outputs, and invalid ones with `@error`,
+/// which are checked against the tested type, as no `value` type is given.
+mod spec {
+    #[conformance::tests(exact, ser=json::to_string, de=json::from_str, file="tests/spec.json.test")]
+    fn strings() -> Vec<String> {}
+
+    #[test]
+    fn both_kinds_of_cases_are_tested() {
+        assert_eq!(SPEC_JSON_CASE_COUNT, 7);
+        spec_json_two_strings().unwrap();
+        spec_json_not_strings().unwrap();
+    }
+}
+
+/// The same spec, with the tests spliced into the module without a tested function.
+mod included {
+    conformance::include_tests! {
+        file = "tests/spec.json.test", ser = json::to_string, de = json::from_str,
+        type = Vec<String>,
+    }
+
+    #[test]
+    fn tests_are_at_module_scope() {
+        assert_eq!(SPEC_JSON_CASE_COUNT, 7);
+        spec_json_two_strings().unwrap();
+    }
+}
+
+mod approx {
+    /// The sum and mean of a JSON array of numbers.
+    #[conformance::tests(approx, serde = json, file = "tests/approx.json.test")]
+    pub(crate) fn stats(s: &str) -> json::Value {
+        let numbers: Vec<f64> = json::from_str(s).unwrap();
+        let sum: f64 = numbers.iter().sum();
+        json::json!({"sum": sum, "mean": sum / numbers.len() as f64})
+    }
+
+    #[test]
+    fn mismatch_is_reported_at_its_path() {
+        let e = approx_json_sum_off().unwrap_err().to_string();
+        assert!(
+            e.contains(": at `$.sum`, expected 0.4, found 0.30000000000000004, more than 1e-9 apart\n"),
+            "{}",
+            e
+        );
+    }
+}
+
+/// Outputs only written differently, which are the same once read.
+mod consistency {
+    #[conformance::tests(exact, serde=json, file="tests/consistency/lines.json.test", check_consistency)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn different_outputs_are_reported() {
+        let error = std::panic::catch_unwind(|| {
+            lines_json_consistent(r#"["a"]"#, r#"[ "b" ]"#, ("one", "other"))
+        })
+        .unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(
+            message.contains(
+                "tests `one` and `other` have the same input but expect different outputs; \
+                 first difference at byte 2"
+            ),
+            "{}",
+            message
+        );
+    }
+
+    mod dir {
+        #[conformance::tests(exact, serde=json, dir="tests/consistency", check_consistency)]
+        fn lines(s: &str) -> Vec<String> {
+            crate::lines(s)
+        }
+    }
+
+    mod approx {
+        #[conformance::tests(approx, serde = json, file = "tests/consistency_approx.json.test", check_consistency)]
+        fn stats(s: &str) -> json::Value {
+            super::super::approx::stats(s)
+        }
+    }
+}
+
+mod identity {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    /// The empty body only names the type, which each input is deserialized to.
+    #[conformance::tests(exact, serde=json, file="tests/identity.json.test", literal_expected)]
+    fn point() -> Point {}
+}
+
+mod value_cmp {
+    use json::Value;
+
+    /// Numbers within 1% of each other, and strings in any case, are the same.
+    fn roughly_equal(actual: &Value, expected: &Value) -> bool {
+        match (actual, expected) {
+            (Value::Number(a), Value::Number(b)) => {
+                let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+                (a - b).abs() <= b.abs() / 100.0
+            }
+            (Value::String(a), Value::String(b)) => a.eq_ignore_ascii_case(b),
+            (Value::Array(a), Value::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| roughly_equal(a, b))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Words stay words, and numbers are divided by three.
+    #[conformance::tests(exact, serde=json, file="tests/value_cmp.json.test", value_cmp = roughly_equal)]
+    fn thirds(s: &str) -> Vec<Value> {
+        s.split_whitespace()
+            .map(|word| match word.parse::<f64>() {
+                Ok(n) => Value::from(n / 3.0),
+                Err(_) => Value::from(word.to_uppercase()),
+            })
+            .collect()
+    }
+
+    #[test]
+    #[should_panic(expected = "first difference")]
+    fn differences_are_reported() {
+        value_cmp_json(r#"[0.4]"#, "1").unwrap();
+    }
+}
+
+mod tested_type {
+    use serde::Serialize;
+
+    /// Serializes as its lines, but can't be deserialized.
+    #[derive(Serialize)]
+    #[serde(transparent)]
+    struct Lines(Vec<String>);
+
+    /// The expected outputs are deserialized as the lines, not the wrapper.
+    #[conformance::tests(exact, ser=json::to_string, de=json::from_str, file="tests/ids.json.test",
+        type = Vec<String>)]
+    fn wrapped_lines(s: &str) -> Lines {
+        Lines(crate::lines(s))
+    }
+}
+
+mod aggregate {
+    use std::cell::Cell;
+
+    thread_local! {
+        /// Whether `words` drops its last word, failing every case with one.
+        static BROKEN: Cell<bool> = const { Cell::new(false) };
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/aggregate.json.test", aggregate)]
+    fn words(s: &str) -> Vec<&str> {
+        let mut words: Vec<_> = s.split_whitespace().collect();
+        if BROKEN.with(Cell::get) {
+            words.pop();
+        }
+        words
+    }
+
+    #[test]
+    #[should_panic(expected = "2 of 3 cases failed (1 passed; 1 ignored):\ntests/aggregate.json.test:1: ")]
+    fn failures_are_all_reported() {
+        BROKEN.with(|broken| broken.set(true));
+        aggregate_json_all_cases();
+    }
+}
+
+mod qualified {
+    use std::{fmt, num::ParseIntError, str::FromStr};
+
+    /// A temperature, read in Celsius and written in Fahrenheit.
+    pub struct Temperature(i32);
+
+    impl FromStr for Temperature {
+        type Err = ParseIntError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            s.trim_end_matches('C').parse().map(Temperature)
+        }
+    }
+
+    impl fmt::Display for Temperature {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}F", self.0 * 9 / 5 + 32)
+        }
+    }
+
+    #[conformance::tests(exact, ser=<Temperature as ToString>::to_string,
+        de=<Temperature as FromStr>::from_str, file="tests/qualified.txt.test",
+        ser_infallible, literal_expected)]
+    fn temperature() -> Temperature {}
+}
+
+mod sort_keys {
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Word<'a> {
+        text: &'a str,
+        length: usize,
+    }
+
+    #[derive(Serialize)]
+    struct Summary<'a> {
+        words: Vec<Word<'a>>,
+        count: usize,
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/sort_keys.json.test", literal_expected, sort_keys)]
+    fn summarize(s: &str) -> Summary<'_> {
+        let words: Vec<_> = s
+            .split_whitespace()
+            .map(|text| Word {
+                text,
+                length: text.len(),
+            })
+            .collect();
+        Summary {
+            count: words.len(),
+            words,
+        }
+    }
+
+    #[test]
+    fn keys_are_serialized_unsorted() {
+        let s = json::to_string(&summarize("b a")).unwrap();
+        assert!(s.starts_with(r#"{"words":[{"text":"b","length":1}"#));
+    }
+}
+
+mod transform_turbofish {
+    use std::{fmt::Debug, str::FromStr};
+
+    #[conformance::tests(exact, serde=json, file="tests/generic.json.test", transform_turbofish = <i64>)]
+    fn parse_all<T: FromStr>(s: &str) -> Vec<T>
+    where
+        T::Err: Debug,
+    {
+        s.split_whitespace().map(|s| s.parse().unwrap()).collect()
+    }
+}
+
+mod frontmatter_type {
+    use std::{fmt::Debug, str::FromStr};
+
+    /// Files without a frontmatter `type` use `transform_turbofish`.
+    #[conformance::tests(exact, serde=json, dir="tests/typed", transform_turbofish = <i64>)]
+    fn parse_all<T: FromStr>(s: &str) -> Vec<T>
+    where
+        T::Err: Debug,
+    {
+        s.split_whitespace().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/typed/floats.json.test")]
+    fn parse_floats<T: FromStr>(s: &str) -> Vec<T>
+    where
+        T::Err: Debug,
+    {
+        parse_all(s)
+    }
+}
+
+#[conformance::tests(exact, serde=json, file="tests/cases/groups.json.test")]
+fn group_words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+mod regex {
+    #[conformance::tests(regex, ser=json::to_string_pretty, de=json::from_str, file="tests/regex.json.test")]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    #[test]
+    fn mismatched_line_is_reported() {
+        let pattern = "\\[\n  \"event\",\n  \"[a-z]+\"\n\\]";
+        let error = regex_json(pattern, "event 2019").unwrap_err();
+        assert!(error.to_string().ends_with(concat!(
+            "first mismatch on line 3",
+            "\n  actual:   \"2019\"",
+            "\n pattern:   \"[a-z]+\"",
+        )));
+        let error = regex_json(r#"\[\]"#, "event 2019").unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("the output has 4 lines, the pattern 1"));
+    }
+}
+
+mod preprocess {
+    fn strip_comments(s: &str) -> String {
+        s.lines()
+            .map(|line| line.split('#').next().unwrap())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn expand_includes(s: &str) -> Result<String, String> {
+        match s.strip_prefix("@include ") {
+            Some(name) => Err(format!("no file `{}`", name)),
+            None => Ok(strip_comments(s)),
+        }
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/preprocess.json.test", preprocess=strip_comments)]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    mod fallible {
+        #[conformance::tests(exact, serde=json, file="tests/preprocess.json.test", preprocess=super::expand_includes)]
+        fn words(s: &str) -> Vec<&str> {
+            s.split_whitespace().collect()
+        }
+
+        #[test]
+        fn errors_are_reported() {
+            let error = preprocess_json("[]", "@include x").unwrap_err();
+            assert_eq!(error.to_string(), "`preprocess` failed: no file `x`");
+        }
+    }
+
+    /// A `preprocess` function named like the option, and the helper calling it.
+    mod same_name {
+        fn preprocess(s: &str) -> String {
+            super::strip_comments(s)
+        }
+
+        #[conformance::tests(exact, serde=json, file="tests/preprocess.json.test", preprocess = preprocess)]
+        fn words(s: &str) -> Vec<&str> {
+            s.split_whitespace().collect()
+        }
+    }
+
+    #[test]
+    fn preprocessed_input_is_reported() {
+        let error = std::panic::catch_unwind(|| preprocess_json(r#"["one"]"#, "one two # three"))
+            .unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.contains("\npreprocessed input:\none two \n"));
+    }
+}
+
+mod deterministic {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static PARSES: AtomicUsize = AtomicUsize::new(0);
+
+    /// Numbers the lines of inputs starting with `flaky` by how often it has been called.
+    #[conformance::tests(exact, serde=json, file="tests/repeat.json.test", deterministic)]
+    fn lines(s: &str) -> Vec<String> {
+        let mut lines = crate::lines(s);
+        if s.starts_with("flaky") {
+            lines.push(PARSES.fetch_add(1, Ordering::Relaxed).to_string());
+        }
+        lines
+    }
+
+    #[test]
+    fn divergence_is_reported() {
+        let error = repeat_json(r#"["flaky"]"#, "flaky").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("the second parse of the input serialized differently from the first;"));
+    }
+}
+
+mod ser_stable {
+    use {
+        serde::Serialize,
+        std::sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    static SERIALIZATIONS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Appends how often it has been called to values starting with `flaky`, as a timestamp would.
+    fn to_string<T: Serialize>(value: &T) -> json::Result<String> {
+        let s = json::to_string(value)?;
+        if s.starts_with(r#"["flaky""#) {
+            return Ok(format!("{}{}", s, SERIALIZATIONS.fetch_add(1, Ordering::Relaxed)));
+        }
+        Ok(s)
+    }
+
+    #[conformance::tests(exact, ser=to_string, de=json::from_str, value=Vec<String>, file="tests/repeat.json.test", ser_stable)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn both_serializations_are_reported() {
+        let error = repeat_json(r#"["flaky"]"#, "flaky").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "serializing the same value twice gave different outputs:\n\
+             first:  \"[\\\"flaky\\\"]0\"\n\
+             second: \"[\\\"flaky\\\"]1\""
+        );
+    }
+}
+
+mod max_diff_lines {
+    #[conformance::tests(exact, ser=json::to_string_pretty, de=json::from_str, value=Vec<String>, file="tests/repeat.json.test", max_diff_lines = 3)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn diff_is_truncated() {
+        let error =
+            std::panic::catch_unwind(|| repeat_json(r#"["a", "b", "c", "d"]"#, "a\nx\ny\nz"))
+                .unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.ends_with(
+            "--- expected\n+++ actual\n@@ line 3 @@\n-  \"b\",\n-  \"c\",\n-  \"d\"\n... (3 more lines)"
+        ), "{}", message);
+    }
+}
+
+/// Compares against `cat` and `echo`, standing in for a reference implementation.
+#[cfg(unix)]
+mod oracle {
+    #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/oracle.txt.test", ser_infallible, de_infallible, oracle = "cat {input_file}")]
+    fn words(s: &str) -> String {
+        s.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    mod input {
+        #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/oracle.txt.test", ser_infallible, de_infallible, oracle = "echo {input}")]
+        fn words(s: &str) -> String {
+            super::words(s)
+        }
+
+        #[test]
+        fn mismatch_is_reported() {
+            let error = std::panic::catch_unwind(|| oracle_txt("", "a  b")).unwrap_err();
+            let message = error.downcast_ref::<String>().unwrap();
+            assert!(message.contains("first difference"), "{}", message);
+        }
+    }
+}
+
+/// This crate's own `gzip` feature, as the `slow-tests` feature of a consuming crate would be.
+#[conformance::tests(exact, serde=json, file="tests/ignore.json.test", ignore_unless_feature = "gzip", index)]
+fn ignored(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+mod normalize {
+    /// Compares outputs as sets of lines, ignoring case.
+    fn lowercase_sorted(s: &str) -> String {
+        let mut lines: Vec<String> = s.lines().map(str::to_lowercase).collect();
+        lines.sort();
+        lines.join("\n")
+    }
+
+    #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/normalize.txt.test", ser_infallible, de_infallible, normalize = lowercase_sorted)]
+    fn lines(s: &str) -> String {
+        s.to_string()
+    }
+
+    #[test]
+    fn normalized_outputs_can_still_differ() {
+        assert!(std::panic::catch_unwind(|| normalize_txt("a\nb", "a\nc")).is_err());
+    }
+}
+
+mod strip_bom {
+    #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/bom.txt.test", ser_infallible, de_infallible, allow_identical, strip_bom)]
+    fn with_bom(s: &str) -> String {
+        format!("\u{feff}{}", s)
+    }
+}
+
+/// A `...` line of an output is escaped as `\...`.
+#[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/ellipsis.txt.test", ser_infallible, de_infallible)]
+fn pauses(s: &str) -> String {
+    s.replace(", ", "\n...\n")
+}
+
+/// Sorted words are sorted already, so the outputs round-trip.
+#[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str, file="tests/modes.txt.test", ser_infallible, de_infallible)]
+fn sort_words(s: &str) -> String {
+    let mut words: Vec<_> = s.split_whitespace().collect();
+    words.sort_unstable();
+    words.join(" ")
+}
+
+#[test]
+fn modes_are_all_checked() {
+    let e = modes_txt_failing().unwrap_err().to_string();
+    // the case is pointed at, for an editor to jump to
+    assert!(e.starts_with("tests/modes.txt.test:8: mode `idempotent`: "), "{}", e);
+    assert!(!e.contains("mode `exact`") && !e.contains("mode `roundtrip`"));
+}
+
+mod max_bytes {
+    /// `["ab"]` is 6 bytes.
+    #[conformance::tests(exact, serde=json, file="tests/max_bytes.json.test", max_bytes = 6)]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    #[test]
+    fn budget_is_checked_before_comparing() {
+        let error = max_bytes_json(r#"["abc"]"#, "abc").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "serialized output is 7 bytes, over the budget of 6 bytes"
+        );
+    }
+}
+
+mod single {
+    #[conformance::tests(exact, serde=json, file="tests/single.json.test", single)]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    #[test]
+    fn whole_file_is_one_case() {
+        assert_eq!(SINGLE_JSON_CASE_NAMES, &["single"]);
+        assert_eq!(SINGLE_JSON_CASE_COUNT, 1);
+    }
+}
+
+mod seed {
+    use serde::de::{Deserialize, DeserializeSeed, Deserializer};
+
+    /// Deserializes a number, multiplied by the scale.
+    pub struct Scale(pub u64);
+
+    impl<'de> DeserializeSeed<'de> for Scale {
+        type Value = u64;
+
+        fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<u64, D::Error> {
+            u64::deserialize(deserializer).map(|n| n * self.0)
+        }
+    }
+
+    /// The expected outputs count pairs of words.
+    #[conformance::tests(exact, serde=json, file="tests/seed.json.test", seed = Scale(2))]
+    fn count_words(s: &str) -> u64 {
+        s.split_whitespace().count() as u64
+    }
+}
+
+mod direction {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    /// Only gives the type: the inputs are points as JSON, deserialized with `de`.
+    #[conformance::tests(exact, serde=json, ser=json::to_string_pretty,
+        file="tests/serialize.json.test", direction = serialize)]
+    fn point(s: &str) -> Point {
+        let (x, y) = s.split_once(',').unwrap();
+        Point {
+            x: x.trim().parse().unwrap(),
+            y: y.trim().parse().unwrap(),
+        }
+    }
+}
+
+mod ser_writer {
+    use std::io::{self, Write};
+
+    /// Prettified, where the expected outputs are minified.
+    #[conformance::tests(exact, serde=json, file="tests/ser_writer.json.test",
+        ser_writer = json::to_writer_pretty)]
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    /// Writes each character as a byte, as ISO 8859-1.
+    fn to_latin1<W: Write>(mut writer: W, s: &str) -> io::Result<()> {
+        let bytes: Vec<u8> = s.chars().map(|c| c as u8).collect();
+        writer.write_all(&bytes)
+    }
+
+    /// The outputs aren't UTF-8, which is allowed, so they're compared as bytes.
+    #[conformance::tests(exact, ser=crate::text::to_string, de=crate::text::from_str,
+        file="tests/latin1.txt.test", de_infallible, literal_expected, ser_writer = to_latin1,
+        allow_invalid_utf8)]
+    fn latin1(s: &str) -> String {
+        s.to_string()
+    }
+
+    /// Otherwise, output which isn't UTF-8 fails instead.
+    #[conformance::tests(idempotent, ser=crate::text::to_string, de=crate::text::from_str,
+        file="tests/utf8.txt.test", de_infallible, literal_expected, ser_writer = to_latin1)]
+    fn utf8(s: &str) -> String {
+        s.to_string()
+    }
+
+    #[test]
+    fn invalid_utf8_is_located() {
+        assert_eq!(
+            utf8_txt_accented().unwrap_err().to_string(),
+            r#"tests/utf8.txt.test:1: `to_latin1` wrote invalid UTF-8 at byte 3: b"caf\xe9 au lait""#
+        );
+        utf8_txt_ascii().unwrap();
+    }
+}
+
+/// A borrowed value is serialized as is, and the expected outputs deserialized to `json::Value`.
+#[conformance::tests(exact, serde=json, file="tests/borrowed.json.test")]
+fn first_word(s: &str) -> &str {
+    s.split_whitespace().next().unwrap_or_default()
+}
+
+mod zero_copy {
+    use serde::{Deserialize, Serialize};
+
+    /// Borrows from the string it is deserialized from.
+    #[derive(Serialize, Deserialize)]
+    pub struct Word<'a> {
+        text: &'a str,
+    }
+
+    /// With no `value` type, the expected outputs are deserialized to `Word`s borrowing from
+    /// them, too.
+    #[conformance::tests(exact, ser=json::to_string, de=json::from_str, file="tests/zero_copy.json.test")]
+    fn first_word<'a>(s: &'a str) -> Word<'a> {
+        Word {
+            text: s.split_whitespace().next().unwrap_or_default(),
+        }
+    }
+}
+
+mod stream {
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    /// Deserializes each of the whitespace-separated JSON documents in `s`.
+    fn from_str<'a, T: DeserializeOwned + 'a>(
+        s: &'a str,
+    ) -> impl Iterator<Item = json::Result<T>> + 'a {
+        json::Deserializer::from_str(s).into_iter()
+    }
+
+    /// Only gives the type of each document.
+    #[conformance::tests(exact, ser=json::to_string, de=from_str, file="tests/stream.json.test", stream)]
+    fn point(_: &str) -> Point {
+        unimplemented!()
+    }
+}
+
+mod error_kind {
+    use json::error::Category;
+
+    /// Names the category of a JSON error.
+    fn category(e: &json::Error) -> &'static str {
+        match e.classify() {
+            Category::Io => "Io",
+            Category::Syntax => "Syntax",
+            Category::Data => "Data",
+            Category::Eof => "Eof",
+        }
+    }
+
+    #[conformance::tests(exact, ser=json::to_string, de=json::from_str, value=Vec<String>, file="tests/error_kind.json.test", error_kind = category)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn kinds_are_compared() {
+        let e = error_kind_json_misclassified().unwrap_err().to_string();
+        assert!(
+            e.ends_with(r#"`de` error "invalid type: integer `1`, expected a sequence at line 1 column 1" is of kind `Data`, not `Syntax`"#),
+            "{}",
+            e
+        );
+    }
+
+    /// A classifier named like the kinds it's compared with.
+    mod same_name {
+        fn kind(e: &json::Error) -> &'static str {
+            super::category(e)
+        }
+
+        #[conformance::tests(exact, ser=json::to_string, de=json::from_str, value=Vec<String>, file="tests/error_kind.json.test", error_kind = kind)]
+        fn lines(s: &str) -> Vec<String> {
+            crate::lines(s)
+        }
+    }
+}
+
+mod error_pos {
+    /// Where `serde_json` found the error.
+    fn position(e: &json::Error) -> (usize, usize) {
+        (e.line(), e.column())
+    }
+
+    #[conformance::tests(exact, ser=json::to_string, de=json::from_str, value=Vec<String>, file="tests/error_at.json.test", error_pos = position)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn positions_are_compared() {
+        let e = error_at_json_misplaced().unwrap_err().to_string();
+        assert!(
+            e.ends_with(r#"`de` error "expected value at line 1 column 2" is at 1:2, not 1:1"#),
+            "{}",
+            e
+        );
+    }
+
+    /// A locator named like the positions it's compared with.
+    mod same_name {
+        fn at(e: &json::Error) -> (usize, usize) {
+            super::position(e)
+        }
+
+        #[conformance::tests(exact, ser=json::to_string, de=json::from_str, value=Vec<String>, file="tests/error_at.json.test", error_pos = at)]
+        fn lines(s: &str) -> Vec<String> {
+            crate::lines(s)
+        }
+    }
+}
+
+mod error_ser {
+    use json::error::Category;
+
+    /// What `serde_json` found wrong, and where, as a JSON object.
+    fn describe(e: &json::Error) -> String {
+        let category = match e.classify() {
+            Category::Io => "Io",
+            Category::Syntax => "Syntax",
+            Category::Data => "Data",
+            Category::Eof => "Eof",
+        };
+        json::json!({"category": category, "line": e.line(), "column": e.column()}).to_string()
+    }
+
+    #[conformance::tests(exact, ser=json::to_string, de=json::from_str, value=Vec<String>, file="tests/error_ser.json.test", error_ser = describe)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn serialized_errors_are_compared_whole() {
+        let e = error_ser_json_misplaced().unwrap_err().to_string();
+        assert!(
+            e.ends_with(r#"`de` error serialized by `describe` to "{\"category\":\"Syntax\",\"column\":2,\"line\":1}", not "{\"category\":\"Syntax\",\"column\":1,\"line\":1}""#),
+            "{}",
+            e
+        );
+    }
+
+    /// A serializer named like the error it's given.
+    mod same_name {
+        fn e(e: &json::Error) -> String {
+            super::describe(e)
+        }
+
+        #[conformance::tests(exact, ser=json::to_string, de=json::from_str, value=Vec<String>, file="tests/error_ser.json.test", error_ser = e)]
+        fn lines(s: &str) -> Vec<String> {
+            crate::lines(s)
+        }
+    }
+}
+
+mod runtime {
+    use std::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    };
+
+    /// A runtime polling a future on the current thread until it is ready.
+    struct CurrentThread;
+
+    impl CurrentThread {
+        fn block_on<F: Future>(&self, future: F) -> F::Output {
+            let mut future = Box::pin(future);
+            let mut cx = Context::from_waker(Waker::noop());
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+        }
+    }
+
+    fn current_thread() -> CurrentThread {
+        CurrentThread
+    }
+
+    /// Pending the first time it is polled, as a future waiting on I/O would be.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                Poll::Pending
+            }
+        }
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/fallibility.json.test", runtime = current_thread)]
+    async fn lines(s: &str) -> Vec<String> {
+        YieldOnce(false).await;
+        crate::lines(s)
+    }
+}
+
+/// JSON inputs, converted to YAML and back, which must come back the same.
+mod cross_format {
+    #[conformance::tests(idempotent, ser_a=json::to_string, de_a=json::from_str, file="tests/cross_format.json.test", ser_b = yaml::to_string, de_b = yaml::from_str)]
+    fn document(_: &str) -> json::Value {}
+
+    /// The first format named as the only one would be.
+    mod first_as_only {
+        #[conformance::tests(idempotent, ser=json::to_string, de=json::from_str, file="tests/cross_format.json.test", ser_b = yaml::to_string, de_b = yaml::from_str)]
+        fn document(_: &str) -> json::Value {}
+
+        mod serde {
+            #[conformance::tests(idempotent, serde=json, file="tests/cross_format.json.test", ser_b = yaml::to_string, de_b = yaml::from_str)]
+            fn document(_: &str) -> json::Value {}
+        }
+    }
+}
+
+/// The same corpus, tested with whichever type or serializer a feature selects,
+/// as with a `Foo` and a `FooLegacy`.
+mod cfg_selected {
+    #[cfg(feature = "gzip")]
+    type Lines = Vec<String>;
+    #[cfg(not(feature = "gzip"))]
+    type Lines = std::collections::VecDeque<String>;
+
+    /// Only one of these is compiled, and with it only its tests, so their names don't collide.
+    #[conformance::tests(exact, serde=json, file="tests/repeat.json.test")]
+    #[cfg(feature = "gzip")]
+    fn lines(s: &str) -> Lines {
+        s.lines().map(String::from).collect()
+    }
+
+    #[conformance::tests(exact, serde=json, ser=json::to_string_pretty, file="tests/repeat.json.test")]
+    #[cfg(not(feature = "gzip"))]
+    fn lines(s: &str) -> Lines {
+        s.lines().map(String::from).collect()
+    }
+}
+
+mod table {
+    /// Each row is a test, with quoted cells spanning lines.
+    #[conformance::tests(exact, serde=json, file="tests/lines.json.csv", format = csv)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    /// The columns may be in any order.
+    #[conformance::tests(exact, serde=json, file="tests/columns.json.tsv", format = tsv)]
+    fn tab_separated(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn rows_are_cases() {
+        assert_eq!(LINES_JSON_CASE_NAMES, &["one line", "two lines"]);
+    }
+}
+
+mod json_lines {
+    /// Each line is a test, with the escapes of JSON strings.
+    #[conformance::tests(exact, serde=json, file="tests/lines.json.jsonl", format = jsonl)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn lines_are_cases() {
+        assert_eq!(LINES_JSON_CASE_NAMES, &["one line", "two lines", "escaped"]);
+    }
+}
+
+mod check {
+    use std::error::Error;
+
+    #[test]
+    fn inline_cases() -> Result<(), Box<dyn Error>> {
+        conformance::check!(exact, serde=json, fn = crate::lines, input = "a\nb", expected = r#"["a", "b"]"#)?;
+        conformance::check!(idempotent, serde=json, fn = |s: &str| json::from_str::<json::Value>(s).unwrap(), input = "[1, 2]")?;
+        conformance::check!(exact, serde=json, fn = crate::lines, input = "b\na", expected = r#"["a", "b"]"#, unordered_arrays)
+    }
+
+    #[test]
+    fn mismatches_panic() {
+        let mismatch = std::panic::catch_unwind(|| {
+            conformance::check!(exact, serde=json, fn = crate::lines, input = "a", expected = r#"["b"]"#)
+        });
+        assert!(mismatch.is_err());
+    }
+
+    #[test]
+    fn errors_are_returned() {
+        let e = conformance::check!(exact, serde=json, fn = crate::lines, input = "a", expected = "[").unwrap_err();
+        assert!(e.to_string().contains("EOF while parsing"), "{}", e);
+    }
+}
+
+mod canon {
+    use std::convert::Infallible;
+
+    /// The serializer under test, which only writes arrays of strings without escapes.
+    fn to_compact(lines: &[String]) -> Result<String, Infallible> {
+        let quoted: Vec<_> = lines.iter().map(|line| format!("\"{}\"", line)).collect();
+        Ok(format!("[{}]", quoted.join(",")))
+    }
+
+    /// The hand-written goldens aren't canonical, so `serde_json` rewrites them first.
+    #[conformance::tests(exact, ser=to_compact, de=json::from_str, value=Vec<String>,
+        file="tests/canon.json.test", canon_ser = json::to_string)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+}
+
+/// The notes after the final `...` are ignored.
+#[conformance::tests(exact, serde=json, file="tests/notes.json.test", allow_trailing_content)]
+fn noted_words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+/// Generates the documented module `doc_examples_json_examples`, and no tests.
+#[conformance::tests(exact, serde=json, file="tests/doc_examples.json.test", doc_examples)]
+fn documented_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+/// The file is found in `tests/nearby`, next to this source file.
+#[conformance::tests(exact, serde=json, file="nearby/words.json.test", relative_to = source)]
+fn nearby_words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+/// The file was written with CRLF newlines, which the tested function doesn't give.
+#[conformance::tests(exact, ser=text::to_string, de=text::from_str, file="tests/crlf.txt.test",
+    ser_infallible, de_infallible, literal_expected, normalize_newlines)]
+fn sorted_lines(s: &str) -> String {
+    let mut lines: Vec<_> = s.lines().collect();
+    lines.sort_unstable();
+    lines.join("\n")
+}
+
+/// Each input is only well-formed with the header and footer the file shares between them.
+#[conformance::tests(exact, serde=json, file="tests/prelude.json.test")]
+fn framed_words(s: &str) -> Vec<&str> {
+    let mut lines = s.lines();
+    assert_eq!(lines.next(), Some("format 1"), "missing header");
+    assert_eq!(lines.next_back(), Some("end"), "missing footer");
+    lines.flat_map(str::split_whitespace).collect()
+}
+
+/// The long output is kept compressed.
+#[conformance::tests(exact, serde=json, file="tests/golden.json.test")]
+fn golden_words(s: &str) -> Vec<&str> {
+    s.split_whitespace().collect()
+}
+
+/// The output of `recorded` is kept in `tests/snapshots.recorded.snap`.
+#[conformance::tests(exact, serde=json, file="tests/snapshots.json.test", snapshots)]
+fn snapshot_words(s: &str) -> Vec<&str> {
+    let mut words: Vec<_> = s.split_whitespace().collect();
+    words.sort_unstable();
+    words
+}
+
+/// A truncated JSON document is one which needs more data, rather than a malformed one.
+mod incomplete {
+    fn needs_more_data(result: &Result<Vec<String>, json::Error>) -> bool {
+        matches!(result, Err(e) if e.is_eof())
+    }
+
+    #[conformance::tests(exact, ser=json::to_string, de=json::from_str, value=Vec<String>, file="tests/incomplete.json.test", incomplete = needs_more_data)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn complete_inputs_fail() {
+        let e = incomplete_json_complete().unwrap_err().to_string();
+        assert!(
+            e.ends_with("`needs_more_data` found that `de` didn't need more data"),
+            "{}",
+            e
+        );
+    }
+}
+
+/// The file is also tested by `error_lines`, whose names would otherwise clash with these.
+#[conformance::tests(exact, serde=json, file="tests/error.json.test", mod_name = rejected)]
+fn rejected_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[test]
+fn mod_name_names_the_tests() {
+    assert_eq!(REJECTED_CASE_COUNT, ERROR_JSON_CASE_COUNT);
+    assert!(rejected_valid().is_ok());
+}
+
+#[conformance::tests(exact, serde=json, file="tests/expr.json.test")]
+fn expr_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[test]
+fn different_values_fail() {
+    let e = std::panic::catch_unwind(expr_json_different).unwrap_err();
+    let e = e.downcast_ref::<String>().unwrap();
+    assert!(e.contains(r#"["two"]"#), "{}", e);
+}
+
+#[conformance::tests(exact, serde=json, file="tests/exclude.json.test", exclude = ["known failure", "EXCLUDED-1"])]
+fn excluded_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[test]
+fn excluded_cases_are_ignored() {
+    assert_eq!(EXCLUDE_JSON_CASE_COUNT, 3);
+    assert!(std::panic::catch_unwind(exclude_json_known_failure).is_err());
+}
+
+#[conformance::tests(exact, serde=json, input_dir="tests/pairs/in", output_dir="tests/pairs/out")]
+fn paired_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[test]
+fn pairs_are_named_after_their_stem() {
+    // `in` is a keyword, so the prefix is escaped as with `dir`
+    assert_eq!(_IN_CASE_NAMES, ["single", "two_lines"]);
+    assert!(_in_two_lines().is_ok());
+}
+
+/// A serializer whose output its own parser accepts, but a standard one doesn't.
+mod de_ref {
+    mod lenient {
+        /// Writes a trailing comma after the last of several elements.
+        pub fn to_string(lines: &Vec<String>) -> json::Result<String> {
+            let s = json::to_string(lines)?;
+            Ok(match lines.len() {
+                0 | 1 => s,
+                _ => s.replace(']', ",]"),
+            })
+        }
+
+        pub fn from_str<T: serde::de::DeserializeOwned>(s: &str) -> json::Result<T> {
+            json::from_str(&s.replace(",]", "]"))
+        }
+    }
+
+    #[conformance::tests(exact, ser=lenient::to_string, de=lenient::from_str, file="tests/de_ref.json.test", de_ref = json::from_str)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn reference_parser_rejects_trailing_commas() {
+        let e = de_ref_json_trailing_comma().unwrap_err().to_string();
+        assert!(
+            e.contains("rejected the serialized output: trailing comma"),
+            "{}",
+            e
+        );
+        assert!(e.ends_with(r#"output: ["one","two",]"#), "{}", e);
+    }
+}
+
+/// Numbered cases, named `numbered_json_case_1` rather than `numbered_json_1`.
+#[conformance::tests(exact, serde=json, file="tests/numbered.json.test", ident_prefix = "case_")]
+fn numbered_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[test]
+fn ident_prefix_names_the_tests() {
+    assert!(numbered_json_case_1().is_ok());
+    assert!(numbered_json_case_2().is_ok());
+}
+
+// checked without generating tests, as a pre-check target would
+conformance::validate!("tests/identity.json.test");
+conformance::validate!(idempotent, "tests/idempotent.json.test");
+
+/// The file's last test has no `...`.
+#[conformance::tests(exact, serde=json, file="tests/unterminated.json.test", lenient_terminator)]
+fn unterminated_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[test]
+fn unterminated_final_test_is_read() {
+    assert_eq!(UNTERMINATED_JSON_CASE_NAMES, ["first", "last"]);
+}
+
+/// Strict about empty sections, but for the one case allowed an empty input.
+#[conformance::tests(exact, serde=json, file="tests/allow_empty.json.test", forbid_empty)]
+fn allow_empty_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+/// Cases of a JSON file which are written one string per line instead.
+mod overrides {
+    use serde::{de::DeserializeOwned, Serialize};
+
+    pub fn from_lines<T: DeserializeOwned>(s: &str) -> json::Result<T> {
+        json::from_value(json::Value::from(crate::lines(s)))
+    }
+
+    pub fn to_lines<T: Serialize>(value: &T) -> json::Result<String> {
+        let lines: Vec<String> = json::from_value(json::to_value(value)?)?;
+        Ok(lines.join("\n"))
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/overrides.json.test", allow_identical)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn defaults_reject_overridden_cases() {
+        assert!(overrides_json("a\nb", "a\nb").is_err());
+    }
+}
+
+/// The file's outputs were captured from an earlier version.
+#[conformance::tests(exact, serde=json, file="tests/baseline.json.test", strict_format)]
+fn baseline_lines(s: &str) -> Vec<String> {
+    lines(s)
+}
+
+#[test]
+fn baselines_are_named_on_failure() {
+    let e = baseline_json_unreadable().unwrap_err().to_string();
+    let expected = format!(
+        "tests/baseline.json.test:10: baseline from 0.0.1 differs in {}: ",
+        env!("CARGO_PKG_VERSION")
+    );
+    assert!(e.starts_with(&expected), "{}", e);
+}
+
+mod inline {
+    #[conformance::tests(exact, serde=json, cases = [
+        ("one line", "a", r#"["a"]"#),
+        ("two lines", "a\nb", r#"["a", "b"]"#),
+    ])]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[conformance::tests(idempotent, serde=json, cases = [("array", "[1, 2]")], mod_name = formatted)]
+    fn values(s: &str) -> json::Value {
+        json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn cases_are_named_as_given() {
+        assert_eq!(CASES_CASE_NAMES, ["one line", "two lines"]);
+        assert_eq!(FORMATTED_CASE_COUNT, 1);
+    }
+}
+
+mod template {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+    /// The outputs have a fresh `id` each run, which the templates match with placeholders.
+    #[conformance::tests(exact, serde=json, file="tests/template.json.test", template)]
+    fn tagged(s: &str) -> json::Value {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        json::json!({ "id": id, "name": s, "links": { "self": id } })
+    }
+
+    #[test]
+    fn captures_must_match() {
+        let e = template_json_different_captures().unwrap_err().to_string();
+        assert!(e.contains(r#"at `$.name`, `<<id>>` is "foo", but was "#), "{}", e);
+    }
+}
+
+mod warn {
+    use serde::Serialize;
+
+    /// Lines which carry the warnings found reading them, without serializing them.
+    #[derive(Serialize)]
+    #[serde(transparent)]
+    pub struct Linted {
+        lines: Vec<String>,
+        #[serde(skip)]
+        warnings: Vec<String>,
+    }
+
+    fn warnings(linted: &Linted) -> Vec<String> {
+        linted.warnings.clone()
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/warn.json.test", warnings = warnings)]
+    fn linted_lines(s: &str) -> Linted {
+        let lines = crate::lines(s);
+        let warnings = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.is_empty())
+            .map(|(i, _)| format!("line {} is blank", i + 1))
+            .collect();
+        Linted { lines, warnings }
+    }
+
+    #[test]
+    fn missing_warnings_fail() {
+        let e = warn_json_missing_warning().unwrap_err().to_string();
+        assert!(
+            e.ends_with(r#"expected a warning containing "line 1 is blank", but the warnings were []"#),
+            "{}",
+            e
+        );
+    }
+}
+
+/// Each input is a list of sub-inputs, passed to the tested function together.
+#[conformance::tests(exact, serde=json, file="tests/batch.json.test", batch, allow_identical)]
+fn concatenated(lists: Vec<Vec<String>>) -> Vec<String> {
+    lists.concat()
+}
+
+mod clone_stable {
+    use serde::Serialize;
+
+    /// Lines which count how many times they were cloned, which a clone serializes.
+    #[derive(Serialize)]
+    pub struct Counted {
+        lines: Vec<String>,
+        clones: usize,
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            Counted {
+                lines: self.lines.clone(),
+                clones: self.clones + 1,
+            }
+        }
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/clone_stable.json.test", clone_stable)]
+    fn counted(s: &str) -> Counted {
+        Counted {
+            lines: crate::lines(s),
+            clones: 0,
+        }
+    }
+
+    #[conformance::tests(exact, serde=json, file="tests/ids.json.test", clone_stable, mod_name = stable_ids)]
+    fn lines(s: &str) -> Vec<String> {
+        crate::lines(s)
+    }
+
+    #[test]
+    fn clones_are_serialized_too() {
+        let e = clone_stable_json_counted().unwrap_err().to_string();
+        assert!(e.contains("serializing a clone of the value gave a different output"), "{}", e);
+    }
+}
+
+/// The docs of `tests/docs`, a crate with tests beside its code, built with its private items.
+mod docs {
+    use std::{fs, path::Path, process::Command};
+
+    #[test]
+    fn generated_items_are_hidden_from_docs() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/docs/Cargo.toml");
+        let target = Path::new(env!("CARGO_TARGET_TMPDIR")).join("docs");
+        let output = Command::new(env!("CARGO"))
+            .args(["doc", "--offline", "--no-deps", "--document-private-items"])
+            .arg("--manifest-path")
+            .arg(fixture)
+            .arg("--target-dir")
+            .arg(&target)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let docs = target.join("doc/docs_fixture");
+        let all = fs::read_to_string(docs.join("all.html")).unwrap();
+        for page in &["fn.lines.html", "fn.split.html"] {
+            assert!(all.contains(page), "{} isn't in the docs", page);
+        }
+        // the testing functions and constants are named after the file
+        for entry in fs::read_dir(&docs).unwrap() {
+            let page = entry.unwrap().file_name().into_string().unwrap();
+            assert!(!page.to_lowercase().contains("lines_json"), "{} is in the docs", page);
+        }
+        assert!(!all.to_lowercase().contains("lines_json"), "{}", all);
+    }
+}